@@ -0,0 +1,27 @@
+// Records the git commit the binary was built from for `--version`
+
+use std::process::Command;
+
+fn main() {
+    let git = |args: &[&str]| Command::new("git").args(args).output().ok().filter(|out| out.status.success());
+    let hash = match git(&["rev-parse", "--short=12", "HEAD"]) {
+        Some(out) => {
+            let hash = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            // Uncommitted changes to tracked files
+            let dirty = git(&["diff-index", "--quiet", "HEAD", "--"]).is_none();
+            if dirty { format!("{}-dirty", hash) } else { hash }
+        }
+        None => "unknown".to_string(),
+    };
+    println!("cargo:rustc-env=METALSHADER_GIT_HASH={}", hash);
+    // HEAD names the branch, whose ref moves on commit. Only existing paths:
+    // a missing one would rerun the script every build
+    let branch = std::fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| Some(format!(".git/{}", head.strip_prefix("ref: ")?.trim())));
+    for path in [".git/HEAD".to_string(), ".git/index".to_string()].into_iter().chain(branch) {
+        if std::path::Path::new(&path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Animated GIF and APNG channel textures
+//
+// All frames are decoded up front with the `image` crate (feature
+// `animation`) into full-canvas RGBA8 images, so showing another frame is a
+// plain re-upload of level 0 into the bound image. The frame follows iTime,
+// looping over the animation's total length, so pausing and scrubbing the
+// clock move the footage along with the shader. DDS/KTX2 channels load as a
+// single still frame, so the viewer handles every `--channel0` the same way.
+
+use std::path::Path;
+
+use crate::error::MetalshaderError;
+use crate::texture_file::{self, TextureData};
+
+/// Frames with no delay, or one too short to honour, play at 10 fps as in
+/// browsers
+const DEFAULT_DELAY: f64 = 0.1;
+const MIN_DELAY: f64 = 0.02;
+
+/// Whether a `--channel0` value names an image for this module rather than
+/// a DDS/KTX2 container
+pub fn is_animation(spec: &str) -> bool {
+    let extension = Path::new(spec).extension().and_then(|e| e.to_str()).unwrap_or("");
+    ["gif", "png", "apng"].iter().any(|e| extension.eq_ignore_ascii_case(e))
+}
+
+/// Load a `--channel0` value: the frames of a GIF/PNG, or anything
+/// `texture_file::load_channel` takes as one still frame
+pub fn load_channel(spec: &str, equirect_cube: bool) -> Result<AnimatedTexture, MetalshaderError> {
+    if !is_animation(spec) {
+        return Ok(AnimatedTexture::still(texture_file::load_channel(spec, equirect_cube)?));
+    }
+    if equirect_cube {
+        return Err(MetalshaderError::InvalidTexture {
+            path: spec.into(),
+            reason: "--channel0-cube needs a DDS or KTX2 panorama".to_string(),
+        });
+    }
+    AnimatedTexture::load(Path::new(spec))
+}
+
+pub struct AnimatedTexture {
+    frames: Vec<TextureData>,
+    /// Start of each frame within one loop, in seconds
+    starts: Vec<f64>,
+    /// Length of one loop in seconds
+    duration: f64,
+    /// Frame last returned by `advance`
+    current: usize,
+}
+
+impl AnimatedTexture {
+    /// Decode every frame of a GIF or (A)PNG. A still PNG becomes a single
+    /// frame.
+    pub fn load(path: &Path) -> Result<Self, MetalshaderError> {
+        let invalid = |reason| MetalshaderError::InvalidTexture { path: path.to_path_buf(), reason };
+        Self::from_frames(decode(path).map_err(invalid)?).map_err(invalid)
+    }
+
+    /// A single frame that never changes
+    pub fn still(texture: TextureData) -> Self {
+        Self { frames: vec![texture], starts: vec![0.0], duration: DEFAULT_DELAY, current: 0 }
+    }
+
+    /// Frames with their display time in seconds; all must have the size of
+    /// the first
+    pub fn from_frames(frames: Vec<(TextureData, f64)>) -> Result<Self, String> {
+        let first = frames.first().ok_or("no frames")?.0.clone();
+        if let Some((frame, _)) = frames.iter().find(|(f, _)| (f.width, f.height) != (first.width, first.height)) {
+            return Err(format!(
+                "frames differ in size: {}x{} vs {}x{}",
+                first.width, first.height, frame.width, frame.height
+            ));
+        }
+        let mut starts = Vec::with_capacity(frames.len());
+        let mut duration = 0.0;
+        for (_, delay) in &frames {
+            starts.push(duration);
+            duration += if *delay < MIN_DELAY { DEFAULT_DELAY } else { *delay };
+        }
+        let frames = frames.into_iter().map(|(frame, _)| frame).collect();
+        Ok(Self { frames, starts, duration, current: 0 })
+    }
+
+    /// Frame to bind now: the first until `advance` moves on
+    pub fn current(&self) -> &TextureData {
+        &self.frames[self.current]
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Length of one loop in seconds
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    /// Frame shown at shader time `time`
+    pub fn frame_index(&self, time: f64) -> usize {
+        let t = time.rem_euclid(self.duration);
+        self.starts.partition_point(|&start| start <= t).saturating_sub(1)
+    }
+
+    /// The frame to upload for `time`, or `None` if it is the one already
+    /// bound
+    pub fn advance(&mut self, time: f64) -> Option<&TextureData> {
+        let index = self.frame_index(time);
+        if index == self.current {
+            return None;
+        }
+        self.current = index;
+        Some(&self.frames[index])
+    }
+}
+
+#[cfg(feature = "animation")]
+fn decode(path: &Path) -> Result<Vec<(TextureData, f64)>, String> {
+    use image::codecs::gif::GifDecoder;
+    use image::codecs::png::PngDecoder;
+    use image::{AnimationDecoder, DynamicImage, Frames, RgbaImage};
+
+    let rgba = |image: RgbaImage| TextureData {
+        format: crate::texture_file::TextureFormat::Rgba8,
+        width: image.width(),
+        height: image.height(),
+        faces: 1,
+        levels: vec![image.into_raw()],
+    };
+    let file = std::io::BufReader::new(std::fs::File::open(path).map_err(|e| e.to_string())?);
+    let is_gif = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gif"));
+    let frames: Frames = if is_gif {
+        GifDecoder::new(file).map_err(|e| e.to_string())?.into_frames()
+    } else {
+        let png = PngDecoder::new(file).map_err(|e| e.to_string())?;
+        if !png.is_apng().map_err(|e| e.to_string())? {
+            let still = DynamicImage::from_decoder(png).map_err(|e| e.to_string())?;
+            return Ok(vec![(rgba(still.into_rgba8()), 0.0)]);
+        }
+        png.apng().map_err(|e| e.to_string())?.into_frames()
+    };
+    frames
+        .map(|frame| {
+            let frame = frame.map_err(|e| e.to_string())?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = numer as f64 / denom.max(1) as f64 / 1000.0;
+            Ok((rgba(frame.into_buffer()), delay))
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "animation"))]
+fn decode(_path: &Path) -> Result<Vec<(TextureData, f64)>, String> {
+    Err("GIF/PNG channels need the `animation` feature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture_file::TextureFormat;
+
+    fn frame(shade: u8) -> TextureData {
+        TextureData { format: TextureFormat::Rgba8, width: 1, height: 1, faces: 1, levels: vec![vec![shade; 4]] }
+    }
+
+    #[test]
+    fn test_frame_follows_time_and_loops() {
+        // 0.5s, then a zero delay (played as 0.1s), then 0.4s: one loop is 1s
+        let mut anim = AnimatedTexture::from_frames(vec![(frame(0), 0.5), (frame(1), 0.0), (frame(2), 0.4)]).unwrap();
+        assert_eq!(anim.duration(), 1.0);
+        assert_eq!(anim.frame_index(0.0), 0);
+        assert_eq!(anim.frame_index(0.55), 1);
+        assert_eq!(anim.frame_index(0.7), 2);
+        assert_eq!(anim.frame_index(1.2), 0);
+        assert_eq!(anim.frame_index(-0.1), 2);
+        // Only a change of frame needs an upload
+        assert!(anim.advance(0.1).is_none());
+        assert_eq!(anim.advance(0.55).map(|f| f.levels[0][0]), Some(1));
+        assert!(anim.advance(0.58).is_none());
+        assert_eq!(anim.current().levels[0][0], 1);
+        // A still never needs another upload
+        let mut still = AnimatedTexture::still(frame(7));
+        assert!(still.advance(0.0).is_none() && still.advance(123.4).is_none());
+    }
+
+    #[test]
+    fn test_channel_spec_kind() {
+        assert!(is_animation("loop.gif"));
+        assert!(is_animation("clip.APNG"));
+        assert!(!is_animation("noise.ktx2"));
+        assert!(!is_animation("px.dds,nx.dds,py.dds,ny.dds,pz.dds,nz.dds"));
+    }
+}
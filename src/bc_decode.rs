@@ -0,0 +1,146 @@
+// CPU decoders for BC1-BC5, used when the GPU can't sample a compressed
+// channel texture directly (most Apple Silicon GPUs through MoltenVK lack
+// some BC formats, as do many ARM Linux GPUs)
+//
+// Each decoder takes the level's block data and its pixel size and returns
+// tightly packed RGBA8. Partial blocks at the right/bottom edges are cropped.
+
+fn expand_565(c: u16) -> [u8; 3] {
+    let r = (c >> 11) as u8 & 0x1f;
+    let g = (c >> 5) as u8 & 0x3f;
+    let b = c as u8 & 0x1f;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+/// Colour half of BC1/BC2/BC3. `punch_through` enables BC1's 3-colour mode
+/// with transparent black when c0 <= c1; BC2/BC3 always use 4 colours.
+fn color_block(block: &[u8], punch_through: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (expand_565(c0), expand_565(c1));
+    let mix = |wa: u16, wb: u16| -> [u8; 3] {
+        std::array::from_fn(|i| ((e0[i] as u16 * wa + e1[i] as u16 * wb) / (wa + wb)) as u8)
+    };
+    let opaque = |[r, g, b]: [u8; 3]| [r, g, b, 255];
+    let palette = if c0 > c1 || !punch_through {
+        [opaque(e0), opaque(e1), opaque(mix(2, 1)), opaque(mix(1, 2))]
+    } else {
+        [opaque(e0), opaque(e1), opaque(mix(1, 1)), [0; 4]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|texel| palette[((indices >> (2 * texel)) & 3) as usize])
+}
+
+/// 8-byte BC3-alpha/BC4 block: two endpoints and 3-bit indices
+fn channel_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let palette: [u8; 8] = std::array::from_fn(|i| {
+        let i = i as u32;
+        let value = match i {
+            0 => a0,
+            1 => a1,
+            _ if a0 > a1 => ((8 - i) * a0 + (i - 1) * a1) / 7,
+            2..=5 => ((6 - i) * a0 + (i - 1) * a1) / 5,
+            6 => 0,
+            _ => 255,
+        };
+        value as u8
+    });
+    let mut bits = 0u64;
+    for (i, &b) in block[2..8].iter().enumerate() {
+        bits |= (b as u64) << (8 * i);
+    }
+    std::array::from_fn(|texel| palette[((bits >> (3 * texel)) & 7) as usize])
+}
+
+/// Walk the blocks of a `width` x `height` image, writing each block's 16
+/// texels into the RGBA8 output
+fn decode_blocks(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_bytes: usize,
+    decode: impl Fn(&[u8]) -> [[u8; 4]; 16],
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let mut out = vec![0u8; width * height * 4];
+    for (index, block) in data.chunks_exact(block_bytes).enumerate() {
+        let (bx, by) = (index % blocks_x * 4, index / blocks_x * 4);
+        if by >= height {
+            break;
+        }
+        let texels = decode(block);
+        for (texel, rgba) in texels.iter().enumerate() {
+            let (x, y) = (bx + texel % 4, by + texel / 4);
+            if x < width && y < height {
+                let at = (y * width + x) * 4;
+                out[at..at + 4].copy_from_slice(rgba);
+            }
+        }
+    }
+    out
+}
+
+pub fn decode_bc1(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 8, |block| color_block(block, true))
+}
+
+pub fn decode_bc2(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, |block| {
+        let mut texels = color_block(&block[8..], false);
+        for (texel, rgba) in texels.iter_mut().enumerate() {
+            let nibble = (block[texel / 2] >> (4 * (texel % 2))) & 0xf;
+            rgba[3] = nibble * 17;
+        }
+        texels
+    })
+}
+
+pub fn decode_bc3(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, |block| {
+        let alpha = channel_block(&block[..8]);
+        let mut texels = color_block(&block[8..], false);
+        for (rgba, a) in texels.iter_mut().zip(alpha) {
+            rgba[3] = a;
+        }
+        texels
+    })
+}
+
+pub fn decode_bc4(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 8, |block| channel_block(block).map(|r| [r, 0, 0, 255]))
+}
+
+pub fn decode_bc5(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, |block| {
+        let (red, green) = (channel_block(&block[..8]), channel_block(&block[8..]));
+        std::array::from_fn(|texel| [red[texel], green[texel], 0, 255])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bc1_four_and_three_colour_modes() {
+        // c0 = pure red, c1 = pure blue, texel 0 -> c0, 1 -> c1, 2 -> 2/3 red, 3 -> 1/3 red
+        let block = [0x00, 0xf8, 0x1f, 0x00, 0b1110_0100, 0, 0, 0];
+        let rgba = decode_bc1(&block, 4, 4);
+        assert_eq!(&rgba[0..4], &[255, 0, 0, 255]);
+        assert_eq!(&rgba[4..8], &[0, 0, 255, 255]);
+        assert_eq!(&rgba[8..12], &[170, 0, 85, 255]);
+        // Swapped endpoints select 3-colour mode: index 3 is transparent black
+        let block = [0x1f, 0x00, 0x00, 0xf8, 0b1100_0000, 0, 0, 0];
+        assert_eq!(&decode_bc1(&block, 4, 4)[12..16], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_bc4_interpolation_and_cropping() {
+        // a0 = 255 > a1 = 0: index 2 is 6/7 of a0
+        let block = [255, 0, 0b010, 0, 0, 0, 0, 0];
+        let rgba = decode_bc4(&block, 2, 1);
+        assert_eq!(rgba, vec![218, 0, 0, 255, 255, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,19 @@
+#version 450
+
+// Vertex stage of every shader without its own .vert, embedded as
+// fullscreen.vert.spv. Only iResolution is read, so the block stops there
+
+layout(location = 0) out vec2 fragCoord;
+
+layout(binding = 0, set = 0) uniform UniformBufferObject {
+    vec3 iResolution;
+} ubo;
+
+void main() {
+    vec2 positions[6] = vec2[](
+        vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+        vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+    );
+    gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
+    fragCoord = (positions[gl_VertexIndex] * 0.5 + 0.5) * ubo.iResolution.xy;
+}
@@ -0,0 +1,20 @@
+#version 450
+
+// fullscreen.vert with fragCoord in 0-1 instead of pixels, for
+// --frag-coord normalized; embedded as fullscreen_normalized.vert.spv. The
+// block is declared so the module's interface matches fullscreen.vert
+
+layout(location = 0) out vec2 fragCoord;
+
+layout(binding = 0, set = 0) uniform UniformBufferObject {
+    vec3 iResolution;
+} ubo;
+
+void main() {
+    vec2 positions[6] = vec2[](
+        vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+        vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+    );
+    gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
+    fragCoord = positions[gl_VertexIndex] * 0.5 + 0.5;
+}
@@ -0,0 +1,19 @@
+#version 450
+
+// Built-in test pattern: red grows to the right and green downwards
+// (fragCoord origin, see README "Coordinate convention"), blue pulses with
+// iTime, and a 32-pixel checkerboard shows the render resolution
+
+layout(location = 0) out vec4 outColor;
+
+layout(binding = 0, set = 0) uniform UniformBufferObject {
+    vec3 iResolution;
+    float iTime;
+} ubo;
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / ubo.iResolution.xy;
+    vec2 cell = floor(gl_FragCoord.xy / 32.0);
+    float shade = mod(cell.x + cell.y, 2.0) * 0.25 + 0.75;
+    outColor = vec4(vec3(uv, sin(ubo.iTime) * 0.5 + 0.5) * shade, 1.0);
+}
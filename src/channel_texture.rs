@@ -0,0 +1,498 @@
+// Device-local image for a channel texture loaded from a file
+//
+// The data goes through a staging buffer into an OPTIMAL image with all of
+// its mip levels (six layers behind a CUBE view for cubemaps), then is left
+// in SHADER_READ_ONLY_OPTIMAL for the iChannel descriptor. Block-compressed
+// formats the device can't sample are decoded to RGBA8 on the CPU first.
+// Files without a mip chain get one generated by blitting down from level 0,
+// so minified and `textureLod` lookups don't alias. Animated channels
+// overwrite level 0 of the same image with `update` on each frame change.
+
+use ash::vk;
+
+use crate::error::MetalshaderError;
+use crate::mapped_memory::MappedMemory;
+use crate::options::{ChannelFilter, ChannelSampler, ChannelWrap};
+use crate::texture_file::{self, TextureData};
+
+pub struct ChannelTexture {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    /// Bound as samplerCube rather than sampler2D
+    pub cube: bool,
+    /// Mip levels in the image, from the file or generated
+    pub mip_levels: u32,
+    /// Format and size of level 0, after any CPU decoding
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+}
+
+impl ChannelTexture {
+    /// Upload `data` and wait for the copy to finish. The queue must be idle
+    /// or only running work that doesn't touch the new image.
+    pub unsafe fn upload(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+        queue: vk::Queue,
+        queue_family_index: u32,
+        data: &TextureData,
+    ) -> Result<Self, MetalshaderError> {
+        let supported = |data: &TextureData| {
+            let format = vk::Format::from_raw(data.format.vk_raw());
+            instance
+                .get_physical_device_format_properties(physical_device, format)
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE)
+        };
+        let decoded;
+        let data = if supported(data) {
+            data
+        } else {
+            decoded = data.decode_to_rgba().map_err(MetalshaderError::UnsupportedTextureFormat)?;
+            println!("Device can't sample {:?}, decoded texture to {:?}", data.format, decoded.format);
+            &decoded
+        };
+        let format = vk::Format::from_raw(data.format.vk_raw());
+
+        // Blit a mip chain when the file has none; block-compressed formats
+        // never support blitting into them, so they keep their single level
+        let full_chain = texture_file::mip_count(data.width, data.height);
+        let blit_features = vk::FormatFeatureFlags::BLIT_SRC
+            | vk::FormatFeatureFlags::BLIT_DST
+            | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+        let generate_mips = data.levels.len() == 1
+            && full_chain > 1
+            && instance
+                .get_physical_device_format_properties(physical_device, format)
+                .optimal_tiling_features
+                .contains(blit_features);
+        if data.levels.len() == 1 && full_chain > 1 && !generate_mips {
+            println!("{:?} can't be blitted with linear filtering here; iChannel0 has no mipmaps", data.format);
+        }
+        let level_count = if generate_mips { full_chain } else { data.levels.len() as u32 };
+
+        let mem_properties = instance.get_physical_device_memory_properties(physical_device);
+        let (staging, staging_memory, regions) = stage(device, &mem_properties, data)?;
+
+        let image_flags = if data.is_cube() {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        };
+        let image_info = vk::ImageCreateInfo::default()
+            .flags(image_flags)
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D { width: data.width, height: data.height, depth: 1 })
+            .mip_levels(level_count)
+            .array_layers(data.faces)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(
+                vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_DST
+                    | vk::ImageUsageFlags::TRANSFER_SRC,
+            )
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let image = device.create_image(&image_info, None)?;
+        let image_req = device.get_image_memory_requirements(image);
+        let image_alloc = vk::MemoryAllocateInfo::default()
+            .allocation_size(image_req.size)
+            .memory_type_index(find_memory_type(
+                &mem_properties,
+                image_req.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?);
+        let memory = device.allocate_memory(&image_alloc, None)?;
+        device.bind_image_memory(image, memory, 0)?;
+
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count,
+            base_array_layer: 0,
+            layer_count: data.faces,
+        };
+        let submitted = submit_copy(device, queue, queue_family_index, |cmd| {
+            record_copy(device, cmd, staging, image, &regions, vk::ImageLayout::UNDEFINED, range);
+            if generate_mips {
+                record_mip_chain(device, cmd, image, (data.width, data.height), level_count, data.faces);
+            } else {
+                record_to_shader(device, cmd, image, range);
+            }
+        });
+        device.destroy_buffer(staging, None);
+        device.free_memory(staging_memory, None);
+        if let Err(e) = submitted {
+            device.destroy_image(image, None);
+            device.free_memory(memory, None);
+            return Err(e.into());
+        }
+
+        let view_type = if data.is_cube() { vk::ImageViewType::CUBE } else { vk::ImageViewType::TYPE_2D };
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(view_type)
+            .format(format)
+            .subresource_range(range);
+        let view = device.create_image_view(&view_info, None)?;
+
+        Ok(Self {
+            image,
+            memory,
+            view,
+            cube: data.is_cube(),
+            mip_levels: level_count,
+            format,
+            extent: vk::Extent2D { width: data.width, height: data.height },
+        })
+    }
+
+    /// Whether `data` can replace the contents with `update`: same size and
+    /// format, one level of one face
+    pub fn can_update(&self, data: &TextureData) -> bool {
+        !self.cube
+            && data.faces == 1
+            && data.levels.len() == 1
+            && vk::Format::from_raw(data.format.vk_raw()) == self.format
+            && (data.width, data.height) == (self.extent.width, self.extent.height)
+    }
+
+    /// Overwrite level 0 with `data` (see `can_update`) and regenerate the
+    /// mip chain, keeping the image and view. Waits for the copy; the image
+    /// must not be in use by a submitted command buffer.
+    pub unsafe fn update(
+        &self,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+        queue: vk::Queue,
+        queue_family_index: u32,
+        data: &TextureData,
+    ) -> Result<(), MetalshaderError> {
+        let mem_properties = instance.get_physical_device_memory_properties(physical_device);
+        let (staging, staging_memory, regions) = stage(device, &mem_properties, data)?;
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: self.mip_levels,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let submitted = submit_copy(device, queue, queue_family_index, |cmd| {
+            let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+            record_copy(device, cmd, staging, self.image, &regions, layout, range);
+            if self.mip_levels > 1 {
+                record_mip_chain(device, cmd, self.image, (data.width, data.height), self.mip_levels, 1);
+            } else {
+                record_to_shader(device, cmd, self.image, range);
+            }
+        });
+        device.destroy_buffer(staging, None);
+        device.free_memory(staging_memory, None);
+        submitted.map_err(Into::into)
+    }
+
+    /// The image must no longer be used by any submitted command buffer
+    pub unsafe fn destroy(&self, device: &ash::Device) {
+        device.destroy_image_view(self.view, None);
+        device.destroy_image(self.image, None);
+        device.free_memory(self.memory, None);
+    }
+}
+
+/// The iChannel0 sampler for `settings`
+pub unsafe fn create_sampler(device: &ash::Device, settings: ChannelSampler) -> Result<vk::Sampler, vk::Result> {
+    let address_mode = match settings.wrap {
+        ChannelWrap::Repeat => vk::SamplerAddressMode::REPEAT,
+        ChannelWrap::Clamp => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        ChannelWrap::Mirror => vk::SamplerAddressMode::MIRRORED_REPEAT,
+    };
+    // `linear` and `nearest` stay on level 0, as on ShaderToy
+    let (filter, mipmap_mode, max_lod) = match settings.filter {
+        ChannelFilter::Mipmap => (vk::Filter::LINEAR, vk::SamplerMipmapMode::LINEAR, vk::LOD_CLAMP_NONE),
+        ChannelFilter::Linear => (vk::Filter::LINEAR, vk::SamplerMipmapMode::NEAREST, 0.0),
+        ChannelFilter::Nearest => (vk::Filter::NEAREST, vk::SamplerMipmapMode::NEAREST, 0.0),
+    };
+    let sampler_info = vk::SamplerCreateInfo::default()
+        .mag_filter(filter)
+        .min_filter(filter)
+        .mipmap_mode(mipmap_mode)
+        .max_lod(max_lod)
+        .address_mode_u(address_mode)
+        .address_mode_v(address_mode)
+        .address_mode_w(address_mode);
+    device.create_sampler(&sampler_info, None)
+}
+
+/// Host-visible staging buffer with every level of `data` back to back, and
+/// the copy regions into an image of the same layout
+unsafe fn stage(
+    device: &ash::Device,
+    mem_properties: &vk::PhysicalDeviceMemoryProperties,
+    data: &TextureData,
+) -> Result<(vk::Buffer, vk::DeviceMemory, Vec<vk::BufferImageCopy>), MetalshaderError> {
+    let total: usize = data.levels.iter().map(Vec::len).sum();
+    let buffer_info = vk::BufferCreateInfo::default()
+        .size(total as u64)
+        .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    let staging = device.create_buffer(&buffer_info, None)?;
+    let staging_req = device.get_buffer_memory_requirements(staging);
+    let staging_alloc = vk::MemoryAllocateInfo::default()
+        .allocation_size(staging_req.size)
+        .memory_type_index(find_memory_type(
+            mem_properties,
+            staging_req.memory_type_bits,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?);
+    let staging_memory = device.allocate_memory(&staging_alloc, None)?;
+    device.bind_buffer_memory(staging, staging_memory, 0)?;
+    let mut mapped = MappedMemory::map(device, staging_memory, total)?;
+    let mut regions = Vec::with_capacity(data.levels.len());
+    let mut offset = 0;
+    for (level, bytes) in data.levels.iter().enumerate() {
+        mapped.as_mut_slice()[offset..offset + bytes.len()].copy_from_slice(bytes);
+        let (width, height) = data.level_size(level);
+        // Faces are consecutive layers, so one region covers them all
+        regions.push(
+            vk::BufferImageCopy::default()
+                .buffer_offset(offset as u64)
+                .image_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: level as u32,
+                    base_array_layer: 0,
+                    layer_count: data.faces,
+                })
+                .image_extent(vk::Extent3D { width, height, depth: 1 }),
+        );
+        offset += bytes.len();
+    }
+    mapped.unmap(device);
+    Ok((staging, staging_memory, regions))
+}
+
+/// Record `record` into a one-shot command buffer from a transient pool,
+/// submit it and wait
+unsafe fn submit_copy(
+    device: &ash::Device,
+    queue: vk::Queue,
+    queue_family_index: u32,
+    record: impl FnOnce(vk::CommandBuffer),
+) -> Result<(), vk::Result> {
+    let pool_info = vk::CommandPoolCreateInfo::default()
+        .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+        .queue_family_index(queue_family_index);
+    let pool = device.create_command_pool(&pool_info, None)?;
+    let alloc_info = vk::CommandBufferAllocateInfo::default()
+        .command_pool(pool)
+        .level(vk::CommandBufferLevel::PRIMARY)
+        .command_buffer_count(1);
+    let recorded = device.allocate_command_buffers(&alloc_info).and_then(|buffers| {
+        let cmd = buffers[0];
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        device.begin_command_buffer(cmd, &begin_info)?;
+        record(cmd);
+        device.end_command_buffer(cmd)?;
+        Ok(cmd)
+    });
+    let submitted = recorded.and_then(|cmd| {
+        let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+        let submit_info = vk::SubmitInfo::default().command_buffers(std::slice::from_ref(&cmd));
+        let result = device
+            .queue_submit(queue, &[submit_info], fence)
+            .and_then(|()| device.wait_for_fences(&[fence], true, u64::MAX));
+        device.destroy_fence(fence, None);
+        result
+    });
+    device.destroy_command_pool(pool, None);
+    submitted
+}
+
+/// Move all of `range` from `old_layout` to TRANSFER_DST_OPTIMAL and copy
+/// the staged `regions` in
+unsafe fn record_copy(
+    device: &ash::Device,
+    cmd: vk::CommandBuffer,
+    staging: vk::Buffer,
+    image: vk::Image,
+    regions: &[vk::BufferImageCopy],
+    old_layout: vk::ImageLayout,
+    range: vk::ImageSubresourceRange,
+) {
+    // Replacing a sampled image waits for the fragment shaders reading it
+    let (src_stage, src_access) = if old_layout == vk::ImageLayout::UNDEFINED {
+        (vk::PipelineStageFlags::TOP_OF_PIPE, vk::AccessFlags::empty())
+    } else {
+        (vk::PipelineStageFlags::FRAGMENT_SHADER, vk::AccessFlags::SHADER_READ)
+    };
+    let to_transfer = vk::ImageMemoryBarrier::default()
+        .src_access_mask(src_access)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .old_layout(old_layout)
+        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(range);
+    device.cmd_pipeline_barrier(
+        cmd,
+        src_stage,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[to_transfer],
+    );
+    device.cmd_copy_buffer_to_image(cmd, staging, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, regions);
+}
+
+/// Hand the freshly copied `range` to the fragment shader
+unsafe fn record_to_shader(
+    device: &ash::Device,
+    cmd: vk::CommandBuffer,
+    image: vk::Image,
+    range: vk::ImageSubresourceRange,
+) {
+    let to_shader = vk::ImageMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(range);
+    device.cmd_pipeline_barrier(
+        cmd,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[to_shader],
+    );
+}
+
+/// Fill levels 1.. of `image` by halving blits from level 0, leaving every
+/// level in SHADER_READ_ONLY_OPTIMAL. All levels start in TRANSFER_DST_OPTIMAL.
+unsafe fn record_mip_chain(
+    device: &ash::Device,
+    cmd: vk::CommandBuffer,
+    image: vk::Image,
+    (width, height): (u32, u32),
+    level_count: u32,
+    layer_count: u32,
+) {
+    let barrier = |level, old_layout, new_layout, src_access_mask, dst_access_mask| {
+        vk::ImageMemoryBarrier::default()
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask)
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: level,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count,
+            })
+    };
+    let layers = |mip_level| vk::ImageSubresourceLayers {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        mip_level,
+        base_array_layer: 0,
+        layer_count,
+    };
+
+    let (mut w, mut h) = (width as i32, height as i32);
+    for level in 1..level_count {
+        // The previous level was just written (by the copy or the last blit)
+        let to_src = barrier(
+            level - 1,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::TRANSFER_READ,
+        );
+        device.cmd_pipeline_barrier(
+            cmd,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_src],
+        );
+
+        let (next_w, next_h) = ((w / 2).max(1), (h / 2).max(1));
+        let blit = vk::ImageBlit::default()
+            .src_subresource(layers(level - 1))
+            .src_offsets([vk::Offset3D::default(), vk::Offset3D { x: w, y: h, z: 1 }])
+            .dst_subresource(layers(level))
+            .dst_offsets([vk::Offset3D::default(), vk::Offset3D { x: next_w, y: next_h, z: 1 }]);
+        device.cmd_blit_image(
+            cmd,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[blit],
+            vk::Filter::LINEAR,
+        );
+
+        let to_shader = barrier(
+            level - 1,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags::TRANSFER_READ,
+            vk::AccessFlags::SHADER_READ,
+        );
+        device.cmd_pipeline_barrier(
+            cmd,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_shader],
+        );
+        (w, h) = (next_w, next_h);
+    }
+
+    // The last level is only ever written
+    let last = barrier(
+        level_count - 1,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        vk::AccessFlags::TRANSFER_WRITE,
+        vk::AccessFlags::SHADER_READ,
+    );
+    device.cmd_pipeline_barrier(
+        cmd,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[last],
+    );
+}
+
+fn find_memory_type(
+    mem_properties: &vk::PhysicalDeviceMemoryProperties,
+    type_bits: u32,
+    flags: vk::MemoryPropertyFlags,
+) -> Result<u32, MetalshaderError> {
+    (0..mem_properties.memory_type_count)
+        .find(|&i| {
+            type_bits & (1 << i) != 0 && mem_properties.memory_types[i as usize].property_flags.contains(flags)
+        })
+        .ok_or(MetalshaderError::NoMemoryType)
+}
@@ -0,0 +1,73 @@
+// `--check`: compile and load every shader without a display, for CI
+//
+// Each `.frag` (or `.wgsl`) source is compiled fresh and loaded into
+// a small headless `VulkanRenderer`, which runs the same entry-point and
+// descriptor-layout validation as the viewer. The exit code reflects failures.
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use metalshader::options::Options;
+use metalshader::shader;
+use metalshader::{RenderConfig, ShaderCompiler, VulkanRenderer};
+
+/// Render target size; only pipeline creation matters here
+const CHECK_SIZE: u32 = 64;
+
+pub fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let dirs = match &options.check_dir {
+        Some(dir) => vec![dir.clone()],
+        None => options.search_dirs.clone(),
+    };
+    let sources = frag_sources(&dirs);
+    if sources.is_empty() {
+        return Err(format!("No .frag/.wgsl sources found in {}", dirs.join(" ")).into());
+    }
+
+    let compiler = ShaderCompiler::new().with_werror(options.werror);
+    let render_config = RenderConfig {
+        tiling: options.tiling,
+        software: options.software,
+        ..RenderConfig::default()
+    };
+    let mut renderer = VulkanRenderer::new(CHECK_SIZE, CHECK_SIZE, render_config)?;
+    println!("Checking {} shader(s) on {}", sources.len(), renderer.get_device_name());
+
+    let mut failures = Vec::new();
+    for source in &sources {
+        let result = compiler
+            .compile(source)
+            .and_then(|(vert, frag)| renderer.load_shader(&vert, &frag, &options.entry_points));
+        match result {
+            Ok(()) => println!("PASS {}", source.display()),
+            Err(e) => {
+                println!("FAIL {}: {}", source.display(), e);
+                failures.push(source);
+            }
+        }
+    }
+
+    println!();
+    println!("{} passed, {} failed", sources.len() - failures.len(), failures.len());
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} of {} shaders failed", failures.len(), sources.len()).into())
+    }
+}
+
+/// Shader sources directly in `dirs`, sorted for a stable report
+fn frag_sources(dirs: &[String]) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = dirs
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file() && path.extension().is_some_and(|ext| shader::SOURCE_EXTENSIONS.iter().any(|e| ext == *e))
+        })
+        .collect();
+    sources.sort();
+    sources.dedup();
+    sources
+}
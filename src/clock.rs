@@ -0,0 +1,222 @@
+// Shader time (iTime) shared by all platform entry points
+
+use std::time::Instant;
+
+/// Time step for Left/Right while paused: one frame at 60 fps
+pub const FRAME_STEP: f64 = 1.0 / 60.0;
+/// Time step for Shift+Left/Right while paused
+pub const COARSE_STEP: f64 = 1.0;
+/// Seek for `,`/`.`, paused or not
+pub const SEEK_STEP: f64 = 5.0;
+
+/// iTime (2^15 s, about 9 hours) beyond which f32 can no longer resolve a
+/// quarter of a 60 fps frame: representable values are 1/256 s apart there,
+/// and shaders that multiply time by a speed factor visibly stutter soon after.
+/// Time is kept in f64 throughout and only narrowed in `uniform_time`, so
+/// `--loop-time` (which keeps iTime below its period) is the mitigation.
+pub const F32_PRECISION_LIMIT: f64 = 32768.0;
+
+/// Drives `iTime`: wall-clock seconds since start, shifted by `--start-time`
+/// and by any scrubbing, frozen while paused, wrapped by `--loop-time`
+pub struct ShaderClock {
+    start: Instant,
+    /// iTime at `start`
+    offset: f64,
+    /// iTime while paused
+    paused_at: Option<f64>,
+    initial_offset: f64,
+    /// iTime wraps to 0 after this many seconds
+    period: Option<f64>,
+    precision_warned: bool,
+    /// Untouched by pause/scrub/restart, for FPS statistics
+    created: Instant,
+}
+
+impl ShaderClock {
+    pub fn new(offset: f64) -> Self {
+        let now = Instant::now();
+        Self { start: now, offset, paused_at: None, initial_offset: offset, period: None, precision_warned: false, created: now }
+    }
+
+    /// Wrap iTime into `[0, period)`; `None` or a non-positive period disables it
+    pub fn with_loop(mut self, period: Option<f64>) -> Self {
+        self.period = period.filter(|p| *p > 0.0);
+        self
+    }
+
+    /// Current shader time in seconds
+    pub fn time(&self) -> f64 {
+        let t = self.unwrapped_time();
+        match self.period {
+            Some(period) => t.rem_euclid(period),
+            None => t,
+        }
+    }
+
+    /// iTime narrowed to f32 for the UBO; warns once when it grows past
+    /// `F32_PRECISION_LIMIT`
+    pub fn uniform_time(&mut self) -> f32 {
+        let t = self.time();
+        if t.abs() >= F32_PRECISION_LIMIT && !self.precision_warned {
+            self.precision_warned = true;
+            eprintln!(
+                "Warning: iTime = {:.0}s exceeds f32 precision for smooth animation; use --loop-time to wrap it",
+                t
+            );
+        }
+        t as f32
+    }
+
+    fn unwrapped_time(&self) -> f64 {
+        self.paused_at
+            .unwrap_or_else(|| self.offset + self.start.elapsed().as_secs_f64())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Freeze or resume; resuming continues from the (possibly scrubbed) paused time
+    pub fn toggle_pause(&mut self) {
+        match self.paused_at.take() {
+            Some(t) => {
+                self.start = Instant::now();
+                self.offset = t;
+            }
+            None => self.paused_at = Some(self.unwrapped_time()),
+        }
+    }
+
+    /// Move iTime by `delta` seconds
+    pub fn step(&mut self, delta: f64) {
+        match &mut self.paused_at {
+            Some(t) => *t += delta,
+            None => self.offset += delta,
+        }
+    }
+
+    /// Move iTime by `delta` seconds, stopping at 0 rather than going negative
+    pub fn seek(&mut self, delta: f64) {
+        self.step(delta.max(-self.unwrapped_time()));
+    }
+
+    /// Real seconds since the clock was created, for FPS statistics
+    pub fn elapsed(&self) -> f64 {
+        self.created.elapsed().as_secs_f64()
+    }
+
+    /// Start over at the initial offset (e.g. a newly opened shader)
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+        self.offset = self.initial_offset;
+        if self.paused_at.is_some() {
+            self.paused_at = Some(self.initial_offset);
+        }
+    }
+}
+
+/// Rolling frames-per-second estimate for `iFrameRate`
+///
+/// An exponential moving average of frame times with a one-second time
+/// constant, so it follows a drop within a second or two regardless of the
+/// frame rate itself. Starts at 60.
+pub struct FrameRate {
+    last: Option<Instant>,
+    /// Smoothed seconds per frame
+    frame_time: f64,
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        Self { last: None, frame_time: FRAME_STEP }
+    }
+}
+
+impl FrameRate {
+    /// Record a frame now and return the updated estimate
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        if let Some(last) = self.last.replace(now) {
+            self.record(now.duration_since(last).as_secs_f64());
+        }
+        self.fps()
+    }
+
+    fn record(&mut self, dt: f64) {
+        let alpha = dt.min(1.0);
+        self.frame_time += (dt - self.frame_time) * alpha;
+    }
+
+    pub fn fps(&self) -> f32 {
+        (1.0 / self.frame_time.max(1e-6)) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_starts_at_offset() {
+        let clock = ShaderClock::new(42.5);
+        let t = clock.time();
+        assert!(t >= 42.5 && t < 43.5);
+        assert!(clock.elapsed() < 1.0);
+    }
+
+    #[test]
+    fn test_scrub_while_paused_then_resume() {
+        let mut clock = ShaderClock::new(10.0);
+        clock.toggle_pause();
+        let paused = clock.time();
+        clock.step(COARSE_STEP);
+        clock.step(-FRAME_STEP);
+        assert_eq!(clock.time(), paused + COARSE_STEP - FRAME_STEP);
+
+        clock.toggle_pause();
+        assert!(!clock.is_paused());
+        assert!(clock.time() >= paused + COARSE_STEP - FRAME_STEP);
+    }
+
+    #[test]
+    fn test_seek_stops_at_zero() {
+        let mut clock = ShaderClock::new(3.0);
+        clock.seek(SEEK_STEP);
+        assert!(clock.time() >= 8.0);
+        clock.seek(-SEEK_STEP);
+        clock.seek(-SEEK_STEP);
+        let t = clock.time();
+        assert!((0.0..0.5).contains(&t), "{}", t);
+    }
+
+    #[test]
+    fn test_loop_wraps_time() {
+        let mut clock = ShaderClock::new(9.5).with_loop(Some(4.0));
+        clock.toggle_pause();
+        assert!((clock.time() - 1.5).abs() < 0.1);
+        clock.step(-2.0);
+        assert!((clock.time() - 3.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_precision_warning_only_without_loop() {
+        let mut clock = ShaderClock::new(F32_PRECISION_LIMIT + 1.0);
+        clock.uniform_time();
+        assert!(clock.precision_warned);
+
+        let mut looped = ShaderClock::new(F32_PRECISION_LIMIT + 1.0).with_loop(Some(60.0));
+        assert!(looped.uniform_time() < 60.0);
+        assert!(!looped.precision_warned);
+    }
+
+    #[test]
+    fn test_frame_rate_follows_a_drop() {
+        let mut rate = FrameRate::default();
+        assert_eq!(rate.fps(), 60.0);
+        // Five seconds at 20 fps
+        for _ in 0..100 {
+            rate.record(0.05);
+        }
+        assert!((rate.fps() - 20.0).abs() < 0.5, "{}", rate.fps());
+    }
+}
@@ -0,0 +1,245 @@
+// Persistent defaults from config.toml in the platform config directory
+//
+// Every key is optional; whatever the file sets becomes the default that
+// command line flags then override. Example:
+//
+//     shader = "seascape"
+//     search_dirs = ["~/shaders", "./shaders"]
+//     mouse_smoothing = 0.1
+//     tiling = "optimal"
+
+use crate::options::{self, Options};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub shader: Option<String>,
+    pub search_dirs: Option<Vec<String>>,
+    pub mouse_smoothing: Option<f64>,
+    pub tiling: Option<String>,
+    pub format: Option<String>,
+    pub tonemap: Option<String>,
+    pub composite_alpha: Option<String>,
+    pub blend: Option<bool>,
+    pub software: Option<bool>,
+    pub werror: Option<bool>,
+    pub monitor: Option<usize>,
+    pub size: Option<String>,
+    pub refresh: Option<f64>,
+    pub window_sizes: Option<String>,
+    pub osc: Option<u16>,
+    pub midi: Option<String>,
+    pub midi_cc: Option<String>,
+    pub fullscreen: Option<bool>,
+    pub kiosk: Option<bool>,
+    pub kiosk_interval: Option<f64>,
+    pub overlay: Option<f64>,
+    pub show_fps: Option<bool>,
+    pub start_time: Option<f64>,
+    pub loop_time: Option<f64>,
+    pub render_scale: Option<f32>,
+    pub aspect: Option<String>,
+    pub flip_y: Option<bool>,
+    pub frag_coord: Option<String>,
+    pub no_vsync: Option<bool>,
+    pub shader_resolution: Option<bool>,
+    pub dpi: Option<String>,
+    pub wide_gamut: Option<bool>,
+    pub vert_entry: Option<String>,
+    pub frag_entry: Option<String>,
+    pub channel0: Option<String>,
+    pub channel0_cube: Option<bool>,
+    pub channel0_wrap: Option<String>,
+    pub channel0_filter: Option<String>,
+}
+
+/// `config.toml` in the platform config directory (`paths::config_dir`).
+/// On macOS a file left at the old `~/.config/metalshader/config.toml` is
+/// still read while the new one doesn't exist
+pub fn config_path() -> Option<PathBuf> {
+    let path = crate::paths::config_dir()?.join("config.toml");
+    let legacy = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/metalshader/config.toml"));
+    match legacy {
+        Some(legacy) if !path.exists() && legacy.exists() => Some(legacy),
+        _ => Some(path),
+    }
+}
+
+impl Config {
+    /// Load the config file; a missing file is an empty config, a broken one
+    /// is reported and ignored
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        match toml::from_str(&text) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Warning: ignoring {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Options with this config applied over the built-in defaults
+    pub fn apply(&self, mut opts: Options) -> Result<Options, String> {
+        if let Some(shader) = &self.shader {
+            opts.shader = shader.clone();
+        }
+        if let Some(dirs) = &self.search_dirs {
+            opts.search_dirs = dirs.iter().map(|d| expand_home(d)).collect();
+        }
+        if let Some(tau) = self.mouse_smoothing.filter(|tau| tau.is_finite()) {
+            opts.mouse_smoothing = tau.max(0.0);
+        }
+        if let Some(tiling) = &self.tiling {
+            opts.tiling = options::parse_tiling(tiling).map_err(|e| format!("config: {}", e))?;
+        }
+        if let Some(format) = &self.format {
+            opts.format = options::parse_render_format(format).map_err(|e| format!("config: {}", e))?;
+        }
+        if let Some(tonemap) = &self.tonemap {
+            opts.tonemap = options::parse_tonemap(tonemap).map_err(|e| format!("config: {}", e))?;
+        }
+        if let Some(mode) = &self.composite_alpha {
+            opts.composite_alpha = options::parse_composite_alpha(mode).map_err(|e| format!("config: {}", e))?;
+        }
+        if let Some(blend) = self.blend {
+            opts.blend = blend;
+        }
+        if let Some(software) = self.software {
+            opts.software = software;
+        }
+        if let Some(werror) = self.werror {
+            opts.werror = werror;
+        }
+        if let Some(fullscreen) = self.fullscreen {
+            opts.fullscreen = fullscreen;
+        }
+        if let Some(kiosk) = self.kiosk {
+            opts.kiosk = kiosk;
+        }
+        if let Some(interval) = self.kiosk_interval.filter(|i| *i > 0.0 && i.is_finite()) {
+            opts.kiosk_interval = interval;
+        }
+        if let Some(secs) = self.overlay {
+            opts.overlay_secs = secs.max(0.0);
+        }
+        if let Some(show_fps) = self.show_fps {
+            opts.show_fps = show_fps;
+        }
+        if let Some(start_time) = self.start_time {
+            opts.start_time = start_time;
+        }
+        if let Some(period) = self.loop_time.filter(|p| *p > 0.0) {
+            opts.loop_time = Some(period);
+        }
+        if let Some(scale) = self.render_scale {
+            if !(scale > 0.0 && scale <= 1.0) {
+                return Err("config: render_scale must be in (0, 1]".to_string());
+            }
+            opts.render_scale = scale;
+        }
+        if let Some(aspect) = &self.aspect {
+            opts.aspect = Some(options::parse_aspect(aspect).map_err(|e| format!("config: {}", e))?);
+        }
+        if let Some(flip_y) = self.flip_y {
+            opts.flip_y = flip_y;
+        }
+        if let Some(frag_coord) = &self.frag_coord {
+            opts.frag_coord = options::parse_frag_coord(frag_coord).map_err(|e| format!("config: {}", e))?;
+        }
+        if let Some(no_vsync) = self.no_vsync {
+            opts.no_vsync = no_vsync;
+        }
+        if let Some(shader_resolution) = self.shader_resolution {
+            opts.shader_resolution = shader_resolution;
+        }
+        if let Some(wide_gamut) = self.wide_gamut {
+            opts.wide_gamut = wide_gamut;
+        }
+        if let Some(dpi) = &self.dpi {
+            opts.dpi = options::parse_dpi(dpi).map_err(|e| format!("config: {}", e))?;
+        }
+        if let Some(name) = &self.vert_entry {
+            opts.entry_points.vertex = name.clone();
+        }
+        if let Some(name) = &self.frag_entry {
+            opts.entry_points.fragment = name.clone();
+        }
+        if let Some(spec) = &self.channel0 {
+            opts.channel0 = Some(spec.split(',').map(|path| expand_home(path.trim())).collect::<Vec<_>>().join(","));
+        }
+        if let Some(cube) = self.channel0_cube {
+            opts.channel0_cube = cube;
+        }
+        if let Some(wrap) = &self.channel0_wrap {
+            opts.channel0_sampler.wrap = options::parse_channel_wrap(wrap).map_err(|e| format!("config: {}", e))?;
+        }
+        if let Some(filter) = &self.channel0_filter {
+            opts.channel0_sampler.filter = options::parse_channel_filter(filter).map_err(|e| format!("config: {}", e))?;
+        }
+        if self.monitor.is_some() {
+            opts.monitor = self.monitor;
+        }
+        if let Some(size) = &self.size {
+            opts.size = Some(options::parse_size(size).map_err(|e| format!("config: {}", e))?);
+        }
+        if let Some(refresh) = self.refresh {
+            if refresh <= 0.0 {
+                return Err("config: refresh must be > 0".to_string());
+            }
+            opts.refresh = Some(refresh);
+        }
+        if let Some(sizes) = &self.window_sizes {
+            opts.window_sizes = options::parse_window_sizes(sizes).map_err(|e| format!("config: {}", e))?;
+        }
+        if self.osc.is_some() {
+            opts.osc_port = self.osc;
+        }
+        if self.midi.is_some() {
+            opts.midi = self.midi.clone();
+        }
+        if let Some(cc) = &self.midi_cc {
+            opts.midi_cc = options::parse_midi_cc(cc).map_err(|e| format!("config: {}", e))?;
+        }
+        Ok(opts)
+    }
+}
+
+fn expand_home(dir: &str) -> String {
+    match (dir.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ => dir.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_overrides_defaults() {
+        let config: Config = toml::from_str("shader = \"seascape\"\ntiling = \"optimal\"\n").unwrap();
+        let opts = config.apply(Options::default()).unwrap();
+        assert_eq!(opts.shader, "seascape");
+        assert_eq!(opts.tiling, options::RenderTiling::Optimal);
+        assert_eq!(opts.mouse_smoothing, Options::default().mouse_smoothing);
+    }
+
+    #[test]
+    fn test_window_sizes() {
+        let config: Config = toml::from_str("window_sizes = \"2560x1080, 1080x1920\"\n").unwrap();
+        let opts = config.apply(Options::default()).unwrap();
+        assert_eq!(opts.window_sizes, vec![(2560, 1080), (1080, 1920)]);
+        assert_eq!(Options::default().window_sizes.len(), 5);
+
+        let config: Config = toml::from_str("window_sizes = \"2560:1080\"\n").unwrap();
+        assert!(config.apply(Options::default()).is_err());
+    }
+}
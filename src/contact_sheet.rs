@@ -0,0 +1,102 @@
+// `--contact-sheet FILE.png`: every shader in one image, for browsing a library
+//
+// Each scanned shader is rendered by the `Thumbnailer` with its name drawn
+// in the overlay font, and the frames are tiled `--cols` wide into a PNG
+// (feature `png`). A shader that fails to load leaves a dark tile.
+
+use std::error::Error;
+use std::path::Path;
+
+use metalshader::options::Options;
+use metalshader::ShaderManager;
+
+use crate::thumbnail::{self, Thumbnailer};
+
+/// RGBA of tiles whose shader didn't render
+const BACKGROUND: [u8; 4] = [16, 16, 16, 255];
+
+pub fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let out = options.contact_sheet.as_deref().unwrap_or_default();
+    if !out.to_lowercase().ends_with(".png") {
+        return Err(format!("--contact-sheet writes PNG; use a .png file name, not '{}'", out).into());
+    }
+
+    let mut shader_manager = ShaderManager::new();
+    let search_dirs: Vec<&str> = options.search_dirs.iter().map(String::as_str).collect();
+    shader_manager.scan_shaders(&search_dirs)?;
+    if shader_manager.is_empty() {
+        return Err(format!("No compiled shaders found in {}", options.search_dirs.join(" ")).into());
+    }
+
+    let mut thumbnailer = Thumbnailer::new(options)?;
+    let (width, height) = thumbnailer.size();
+    let mut sheet = Sheet::new(shader_manager.len(), options.cols, (width, height));
+    let mut rendered = 0;
+    for index in 0..shader_manager.len() {
+        let shader = shader_manager.get(index).unwrap();
+        match thumbnailer.render(shader, &options.entry_points, true) {
+            Ok(rgba) => {
+                sheet.place(index, &rgba);
+                rendered += 1;
+            }
+            Err(e) => eprintln!("Skipping '{}': {}", shader.name, e),
+        }
+    }
+
+    thumbnail::write_png(Path::new(out), &sheet.pixels, sheet.width, sheet.height)?;
+    println!(
+        "Wrote {} ({}x{}, {} of {} shaders at {}x{})",
+        out, sheet.width, sheet.height, rendered, shader_manager.len(), width, height
+    );
+    Ok(())
+}
+
+/// RGBA8 image the thumbnails are tiled into, row by row
+struct Sheet {
+    cols: u32,
+    tile: (u32, u32),
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Sheet {
+    /// Room for `count` tiles, `cols` per row (fewer if there aren't as many)
+    fn new(count: usize, cols: u32, tile: (u32, u32)) -> Self {
+        let cols = cols.min(count as u32).max(1);
+        let rows = (count as u32).div_ceil(cols).max(1);
+        let (width, height) = (cols * tile.0, rows * tile.1);
+        let pixels = BACKGROUND.repeat((width * height) as usize);
+        Self { cols, tile, width, height, pixels }
+    }
+
+    /// Copy an RGBA8 frame of the tile size into tile `index`
+    fn place(&mut self, index: usize, frame: &[u8]) {
+        let (col, row) = (index as u32 % self.cols, index as u32 / self.cols);
+        let row_bytes = self.tile.0 as usize * 4;
+        for (y, src) in frame.chunks_exact(row_bytes).take(self.tile.1 as usize).enumerate() {
+            let start = ((row * self.tile.1 + y as u32) * self.width + col * self.tile.0) as usize * 4;
+            self.pixels[start..start + row_bytes].copy_from_slice(src);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiles() {
+        let mut sheet = Sheet::new(3, 2, (2, 1));
+        assert_eq!((sheet.width, sheet.height), (4, 2));
+        sheet.place(1, &[1, 2, 3, 255, 4, 5, 6, 255]);
+        sheet.place(2, &[7, 8, 9, 255, 7, 8, 9, 255]);
+        assert_eq!(&sheet.pixels[8..16], &[1, 2, 3, 255, 4, 5, 6, 255]);
+        assert_eq!(&sheet.pixels[16..24], &[7, 8, 9, 255, 7, 8, 9, 255]);
+        assert_eq!(&sheet.pixels[0..4], &BACKGROUND);
+        assert_eq!(&sheet.pixels[24..32], &[BACKGROUND, BACKGROUND].concat()[..]);
+
+        // Fewer shaders than columns
+        assert_eq!(Sheet::new(1, 4, (8, 8)).width, 8);
+    }
+}
@@ -0,0 +1,93 @@
+// Error type of the renderers, the shader compiler and the Vulkan loader
+//
+// Callers can match on the variant (e.g. skip a shader on `FrameTimeout`,
+// rebuild the renderer on a lost device) instead of parsing messages. The
+// messages themselves are unchanged from the former string errors.
+
+use std::path::PathBuf;
+
+use ash::vk;
+
+use crate::vulkan_loader::INSTALL_HINT;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetalshaderError {
+    /// libvulkan couldn't be loaded
+    #[error("Vulkan loader not found ({0}).\n{}", INSTALL_HINT)]
+    LoaderNotFound(String),
+
+    /// The instance enumerates no usable physical device
+    #[error("No Vulkan physical device found.\n{}", INSTALL_HINT)]
+    NoDevice,
+
+    /// The window has no display or window handle to create a surface from
+    #[error("Window handle unavailable: {0}")]
+    WindowHandle(String),
+
+    #[error("Failed to create Vulkan instance: {0}")]
+    InstanceCreation(vk::Result),
+
+    /// Any other failing Vulkan call
+    #[error("Vulkan error: {0}")]
+    VulkanError(#[from] vk::Result),
+
+    #[error("No suitable memory type found")]
+    NoMemoryType,
+
+    /// The GPU didn't finish a frame in time, most likely a runaway shader.
+    /// The frame stays pending and is waited for again before the next
+    /// `render_frame` or `load_shader` touches the command buffer.
+    #[error("GPU did not finish the frame within {0}s")]
+    FrameTimeout(u64),
+
+    #[error("No shader loaded")]
+    NoShaderLoaded,
+
+    /// `export_render_target_fd` on a renderer built without
+    /// `RenderConfig::exportable`
+    #[error("Render target is not exportable (set RenderConfig::exportable)")]
+    NotExportable,
+
+    /// SPIR-V that doesn't parse or doesn't fit the pipeline layout
+    /// (entry points, descriptor bindings, push-constant size)
+    #[error("{0}")]
+    InvalidShader(String),
+
+    #[error("Shader file not found: {}", .0.display())]
+    ShaderNotFound(PathBuf),
+
+    #[error("Unknown shader type: {} (expected .frag, .glsl, .fsh, .wgsl or .spv)", .0.display())]
+    UnknownShaderType(PathBuf),
+
+    #[error("glslangValidator not found. Install with: brew install glslang, or build with --features shaderc")]
+    CompilerNotFound,
+
+    /// The compiler rejected the shader; `stderr` holds its diagnostics
+    #[error("Failed to compile {}:\n{}", .path.display(), .stderr.trim_end())]
+    ShaderCompile { path: PathBuf, stderr: String },
+
+    /// `--werror` and the compiler printed warnings
+    #[error("{} has {count} warning(s) (--werror)", .path.display())]
+    CompileWarnings { path: PathBuf, count: usize },
+
+    /// A `.dds`/`.ktx2` channel texture that can't be parsed or isn't a
+    /// supported layout
+    #[error("Invalid texture {}: {reason}", .path.display())]
+    InvalidTexture { path: PathBuf, reason: String },
+
+    /// The device can't sample the texture's format and there is no CPU
+    /// decoder for it
+    #[error("Unsupported texture format: {0}")]
+    UnsupportedTextureFormat(String),
+
+    /// A display backend couldn't open or configure its output
+    #[error("Display initialization failed: {0}")]
+    DisplayInit(String),
+
+    /// No MIDI backend, or the `--midi` port couldn't be found or opened
+    #[error("MIDI input: {0}")]
+    Midi(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,198 @@
+// Copy a rendered frame into a scanout buffer with a different row pitch
+//
+// Used by the DRM and Redox display backends. With the `rayon` feature rows are
+// copied in parallel, which matters at 4K where a serial copy eats most of the
+// frame budget. Frames rendered below display resolution (`--render-scale`)
+// are upscaled on the way, and letterboxed frames (`--aspect`) get black bars.
+
+use crate::viewport::Viewport;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Copy `height` rows of `row_size` bytes from `src` (stride `src_stride`) to
+/// `dst` (stride `dst_stride`)
+///
+/// When both strides equal the row size (`width * 4` already meets the scanout
+/// alignment, the common case) the frame is contiguous and copied with a single
+/// `copy_from_slice`. Rows that would run past either buffer are skipped.
+pub fn copy_rows(
+    dst: &mut [u8],
+    dst_stride: usize,
+    src: &[u8],
+    src_stride: usize,
+    row_size: usize,
+    height: usize,
+) {
+    if row_size == 0 || height == 0 {
+        return;
+    }
+
+    if dst_stride == row_size && src_stride == row_size {
+        let len = (row_size * height).min(dst.len()).min(src.len());
+        // Keep whole rows only, matching the strided path
+        let len = len - len % row_size;
+        copy_contiguous(&mut dst[..len], &src[..len], row_size);
+        return;
+    }
+
+    // Only rows fully inside both buffers
+    let rows = height
+        .min(rows_fitting(dst.len(), dst_stride, row_size))
+        .min(rows_fitting(src.len(), src_stride, row_size));
+
+    #[cfg(feature = "rayon")]
+    {
+        dst.par_chunks_mut(dst_stride)
+            .zip(src.par_chunks(src_stride))
+            .take(rows)
+            .for_each(|(d, s)| d[..row_size].copy_from_slice(&s[..row_size]));
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        // Zipped chunks instead of indexing: no per-row offset arithmetic or
+        // bounds checks beyond the single slice per row
+        dst.chunks_mut(dst_stride)
+            .zip(src.chunks(src_stride))
+            .take(rows)
+            .for_each(|(d, s)| d[..row_size].copy_from_slice(&s[..row_size]));
+    }
+}
+
+/// Nearest-neighbour scale a `src_size` frame (stride `src_stride`) to
+/// `dst_size` (stride `dst_stride`), 4 bytes per pixel
+///
+/// Rows that would run past either buffer are skipped, as in `copy_rows`.
+pub fn scale_rows(
+    dst: &mut [u8],
+    dst_stride: usize,
+    dst_size: (u32, u32),
+    src: &[u8],
+    src_stride: usize,
+    src_size: (u32, u32),
+) {
+    let (dst_width, dst_height) = (dst_size.0 as usize, dst_size.1 as usize);
+    let (src_width, src_height) = (src_size.0 as usize, src_size.1 as usize);
+    if dst_width == 0 || dst_height == 0 || src_width == 0 || src_height == 0 {
+        return;
+    }
+
+    let dst_rows = dst_height.min(rows_fitting(dst.len(), dst_stride, dst_width * 4));
+    let src_rows = src_height.min(rows_fitting(src.len(), src_stride, src_width * 4));
+    // Source byte offset of every destination pixel within a row
+    let columns: Vec<usize> = (0..dst_width).map(|x| x * src_width / dst_width * 4).collect();
+    let scale_row = |y: usize, d: &mut [u8]| {
+        let sy = y * src_height / dst_height;
+        if sy >= src_rows {
+            return;
+        }
+        let s = &src[sy * src_stride..];
+        for (pixel, &sx) in d[..dst_width * 4].chunks_exact_mut(4).zip(&columns) {
+            pixel.copy_from_slice(&s[sx..sx + 4]);
+        }
+    };
+
+    #[cfg(feature = "rayon")]
+    {
+        dst.par_chunks_mut(dst_stride)
+            .take(dst_rows)
+            .enumerate()
+            .for_each(|(y, d)| scale_row(y, d));
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        dst.chunks_mut(dst_stride)
+            .take(dst_rows)
+            .enumerate()
+            .for_each(|(y, d)| scale_row(y, d));
+    }
+}
+
+/// Zero everything in a `size` frame (stride `stride`) outside `viewport`
+pub fn clear_outside(dst: &mut [u8], stride: usize, size: (u32, u32), viewport: Viewport) {
+    let row_size = size.0 as usize * 4;
+    let left = (viewport.x as usize * 4).min(row_size);
+    let right = ((viewport.x + viewport.width) as usize * 4).min(row_size);
+    let (top, bottom) = (viewport.y as usize, (viewport.y + viewport.height) as usize);
+    let rows = (size.1 as usize).min(rows_fitting(dst.len(), stride, row_size));
+    for (y, row) in dst.chunks_mut(stride).take(rows).enumerate() {
+        if y < top || y >= bottom {
+            row[..row_size].fill(0);
+        } else {
+            row[..left].fill(0);
+            row[right..row_size].fill(0);
+        }
+    }
+}
+
+/// Number of `row_size` rows at `stride` that fit entirely in `len` bytes
+fn rows_fitting(len: usize, stride: usize, row_size: usize) -> usize {
+    if len < row_size {
+        0
+    } else {
+        (len - row_size) / stride.max(1) + 1
+    }
+}
+
+#[cfg(feature = "rayon")]
+fn copy_contiguous(dst: &mut [u8], src: &[u8], row_size: usize) {
+    // Chunks of many rows keep per-task overhead negligible
+    let chunk = row_size * 64;
+    dst.par_chunks_mut(chunk)
+        .zip(src.par_chunks(chunk))
+        .for_each(|(d, s)| d.copy_from_slice(s));
+}
+
+#[cfg(not(feature = "rayon"))]
+fn copy_contiguous(dst: &mut [u8], src: &[u8], _row_size: usize) {
+    dst.copy_from_slice(src);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_rows_with_padding() {
+        // 2 rows of 4 bytes, source padded to 6, destination padded to 8
+        let src = [1, 2, 3, 4, 0, 0, 5, 6, 7, 8, 0, 0];
+        let mut dst = [9u8; 16];
+        copy_rows(&mut dst, 8, &src, 6, 4, 2);
+        assert_eq!(dst, [1, 2, 3, 4, 9, 9, 9, 9, 5, 6, 7, 8, 9, 9, 9, 9]);
+    }
+
+    #[test]
+    fn test_equal_strides_copy_whole_frame() {
+        let src: Vec<u8> = (0..12).collect();
+        let mut dst = [0u8; 12];
+        copy_rows(&mut dst, 4, &src, 4, 4, 3);
+        assert_eq!(dst.to_vec(), src);
+
+        // A short source only yields the rows it fully contains
+        let mut dst = [0u8; 12];
+        copy_rows(&mut dst, 4, &src[..6], 4, 4, 3);
+        assert_eq!(dst, [0, 1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_scale_rows_doubles_pixels() {
+        // 2x1 source (row padded to 12 bytes) into a 4x2 destination
+        let src = [1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0];
+        let mut dst = [0u8; 32];
+        scale_rows(&mut dst, 16, (4, 2), &src, 12, (2, 1));
+        let row = [1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2];
+        assert_eq!(dst[..16], row);
+        assert_eq!(dst[16..], row);
+    }
+
+    #[test]
+    fn test_clear_outside_leaves_viewport() {
+        // 3x3 frame, viewport is the center pixel
+        let mut dst = [7u8; 36];
+        clear_outside(&mut dst, 12, (3, 3), Viewport { x: 1, y: 1, width: 1, height: 1 });
+        let lit: Vec<usize> = (0..9).filter(|p| dst[p * 4] != 0).collect();
+        assert_eq!(lit, vec![4]);
+    }
+}
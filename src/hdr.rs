@@ -0,0 +1,119 @@
+// Float render target readback (`--format rgba16f|rgba32f`)
+//
+// Float targets hold RGBA in the target's own precision. The EXR writer reads
+// them as f32, and the DRM/Redox display path converts them to the 8-bit BGRA
+// its framebuffers take, through the `--tonemap` curve and then clamped to
+// [0, 1] like an 8-bit target would be.
+
+use crate::options::{RenderFormat, Tonemap};
+
+/// IEEE binary16 to f32, for rgba16f readback
+pub fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        e => (1.0 + mantissa / 1024.0) * 2f32.powi(e as i32 - 15),
+    }
+}
+
+/// RGBA of pixel `x` in a `format` row; 8-bit rows are BGRA
+pub fn pixel(format: RenderFormat, row: &[u8], x: usize) -> [f32; 4] {
+    let at = x * format.bytes_per_pixel();
+    match format {
+        RenderFormat::Rgba8 => {
+            let [b, g, r, a] = [row[at], row[at + 1], row[at + 2], row[at + 3]];
+            [r, g, b, a].map(|c| c as f32 / 255.0)
+        }
+        RenderFormat::Rgba16f => std::array::from_fn(|c| {
+            half_to_f32(u16::from_le_bytes([row[at + 2 * c], row[at + 2 * c + 1]]))
+        }),
+        RenderFormat::Rgba32f => std::array::from_fn(|c| {
+            f32::from_le_bytes(row[at + 4 * c..at + 4 * c + 4].try_into().unwrap())
+        }),
+    }
+}
+
+/// Map one linear color channel through `curve`; alpha is left alone
+pub fn tonemap(curve: Tonemap, value: f32) -> f32 {
+    match curve {
+        Tonemap::None => value,
+        Tonemap::Reinhard => {
+            let value = value.max(0.0);
+            value / (1.0 + value)
+        }
+        Tonemap::Aces => {
+            let value = value.max(0.0);
+            value * (2.51 * value + 0.03) / (value * (2.43 * value + 0.59) + 0.14)
+        }
+    }
+}
+
+/// Convert a `size` frame of `format` rows (stride `src_stride`) to tightly
+/// packed 8-bit BGRA in `dst` through `curve`, resizing it to fit
+pub fn to_bgra8(
+    dst: &mut Vec<u8>,
+    src: &[u8],
+    src_stride: usize,
+    size: (u32, u32),
+    format: RenderFormat,
+    curve: Tonemap,
+) {
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    dst.resize(width * height * 4, 0);
+    if width == 0 {
+        return;
+    }
+    for (y, out) in dst.chunks_exact_mut(width * 4).enumerate() {
+        let Some(row) = src.get(y * src_stride..y * src_stride + width * format.bytes_per_pixel()) else {
+            break;
+        };
+        for (x, bgra) in out.chunks_exact_mut(4).enumerate() {
+            let [r, g, b, a] = pixel(format, row, x);
+            let [r, g, b] = [r, g, b].map(|c| unorm8(tonemap(curve, c)));
+            bgra.copy_from_slice(&[b, g, r, unorm8(a)]);
+        }
+    }
+}
+
+/// Clamp to [0, 1] and quantize; NaN becomes 0
+fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_float_rows_to_bgra8() {
+        // 1.0, 0.5, 0.0, 2.0 as halves, then the same pixel as f32 with padding
+        let half: Vec<u8> = [0x3c00u16, 0x3800, 0x0000, 0x4000].iter().flat_map(|h| h.to_le_bytes()).collect();
+        let mut full: Vec<u8> = [1.0f32, 0.5, -1.0, 2.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        full.extend([0; 16]);
+
+        let mut out = Vec::new();
+        to_bgra8(&mut out, &half, 8, (1, 1), RenderFormat::Rgba16f, Tonemap::None);
+        assert_eq!(out, [0, 128, 255, 255]);
+        to_bgra8(&mut out, &full, 32, (1, 1), RenderFormat::Rgba32f, Tonemap::None);
+        assert_eq!(out, [0, 128, 255, 255]);
+        // Reinhard: 1.0 -> 0.5, 0.5 -> 1/3; alpha is only clamped
+        to_bgra8(&mut out, &full, 32, (1, 1), RenderFormat::Rgba32f, Tonemap::Reinhard);
+        assert_eq!(out, [0, 85, 128, 255]);
+        assert_eq!(unorm8(f32::NAN), 0);
+    }
+
+    #[test]
+    fn test_curves_are_monotonic_into_unit_range() {
+        for curve in [Tonemap::Reinhard, Tonemap::Aces] {
+            assert_eq!(tonemap(curve, 0.0), 0.0);
+            let samples: Vec<f32> = [0.01, 0.1, 0.5, 1.0, 4.0, 100.0].iter().map(|&v| tonemap(curve, v)).collect();
+            assert!(samples.windows(2).all(|w| w[0] < w[1]), "{:?}: {:?}", curve, samples);
+            // The ACES fit levels off at 2.51 / 2.43, just above 1; unorm8 clamps
+            assert!(samples.iter().all(|&v| v < 1.04), "{:?}: {:?}", curve, samples);
+        }
+    }
+}
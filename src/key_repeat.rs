@@ -0,0 +1,58 @@
+// Auto-repeat of held keys, shared by the evdev and winit input paths
+//
+// Holding Left/Right keeps stepping through shaders (or scrubbing iTime
+// while paused), and holding `,`/`.` keeps seeking. The OS repeats at its
+// own rate, often faster than a shader loads, so repeats are thinned to one
+// per `REPEAT_INTERVAL`. Repeats of every other key (toggles, resolution
+// modes, quit) are dropped, so holding F doesn't flicker fullscreen.
+
+use std::time::{Duration, Instant};
+
+/// Shortest time between two acted-on repeats of a held key
+pub const REPEAT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Decides which key presses and repeats to act on
+#[derive(Debug, Default)]
+pub struct KeyRepeat {
+    /// When the last press or accepted repeat happened
+    last: Option<Instant>,
+}
+
+impl KeyRepeat {
+    /// Whether to act on a key event at `now`: presses always, repeats
+    /// (`repeat`) only of keys that `repeats` and at most once per
+    /// `REPEAT_INTERVAL`
+    pub fn accept(&mut self, repeats: bool, repeat: bool, now: Instant) -> bool {
+        if repeat {
+            let due = self.last.is_none_or(|last| now.duration_since(last) >= REPEAT_INTERVAL);
+            if !repeats || !due {
+                return false;
+            }
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept() {
+        let mut keys = KeyRepeat::default();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert!(keys.accept(true, false, at(0)));
+        // Too soon after the press, then due
+        assert!(!keys.accept(true, true, at(30)));
+        assert!(keys.accept(true, true, at(100)));
+        assert!(!keys.accept(true, true, at(160)));
+        assert!(keys.accept(true, true, at(230)));
+        // Presses are never held back; keys that don't repeat ignore repeats
+        assert!(keys.accept(true, false, at(240)));
+        assert!(!keys.accept(false, true, at(1000)));
+        assert!(keys.accept(false, false, at(1010)));
+    }
+}
@@ -0,0 +1,58 @@
+// Metalshader library: Vulkan ShaderToy-style renderers, shader discovery
+// and compilation, usable without the viewer binary
+//
+// `VulkanRenderer` renders offscreen into host-visible memory (Linux/Redox),
+// `SwapchainRenderer` renders into a winit window (macOS, feature `window`).
+// Both take the `ShaderManager` paths and a `#[repr(C)]` uniform block such
+// as `ShaderToyUBO`. The DRM/evdev and Redox display and input backends the
+// binary uses live in `platform` (feature `drm`).
+
+pub mod animated_texture;
+pub mod clock;
+pub mod config;
+pub mod error;
+pub mod hdr;
+pub mod key_repeat;
+pub mod midi;
+pub mod mouse;
+pub mod options;
+pub mod osc;
+pub mod overlay;
+pub mod paths;
+pub mod picker;
+pub mod prefetch;
+pub mod remote;
+pub mod shader;
+pub mod shader_compiler;
+pub mod shadertoy;
+pub mod shutdown;
+pub mod texture_file;
+pub mod ubo;
+pub mod viewport;
+pub mod vulkan_loader;
+
+mod bc_decode;
+mod channel_texture;
+mod mapped_memory;
+mod shader_layout;
+mod spirv_reflect;
+
+#[cfg(not(target_os = "macos"))]
+pub mod renderer;
+#[cfg(all(not(target_os = "macos"), feature = "drm"))]
+mod frame_copy;
+#[cfg(all(not(target_os = "macos"), feature = "drm"))]
+pub mod platform;
+
+#[cfg(all(target_os = "macos", feature = "window"))]
+pub mod renderer_swapchain;
+
+#[cfg(not(target_os = "macos"))]
+pub use renderer::{RenderConfig, RenderTargetHandle, VulkanRenderer};
+#[cfg(all(target_os = "macos", feature = "window"))]
+pub use renderer_swapchain::{SwapchainConfig, SwapchainRenderer};
+pub use error::MetalshaderError;
+pub use shader::{ShaderHistory, ShaderInfo, ShaderManager};
+pub use shader_compiler::ShaderCompiler;
+pub use texture_file::TextureData;
+pub use ubo::ShaderToyUBO;
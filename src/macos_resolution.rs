@@ -3,7 +3,7 @@
 
 use std::ffi::c_void;
 
-type CGDirectDisplayID = u32;
+pub type CGDirectDisplayID = u32;
 type CGDisplayModeRef = *mut c_void;
 type CGError = i32;
 type CFArrayRef = *mut c_void;
@@ -12,6 +12,7 @@ type CFIndex = isize;
 #[link(name = "CoreGraphics", kind = "framework")]
 extern "C" {
     fn CGMainDisplayID() -> CGDirectDisplayID;
+    fn CGGetActiveDisplayList(max_displays: u32, displays: *mut CGDirectDisplayID, count: *mut u32) -> CGError;
     fn CGDisplayCopyDisplayMode(display: CGDirectDisplayID) -> CGDisplayModeRef;
     fn CGDisplayCopyAllDisplayModes(display: CGDirectDisplayID, options: *mut c_void) -> CFArrayRef;
     fn CGDisplaySetDisplayMode(display: CGDirectDisplayID, mode: CGDisplayModeRef, options: *mut c_void) -> CGError;
@@ -26,6 +27,38 @@ extern "C" {
     fn CFRelease(cf: *const c_void);
 }
 
+pub fn main_display_id() -> CGDirectDisplayID {
+    unsafe { CGMainDisplayID() }
+}
+
+/// Displays currently part of the desktop
+pub fn active_displays() -> Vec<CGDirectDisplayID> {
+    unsafe {
+        let mut count = 0u32;
+        if CGGetActiveDisplayList(0, std::ptr::null_mut(), &mut count) != 0 || count == 0 {
+            return Vec::new();
+        }
+        let mut displays = vec![0; count as usize];
+        if CGGetActiveDisplayList(count, displays.as_mut_ptr(), &mut count) != 0 {
+            return Vec::new();
+        }
+        displays.truncate(count as usize);
+        displays
+    }
+}
+
+/// The active display matching `candidate` (e.g. the window's monitor),
+/// falling back to the main display
+pub fn display_for(candidate: Option<CGDirectDisplayID>) -> CGDirectDisplayID {
+    candidate
+        .filter(|id| active_displays().contains(id))
+        .unwrap_or_else(main_display_id)
+}
+
+/// How far a mode's width and height may be from a `set_resolution`
+/// request, relative to it
+const SIZE_TOLERANCE: f64 = 0.1;
+
 // Raw pointer wrapper — only used on main thread
 struct ModeRef(CGDisplayModeRef);
 unsafe impl Send for ModeRef {}
@@ -39,16 +72,25 @@ pub struct DisplayMode {
 }
 
 pub struct ResolutionManager {
+    /// CGDirectDisplayID of the managed display
     display: CGDirectDisplayID,
     original_mode: ModeRef,
+    /// Every size and refresh rate, by pixel count, each size's rates
+    /// highest first
     pub modes: Vec<DisplayMode>,
     current_index: Option<usize>,
+    /// `--refresh`: rate to pick for each size instead of the highest
+    refresh: Option<f64>,
 }
 
 impl ResolutionManager {
-    pub fn new() -> Self {
+    /// Manage the modes of `display`
+    ///
+    /// winit's `MonitorHandle::native_id()` is the CGDirectDisplayID; use
+    /// `display_for` to validate it against the active displays. Modes are
+    /// chosen at the refresh rate nearest `refresh`, or the highest.
+    pub fn new(display: CGDirectDisplayID, refresh: Option<f64>) -> Self {
         unsafe {
-            let display = CGMainDisplayID();
             let original = CGDisplayCopyDisplayMode(display);
             let all = CGDisplayCopyAllDisplayModes(display, std::ptr::null_mut());
             let count = CFArrayGetCount(all) as usize;
@@ -72,21 +114,45 @@ impl ResolutionManager {
             // Sort by pixel count ascending, then refresh rate descending
             modes.sort_by(|a, b| {
                 (a.width * a.height).cmp(&(b.width * b.height))
+                    .then_with(|| (a.width, a.height).cmp(&(b.width, b.height)))
                     .then_with(|| b.refresh_rate.partial_cmp(&a.refresh_rate)
                         .unwrap_or(std::cmp::Ordering::Equal))
             });
-            // One entry per resolution (keep highest refresh rate)
-            modes.dedup_by(|a, b| a.width == b.width && a.height == b.height);
+            // One entry per resolution and rate (other pixel depths repeat them)
+            modes.dedup_by(|a, b| a.width == b.width && a.height == b.height && a.refresh_rate == b.refresh_rate);
 
-            println!("Available display modes ({}):", modes.len());
-            for (i, m) in modes.iter().enumerate() {
-                println!("  [{}] {}x{} @ {:.0}Hz", i + 1, m.width, m.height, m.refresh_rate);
+            let manager = Self { display, original_mode: ModeRef(original), modes, current_index: None, refresh };
+            let sizes = manager.per_resolution();
+            println!("Available display modes for display {} ({}):", display, sizes.len());
+            for (i, &idx) in sizes.iter().enumerate() {
+                let m = &manager.modes[idx];
+                let rates: Vec<String> = manager.modes.iter()
+                    .filter(|o| (o.width, o.height) == (m.width, m.height))
+                    .map(|o| format!("{:.0}", o.refresh_rate))
+                    .collect();
+                println!("  [{}] {}x{} @ {:.0}Hz (of {}Hz)", i + 1, m.width, m.height, m.refresh_rate, rates.join("/"));
             }
-
-            Self { display, original_mode: ModeRef(original), modes, current_index: None }
+            manager
         }
     }
 
+    pub fn display_id(&self) -> CGDirectDisplayID {
+        self.display
+    }
+
+    /// The display's modes as (width, height, refresh rate in Hz if known),
+    /// like `DisplayBackend::available_modes` on Linux/Redox
+    pub fn available_modes(&self) -> Vec<(u32, u32, Option<f32>)> {
+        self.modes
+            .iter()
+            .map(|m| {
+                // CoreGraphics reports 0 for displays without a fixed rate
+                let refresh = Some(m.refresh_rate as f32).filter(|&hz| hz > 0.0);
+                (m.width as u32, m.height as u32, refresh)
+            })
+            .collect()
+    }
+
     /// Set display mode by 1-based key.
     /// Keys 1-5: evenly spread across native-aspect (16:9) modes ≥1280px wide.
     /// Keys 6-9: evenly spread across other-aspect modes.
@@ -95,6 +161,10 @@ impl ResolutionManager {
             .map(|m| m.width as f64 / m.height as f64)
             .unwrap_or(16.0 / 9.0);
 
+        if !(1..=9).contains(&key) {
+            return Err(format!("No resolution for key {} (keys 1-9)", key));
+        }
+        let sizes = self.per_resolution();
         if key <= 5 {
             // Keys 1-5: fixed widths (find closest 16:9 mode to each target)
             let native_w = self.modes.last().map(|m| m.width).unwrap_or(3840);
@@ -102,7 +172,7 @@ impl ResolutionManager {
             let target_w = targets[(key - 1) as usize];
 
             // Find the 16:9 mode with width closest to target
-            let best = (0..self.modes.len())
+            let best = sizes.into_iter()
                 .filter(|&i| {
                     let r = self.modes[i].width as f64 / self.modes[i].height as f64;
                     (r - native_ratio).abs() < 0.02
@@ -111,7 +181,7 @@ impl ResolutionManager {
                 .ok_or_else(|| format!("No 16:9 mode near {}px wide", target_w))?;
             self.set_index(best)
         } else {
-            let other: Vec<usize> = (0..self.modes.len())
+            let other: Vec<usize> = sizes.into_iter()
                 .filter(|&i| {
                     let r = self.modes[i].width as f64 / self.modes[i].height as f64;
                     (r - native_ratio).abs() >= 0.02
@@ -126,6 +196,24 @@ impl ResolutionManager {
         }
     }
 
+    /// Set the display mode closest to `width` x `height`, e.g. for `--size`.
+    /// Errors if no mode is within `SIZE_TOLERANCE` of it in both dimensions
+    pub fn set_resolution(&mut self, width: usize, height: usize) -> Result<(usize, usize), String> {
+        let sizes = self.per_resolution();
+        let modes: Vec<(usize, usize, f64)> =
+            sizes.iter().map(|&i| (self.modes[i].width, self.modes[i].height, self.modes[i].refresh_rate)).collect();
+        let idx = closest_mode(&modes, (width, height)).ok_or_else(|| {
+            format!("No display mode within {:.0}% of {}x{}", SIZE_TOLERANCE * 100.0, width, height)
+        })?;
+        self.set_index(sizes[idx])
+    }
+
+    /// Index into `modes` of one mode per resolution, at the preferred rate
+    fn per_resolution(&self) -> Vec<usize> {
+        let modes: Vec<(usize, usize, f64)> = self.modes.iter().map(|m| (m.width, m.height, m.refresh_rate)).collect();
+        per_resolution(&modes, self.refresh)
+    }
+
     fn set_index(&mut self, idx: usize) -> Result<(usize, usize), String> {
         let (w, h, r, mode_ptr) = {
             let m = &self.modes[idx];
@@ -164,3 +252,62 @@ impl Drop for ResolutionManager {
         }
     }
 }
+
+/// Indices of one mode per resolution in `modes` (sorted so each size's
+/// modes are adjacent, highest rate first): the rate nearest `refresh` if
+/// given, else the highest
+fn per_resolution(modes: &[(usize, usize, f64)], refresh: Option<f64>) -> Vec<usize> {
+    let mut picked: Vec<usize> = Vec::new();
+    for (index, &(width, height, rate)) in modes.iter().enumerate() {
+        match picked.last_mut() {
+            Some(last) if (modes[*last].0, modes[*last].1) == (width, height) => {
+                // Strictly closer, so a tie keeps the higher rate
+                if refresh.is_some_and(|hz| (rate - hz).abs() < (modes[*last].2 - hz).abs()) {
+                    *last = index;
+                }
+            }
+            _ => picked.push(index),
+        }
+    }
+    picked
+}
+
+/// Index of the (width, height, refresh rate) mode nearest to `target`: the
+/// smallest relative error in the worse of the two dimensions, then the
+/// higher refresh rate. None if even that one is off by more than
+/// `SIZE_TOLERANCE`
+fn closest_mode(modes: &[(usize, usize, f64)], target: (usize, usize)) -> Option<usize> {
+    let error = |size: usize, wanted: usize| (size as f64 - wanted as f64).abs() / wanted.max(1) as f64;
+    modes
+        .iter()
+        .enumerate()
+        .map(|(index, &(width, height, refresh))| (index, error(width, target.0).max(error(height, target.1)), refresh))
+        .filter(|&(_, error, _)| error <= SIZE_TOLERANCE)
+        .min_by(|a, b| a.1.total_cmp(&b.1).then(b.2.total_cmp(&a.2)))
+        .map(|(index, _, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest_mode() {
+        let modes = [(1920, 1080, 60.0), (2560, 1080, 60.0), (2560, 1440, 60.0), (2560, 1440, 120.0)];
+        assert_eq!(closest_mode(&modes, (1920, 1080)), Some(0));
+        // Off by a little, and the faster of two equal sizes
+        assert_eq!(closest_mode(&modes, (2500, 1080)), Some(1));
+        assert_eq!(closest_mode(&modes, (2560, 1440)), Some(3));
+        // Portrait is no match for any of them
+        assert_eq!(closest_mode(&modes, (1080, 1920)), None);
+    }
+
+    #[test]
+    fn test_per_resolution() {
+        let modes = [(1920, 1080, 120.0), (1920, 1080, 60.0), (2560, 1440, 144.0), (2560, 1440, 100.0), (2560, 1440, 50.0)];
+        assert_eq!(per_resolution(&modes, None), vec![0, 2]);
+        assert_eq!(per_resolution(&modes, Some(60.0)), vec![1, 4]);
+        // Halfway between 100 and 50 on the second size: the higher
+        assert_eq!(per_resolution(&modes, Some(75.0)), vec![1, 3]);
+    }
+}
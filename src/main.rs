@@ -11,87 +11,181 @@ use std::fs::File;
 #[cfg(any(target_os = "linux", target_os = "redox"))]
 use std::io::{Read, Write};
 #[cfg(any(target_os = "linux", target_os = "redox"))]
-use std::time::Instant;
+use std::path::Path;
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+use std::time::{Duration, Instant};
 
-mod shader;
-mod shader_compiler;
+// The viewer binary; renderers, shader handling and platform backends live
+// in the metalshader library (src/lib.rs)
 
-#[cfg(not(target_os = "macos"))]
-mod renderer;
-#[cfg(not(target_os = "macos"))]
-mod platform;
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+use metalshader::{clock, hdr, midi, mouse, options, osc, overlay, prefetch, remote, shader, shader_compiler, shadertoy, shutdown, viewport, vulkan_loader};
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+use metalshader::animated_texture::{self, AnimatedTexture};
 
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+mod check;
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+mod contact_sheet;
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+mod snapshot;
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+mod thumbnail;
 #[cfg(target_os = "macos")]
 mod main_macos;
 #[cfg(target_os = "macos")]
-mod renderer_swapchain;
-#[cfg(target_os = "macos")]
 mod macos_resolution;
+#[cfg(target_os = "macos")]
+mod window_state;
 
 // Platform-conditional imports
 #[cfg(target_os = "linux")]
-use platform::linux::{LinuxDisplay as Display, LinuxInput as Input};
+use metalshader::platform::linux::{LinuxDisplay as Display, LinuxInput as Input};
 
 #[cfg(target_os = "redox")]
-use platform::redox::{RedoxDisplay as Display, RedoxInput as Input};
+use metalshader::platform::redox::{RedoxDisplay as Display, RedoxInput as Input};
 
 #[cfg(not(target_os = "macos"))]
-use platform::{DisplayBackend, InputBackend, KeyEvent};
+use metalshader::platform::{DisplayBackend, InputBackend, KeyEvent};
 
 #[cfg(not(target_os = "macos"))]
-use renderer::VulkanRenderer;
+use metalshader::{MetalshaderError, RenderConfig, ShaderManager, ShaderToyUBO, TextureData, VulkanRenderer};
 
-#[cfg(not(target_os = "macos"))]
-use shader::ShaderManager;
+use metalshader::options::{Options, Parsed};
 
+/// Quiet time after the last number key before its mode switch happens
 #[cfg(any(target_os = "linux", target_os = "redox"))]
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-struct ShaderToyUBO {
-    i_resolution: [f32; 3],
-    i_time: f32,
-    i_mouse: [f32; 4],
+const RESOLUTION_SETTLE: Duration = Duration::from_millis(300);
+
+/// Options from the command line and config file; None once `--help` or
+/// `--version` has printed its text
+#[cfg(any(target_os = "linux", target_os = "redox", target_os = "macos"))]
+fn parse_options() -> Result<Option<Options>, String> {
+    Ok(match Options::from_env()? {
+        Parsed::Run(options) => Some(*options),
+        Parsed::Help => {
+            metalshader::options::print_usage();
+            None
+        }
+        Parsed::Version => {
+            metalshader::options::print_version();
+            None
+        }
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "redox", target_os = "macos"))]
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
 
 #[cfg(target_os = "macos")]
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     // macOS uses windowed swapchain-based renderer
-    let args: Vec<String> = std::env::args().collect();
-    let shader_path = if args.len() < 2 {
-        "example"
-    } else {
-        args[1].as_str()
+    let Some(options) = parse_options()? else {
+        return Ok(());
     };
+    if options.check {
+        // The headless renderer is the Linux/Redox offscreen one
+        return Err("--check needs the offscreen renderer (Linux/Redox); run it there or in CI".into());
+    }
+    if options.contact_sheet.is_some() || options.thumbnails.is_some() {
+        return Err("--contact-sheet and --thumbnails need the offscreen renderer (Linux/Redox)".into());
+    }
+
+    if options.reindex {
+        let search_dirs: Vec<&str> = options.search_dirs.iter().map(String::as_str).collect();
+        metalshader::ShaderManager::write_index(&search_dirs)?;
+        return Ok(());
+    }
 
     // Pass the full path to run_macos (preserving directory)
-    main_macos::run_macos(shader_path)
+    main_macos::run_macos(options)
 }
 
 #[cfg(any(target_os = "linux", target_os = "redox"))]
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Parse command line arguments
-    let args: Vec<String> = std::env::args().collect();
-    let shader_name = if args.len() < 2 {
-        "example"
-    } else {
-        args[1].as_str()
+    let Some(mut options) = parse_options()? else {
+        return Ok(());
     };
+    shutdown::install();
+    if options.software {
+        vulkan_loader::use_software_icd();
+    }
+
+    // --check validates shaders headlessly and never opens the display
+    if options.check {
+        return check::run(&options);
+    }
+    if options.contact_sheet.is_some() {
+        return contact_sheet::run(&options);
+    }
+    if options.thumbnails.is_some() {
+        return thumbnail::run(&options);
+    }
+    if options.reindex {
+        let search_dirs: Vec<&str> = options.search_dirs.iter().map(String::as_str).collect();
+        ShaderManager::write_index(&search_dirs)?;
+        return Ok(());
+    }
+
+    if options.tonemap != options::Tonemap::None && !options.format.is_float() {
+        eprintln!("Warning: --tonemap only applies to --format rgba16f/rgba32f");
+    }
+
+    if let Some(export) = &options.shadertoy {
+        let compiler = shader_compiler::ShaderCompiler::new().with_werror(options.werror);
+        options.shader = shadertoy::import(Path::new(export), &compiler)?;
+    }
+
+    // `-` / --frag-spv: load that module alone instead of scanning
+    let mut shader_manager = ShaderManager::new();
+    if let Some(frag) = &options.frag_spv {
+        let compiler = shader_compiler::ShaderCompiler::new().with_werror(options.werror);
+        let info = shader::direct_shader(frag, options.vert_spv.as_deref(), &compiler)?;
+        options.shader = info.name.clone();
+        shader_manager = ShaderManager::single(info);
+    }
+
+    // Loaded once; every renderer (new resolution, lost device) gets the
+    // current frame bound
+    let mut channel0 = options
+        .channel0
+        .as_deref()
+        .map(|spec| animated_texture::load_channel(spec, options.channel0_cube))
+        .transpose()?;
+    if let Some(channel0) = &channel0 {
+        let texture = channel0.current();
+        let kind = if texture.is_cube() { "cubemap" } else { "2D" };
+        println!("iChannel0: {}x{} {:?} {}", texture.width, texture.height, texture.format, kind);
+        if channel0.frame_count() > 1 {
+            println!("iChannel0: {} frames, {:.2}s loop", channel0.frame_count(), channel0.duration());
+        }
+    }
 
     // Extract base name from path
-    let shader_name = Path::new(shader_name)
+    let mut shader_name = Path::new(&options.shader)
         .file_name()
         .and_then(|s| s.to_str())
         .unwrap_or("example");
 
     // Initialize shader manager and scan for shaders
-    let mut shader_manager = ShaderManager::new();
-    shader_manager.scan_shaders(&[".", "./shaders", "/root/metalshade/shaders"])?;
+    let search_dirs: Vec<&str> = options.search_dirs.iter().map(String::as_str).collect();
+    if options.frag_spv.is_none() {
+        shader_manager.scan_shaders(&search_dirs)?;
+    }
 
     if shader_manager.is_empty() {
         eprintln!("No compiled shaders found.");
-        eprintln!("Searched: . ./shaders /root/metalshade/shaders");
+        eprintln!("Searched: {}", options.search_dirs.join(" "));
         eprintln!("Compile shaders with: glslangValidator -V <shader>.vert -o <shader>.vert.spv");
-        return Err("No shaders found".into());
+        eprintln!("Showing the built-in test pattern");
+        shader_manager.add_builtin()?;
+        shader_name = shader::BUILTIN_NAME;
     }
 
     shader_manager.print_available();
@@ -102,58 +196,265 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .ok_or_else(|| {
             eprintln!("Shader '{}' not found. Available shaders:", shader_name);
             shader_manager.print_available();
+            if let Some(closest) = shader_manager.closest_name(shader_name) {
+                eprintln!("Did you mean '{}'?", closest);
+            }
             "Shader not found"
         })?;
 
     println!("Starting with shader: {}", shader_name);
 
+    // --out renders one frame headlessly instead of opening the display
+    if options.out.is_some() {
+        let shader_info = shader_manager.get(current_shader_idx).unwrap();
+        return snapshot::run(&options, shader_info, channel0.as_mut());
+    }
+
     // Initialize display
     let mut display = Display::new()?;
-    let (mut width, mut height) = display.get_resolution();
-    println!("Display resolution: {}x{}", width, height);
+    let (width, height) = display.get_resolution();
+    // DRM output always covers the whole display at its first (native) mode,
+    // which is what --fullscreen asks for elsewhere
+    println!("Display resolution: {}x{} (fullscreen)", width, height);
 
     // Initialize keyboard input
     let mut keyboard = Input::new()?;
+    keyboard.set_kiosk(options.kiosk);
+    if options.kiosk {
+        println!("Kiosk mode: Esc/Q disabled (Ctrl+Alt+Q quits), next shader every {}s", options.kiosk_interval);
+    }
+    // OSC and MIDI controllers share the iParams and navigation state
+    let remote = remote::Remote::new();
+    if let Some(port) = options.osc_port {
+        osc::listen(port, remote.clone())?;
+        println!("OSC: listening on UDP port {}", port);
+    }
+    let _midi = options
+        .midi
+        .as_deref()
+        .map(|port| midi::MidiListener::connect(port, options.midi_cc.clone(), remote.clone()))
+        .transpose()?;
 
     // Initialize Vulkan renderer
-    let mut renderer = VulkanRenderer::new(width, height)?;
+    let render_config = RenderConfig {
+        tiling: options.tiling,
+        format: options.format,
+        blend: options.blend,
+        software: options.software,
+        flip_y: options.flip_y,
+        frag_coord: options.frag_coord,
+        channel0_sampler: options.channel0_sampler,
+        exportable: false,
+    };
+    // --aspect renders into a centered sub-rectangle, --render-scale renders
+    // smaller; the display upscales into the viewport
+    let mut viewport = viewport::Viewport::letterbox(width, height, options.aspect);
+    let (mut render_width, mut render_height) =
+        options::scaled_size(viewport.width, viewport.height, options.render_scale);
+    let mut renderer = new_renderer(render_width, render_height, render_config, channel0.as_ref().map(AnimatedTexture::current))?;
+    if channel0.is_some() {
+        println!("iChannel0: {} mip level(s)", renderer.channel0_mip_levels());
+    }
     println!(
-        "Metalshader on {} ({}x{})",
+        "Metalshader on {} ({}x{}, {:?} {:?} render target)",
         renderer.get_device_name(),
-        width,
-        height
+        render_width,
+        render_height,
+        options.tiling,
+        options.format
     );
 
     // Main loop state
     let mut current_shader_idx = current_shader_idx;
     let mut reload_requested = true;
-    let start_time = Instant::now();
+    let mut clock = clock::ShaderClock::new(options.start_time).with_loop(options.loop_time);
     let mut frame_count = 0u32;
+    let mut frame_rate = clock::FrameRate::default();
+    let mut last_switch = Instant::now();
+    let mut overlay = overlay::Overlay::new(options.overlay_secs).with_fps(options.show_fps);
+    // 8-bit copy of a float render target for the display
+    let mut display_frame = Vec::new();
+    let mut test_pattern_done = false;
+    // Sidecar resolution in effect, and the mode size to return to without one
+    let mut shader_resolution: Option<(u32, u32)> = None;
+    let mut desktop_size = (width, height);
+    // SPIR-V of the previous and next shaders, read ahead while this one plays
+    let mut prefetcher = prefetch::Prefetcher::new(shader_compiler::ShaderCompiler::new().with_werror(options.werror));
+    // Shaders shown so far, for Backspace
+    let mut history = shader::ShaderHistory::new();
+    // Shader whose pipeline is loaded, to go back to when another fails
+    let mut shown_shader_idx: Option<usize> = None;
+    // Number key pressed last and when, until its mode switch
+    let mut pending_mode: Option<(u8, Instant)> = None;
 
     loop {
+        if shutdown::requested() {
+            // Same path as the Quit key: dropping the display restores the console
+            println!("\nSignal received, exiting...");
+            break;
+        }
+
+        // Kiosk mode cycles through the shaders on its own
+        if options.kiosk && last_switch.elapsed().as_secs_f64() >= options.kiosk_interval {
+            current_shader_idx = shader_manager.next(current_shader_idx);
+            reload_requested = true;
+            last_switch = Instant::now();
+            println!("\n>> Kiosk: {}", shader_manager.get(current_shader_idx).unwrap().name);
+        }
+
+        // Navigation and reloads from an OSC or MIDI controller
+        for command in remote.take_commands() {
+            current_shader_idx = match command {
+                remote::RemoteCommand::Next => shader_manager.next(current_shader_idx),
+                remote::RemoteCommand::Previous => shader_manager.prev(current_shader_idx),
+                remote::RemoteCommand::Reload => {
+                    reload_requested |= recompile(shader_manager.get(current_shader_idx).unwrap(), options.werror, ">> Remote:");
+                    continue;
+                }
+                remote::RemoteCommand::Open(path) => match shader_manager.find_by_name(&shader::name_from_path(&path)) {
+                    Some(idx) => idx,
+                    None => {
+                        eprintln!("\n>> Remote: no shader for {}", path);
+                        continue;
+                    }
+                },
+            };
+            reload_requested = true;
+            last_switch = Instant::now();
+            println!("\n>> Remote: {}", shader_manager.get(current_shader_idx).unwrap().name);
+        }
+
+        // Mode switch for the last number key, once no other followed it
+        // within RESOLUTION_SETTLE: every switch reallocates the scanout
+        // buffers and the render target, so mashing keys only does one
+        if let Some((mode_num, _)) = pending_mode.filter(|(_, at)| at.elapsed() >= RESOLUTION_SETTLE) {
+            pending_mode = None;
+            println!("\n[{}] Changing resolution...", mode_num);
+            match display.set_mode(mode_num) {
+                Ok((new_width, new_height)) => {
+                    desktop_size = (new_width, new_height);
+                    // The reload resizes the render target and rebuilds the pipeline
+                    viewport = viewport::Viewport::letterbox(new_width, new_height, options.aspect);
+                    (render_width, render_height) =
+                        options::scaled_size(viewport.width, viewport.height, options.render_scale);
+                    reload_requested = true;
+                    println!("    Resolution changed to {}x{}", new_width, new_height);
+                }
+                Err(e) => {
+                    eprintln!("    Failed to change resolution: {}", e);
+                    for (number, (width, height, refresh)) in display.available_modes().into_iter().take(9).enumerate() {
+                        let refresh = refresh.map(|hz| format!(" @ {:.0} Hz", hz)).unwrap_or_default();
+                        eprintln!("    [{}] {}x{}{}", number + 1, width, height, refresh);
+                    }
+                }
+            }
+        }
+
         // Handle shader reload
         if reload_requested {
             let shader_info = shader_manager.get(current_shader_idx).unwrap();
-            match renderer.load_shader(&shader_info.vert_path, &shader_info.frag_path) {
+            let wanted = shader_info.resolution.filter(|_| options.shader_resolution);
+            if wanted != shader_resolution {
+                shader_resolution = wanted;
+                // A display mode of that size if there is one, else render at
+                // that size and letterbox it onto the current mode
+                let size = wanted.unwrap_or(desktop_size);
+                if display.get_resolution() != size {
+                    if let Some(mode) = display.find_mode(size) {
+                        if let Err(e) = display.set_mode(mode) {
+                            eprintln!("Failed to switch to {}x{}: {}", size.0, size.1, e);
+                        }
+                    }
+                }
+                let (display_width, display_height) = display.get_resolution();
+                match wanted {
+                    Some(size) if size != (display_width, display_height) => {
+                        viewport = viewport::Viewport::letterbox(display_width, display_height, Some(size));
+                        (render_width, render_height) = size;
+                    }
+                    _ => {
+                        viewport = viewport::Viewport::letterbox(display_width, display_height, options.aspect);
+                        (render_width, render_height) =
+                            options::scaled_size(viewport.width, viewport.height, options.render_scale);
+                    }
+                }
+                println!("Rendering '{}' at {}x{}", shader_info.name, render_width, render_height);
+            }
+            let resized = if renderer.get_size() != (render_width, render_height) {
+                renderer.resize(render_width, render_height)
+            } else {
+                Ok(())
+            };
+            let loaded = resized
+                .and_then(|()| renderer.set_uniforms(&shader_info.uniforms()))
+                .and_then(|()| match prefetcher.take(shader_info) {
+                    Some(compiled) => renderer.load_shader_spirv(&compiled.vertex, &compiled.fragment, &options.entry_points),
+                    None => renderer.load_shader(&shader_info.vert_path, &shader_info.frag_path, &options.entry_points),
+                });
+            match loaded {
                 Ok(_) => {
                     println!("Loaded shader: {}", shader_info.name);
+                    overlay.show(&shader_info.name);
                     reload_requested = false;
+                    shown_shader_idx = Some(current_shader_idx);
+                    history.visit(current_shader_idx);
+                    let neighbours = [shader_manager.prev(current_shader_idx), shader_manager.next(current_shader_idx)];
+                    prefetcher.prefetch(
+                        neighbours.iter().filter(|&&idx| idx != current_shader_idx).filter_map(|&idx| shader_manager.get(idx)),
+                    );
+                }
+                Err(e) if is_gpu_failure(&e) => {
+                    // Don't retry a shader that hung the GPU; the next one
+                    // loads on the following pass, after input is polled
+                    recover_gpu(&mut renderer, e, &shader_info.name, (render_width, render_height), render_config, channel0.as_ref())?;
+                    (render_width, render_height) = renderer.get_size();
+                    current_shader_idx = shader_manager.next(current_shader_idx);
                 }
                 Err(e) => {
+                    // Back to the shader shown before, if any: reloaded, as a
+                    // resize since may have dropped its pipeline, and its
+                    // uniforms were overwritten. Not retried if that fails too
                     eprintln!("Failed to load shader '{}': {}", shader_info.name, e);
-                    std::thread::sleep(std::time::Duration::from_secs(1));
-                    continue;
+                    reload_requested = false;
+                    if let Some(shown) = shown_shader_idx.filter(|&idx| idx != current_shader_idx) {
+                        current_shader_idx = shown;
+                        reload_requested = true;
+                    }
                 }
             }
         }
 
         // Calculate time
-        let elapsed = start_time.elapsed().as_secs_f32();
+        let elapsed = clock.elapsed() as f32;
 
         // Check keyboard input
         if let Some(event) = keyboard.poll_event() {
             match event {
-                KeyEvent::Left => {
+                KeyEvent::Pause => {
+                    clock.toggle_pause();
+                    if clock.is_paused() {
+                        println!("\n[Space] Paused at {:.3}s (Left/Right to scrub)", clock.time());
+                    } else {
+                        println!("\n[Space] Resumed");
+                    }
+                }
+                KeyEvent::Left | KeyEvent::Right | KeyEvent::ShiftLeft | KeyEvent::ShiftRight
+                    if clock.is_paused() =>
+                {
+                    let delta = match event {
+                        KeyEvent::Left => -clock::FRAME_STEP,
+                        KeyEvent::Right => clock::FRAME_STEP,
+                        KeyEvent::ShiftLeft => -clock::COARSE_STEP,
+                        _ => clock::COARSE_STEP,
+                    };
+                    clock.step(delta);
+                    println!("    iTime = {:.3}s", clock.time());
+                }
+                KeyEvent::SeekBack | KeyEvent::SeekForward => {
+                    clock.seek(if event == KeyEvent::SeekBack { -clock::SEEK_STEP } else { clock::SEEK_STEP });
+                    println!("    iTime = {:.3}s", clock.time());
+                }
+                KeyEvent::Left | KeyEvent::ShiftLeft => {
                     current_shader_idx = shader_manager.prev(current_shader_idx);
                     reload_requested = true;
                     println!(
@@ -161,7 +462,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         shader_manager.get(current_shader_idx).unwrap().name
                     );
                 }
-                KeyEvent::Right => {
+                KeyEvent::Right | KeyEvent::ShiftRight => {
                     current_shader_idx = shader_manager.next(current_shader_idx);
                     reload_requested = true;
                     println!(
@@ -169,23 +470,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         shader_manager.get(current_shader_idx).unwrap().name
                     );
                 }
-                KeyEvent::Resolution(mode_num) => {
-                    println!("\n[{}] Changing resolution...", mode_num);
-                    match display.set_mode(mode_num) {
-                        Ok((new_width, new_height)) => {
-                            // Recreate renderer at new resolution
-                            renderer = VulkanRenderer::new(new_width, new_height)?;
-                            width = new_width;
-                            height = new_height;
-                            reload_requested = true;
-                            println!("    Resolution changed to {}x{}", new_width, new_height);
-                            // Skip rendering this frame - reload shader first
-                            continue;
-                        }
-                        Err(e) => {
-                            eprintln!("    Failed to change resolution: {}", e);
-                        }
+                KeyEvent::Back => match history.back() {
+                    Some(idx) => {
+                        current_shader_idx = idx;
+                        reload_requested = true;
+                        println!("\n<< Back to: {}", shader_manager.get(current_shader_idx).unwrap().name);
                     }
+                    None => println!("\n[Backspace] No earlier shader"),
+                },
+                KeyEvent::Resolution(mode_num) => {
+                    // Switched once the keys settle, to the last mode pressed
+                    println!("\n[{}] Resolution mode {} requested", mode_num, mode_num);
+                    pending_mode = Some((mode_num, Instant::now()));
                 }
                 KeyEvent::Fullscreen => {
                     println!("\n[F] Toggling host fullscreen...");
@@ -194,7 +490,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         eprintln!("    Press Ctrl+Alt+F on Mac host");
                     }
                 }
+                KeyEvent::Reload => {
+                    reload_requested |= recompile(shader_manager.get(current_shader_idx).unwrap(), options.werror, "[Ctrl+R]");
+                }
+                KeyEvent::Overlay => {
+                    let shown = overlay.toggle();
+                    println!("\n[N] Shader name overlay {}", if shown { "on" } else { "off" });
+                }
                 KeyEvent::Quit => {
+                    // Leaving the loop drops the display, which restores the console
                     println!("\nExiting...");
                     break;
                 }
@@ -203,25 +507,65 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // Update UBO
         let ubo = ShaderToyUBO {
-            i_resolution: [width as f32, height as f32, 1.0],
-            i_time: elapsed,
-            i_mouse: [0.0, 0.0, 0.0, 0.0],
+            i_resolution: [render_width as f32, render_height as f32, 1.0],
+            i_time: clock.uniform_time(),
+            // No mouse input on DRM yet; keep the ShaderToy encoding for when it lands
+            i_mouse: mouse::shadertoy_mouse([0.0, 0.0], [0.0, 0.0], false, false),
+            i_frame_rate: frame_rate.tick(),
+            i_params: remote.params(),
+            ..Default::default()
         };
 
         // DEBUG: Test pattern first to verify display works
-        static mut TEST_DONE: bool = false;
-        unsafe {
-            if !TEST_DONE {
-                renderer.fill_test_pattern();
-                TEST_DONE = true;
-            }
+        if !test_pattern_done {
+            renderer.fill_test_pattern();
+            test_pattern_done = true;
         }
 
-        // Render frame
-        renderer.render_frame(&ubo)?;
+        // A shader is still to load (the last one failed); nothing to show yet
+        if reload_requested {
+            continue;
+        }
 
-        // Copy to display (with correct row pitch)
-        display.present(renderer.get_frame_buffer(), renderer.get_row_pitch())?;
+        // An animated channel shows the frame for this iTime; a frame that
+        // fails to upload leaves the last one bound
+        let frame = channel0.as_mut().and_then(|channel0| channel0.advance(ubo.i_time as f64));
+        let channel_updated = match frame.map(|frame| renderer.update_channel0(frame)) {
+            Some(Err(e)) if !is_gpu_failure(&e) => {
+                eprintln!("Failed to update iChannel0: {}", e);
+                Ok(())
+            }
+            updated => updated.unwrap_or(Ok(())),
+        };
+
+        // Render frame; a runaway shader times out instead of freezing the viewer
+        overlay.set_fps(ubo.i_frame_rate);
+        let rendered = channel_updated
+            .and_then(|()| renderer.set_overlay(overlay.labels(render_width, render_height)))
+            .and_then(|()| renderer.render_frame(&ubo));
+        if let Err(e) = rendered {
+            if matches!(e, MetalshaderError::NoShaderLoaded) {
+                // Every shader so far failed to load; wait for a key
+                std::thread::sleep(std::time::Duration::from_millis(16));
+                continue;
+            }
+            let name = &shader_manager.get(current_shader_idx).unwrap().name;
+            recover_gpu(&mut renderer, e, name, (render_width, render_height), render_config, channel0.as_ref())?;
+            current_shader_idx = shader_manager.next(current_shader_idx);
+            reload_requested = true;
+            continue;
+        }
+
+        // Copy to display (with correct row pitch); float targets are
+        // tonemapped to the framebuffer's 8-bit BGRA first
+        if render_config.format.is_float() {
+            let size = (render_width, render_height);
+            let (frame, row_pitch) = (renderer.get_frame_buffer(), renderer.get_row_pitch());
+            hdr::to_bgra8(&mut display_frame, frame, row_pitch, size, render_config.format, options.tonemap);
+            display.present(&display_frame, render_width as usize * 4, size, viewport)?;
+        } else {
+            display.present(renderer.get_frame_buffer(), renderer.get_row_pitch(), (render_width, render_height), viewport)?;
+        }
 
         // Print FPS
         frame_count += 1;
@@ -235,6 +579,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 shader_manager.get(current_shader_idx).unwrap().name
             );
         }
+
+        // --once / --repeat N
+        if options.frames.is_some_and(|frames| frame_count >= frames) {
+            println!("\nRendered {} frames, exiting...", frame_count);
+            break;
+        }
     }
 
     Ok(())
@@ -246,6 +596,79 @@ fn main() {
     eprintln!("Supported platforms: Linux, Redox, macOS");
 }
 
+/// Offscreen renderer with the `--channel0` texture bound, if any
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+fn new_renderer(width: u32, height: u32, config: RenderConfig, channel0: Option<&TextureData>)
+    -> Result<VulkanRenderer, MetalshaderError>
+{
+    let mut renderer = VulkanRenderer::new(width, height, config)?;
+    if let Some(texture) = channel0 {
+        renderer.set_channel0(texture)?;
+    }
+    Ok(renderer)
+}
+
+/// Whether a renderer call failed because the GPU hung or was reset, rather
+/// than because of the shader or the request
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+fn is_gpu_failure(error: &MetalshaderError) -> bool {
+    matches!(
+        error,
+        MetalshaderError::FrameTimeout(_) | MetalshaderError::VulkanError(ash::vk::Result::ERROR_DEVICE_LOST)
+    )
+}
+
+/// Deal with a renderer call that failed with `error` while `name` was up.
+/// After a `FrameTimeout` the renderer is left as it is (its fence signals
+/// once the GPU catches up); after a lost device it is rebuilt at `size`.
+/// Either way the caller skips the shader. Other errors are returned
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+fn recover_gpu(
+    renderer: &mut VulkanRenderer,
+    error: MetalshaderError,
+    name: &str,
+    size: (u32, u32),
+    config: RenderConfig,
+    channel0: Option<&AnimatedTexture>,
+) -> Result<(), MetalshaderError> {
+    match error {
+        MetalshaderError::FrameTimeout(_) => {
+            eprintln!("\nWarning: {} running '{}', skipping to the next shader", error, name);
+        }
+        MetalshaderError::VulkanError(ash::vk::Result::ERROR_DEVICE_LOST) => {
+            // The driver reset the GPU after the hang; start over on a new device
+            eprintln!("\nWarning: GPU device lost running '{}', recreating renderer", name);
+            *renderer = new_renderer(size.0, size.1, config, channel0.map(AnimatedTexture::current))?;
+        }
+        error => return Err(error),
+    }
+    Ok(())
+}
+
+/// Ctrl+R: recompile `shader_info` from its source, if it has one, logging
+/// under `label`. Returns whether to reload it; a compile error keeps the
+/// running shader on screen
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+fn recompile(shader_info: &shader::ShaderInfo, werror: bool, label: &str) -> bool {
+    match shader_info.source_path() {
+        Some(source) => {
+            println!("\n{} Recompiling {}", label, source.display());
+            let compiler = shader_compiler::ShaderCompiler::new().with_werror(werror);
+            match compiler.compile(&source) {
+                Ok(_) => true,
+                Err(e) => {
+                    eprintln!("    {}", e);
+                    false
+                }
+            }
+        }
+        None => {
+            println!("\n{} No source for '{}', reloading its SPIR-V", label, shader_info.name);
+            true
+        }
+    }
+}
+
 #[cfg(any(target_os = "linux", target_os = "redox"))]
 fn send_fullscreen_command() -> Result<(), Box<dyn std::error::Error>> {
     // Find QEMU display control port
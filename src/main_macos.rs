@@ -1,33 +1,70 @@
 // macOS-specific main with windowed swapchain support
 #![cfg(target_os = "macos")]
 
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
-use winit::event::{ElementState, WindowEvent};
+use winit::event::{ElementState, KeyEvent, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::monitor::MonitorHandle;
+use winit::platform::macos::MonitorHandleExtMacOS;
 use winit::window::{Window, WindowId};
 
 use objc2::runtime::{AnyObject, AnyClass};
 use objc2::sel;
 
-use crate::macos_resolution::ResolutionManager;
-use crate::renderer_swapchain::SwapchainRenderer;
-use crate::shader::ShaderManager;
-use crate::shader_compiler::ShaderCompiler;
+use metalshader::animated_texture::{self, AnimatedTexture};
+use metalshader::clock::{self, FrameRate, ShaderClock};
+use crate::macos_resolution::{self, ResolutionManager};
+use metalshader::mouse::{pinch_scroll, shadertoy_mouse, smoothing_alpha, zoom_factor};
+use metalshader::options::{CompositeAlpha, Dpi, Options, Tonemap};
+use metalshader::midi::MidiListener;
+use metalshader::key_repeat::KeyRepeat;
+use metalshader::osc;
+use metalshader::remote::{Remote, RemoteCommand};
+use metalshader::overlay::Overlay;
+use metalshader::picker::Picker;
+use metalshader::prefetch::Prefetcher;
+use metalshader::renderer_swapchain::{SwapchainConfig, SwapchainRenderer};
+use metalshader::shader::{self, ShaderHistory, ShaderManager};
+use metalshader::shader_compiler::ShaderCompiler;
+use metalshader::ubo::PARAM_COUNT;
+use crate::window_state::WindowState;
 
-// Pending file path from Finder "Open With" → shader switcher
-static PENDING_FILE: Mutex<Option<String>> = Mutex::new(None);
+/// Requests for the render loop from any thread. The openFile delegate gets
+/// no app state from AppKit, so the app's `Remote` lives here where it can
+/// reach it; Finder "Open With" arrives as `RemoteCommand::Open`
+static REMOTE: OnceLock<Remote> = OnceLock::new();
 
-fn store_pending_path_str(path: String) {
-    if let Ok(mut guard) = PENDING_FILE.lock() {
-        *guard = Some(path);
+fn app_remote() -> &'static Remote {
+    REMOTE.get_or_init(Remote::new)
+}
+
+/// Match the window's CAMetalLayer to `scale_factor`. The layer's
+/// contentsScale is only set when the surface is created, so without this the
+/// drawable keeps the old display's pixel density after the window moves
+/// between a retina and a standard display
+fn set_layer_scale(window: &Window, scale_factor: f64) {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    let Ok(handle) = window.window_handle() else {
+        return;
+    };
+    if let RawWindowHandle::AppKit(handle) = handle.as_raw() {
+        unsafe {
+            let view = handle.ns_view.as_ptr() as *mut AnyObject;
+            let layer: *mut AnyObject = objc2::msg_send![view, layer];
+            if !layer.is_null() {
+                let _: () = objc2::msg_send![layer, setContentsScale: scale_factor];
+            }
+        }
     }
 }
 
 /// application:openFile: called by AppKit for both initial launch-with-file AND
-/// "Open With" while app is running. Must be added to WinitApplicationDelegate.
+/// "Open With" while app is running. Added to the application delegate by
+/// `install_open_file_handler`.
 extern "C" fn app_open_file(_self: *mut AnyObject, _sel: objc2::runtime::Sel,
     _app: *mut AnyObject, filename: *mut AnyObject) -> bool
 {
@@ -35,37 +72,46 @@ extern "C" fn app_open_file(_self: *mut AnyObject, _sel: objc2::runtime::Sel,
     let utf8: *const std::ffi::c_char = unsafe { objc2::msg_send![filename, UTF8String] };
     if utf8.is_null() { return false; }
     let s = unsafe { std::ffi::CStr::from_ptr(utf8) }.to_string_lossy().into_owned();
-    store_pending_path_str(s);
+    app_remote().push(RemoteCommand::Open(s));
     true
 }
 
-/// Inject application:openFile: into WinitApplicationDelegate BEFORE EventLoop::new()
-/// so it's present when applicationWillFinishLaunching fires.
-fn inject_open_file_handler() {
+/// Add application:openFile: to the application delegate winit installed, so
+/// it is there when applicationWillFinishLaunching fires. Call after
+/// EventLoop::new() and before run_app(). Returns false if there was no
+/// delegate class to extend; files then only arrive through the launch argv.
+fn install_open_file_handler() -> bool {
     unsafe {
-        // The class name is registered by winit's declare_class! macro
-        let cls = AnyClass::get("WinitApplicationDelegate");
-        let cls = match cls {
-            Some(c) => c as *const AnyClass as *mut objc2::ffi::objc_class,
-            None => {
-                // Class not registered yet - we're too early; it will be added by EventLoop::new()
-                // We'll re-try after EventLoop::new() in run_macos()
-                eprintln!("[openFile] WinitApplicationDelegate not found yet");
-                return;
-            }
+        // Whatever class the installed delegate has, rather than relying on
+        // the name winit registers it under
+        let delegate_class = AnyClass::get("NSApplication").and_then(|app_class| {
+            let app: *mut AnyObject = objc2::msg_send![app_class, sharedApplication];
+            let delegate: *mut AnyObject = if app.is_null() { std::ptr::null_mut() } else { objc2::msg_send![app, delegate] };
+            delegate.as_ref().map(AnyObject::class)
+        });
+        let Some(cls) = delegate_class.or_else(|| AnyClass::get("WinitApplicationDelegate")) else {
+            return false;
         };
         let sel = sel!(application:openFile:);
+        if cls.instance_method(sel).is_some() {
+            // Already there (a newer winit, or a second call)
+            return true;
+        }
+        let imp: extern "C" fn(*mut AnyObject, objc2::runtime::Sel, *mut AnyObject, *mut AnyObject) -> bool = app_open_file;
         // types: "B@:@@" = BOOL return, id self, SEL, id NSApplication, id NSString
-        let _added = objc2::ffi::class_addMethod(
-            cls,
+        let added = objc2::ffi::class_addMethod(
+            cls as *const AnyClass as *mut objc2::ffi::objc_class,
             sel.as_ptr() as *const _,
-            Some(std::mem::transmute::<extern "C" fn(*mut AnyObject, objc2::runtime::Sel, *mut AnyObject, *mut AnyObject) -> bool,
-                unsafe extern "C" fn()>(app_open_file)),
+            Some(std::mem::transmute::<_, unsafe extern "C" fn()>(imp)),
             b"B@:@@\0".as_ptr() as *const _,
         );
+        objc2::runtime::Bool::from_raw(added).as_bool()
     }
 }
 
+// Starts with the library's ShaderToyUBO fields; the padding keeps the vec2
+// members at their std140 offsets, and iFrameRate and iParams end up at
+// FRAME_RATE_OFFSET and PARAMS_OFFSET
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 struct ShaderToyUBO {
@@ -78,20 +124,55 @@ struct ShaderToyUBO {
     i_button_middle: f32,
     i_button_4: f32,
     i_button_5: f32,
+    _pad1: f32,
     i_pan: [f32; 2],     // Accumulated pan offset (x, y) in pixels for drag
+    i_frame_rate: f32,
+    _pad0: f32,
+    i_params: [f32; PARAM_COUNT],  // Free parameters, set over OSC
 }
 
 struct MetalshaderApp {
+    options: Options,
     window: Option<Arc<Window>>,
     renderer: Option<SwapchainRenderer>,
     shader_manager: ShaderManager,
-    #[allow(dead_code)]
     shader_compiler: ShaderCompiler,
     resolution_manager: ResolutionManager,
+    /// Monitor chosen with --monitor; fullscreen goes here instead of the current one
+    monitor: Option<MonitorHandle>,
+    /// Geometry from the previous launch; while fullscreen, the windowed geometry to save
+    window_state: Option<WindowState>,
+    /// SPIR-V of the previous and next shaders, read ahead while this one plays
+    prefetcher: Prefetcher,
+    /// Window size before a shader's sidecar resolution was applied
+    restore_size: Option<winit::dpi::PhysicalSize<u32>>,
+    /// Keyboard modifiers, for the kiosk escape hatch (Ctrl+Alt+Q)
+    modifiers: winit::keyboard::ModifiersState,
+    /// Last automatic or manual shader switch, for kiosk cycling
+    last_switch: Instant,
     current_shader_idx: usize,
-    start_time: Instant,
+    /// Shader that last loaded, to go back to when another is rejected
+    shown_shader_idx: Option<usize>,
+    /// Shaders shown so far, for Backspace
+    history: ShaderHistory,
+    clock: ShaderClock,
     frame_count: u32,
+    /// Start of the current 600-frame FPS window
+    fps_window_start: Instant,
+    frame_rate: FrameRate,
     reload_requested: bool,
+    /// Shader name shown after a switch, toggled with N
+    overlay: Overlay,
+    /// Open `/` picker, which gets the keys instead of the shortcuts
+    picker: Option<Picker>,
+    /// Thins out autorepeats of held navigation keys, as on Linux
+    key_repeat: KeyRepeat,
+    /// `--channel0` frames, advanced with iTime when animated
+    channel0: Option<AnimatedTexture>,
+    /// iParams and navigation from `--osc` and `--midi`
+    remote: Remote,
+    /// Keeps the `--midi` input open
+    _midi: Option<MidiListener>,
     // Mouse and scroll state
     mouse_x: f64,
     mouse_y: f64,
@@ -100,6 +181,7 @@ struct MetalshaderApp {
     mouse_click_x: f64,
     mouse_click_y: f64,
     mouse_left_pressed: bool,
+    mouse_left_clicked: bool,  // Left button went down since the last rendered frame
     mouse_right_pressed: bool,
     mouse_middle_pressed: bool,
     button_press_duration: [f32; 5],  // Duration in seconds for each button
@@ -109,48 +191,89 @@ struct MetalshaderApp {
     pan_offset_y: f32,
     base_pan_x: f32,       // Pan in complex-plane units (zoom-independent)
     base_pan_y: f32,
+    scroll_reset_y: f32,   // scroll_y at the last reset, where zoom is 1x
     last_frame_time: Instant,
+    // Set when startup fails so run_macos can exit non-zero
+    fatal_error: Option<String>,
 }
 
 impl MetalshaderApp {
-    fn resolve_shader_path(path: &str) -> String {
-        use std::path::Path;
-
+    /// Find the file `path` names: as given, or with a source or
+    /// `.frag.spv` extension added when it has none, first as is and then
+    /// inside each search directory. On failure, returns every path tried
+    fn resolve_shader_path(path: &str, search_dirs: &[String]) -> Result<String, Vec<String>> {
         // Remove trailing dot if present
-        let working_path = path.trim_end_matches('.').to_string();
-
-        // Check if file exists as-is
-        if Path::new(&working_path).exists() {
-            return working_path;
-        }
-
-        // Check if path has NO extension
-        let path_obj = Path::new(&working_path);
-        let has_extension = path_obj.extension().is_some();
+        let working_path = path.trim_end_matches('.');
+        let extensions: &[&str] = if Path::new(working_path).extension().is_some() {
+            &[""]
+        } else {
+            &["", ".frag", ".fsh", ".glsl", ".wgsl", ".frag.spv"]
+        };
+        let in_dirs = search_dirs
+            .iter()
+            .filter(|_| Path::new(working_path).is_relative())
+            .map(|dir| Path::new(dir).join(working_path).to_string_lossy().into_owned());
 
-        if !has_extension {
-            // Try adding common fragment shader extensions
-            for ext in &[".frag", ".fsh", ".glsl"] {
-                let test_path = format!("{}{}", working_path, ext);
-                if Path::new(&test_path).exists() {
-                    println!("✓ Auto-detected extension: {}", test_path);
-                    return test_path;
+        let mut tried = Vec::new();
+        for base in std::iter::once(working_path.to_string()).chain(in_dirs) {
+            for ext in extensions {
+                let candidate = format!("{}{}", base, ext);
+                if Path::new(&candidate).is_file() {
+                    if !ext.is_empty() {
+                        println!("✓ Auto-detected extension: {}", candidate);
+                    }
+                    return Ok(candidate);
                 }
+                tried.push(candidate);
             }
         }
-
-        working_path
+        Err(tried)
     }
 
-    fn new(shader_path: &str) -> Self {
+    fn new(mut options: Options) -> Self {
         let mut shader_manager = ShaderManager::new();
-        let shader_compiler = ShaderCompiler::new();
+        let shader_compiler = ShaderCompiler::new().with_werror(options.werror);
+
+        if let Some(export) = options.shadertoy.clone() {
+            match metalshader::shadertoy::import(std::path::Path::new(&export), &shader_compiler) {
+                Ok(name) => options.shader = name,
+                Err(e) => eprintln!("Warning: Failed to import ShaderToy export: {}", e),
+            }
+        }
 
         // Resolve shader path with auto-detection
-        let resolved_path = Self::resolve_shader_path(shader_path);
+        let (mut resolved_path, tried) = match options.frag_spv {
+            Some(_) => (options.shader.clone(), Vec::new()),
+            None => match Self::resolve_shader_path(&options.shader, &options.search_dirs) {
+                Ok(path) => (path, Vec::new()),
+                Err(tried) => (options.shader.trim_end_matches('.').to_string(), tried),
+            },
+        };
+
+        // Compiled SPIR-V needs no compiler: load it like --frag-spv, with
+        // its own vertex module if it has one
+        if options.frag_spv.is_none() && resolved_path.ends_with(".frag.spv") {
+            let vert = format!("{}.vert.spv", resolved_path.trim_end_matches(".frag.spv"));
+            if options.vert_spv.is_none() && Path::new(&vert).is_file() {
+                options.vert_spv = Some(vert);
+            }
+            options.frag_spv = Some(resolved_path.clone());
+        }
+
+        // `-` / --frag-spv: load that module alone instead of scanning
+        if let Some(frag) = options.frag_spv.clone() {
+            match metalshader::shader::direct_shader(&frag, options.vert_spv.as_deref(), &shader_compiler) {
+                Ok(info) => {
+                    options.shader = info.name.clone();
+                    resolved_path = info.name.clone();
+                    shader_manager = ShaderManager::single(info);
+                }
+                Err(e) => eprintln!("Warning: Failed to load {}: {}", frag, e),
+            }
+        }
 
         // First, try to compile the requested shader if it's a source file
-        if resolved_path.ends_with(".frag") || resolved_path.ends_with(".glsl") {
+        if resolved_path.ends_with(".frag") || resolved_path.ends_with(".glsl") || resolved_path.ends_with(".wgsl") {
             match shader_compiler.compile_if_needed(&resolved_path) {
                 Ok(_base_name) => {
                     println!("✓ Shader compiled successfully");
@@ -174,46 +297,111 @@ impl MetalshaderApp {
         // When running from bundle, use bundle shaders exclusively to avoid duplicates.
         // Fall back to local dirs only when not bundled (dev/debug mode).
         let bundle_str;
-        let search_dirs: Vec<&str> = if let Some(ref bs) = bundle_shaders {
+        let mut search_dirs: Vec<&str> = if let Some(ref bs) = bundle_shaders {
             bundle_str = bs.as_str();
             vec![bundle_str]
         } else {
-            vec![".", "./shaders", "/root/metalshade/shaders"]
+            options.search_dirs.iter().map(String::as_str).collect()
         };
+        // A file passed in argv (a shell, or `open --args`) may live outside
+        // those; scan its directory too so it can be selected
+        let file_dir = Path::new(&resolved_path)
+            .parent()
+            .filter(|_| Path::new(&resolved_path).is_file())
+            .map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+            .and_then(|dir| dir.to_str());
+        if let Some(dir) = file_dir {
+            let canonical = |d: &str| std::fs::canonicalize(d).ok();
+            if !search_dirs.iter().any(|d| canonical(d) == canonical(dir)) {
+                search_dirs.push(dir);
+            }
+        }
 
-        if let Err(e) = shader_manager.scan_shaders(&search_dirs) {
-            eprintln!("Warning: Failed to scan shaders: {}", e);
+        if options.frag_spv.is_none() {
+            if let Err(e) = shader_manager.scan_shaders(&search_dirs) {
+                eprintln!("Warning: Failed to scan shaders: {}", e);
+            }
         }
 
         if shader_manager.is_empty() {
             eprintln!("No compiled shaders found.");
-            eprintln!("Searched: . ./shaders /root/metalshade/shaders + bundle Resources/shaders");
+            eprintln!("Searched: {}", search_dirs.join(" "));
             eprintln!("Compile shaders with: glslangValidator -V <shader>.vert -o <shader>.vert.spv");
+            eprintln!("Showing the built-in test pattern");
+            if let Err(e) = shader_manager.add_builtin() {
+                eprintln!("Warning: Failed to write the built-in shader: {}", e);
+            }
         } else {
             shader_manager.print_available();
         }
 
-        let base_shader_path = MetalshaderApp::shader_name_from_path(&resolved_path);
+        let base_shader_path = shader::name_from_path(&resolved_path);
 
-        let current_shader_idx = shader_manager
-            .find_by_name(&base_shader_path)
-            .unwrap_or(0);
+        // Unknown names start with the first shader; a likely typo gets a hint
+        let current_shader_idx = shader_manager.find_by_name(&base_shader_path).unwrap_or_else(|| {
+            if let Some(closest) = shader_manager.closest_name(&base_shader_path) {
+                eprintln!("Shader '{}' not found; did you mean '{}'?", base_shader_path, closest);
+            }
+            // Without a shader argument there is nothing worth listing
+            if !tried.is_empty() && options.shader != Options::default().shader {
+                eprintln!("No file for '{}'; tried:", options.shader);
+                for path in &tried {
+                    eprintln!("  {}", path);
+                }
+            }
+            0
+        });
 
         println!("Starting with shader: {}",
             shader_manager.get(current_shader_idx)
                 .map(|s| s.name.as_str())
                 .unwrap_or("(none)"));
 
+        let clock = ShaderClock::new(options.start_time).with_loop(options.loop_time);
+        let overlay = Overlay::new(options.overlay_secs).with_fps(options.show_fps);
+        // A controller that can't be opened only costs the remote control
+        let remote = app_remote().clone();
+        if let Some(port) = options.osc_port {
+            match osc::listen(port, remote.clone()) {
+                Ok(()) => println!("OSC: listening on UDP port {}", port),
+                Err(e) => eprintln!("OSC: can't listen on port {}: {}", port, e),
+            }
+        }
+        let midi = options.midi.as_deref().and_then(|port| {
+            MidiListener::connect(port, options.midi_cc.clone(), remote.clone())
+                .map_err(|e| eprintln!("{}", e))
+                .ok()
+        });
+        let prefetcher = Prefetcher::new(ShaderCompiler::new().with_werror(options.werror));
+        let resolution_manager = ResolutionManager::new(macos_resolution::main_display_id(), options.refresh);
+
         Self {
+            options,
             window: None,
             renderer: None,
             shader_manager,
             shader_compiler,
-            resolution_manager: ResolutionManager::new(),
+            resolution_manager,
+            monitor: None,
+            window_state: WindowState::load(),
+            prefetcher,
+            restore_size: None,
+            modifiers: winit::keyboard::ModifiersState::empty(),
+            last_switch: Instant::now(),
             current_shader_idx,
-            start_time: Instant::now(),
+            shown_shader_idx: None,
+            history: ShaderHistory::new(),
+            clock,
             frame_count: 0,
+            fps_window_start: Instant::now(),
+            frame_rate: FrameRate::default(),
             reload_requested: true,
+            overlay,
+            picker: None,
+            key_repeat: KeyRepeat::default(),
+            channel0: None,
+            remote,
+            _midi: midi,
             mouse_x: 0.0,
             mouse_y: 0.0,
             mouse_smooth_x: 0.0,
@@ -221,6 +409,7 @@ impl MetalshaderApp {
             mouse_click_x: 0.0,
             mouse_click_y: 0.0,
             mouse_left_pressed: false,
+            mouse_left_clicked: false,
             mouse_right_pressed: false,
             mouse_middle_pressed: false,
             button_press_duration: [0.0; 5],
@@ -230,7 +419,93 @@ impl MetalshaderApp {
             pan_offset_y: 0.0,
             base_pan_x: 0.0,
             base_pan_y: 0.0,
+            scroll_reset_y: 0.0,
             last_frame_time: Instant::now(),
+            fatal_error: None,
+        }
+    }
+
+    /// Pick monitor `index` from `available_monitors()` and point the
+    /// resolution manager at its display
+    fn select_monitor(&mut self, event_loop: &ActiveEventLoop, index: usize) {
+        let monitors: Vec<MonitorHandle> = event_loop.available_monitors().collect();
+        println!("Monitors:");
+        for (i, m) in monitors.iter().enumerate() {
+            let size = m.size();
+            println!(
+                "  [{}] {} {}x{}",
+                i,
+                m.name().unwrap_or_else(|| "(unnamed)".to_string()),
+                size.width,
+                size.height
+            );
+        }
+
+        let Some(monitor) = monitors.into_iter().nth(index) else {
+            eprintln!("Warning: --monitor {} not found, using the main display", index);
+            return;
+        };
+
+        self.target_display(Some(monitor.native_id()));
+        self.monitor = Some(monitor);
+    }
+
+    /// Point the resolution manager at `display` (validated, main display as
+    /// fallback), restoring the previous display's mode if it was changed
+    fn target_display(&mut self, display: Option<u32>) {
+        let display = macos_resolution::display_for(display);
+        if display != self.resolution_manager.display_id() {
+            // Dropping the old manager restores its display's original mode
+            self.resolution_manager = ResolutionManager::new(display, self.options.refresh);
+        }
+    }
+
+    /// With --size, switch the display `monitor` is on to the mode nearest
+    /// it before going fullscreen there
+    fn fullscreen_mode(&mut self, monitor: Option<&MonitorHandle>) {
+        let Some((width, height)) = self.options.size else {
+            return;
+        };
+        self.target_display(monitor.map(|m| m.native_id()));
+        if let Err(e) = self.resolution_manager.set_resolution(width as usize, height as usize) {
+            eprintln!("Warning: --size {}x{}: {}", width, height, e);
+        }
+    }
+
+    /// contentsScale for the window's layer: the display's scale factor for
+    /// `--dpi physical`, one pixel per point for `--dpi logical`
+    fn layer_scale(&self, window: &Window) -> f64 {
+        match self.options.dpi {
+            Dpi::Physical => window.scale_factor(),
+            Dpi::Logical => 1.0,
+        }
+    }
+
+    fn windowed_state(window: &Window) -> Option<WindowState> {
+        let position = window.outer_position().ok()?;
+        let size = window.inner_size();
+        Some(WindowState {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            fullscreen: false,
+        })
+    }
+
+    fn save_window_state(&self) {
+        let Some(window) = &self.window else {
+            return;
+        };
+        let state = if window.fullscreen().is_some() {
+            self.window_state
+                .or_else(|| Self::windowed_state(window))
+                .map(|state| WindowState { fullscreen: true, ..state })
+        } else {
+            Self::windowed_state(window)
+        };
+        if let Some(state) = state {
+            state.save();
         }
     }
 
@@ -240,29 +515,74 @@ impl MetalshaderApp {
             .unwrap_or(false);
 
         if is_fullscreen {
-            // Change actual hardware display resolution
+            // Change the hardware resolution of the display the window is on
+            let occupied = self.window.as_ref()
+                .and_then(|w| w.current_monitor())
+                .map(|m| m.native_id());
+            self.target_display(occupied);
+
             match self.resolution_manager.set_by_key(key) {
                 Ok((w, h)) => println!("\n[{}] Hardware resolution -> {}x{}", key, w, h),
-                Err(e) => eprintln!("\n[{}] Resolution change failed: {}", key, e),
+                Err(e) => {
+                    eprintln!("\n[{}] Resolution change failed: {}", key, e);
+                    for (width, height, refresh) in self.resolution_manager.available_modes() {
+                        let refresh = refresh.map(|hz| format!(" @ {:.0} Hz", hz)).unwrap_or_default();
+                        eprintln!("    {}x{}{}", width, height, refresh);
+                    }
+                }
             }
         } else {
-            // Windowed: just resize the window
-            let sizes = [(1024u32, 576u32), (1280, 720), (1920, 1080), (2560, 1440), (3840, 2160)];
-            if let Some(&(w, h)) = sizes.get((key - 1) as usize) {
-                if let Some(window) = &self.window {
-                    let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(w, h));
-                    println!("\n[{}] Window size -> {}x{}", key, w, h);
+            // Windowed: just resize the window, to the --window-sizes preset
+            match key.checked_sub(1).and_then(|index| self.options.window_sizes.get(index as usize)) {
+                Some(&(w, h)) => {
+                    if let Some(window) = &self.window {
+                        let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(w, h));
+                        println!("\n[{}] Window size -> {}x{}", key, w, h);
+                    }
                 }
+                None => println!("\n[{}] No window size for this key (see --window-sizes)", key),
             }
         }
     }
 
     fn handle_key(&mut self, key: PhysicalKey, event_loop: &ActiveEventLoop) {
         match key {
-            PhysicalKey::Code(KeyCode::Escape) | PhysicalKey::Code(KeyCode::KeyQ) => {
+            PhysicalKey::Code(KeyCode::KeyQ)
+                if self.modifiers.control_key() && self.modifiers.alt_key() =>
+            {
+                // Escape hatch that also works in kiosk mode
+                println!("\nExiting...");
+                event_loop.exit();
+            }
+            PhysicalKey::Code(KeyCode::Escape) | PhysicalKey::Code(KeyCode::KeyQ) if !self.options.kiosk => {
                 println!("\nExiting...");
                 event_loop.exit();
             }
+            PhysicalKey::Code(KeyCode::Space) => {
+                self.clock.toggle_pause();
+                if self.clock.is_paused() {
+                    println!("\n[Space] Paused at {:.3}s (Left/Right to scrub)", self.clock.time());
+                } else {
+                    println!("\n[Space] Resumed");
+                }
+            }
+            PhysicalKey::Code(code @ (KeyCode::ArrowLeft | KeyCode::ArrowRight)) if self.clock.is_paused() => {
+                let step = if self.modifiers.shift_key() { clock::COARSE_STEP } else { clock::FRAME_STEP };
+                self.clock.step(if code == KeyCode::ArrowLeft { -step } else { step });
+                println!("    iTime = {:.3}s", self.clock.time());
+            }
+            PhysicalKey::Code(code @ (KeyCode::Comma | KeyCode::Period)) => {
+                self.clock.seek(if code == KeyCode::Comma { -clock::SEEK_STEP } else { clock::SEEK_STEP });
+                println!("    iTime = {:.3}s", self.clock.time());
+            }
+            PhysicalKey::Code(KeyCode::Backspace) => match self.history.back() {
+                Some(idx) => {
+                    self.current_shader_idx = idx;
+                    self.reload_requested = true;
+                    println!("\n<< Back to: {}", self.shader_manager.get(self.current_shader_idx).unwrap().name);
+                }
+                None => println!("\n[Backspace] No earlier shader"),
+            },
             PhysicalKey::Code(KeyCode::ArrowLeft) => {
                 self.current_shader_idx = self.shader_manager.prev(self.current_shader_idx);
                 self.reload_requested = true;
@@ -280,7 +600,7 @@ impl MetalshaderApp {
                 );
             }
             PhysicalKey::Code(KeyCode::KeyF) => {
-                if let Some(window) = &self.window {
+                if let Some(window) = self.window.clone() {
                     let is_fullscreen = window.fullscreen().is_some();
                     if is_fullscreen {
                         let size = window.inner_size();
@@ -289,8 +609,11 @@ impl MetalshaderApp {
                         let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(size.width, size.height));
                         println!("\n[F] Windowed mode at {}x{}", size.width, size.height);
                     } else {
+                        // Fullscreen geometry is not worth restoring; remember the window's
+                        self.window_state = Self::windowed_state(&window);
                         use winit::window::Fullscreen;
-                        if let Some(monitor) = window.current_monitor() {
+                        if let Some(monitor) = self.monitor.clone().or_else(|| window.current_monitor()) {
+                            self.fullscreen_mode(Some(&monitor));
                             window.set_fullscreen(Some(Fullscreen::Borderless(Some(monitor))));
                             println!("\n[F] Fullscreen mode");
                         }
@@ -302,14 +625,32 @@ impl MetalshaderApp {
             PhysicalKey::Code(KeyCode::Digit3) => self.change_resolution(3),
             PhysicalKey::Code(KeyCode::Digit4) => self.change_resolution(4),
             PhysicalKey::Code(KeyCode::Digit5) => self.change_resolution(5),
+            PhysicalKey::Code(KeyCode::Digit6) => self.change_resolution(6),
+            PhysicalKey::Code(KeyCode::Digit7) => self.change_resolution(7),
+            PhysicalKey::Code(KeyCode::Digit8) => self.change_resolution(8),
+            PhysicalKey::Code(KeyCode::Digit9) => self.change_resolution(9),
+            PhysicalKey::Code(KeyCode::Slash) if !self.shader_manager.is_empty() => {
+                self.picker = Some(Picker::default());
+                println!("\n[/] Search: type to filter, Up/Down to choose, Enter to open, Esc to cancel");
+            }
+            PhysicalKey::Code(KeyCode::KeyN) => {
+                let shown = self.overlay.toggle();
+                println!("\n[N] Shader name overlay {}", if shown { "on" } else { "off" });
+            }
+            PhysicalKey::Code(KeyCode::KeyR)
+                if self.modifiers.control_key() || self.modifiers.super_key() =>
+            {
+                self.recompile_current();
+            }
             PhysicalKey::Code(KeyCode::KeyR) => {
-                let elapsed = self.start_time.elapsed().as_secs_f32();
+                let elapsed = self.clock.time() as f32;
                 self.scroll_x = 0.0;
                 self.scroll_y = elapsed;  // For auto-zoom shaders: reset time offset
                 self.pan_offset_x = 0.0;
                 self.pan_offset_y = 0.0;
                 self.base_pan_x = 0.0;
                 self.base_pan_y = 0.0;
+                self.scroll_reset_y = elapsed;
                 println!("\n[R] Reset zoom and pan");
             }
             PhysicalKey::Code(KeyCode::Equal) | PhysicalKey::Code(KeyCode::NumpadAdd) => {
@@ -323,46 +664,374 @@ impl MetalshaderApp {
             _ => {}
         }
     }
+
+    /// A key while the `/` picker is open: text edits the query, Up/Down
+    /// move the highlight, Enter switches to it and Esc closes the picker
+    fn handle_picker_key(&mut self, event: &KeyEvent) {
+        let Some(picker) = &mut self.picker else {
+            return;
+        };
+        match event.physical_key {
+            PhysicalKey::Code(KeyCode::Escape) => self.picker = None,
+            PhysicalKey::Code(KeyCode::Enter | KeyCode::NumpadEnter) => {
+                match picker.selection(&self.shader_manager) {
+                    Some(idx) => {
+                        self.current_shader_idx = idx;
+                        self.reload_requested = true;
+                        self.last_switch = Instant::now();
+                        println!("\n[/] {}", self.shader_manager.get(idx).unwrap().name);
+                    }
+                    None => println!("\n[/] No shader matches '{}'", picker.query()),
+                }
+                self.picker = None;
+            }
+            PhysicalKey::Code(KeyCode::Backspace) => picker.pop(),
+            PhysicalKey::Code(KeyCode::ArrowUp) => picker.move_selection(-1, &self.shader_manager),
+            PhysicalKey::Code(KeyCode::ArrowDown) => picker.move_selection(1, &self.shader_manager),
+            _ => {
+                if let Some(text) = &event.text {
+                    picker.push(text);
+                }
+            }
+        }
+    }
+
+    /// Minimized or zero-area: there is no extent to build a swapchain for,
+    /// so rendering pauses until a nonzero Resized
+    fn window_hidden(&self) -> bool {
+        self.window.as_ref().is_some_and(|window| {
+            let size = window.inner_size();
+            size.width == 0 || size.height == 0 || window.is_minimized() == Some(true)
+        })
+    }
+
+    /// Ctrl+R / Cmd+R: recompile the current shader's source, if it has one,
+    /// and reload it. A compile error keeps the running shader
+    fn recompile_current(&mut self) {
+        let Some(shader_info) = self.shader_manager.get(self.current_shader_idx) else {
+            return;
+        };
+        match shader_info.source_path() {
+            Some(source) => {
+                println!("\n[Ctrl+R] Recompiling {}", source.display());
+                match self.shader_compiler.compile(&source) {
+                    Ok(_) => self.reload_requested = true,
+                    Err(e) => eprintln!("    {}", e),
+                }
+            }
+            None => {
+                println!("\n[Ctrl+R] No source for '{}', reloading its SPIR-V", shader_info.name);
+                self.reload_requested = true;
+            }
+        }
+    }
+
+    /// Reload a pending shader and render one frame
+    fn redraw(&mut self) {
+        if self.window_hidden() {
+            return;
+        }
+
+        // Handle shader reload
+        if self.reload_requested {
+            if let Some(renderer) = &mut self.renderer {
+                if let Some(shader_info) = self.shader_manager.get(self.current_shader_idx) {
+                    let loaded = renderer.set_uniforms(&shader_info.uniforms()).and_then(|()| match self.prefetcher.take(shader_info) {
+                        Some(compiled) => {
+                            renderer.load_shader_spirv(&compiled.vertex, &compiled.fragment, &self.options.entry_points)
+                        }
+                        None => renderer.load_shader(
+                            shader_info.vert_path.to_str().unwrap(),
+                            shader_info.frag_path.to_str().unwrap(),
+                            &self.options.entry_points,
+                        ),
+                    });
+                    match loaded {
+                        Ok(_) => {
+                            println!("Loaded shader: {}", shader_info.name);
+                            self.overlay.show(&shader_info.name);
+                            if let Some(window) = &self.window {
+                                window.set_title(&format!(
+                                    "Metalshader - [{}/{}] {}",
+                                    self.current_shader_idx + 1,
+                                    self.shader_manager.len(),
+                                    shader_info.name
+                                ));
+                            }
+                            // Sidecar resolution: resize the window to it, and
+                            // back once a shader without one loads
+                            let wanted = shader_info.resolution.filter(|_| self.options.shader_resolution);
+                            if let Some(window) = self.window.as_ref().filter(|w| w.fullscreen().is_none()) {
+                                match wanted {
+                                    Some((w, h)) => {
+                                        self.restore_size.get_or_insert(window.inner_size());
+                                        let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(w, h));
+                                    }
+                                    None => {
+                                        if let Some(size) = self.restore_size.take() {
+                                            let _ = window.request_inner_size(size);
+                                        }
+                                    }
+                                }
+                            }
+                            self.reload_requested = false;
+                            self.shown_shader_idx = Some(self.current_shader_idx);
+                            self.history.visit(self.current_shader_idx);
+                            let current = self.current_shader_idx;
+                            let neighbours = [self.shader_manager.prev(current), self.shader_manager.next(current)];
+                            self.prefetcher.prefetch(
+                                neighbours.iter().filter(|&&idx| idx != current).filter_map(|&idx| self.shader_manager.get(idx)),
+                            );
+                        }
+                        Err(e) => {
+                            // Back to the shader shown before, if any, reloaded
+                            // once since its uniforms were overwritten; not
+                            // retried on every frame if that fails too
+                            eprintln!("Failed to load shader '{}': {}", shader_info.name, e);
+                            self.reload_requested = false;
+                            if let Some(shown) = self.shown_shader_idx.filter(|&idx| idx != self.current_shader_idx) {
+                                self.current_shader_idx = shown;
+                                self.reload_requested = true;
+                            }
+                        }
+                    }
+                } else {
+                    eprintln!("No shaders available to load");
+                    self.reload_requested = false;
+                }
+            }
+        }
+
+        // Render frame
+        if let Some(renderer) = &mut self.renderer {
+            if let Some(window) = &self.window {
+                // Shader pixels: the --aspect viewport times --render-scale
+                let size = renderer.render_extent();
+                let viewport = renderer.viewport();
+                let elapsed = self.clock.elapsed() as f32;
+
+                // Update button press durations
+                let now = Instant::now();
+                let delta_time = now.duration_since(self.last_frame_time).as_secs_f32();
+                self.last_frame_time = now;
+
+                if self.mouse_left_pressed {
+                    self.button_press_duration[0] += delta_time;
+                }
+                if self.mouse_right_pressed {
+                    self.button_press_duration[1] += delta_time;
+                }
+                if self.mouse_middle_pressed {
+                    self.button_press_duration[2] += delta_time;
+                }
+
+                // Generic mouse smoothing (shader-agnostic)
+                // Exponential smoothing with time constant --mouse-smoothing
+                // (default 200ms), independent of frame rate; 0 disables it
+                let alpha = smoothing_alpha(delta_time as f64, self.options.mouse_smoothing);
+
+                self.mouse_smooth_x += (self.mouse_x - self.mouse_smooth_x) * alpha;
+                self.mouse_smooth_y += (self.mouse_y - self.mouse_smooth_y) * alpha;
+
+                // Map mouse coordinates from window pixels to render
+                // pixels, relative to the letterbox viewport. Both are
+                // swapchain pixels, so no retina scale factor appears here
+                let scale_x = size.width as f32 / viewport.width as f32;
+                let scale_y = size.height as f32 / viewport.height as f32;
+                // --flip-y: fragCoord counts up from the bottom row
+                let flip_y = self.options.flip_y;
+                let to_render = |x: f64, y: f64| {
+                    let y = (y as f32 - viewport.y as f32) * scale_y;
+                    let y = if flip_y { size.height as f32 - y } else { y };
+                    ((x as f32 - viewport.x as f32) * scale_x, y)
+                };
+
+                // Use smoothed mouse position for shader
+                let (scaled_mouse_x, scaled_mouse_y) = to_render(self.mouse_smooth_x, self.mouse_smooth_y);
+                let (scaled_click_x, scaled_click_y) = to_render(self.mouse_click_x, self.mouse_click_y);
+
+                // ShaderToy mouse convention (see mouse::shadertoy_mouse)
+                let i_mouse = shadertoy_mouse(
+                    [scaled_mouse_x, scaled_mouse_y],
+                    [scaled_click_x, scaled_click_y],
+                    self.mouse_left_pressed,
+                    self.mouse_left_clicked,
+                );
+                self.mouse_left_clicked = false;
+
+                // pan_offset is in pixels at the current zoom, scaled like
+                // the mouse. Shader handles conversion to complex-plane coordinates
+                let zoom = zoom_factor(self.scroll_y - self.scroll_reset_y);
+                self.pan_offset_x = self.base_pan_x * zoom;
+                self.pan_offset_y = self.base_pan_y * zoom;
+
+                let ubo = ShaderToyUBO {
+                    i_resolution: [size.width as f32, size.height as f32, 1.0],
+                    i_time: self.clock.uniform_time(),
+                    i_mouse,
+                    i_scroll: [self.scroll_x, self.scroll_y],
+                    i_button_left: self.button_press_duration[0],
+                    i_button_right: self.button_press_duration[1],
+                    i_button_middle: self.button_press_duration[2],
+                    i_button_4: self.button_press_duration[3],
+                    i_button_5: self.button_press_duration[4],
+                    _pad1: 0.0,
+                    i_pan: [
+                        self.pan_offset_x * scale_x,
+                        if flip_y { -self.pan_offset_y } else { self.pan_offset_y } * scale_y,
+                    ],
+                    i_frame_rate: self.frame_rate.tick(),
+                    _pad0: 0.0,
+                    i_params: self.remote.params(),
+                };
+
+                if let Some(frame) = self.channel0.as_mut().and_then(|c| c.advance(ubo.i_time as f64)) {
+                    if let Err(e) = renderer.update_channel0(frame) {
+                        eprintln!("Failed to update iChannel0: {}", e);
+                    }
+                }
+                self.overlay.set_fps(ubo.i_frame_rate);
+                let labels = match &self.picker {
+                    Some(picker) => picker.labels(&self.shader_manager, size.width, size.height),
+                    None => self.overlay.labels(size.width, size.height),
+                };
+                renderer.set_overlay(labels);
+
+                match renderer.render_frame(&ubo) {
+                    Ok(_) => {
+                        self.frame_count += 1;
+                        if self.frame_count % 600 == 0 {
+                            // Rate over the last 600 frames, not since startup,
+                            // so benchmarks aren't skewed by loading or pauses
+                            let fps = 600.0 / self.fps_window_start.elapsed().as_secs_f32();
+                            self.fps_window_start = Instant::now();
+                            println!(
+                                "{:.1}s: {} frames ({:.1} FPS) - {}",
+                                elapsed,
+                                self.frame_count,
+                                fps,
+                                self.shader_manager.get(self.current_shader_idx).unwrap().name
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Render error: {}", e);
+                    }
+                }
+
+                if !self.options.no_vsync {
+                    window.request_redraw();
+                }
+            }
+        }
+    }
 }
 
 impl ApplicationHandler for MetalshaderApp {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if self.window.is_none() {
-            let window_attributes = Window::default_attributes()
+            if let Some(index) = self.options.monitor {
+                self.select_monitor(event_loop, index);
+            }
+
+            let transparent = self.options.composite_alpha != CompositeAlpha::Opaque;
+            let mut window_attributes = Window::default_attributes()
                 .with_title("Metalshader - Vulkan Shader Viewer")
-                .with_inner_size(winit::dpi::PhysicalSize::new(1280, 800));
+                // Logical, so the window covers the same area on retina and
+                // standard displays; the swapchain is still in physical pixels
+                .with_inner_size(winit::dpi::LogicalSize::new(1280, 800))
+                .with_transparent(transparent)
+                .with_decorations(!transparent);
+            if let Some(monitor) = &self.monitor {
+                window_attributes = window_attributes.with_position(monitor.position());
+            } else if let Some(state) = &self.window_state {
+                window_attributes = window_attributes
+                    .with_inner_size(winit::dpi::PhysicalSize::new(state.width, state.height))
+                    .with_position(winit::dpi::PhysicalPosition::new(state.x, state.y));
+            }
+            if let Some((width, height)) = self.options.size {
+                window_attributes = window_attributes.with_inner_size(winit::dpi::PhysicalSize::new(width, height));
+            }
 
             let window = match event_loop.create_window(window_attributes) {
                 Ok(w) => Arc::new(w),
                 Err(e) => {
-                    eprintln!("Failed to create window: {}", e);
+                    self.fatal_error = Some(format!("Failed to create window: {}", e));
                     event_loop.exit();
                     return;
                 }
             };
 
             // Create renderer with swapchain
-            match SwapchainRenderer::new(window.clone()) {
+            set_layer_scale(&window, self.layer_scale(&window));
+            if self.options.tonemap != Tonemap::None {
+                eprintln!("Warning: --tonemap isn't applied on macOS yet; float targets are clamped");
+            }
+            let config = SwapchainConfig {
+                composite_alpha: self.options.composite_alpha,
+                blend: self.options.blend,
+                software: self.options.software,
+                render_scale: self.options.render_scale,
+                aspect: self.options.aspect,
+                format: self.options.format,
+                flip_y: self.options.flip_y,
+                frag_coord: self.options.frag_coord,
+                no_vsync: self.options.no_vsync,
+                wide_gamut: self.options.wide_gamut,
+                dpi: self.options.dpi,
+                channel0_sampler: self.options.channel0_sampler,
+            };
+            let renderer = SwapchainRenderer::new(window.clone(), config).and_then(|mut renderer| {
+                if let Some(path) = &self.options.channel0 {
+                    let channel0 = animated_texture::load_channel(path, self.options.channel0_cube)?;
+                    renderer.set_channel0(channel0.current())?;
+                    println!(
+                        "iChannel0: {} ({} mip level(s), {} frame(s))",
+                        path,
+                        renderer.channel0_mip_levels(),
+                        channel0.frame_count()
+                    );
+                    self.channel0 = Some(channel0);
+                }
+                Ok(renderer)
+            });
+            match renderer {
                 Ok(renderer) => {
+                    let extent = renderer.render_extent();
                     println!(
                         "Metalshader on {} ({}x{})",
                         renderer.get_device_name(),
-                        window.inner_size().width,
-                        window.inner_size().height
+                        extent.width,
+                        extent.height
                     );
                     self.renderer = Some(renderer);
                 }
                 Err(e) => {
-                    eprintln!("Failed to create renderer: {}", e);
+                    self.fatal_error = Some(format!("Failed to create renderer: {}", e));
                     event_loop.exit();
                     return;
                 }
             }
 
+            if self.options.kiosk {
+                window.set_cursor_visible(false);
+                println!("Kiosk mode: Esc/Q disabled (Ctrl+Alt+Q quits), next shader every {}s", self.options.kiosk_interval);
+            }
+
+            if self.options.fullscreen || self.window_state.is_some_and(|state| state.fullscreen) {
+                let monitor = self.monitor.clone().or_else(|| window.current_monitor());
+                self.fullscreen_mode(monitor.as_ref());
+                window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(monitor)));
+            }
+
             self.window = Some(window);
         }
     }
 
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.save_window_state();
+    }
+
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
@@ -374,125 +1043,26 @@ impl ApplicationHandler for MetalshaderApp {
                 println!("\nExiting...");
                 event_loop.exit();
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
             WindowEvent::KeyboardInput { event, .. } => {
                 if event.state == ElementState::Pressed {
-                    self.handle_key(event.physical_key, event_loop);
-                }
-            }
-            WindowEvent::RedrawRequested => {
-                // Handle shader reload
-                if self.reload_requested {
-                    if let Some(renderer) = &mut self.renderer {
-                        if let Some(shader_info) = self.shader_manager.get(self.current_shader_idx) {
-                            match renderer.load_shader(
-                                shader_info.vert_path.to_str().unwrap(),
-                                shader_info.frag_path.to_str().unwrap()
-                            ) {
-                                Ok(_) => {
-                                    println!("Loaded shader: {}", shader_info.name);
-                                    if let Some(window) = &self.window {
-                                        window.set_title(&format!("Metalshader - {}", shader_info.name));
-                                    }
-                                    self.reload_requested = false;
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to load shader '{}': {}", shader_info.name, e);
-                                }
-                            }
-                        } else {
-                            eprintln!("No shaders available to load");
-                            self.reload_requested = false;
-                        }
-                    }
-                }
-
-                // Render frame
-                if let Some(renderer) = &mut self.renderer {
-                    if let Some(window) = &self.window {
-                        let size = window.inner_size();
-                        let elapsed = self.start_time.elapsed().as_secs_f32();
-
-                        // Update button press durations
-                        let now = Instant::now();
-                        let delta_time = now.duration_since(self.last_frame_time).as_secs_f32();
-                        self.last_frame_time = now;
-
-                        if self.mouse_left_pressed {
-                            self.button_press_duration[0] += delta_time;
-                        }
-                        if self.mouse_right_pressed {
-                            self.button_press_duration[1] += delta_time;
-                        }
-                        if self.mouse_middle_pressed {
-                            self.button_press_duration[2] += delta_time;
-                        }
-
-                        // Generic mouse smoothing (shader-agnostic)
-                        // Provides comfortable smoothing without viewer needing to know zoom logic
-                        // Time constant: ~200ms (feels natural, reduces jitter without lag)
-                        const SMOOTH_FACTOR: f32 = 0.08;  // 8% blend per frame @ 60fps
-                        let smooth_speed = (SMOOTH_FACTOR * delta_time * 60.0).min(1.0);
-
-                        self.mouse_smooth_x += (self.mouse_x - self.mouse_smooth_x) * smooth_speed.min(1.0) as f64;
-                        self.mouse_smooth_y += (self.mouse_y - self.mouse_smooth_y) * smooth_speed.min(1.0) as f64;
-
-                        // Scale mouse coordinates for Retina displays
-                        let scale_x = size.width as f32 / window.inner_size().width as f32;
-                        let scale_y = size.height as f32 / window.inner_size().height as f32;
-
-                        // Use smoothed mouse position for shader
-                        let scaled_mouse_x = self.mouse_smooth_x as f32 * scale_x;
-                        let scaled_mouse_y = self.mouse_smooth_y as f32 * scale_y;
-                        let scaled_click_x = self.mouse_click_x as f32 * scale_x;
-                        let scaled_click_y = self.mouse_click_y as f32 * scale_y;
-
-                        // ShaderToy mouse convention:
-                        // xy = current position, zw = click position (negative if button up)
-                        let i_mouse = if self.mouse_left_pressed {
-                            [scaled_mouse_x, scaled_mouse_y, scaled_click_x, scaled_click_y]
-                        } else {
-                            [scaled_mouse_x, scaled_mouse_y, -scaled_click_x, -scaled_click_y]
-                        };
-
-                        // pan_offset is now in pixels, passed directly to shader
-                        // Shader handles conversion to complex-plane coordinates
-
-                        let ubo = ShaderToyUBO {
-                            i_resolution: [size.width as f32, size.height as f32, 1.0],
-                            i_time: elapsed,
-                            i_mouse,
-                            i_scroll: [self.scroll_x, self.scroll_y],
-                            i_button_left: self.button_press_duration[0],
-                            i_button_right: self.button_press_duration[1],
-                            i_button_middle: self.button_press_duration[2],
-                            i_button_4: self.button_press_duration[3],
-                            i_button_5: self.button_press_duration[4],
-                            i_pan: [self.pan_offset_x, self.pan_offset_y],
-                        };
-
-                        match renderer.render_frame(&ubo) {
-                            Ok(_) => {
-                                self.frame_count += 1;
-                                if self.frame_count % 600 == 0 {
-                                    let fps = self.frame_count as f32 / elapsed;
-                                    println!(
-                                        "{:.1}s: {} frames ({:.1} FPS) - {}",
-                                        elapsed,
-                                        self.frame_count,
-                                        fps,
-                                        self.shader_manager.get(self.current_shader_idx).unwrap().name
-                                    );
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Render error: {}", e);
-                            }
+                    if self.picker.is_some() {
+                        self.handle_picker_key(&event);
+                    } else {
+                        // winit repeats held keys as presses flagged `repeat`
+                        let repeats = matches!(
+                            event.physical_key,
+                            PhysicalKey::Code(KeyCode::ArrowLeft | KeyCode::ArrowRight | KeyCode::Comma | KeyCode::Period)
+                        );
+                        if self.key_repeat.accept(repeats, event.repeat, Instant::now()) {
+                            self.handle_key(event.physical_key, event_loop);
                         }
-
-                        window.request_redraw();
                     }
                 }
             }
+            WindowEvent::RedrawRequested => self.redraw(),
             WindowEvent::Resized(new_size) => {
                 if new_size.width > 0 && new_size.height > 0 {
                     if let Some(renderer) = &mut self.renderer {
@@ -508,6 +1078,9 @@ impl ApplicationHandler for MetalshaderApp {
             }
             WindowEvent::ScaleFactorChanged { .. } => {
                 // Display resolution/DPI changed — swapchain must be recreated
+                if let Some(window) = &self.window {
+                    set_layer_scale(window, self.layer_scale(window));
+                }
                 if let Some(renderer) = &mut self.renderer {
                     if let Err(e) = renderer.recreate_swapchain() {
                         eprintln!("Failed to recreate swapchain on scale change: {}", e);
@@ -518,8 +1091,23 @@ impl ApplicationHandler for MetalshaderApp {
                 }
             }
             WindowEvent::CursorMoved { position, .. } => {
-                self.mouse_x = position.x;
-                self.mouse_y = position.y;
+                // Physical pixels; scaled to the swapchain's pixels, which
+                // are points with --dpi logical, so the viewport mapping
+                // in the render loop works in one unit
+                let scale = match self.options.dpi {
+                    Dpi::Physical => 1.0,
+                    Dpi::Logical => self.window.as_ref().map_or(1.0, |window| window.scale_factor()),
+                };
+                let (x, y) = (position.x / scale, position.y / scale);
+                // Left-drag pans; dividing by the zoom keeps the grabbed
+                // point under the cursor at any magnification
+                if self.mouse_left_pressed {
+                    let zoom = zoom_factor(self.scroll_y - self.scroll_reset_y);
+                    self.base_pan_x += (x - self.mouse_x) as f32 / zoom;
+                    self.base_pan_y += (y - self.mouse_y) as f32 / zoom;
+                }
+                self.mouse_x = x;
+                self.mouse_y = y;
             }
             WindowEvent::MouseInput { state, button, .. } => {
                 use winit::event::MouseButton;
@@ -529,11 +1117,12 @@ impl ApplicationHandler for MetalshaderApp {
                     MouseButton::Left => {
                         if pressed {
                             self.mouse_left_pressed = true;
+                            self.mouse_left_clicked = true;
                             self.mouse_click_x = self.mouse_x;
                             self.mouse_click_y = self.mouse_y;
                             self.button_press_duration[0] = 0.0;
                         } else {
-                            // Mouse released - no drag handling needed (zoom follows cursor directly)
+                            // Drag ends; the pan stays in base_pan
                             self.mouse_left_pressed = false;
                             self.button_press_duration[0] = 0.0;
                         }
@@ -568,38 +1157,80 @@ impl ApplicationHandler for MetalshaderApp {
                     }
                 }
             }
+            WindowEvent::PinchGesture { delta, .. } => {
+                // Trackpad pinch drives the same zoom channel as the wheel
+                self.scroll_y += pinch_scroll(delta);
+            }
             _ => {}
         }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        // Check for Finder "Open With" file requests arriving via Apple Event
-        if let Ok(mut guard) = PENDING_FILE.lock() {
-            if let Some(path) = guard.take() {
-                let base = MetalshaderApp::shader_name_from_path(&path);
-                if let Some(idx) = self.shader_manager.find_by_name(&base) {
-                    self.current_shader_idx = idx;
-                    self.reload_requested = true;
-                    self.start_time = Instant::now();
-                    self.scroll_y = 0.0;
-                }
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if metalshader::shutdown::requested() {
+            // Leave run_app normally so ResolutionManager's Drop restores the display
+            println!("\nSignal received, exiting...");
+            event_loop.exit();
+            return;
+        }
+        // --once / --repeat N
+        if self.options.frames.is_some_and(|frames| self.frame_count >= frames) {
+            println!("\nRendered {} frames, exiting...", self.frame_count);
+            event_loop.exit();
+            return;
+        }
+
+        // Kiosk mode cycles through the shaders on its own
+        if self.options.kiosk
+            && !self.shader_manager.is_empty()
+            && self.last_switch.elapsed().as_secs_f64() >= self.options.kiosk_interval
+        {
+            self.current_shader_idx = self.shader_manager.next(self.current_shader_idx);
+            self.reload_requested = true;
+            self.last_switch = Instant::now();
+            println!("\n>> Kiosk: {}", self.shader_manager.get(self.current_shader_idx).unwrap().name);
+        }
+
+        // Navigation from an OSC or MIDI controller, reloads, and Finder
+        // "Open With" file requests arriving via Apple Event
+        for command in self.remote.take_commands() {
+            if self.shader_manager.is_empty() {
+                break;
             }
+            self.current_shader_idx = match command {
+                RemoteCommand::Next => self.shader_manager.next(self.current_shader_idx),
+                RemoteCommand::Previous => self.shader_manager.prev(self.current_shader_idx),
+                RemoteCommand::Reload => {
+                    self.recompile_current();
+                    continue;
+                }
+                RemoteCommand::Open(path) => {
+                    if let Some(idx) = self.shader_manager.find_by_name(&shader::name_from_path(&path)) {
+                        self.current_shader_idx = idx;
+                        self.reload_requested = true;
+                        self.clock.restart();
+                        self.scroll_y = 0.0;
+                    }
+                    continue;
+                }
+            };
+            self.reload_requested = true;
+            self.last_switch = Instant::now();
+            println!("\n>> Remote: {}", self.shader_manager.get(self.current_shader_idx).unwrap().name);
         }
-        if let Some(window) = &self.window {
-            window.request_redraw();
+        // While hidden, wake up a few times a second for signals and the
+        // kiosk timer instead of polling
+        if self.window_hidden() {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(100)));
+            return;
         }
-    }
-}
+        event_loop.set_control_flow(ControlFlow::Poll);
 
-impl MetalshaderApp {
-    fn shader_name_from_path(path: &str) -> String {
-        let stem1 = std::path::Path::new(path)
-            .file_stem().and_then(|s| s.to_str()).unwrap_or(path);
-        if stem1.ends_with(".vert") || stem1.ends_with(".frag") || stem1.ends_with(".glsl") {
-            std::path::Path::new(stem1).file_stem()
-                .and_then(|s| s.to_str()).unwrap_or(stem1).to_string()
-        } else {
-            stem1.to_string()
+        // --no-vsync: render right here instead of waiting for the redraw
+        // event, which macOS paces to the display refresh
+        if self.options.no_vsync {
+            self.redraw();
+        } else if let Some(window) = &self.window {
+            window.request_redraw();
         }
     }
 }
@@ -629,17 +1260,26 @@ fn setup_bundle_env() {
     }
 }
 
-pub fn run_macos(shader_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn run_macos(options: Options) -> Result<(), Box<dyn std::error::Error>> {
+    metalshader::shutdown::install();
+    // Before the bundled MoltenVK ICD is registered, so lavapipe takes its place
+    if options.software {
+        metalshader::vulkan_loader::use_software_icd();
+    }
     setup_bundle_env();
-    // Attempt injection before EventLoop::new() - might be too early if class not registered
-    inject_open_file_handler();
     let event_loop = EventLoop::new()?;
-    // Retry after EventLoop::new() in case WinitApplicationDelegate wasn't registered yet
-    inject_open_file_handler();
+    // EventLoop::new() installs winit's delegate, which gets the openFile: method
+    if !install_open_file_handler() {
+        eprintln!("Warning: no application delegate to receive Finder \"Open With\"; pass shaders on the command line");
+    }
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = MetalshaderApp::new(shader_path);
+    let mut app = MetalshaderApp::new(options);
     event_loop.run_app(&mut app)?;
 
+    if let Some(e) = app.fatal_error.take() {
+        return Err(e.into());
+    }
+
     Ok(())
 }
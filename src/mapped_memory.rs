@@ -0,0 +1,56 @@
+// Host-visible device memory mapped into the process
+//
+// The renderers map their uniform buffer and the memory frames are read back
+// from when they are created and keep the mapping until they are dropped.
+// `MappedMemory` holds such a mapping with its length, so reads and writes go
+// through bounds-checked slices instead of raw pointers, and `unmap` can only
+// take effect once. Like the other Vulkan objects here it doesn't unmap on
+// drop: the owner calls `unmap` before freeing the memory.
+
+use std::ptr::NonNull;
+
+use ash::vk;
+
+pub struct MappedMemory {
+    memory: vk::DeviceMemory,
+    /// None once unmapped
+    ptr: Option<NonNull<u8>>,
+    len: usize,
+}
+
+impl MappedMemory {
+    /// Map all of host-visible `memory`, exposing its first `len` bytes
+    pub unsafe fn map(device: &ash::Device, memory: vk::DeviceMemory, len: usize) -> Result<Self, vk::Result> {
+        let ptr = device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?;
+        Ok(Self { memory, ptr: NonNull::new(ptr as *mut u8), len })
+    }
+
+    /// The mapped bytes; empty after `unmap`. The GPU may write them while
+    /// a submitted frame is still running
+    pub fn as_slice(&self) -> &[u8] {
+        match self.ptr {
+            Some(ptr) => unsafe { std::slice::from_raw_parts(ptr.as_ptr(), self.len) },
+            None => &[],
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self.ptr {
+            Some(ptr) => unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), self.len) },
+            None => &mut [],
+        }
+    }
+
+    /// Copy `value`'s bytes to the start of the mapping
+    pub fn write<T: Copy>(&mut self, value: &T) {
+        let bytes = unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>()) };
+        self.as_mut_slice()[..bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Unmap the memory; later calls do nothing. Call before freeing it
+    pub unsafe fn unmap(&mut self, device: &ash::Device) {
+        if self.ptr.take().is_some() {
+            device.unmap_memory(self.memory);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// MIDI remote control (`--midi PORT`, feature `midi`)
+//
+// Control changes listed in the CC map (`--midi-cc`, by default CC 1-8 to
+// iParams[0..8]) set their parameter to value / 127; any note-on switches to
+// the next shader. Messages arrive on midir's callback thread and are
+// applied to a `Remote`, which OSC may share.
+
+use crate::error::MetalshaderError;
+use crate::remote::{Remote, RemoteCommand};
+
+/// Apply one raw MIDI message. Channels are not distinguished.
+#[cfg_attr(not(feature = "midi"), allow(dead_code))]
+fn apply(remote: &Remote, cc_map: &[(u8, usize)], message: &[u8]) {
+    match *message {
+        [status, controller, value] if status & 0xf0 == 0xb0 => {
+            for &(_, param) in cc_map.iter().filter(|(cc, _)| *cc == controller) {
+                remote.set_param(param, value as f32 / 127.0);
+            }
+        }
+        // Note-on with velocity 0 is a note-off
+        [status, _, velocity] if status & 0xf0 == 0x90 && velocity > 0 => remote.push(RemoteCommand::Next),
+        _ => {}
+    }
+}
+
+/// Open connection; input stops when it is dropped
+pub struct MidiListener {
+    #[cfg(feature = "midi")]
+    _connection: midir::MidiInputConnection<()>,
+}
+
+impl MidiListener {
+    /// Connect to the input port at index `port`, or the first whose name
+    /// contains it (case-insensitive). The error lists the available ports.
+    #[cfg(feature = "midi")]
+    pub fn connect(port: &str, cc_map: Vec<(u8, usize)>, remote: Remote) -> Result<Self, MetalshaderError> {
+        let err = |e: &dyn std::fmt::Display| MetalshaderError::Midi(e.to_string());
+        let input = midir::MidiInput::new("metalshader").map_err(|e| err(&e))?;
+        let ports = input.ports();
+        let names: Vec<String> = ports.iter().map(|p| input.port_name(p).unwrap_or_default()).collect();
+        let wanted = port.to_lowercase();
+        let index = port
+            .parse::<usize>()
+            .ok()
+            .filter(|&i| i < ports.len())
+            .or_else(|| names.iter().position(|name| name.to_lowercase().contains(&wanted)))
+            .ok_or_else(|| {
+                MetalshaderError::Midi(format!("no input port '{}' (available: {})", port, names.join(", ")))
+            })?;
+        println!("MIDI: listening on {}", names[index]);
+        let connection = input
+            .connect(&ports[index], "metalshader-in", move |_, message, _| apply(&remote, &cc_map, message), ())
+            .map_err(|e| err(&e))?;
+        Ok(Self { _connection: connection })
+    }
+
+    #[cfg(not(feature = "midi"))]
+    pub fn connect(_port: &str, _cc_map: Vec<(u8, usize)>, _remote: Remote) -> Result<Self, MetalshaderError> {
+        Err(MetalshaderError::Midi("--midi needs the `midi` feature".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cc_and_notes() {
+        let remote = Remote::new();
+        let cc_map = [(74, 0), (71, 3)];
+        apply(&remote, &cc_map, &[0xb0, 74, 127]);
+        apply(&remote, &cc_map, &[0xb5, 71, 0x40]); // any channel
+        apply(&remote, &cc_map, &[0xb0, 10, 127]); // unmapped
+        apply(&remote, &cc_map, &[0x90, 60, 0]); // note-off
+        apply(&remote, &cc_map, &[0x99, 36, 100]);
+        let params = remote.params();
+        assert_eq!((params[0], params[3]), (1.0, 64.0 / 127.0));
+        assert_eq!(params.iter().filter(|p| **p != 0.0).count(), 2);
+        assert_eq!(remote.take_commands(), vec![RemoteCommand::Next]);
+    }
+}
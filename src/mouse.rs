@@ -0,0 +1,93 @@
+// ShaderToy iMouse encoding shared by all backends
+
+/// Encode mouse state the way ShaderToy's `iMouse` does:
+///
+/// - `xy`: current cursor position in pixels
+/// - `z`:  x of the last click; positive while the button is held, negative after release
+/// - `w`:  y of the last click; positive only on the frame the button went down,
+///         negative otherwise
+///
+/// The click position is retained (with negative sign) after release, so
+/// `abs(iMouse.zw)` always yields the last click point and `iMouse.z > 0`
+/// tests "button held". Unlike ShaderToy, `xy` follows the cursor even when no
+/// button is held, which the zoom-at-cursor shaders rely on.
+pub fn shadertoy_mouse(
+    position: [f32; 2],
+    click: [f32; 2],
+    pressed: bool,
+    clicked_this_frame: bool,
+) -> [f32; 4] {
+    let z = if pressed { click[0] } else { -click[0] };
+    let w = if pressed && clicked_this_frame { click[1] } else { -click[1] };
+    [position[0], position[1], z, w]
+}
+
+/// Magnification per unit of `iScroll.y` (one wheel notch or `+`/`-` press)
+pub const ZOOM_PER_SCROLL: f32 = 1.1;
+
+/// Zoom factor `scroll` units above the reset level
+pub fn zoom_factor(scroll: f32) -> f32 {
+    ZOOM_PER_SCROLL.powf(scroll)
+}
+
+/// Pinch magnification multiplier: a two-finger spread over most of the
+/// trackpad reports about 1.0 in total, which at 2x gain zooms about 4x
+pub const PINCH_GAIN: f64 = 2.0;
+
+/// `iScroll.y` change for a pinch gesture's magnification `delta`
+///
+/// Works in log space so pinching out and back in returns to the same zoom,
+/// and one pinch matches `zoom_factor` regardless of the current level.
+pub fn pinch_scroll(delta: f64) -> f32 {
+    // Guard against delta <= -1, which would be a zero or negative scale
+    let scale = (1.0 + delta).max(0.01);
+    (PINCH_GAIN * scale.ln() / (ZOOM_PER_SCROLL as f64).ln()) as f32
+}
+
+/// Blend factor for frame-rate independent exponential smoothing
+///
+/// After `dt` seconds the smoothed value covers `1 - exp(-dt / tau)` of the
+/// remaining distance to the target, so the feel is identical at 30 or 144 fps.
+/// `tau <= 0` disables smoothing.
+pub fn smoothing_alpha(dt: f64, tau: f64) -> f64 {
+    if tau <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-dt / tau).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_click_sign_tracks_button_state() {
+        // Frame of the click: both z and w positive
+        assert_eq!(shadertoy_mouse([5.0, 6.0], [10.0, 20.0], true, true), [5.0, 6.0, 10.0, 20.0]);
+        // Held: z positive, w negative
+        assert_eq!(shadertoy_mouse([5.0, 6.0], [10.0, 20.0], true, false), [5.0, 6.0, 10.0, -20.0]);
+        // Released: last click retained, both negative
+        assert_eq!(shadertoy_mouse([5.0, 6.0], [10.0, 20.0], false, false), [5.0, 6.0, -10.0, -20.0]);
+    }
+
+    #[test]
+    fn test_smoothing_is_frame_rate_independent() {
+        // Two 1/60 s steps must land where one 1/30 s step does
+        let a60 = smoothing_alpha(1.0 / 60.0, 0.2);
+        let after_two = 1.0 - (1.0 - a60) * (1.0 - a60);
+        assert!((after_two - smoothing_alpha(1.0 / 30.0, 0.2)).abs() < 1e-12);
+        assert_eq!(smoothing_alpha(0.016, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_zoom_factor_compounds_per_step() {
+        assert_eq!(zoom_factor(0.0), 1.0);
+        assert!((zoom_factor(2.0) - zoom_factor(1.0) * ZOOM_PER_SCROLL).abs() < 1e-6);
+        assert!((zoom_factor(-1.0) * zoom_factor(1.0) - 1.0).abs() < 1e-6);
+        // A 2x pinch zooms 2^PINCH_GAIN, and pinching back in undoes it
+        let out = pinch_scroll(1.0);
+        assert!((zoom_factor(out) - 2f32.powf(PINCH_GAIN as f32)).abs() < 1e-3);
+        assert!((out + pinch_scroll(-0.5)).abs() < 1e-4);
+    }
+}
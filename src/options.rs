@@ -0,0 +1,719 @@
+// Command line option parsing shared by all platform entry points
+//
+// Hand-rolled to keep the dependency footprint small: flags are `--name` or
+// `--name <value>`, the first non-flag argument is the shader to start with.
+
+use crate::ubo::PARAM_COUNT;
+
+/// Offscreen render target tiling (Linux/Redox renderer only)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderTiling {
+    /// Render straight into a host-mapped LINEAR image (no copy)
+    #[default]
+    Linear,
+    /// Render into an OPTIMAL device-local image, then copy into a host buffer
+    Optimal,
+}
+
+/// Pixel format of the render target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderFormat {
+    /// 8-bit BGRA, the layout of the DRM/Redox framebuffers and the swapchain
+    #[default]
+    Rgba8,
+    /// Half-float RGBA: values outside [0, 1] survive
+    Rgba16f,
+    /// Full-float RGBA
+    Rgba32f,
+}
+
+impl RenderFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            RenderFormat::Rgba8 => 4,
+            RenderFormat::Rgba16f => 8,
+            RenderFormat::Rgba32f => 16,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        self != RenderFormat::Rgba8
+    }
+}
+
+/// Curve that maps a float render target to the 8-bit display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemap {
+    /// Clamp to [0, 1]
+    #[default]
+    None,
+    /// c / (1 + c): gentle, never clips
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+/// What the built-in fullscreen vertex shader passes as `fragCoord`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FragCoord {
+    /// Pixels, 0 to iResolution.xy, like ShaderToy's `fragCoord`
+    #[default]
+    Pixels,
+    /// 0 to 1 across the render target, like a `uv` varying
+    Normalized,
+}
+
+/// How the window compositor treats the swapchain's alpha channel (macOS)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompositeAlpha {
+    #[default]
+    Opaque,
+    /// Shader output is blended so the swapchain holds premultiplied color
+    PreMultiplied,
+    /// Swapchain holds straight color; the compositor multiplies by alpha
+    PostMultiplied,
+}
+
+/// Pixel density the macOS window renders at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dpi {
+    /// One shader pixel per device pixel (2x2 per point on retina)
+    #[default]
+    Physical,
+    /// One shader pixel per point; the compositor upscales
+    Logical,
+}
+
+/// iChannel0 addressing outside [0, 1], named as in ShaderToy's channel settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelWrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+/// iChannel0 filtering, named as in ShaderToy's channel settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelFilter {
+    /// Trilinear across the mip chain
+    #[default]
+    Mipmap,
+    /// Bilinear on level 0 only
+    Linear,
+    Nearest,
+}
+
+/// Sampler settings of the iChannel0 slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelSampler {
+    pub wrap: ChannelWrap,
+    pub filter: ChannelFilter,
+}
+
+/// SPIR-V entry point names of the two shader stages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPoints {
+    pub vertex: String,
+    pub fragment: String,
+}
+
+impl Default for EntryPoints {
+    fn default() -> Self {
+        Self { vertex: "main".to_string(), fragment: "main".to_string() }
+    }
+}
+
+/// Settings from the command line over the config file; several fields only
+/// apply to one platform's entry point
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Shader name or path to start with
+    pub shader: String,
+    pub tiling: RenderTiling,
+    /// Render target format
+    pub format: RenderFormat,
+    /// Applied to float render targets on their way to the display
+    pub tonemap: Tonemap,
+    pub composite_alpha: CompositeAlpha,
+    /// Alpha-blend shader output over the clear color (src-alpha, one-minus-src-alpha)
+    pub blend: bool,
+    /// Mouse smoothing time constant in seconds (0 = off)
+    pub mouse_smoothing: f64,
+    /// Render with a CPU Vulkan implementation (lavapipe/SwiftShader)
+    pub software: bool,
+    /// Fail shader compilation on glslangValidator warnings
+    pub werror: bool,
+    /// ShaderToy JSON export to import, compile and start with
+    pub shadertoy: Option<String>,
+    /// Index into the monitor list to open and go fullscreen on (macOS)
+    pub monitor: Option<usize>,
+    /// Window size in pixels at startup, and the display mode nearest to it
+    /// in fullscreen (macOS)
+    pub size: Option<(u32, u32)>,
+    /// Refresh rate to pick display modes at instead of the highest (macOS)
+    pub refresh: Option<f64>,
+    /// Window sizes for keys 1-9 while windowed (macOS)
+    pub window_sizes: Vec<(u32, u32)>,
+    /// UDP port to receive OSC `/param/N` and `/shader/*` messages on
+    pub osc_port: Option<u16>,
+    /// MIDI input port, by index or part of its name
+    pub midi: Option<String>,
+    /// MIDI control change number to iParams index
+    pub midi_cc: Vec<(u8, usize)>,
+    /// Start in borderless fullscreen (macOS; DRM output is always fullscreen)
+    pub fullscreen: bool,
+    /// Installation mode: hidden cursor, quit keys ignored (Ctrl+Alt+Q still
+    /// exits), shaders advance automatically
+    pub kiosk: bool,
+    /// Seconds between automatic shader switches in kiosk mode
+    pub kiosk_interval: f64,
+    /// Seconds the shader name stays on screen after a switch (0 = off)
+    pub overlay_secs: f64,
+    /// Draw an FPS counter in the top-right corner
+    pub show_fps: bool,
+    /// iTime at startup in seconds
+    pub start_time: f64,
+    /// Render target size relative to the display/window, in (0, 1]
+    pub render_scale: f32,
+    /// Letterbox to this aspect ratio (W, H) instead of filling the output
+    pub aspect: Option<(u32, u32)>,
+    /// Apply a shader's sidecar `resolution` when switching to it
+    pub shader_resolution: bool,
+    /// Put `fragCoord` (0, 0) at the bottom-left like ShaderToy instead of
+    /// the top-left
+    pub flip_y: bool,
+    /// `fragCoord` units of shaders without their own vertex shader
+    pub frag_coord: FragCoord,
+    /// Uncapped frame rate for benchmarking: IMMEDIATE present, no redraw
+    /// pacing (macOS)
+    pub no_vsync: bool,
+    /// Render in device pixels or in points (macOS)
+    pub dpi: Dpi,
+    /// Prefer a Display P3 / extended sRGB swapchain (macOS)
+    pub wide_gamut: bool,
+    /// Wrap iTime to 0 after this many seconds
+    pub loop_time: Option<f64>,
+    /// Fragment SPIR-V to load directly instead of scanning (`-` = stdin)
+    pub frag_spv: Option<String>,
+    /// Vertex SPIR-V to pair with `frag_spv`; defaults to the generated
+    /// fullscreen vertex shader
+    pub vert_spv: Option<String>,
+    /// `.dds`/`.ktx2` texture bound as iChannel0 instead of the checkerboard
+    pub channel0: Option<String>,
+    /// Convert a 2D `channel0` from an equirectangular panorama to a cubemap
+    pub channel0_cube: bool,
+    pub channel0_sampler: ChannelSampler,
+    /// Entry points looked up in the SPIR-V modules (default `main`)
+    pub entry_points: EntryPoints,
+    /// Compile and load every shader headlessly, report, and exit
+    pub check: bool,
+    /// Directory `--check` scans instead of `search_dirs`
+    pub check_dir: Option<String>,
+    /// Write a `shaders.index` into each search directory and exit
+    pub reindex: bool,
+    /// Render one frame headlessly to this OpenEXR file and exit
+    pub out: Option<String>,
+    /// Render target size for `out`
+    pub out_size: (u32, u32),
+    /// Frames to render before exiting (`--once` is 1); None loops until
+    /// quit. `--out` renders one frame unless given a count
+    pub frames: Option<u32>,
+    /// Render every shader into a tiled PNG at this path and exit
+    pub contact_sheet: Option<String>,
+    /// Tiles per row of `contact_sheet`
+    pub cols: u32,
+    /// Write a `<name>.png` per shader into this directory and exit
+    pub thumbnails: Option<String>,
+    /// Size of `thumbnails` and the `contact_sheet` tiles
+    pub thumb_size: (u32, u32),
+    /// Directories scanned for compiled shaders
+    pub search_dirs: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            shader: "example".to_string(),
+            tiling: RenderTiling::default(),
+            format: RenderFormat::default(),
+            tonemap: Tonemap::default(),
+            composite_alpha: CompositeAlpha::default(),
+            blend: false,
+            mouse_smoothing: 0.2,
+            software: false,
+            werror: false,
+            shadertoy: None,
+            monitor: None,
+            size: None,
+            refresh: None,
+            window_sizes: default_window_sizes(),
+            fullscreen: false,
+            kiosk: false,
+            kiosk_interval: 60.0,
+            overlay_secs: crate::overlay::DEFAULT_SECS,
+            show_fps: false,
+            start_time: 0.0,
+            render_scale: 1.0,
+            aspect: None,
+            flip_y: false,
+            frag_coord: FragCoord::default(),
+            no_vsync: false,
+            shader_resolution: true,
+            dpi: Dpi::default(),
+            wide_gamut: false,
+            loop_time: None,
+            frag_spv: None,
+            vert_spv: None,
+            osc_port: None,
+            midi: None,
+            midi_cc: default_midi_cc(),
+            channel0: None,
+            channel0_cube: false,
+            channel0_sampler: ChannelSampler::default(),
+            entry_points: EntryPoints::default(),
+            check: false,
+            check_dir: None,
+            reindex: false,
+            out: None,
+            out_size: (1920, 1080),
+            frames: None,
+            contact_sheet: None,
+            cols: 4,
+            thumbnails: None,
+            thumb_size: (320, 180),
+            search_dirs: [".".to_string(), "./shaders".to_string()]
+                .into_iter()
+                .chain(crate::paths::user_shader_dir().map(|dir| dir.to_string_lossy().into_owned()))
+                .collect(),
+        }
+    }
+}
+
+/// What a command line asks for
+#[derive(Debug, Clone)]
+pub enum Parsed {
+    /// Run the viewer (or a headless mode) with these options
+    Run(Box<Options>),
+    /// `--help`: print the usage (`print_usage`) and exit
+    Help,
+    /// `--version`: print the build details (`print_version`) and exit
+    Version,
+}
+
+impl Options {
+    /// Parse options from the process arguments over the config file
+    /// defaults
+    pub fn from_env() -> Result<Parsed, String> {
+        let defaults = crate::config::Config::load().apply(Self::default())?;
+        Self::parse_over(defaults, std::env::args().skip(1))
+    }
+
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Parsed, String> {
+        Self::parse_over(Self::default(), args)
+    }
+
+    /// Parse flags on top of `opts` (built-in or config file defaults);
+    /// `--help` and `--version` stop at once, ignoring the other flags
+    pub fn parse_over<I: IntoIterator<Item = String>>(mut opts: Self, args: I) -> Result<Parsed, String> {
+        let mut shader = None;
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Parsed::Help),
+                "-V" | "--version" => return Ok(Parsed::Version),
+                "--tiling" => opts.tiling = parse_tiling(&value(&mut args, &arg)?)?,
+                "--format" => opts.format = parse_render_format(&value(&mut args, &arg)?)?,
+                "--tonemap" => opts.tonemap = parse_tonemap(&value(&mut args, &arg)?)?,
+                "--blend" => opts.blend = true,
+                "--mouse-smoothing" => {
+                    opts.mouse_smoothing = parsed(&mut args, &arg)?;
+                    // NaN would turn iMouse into NaN for the rest of the session
+                    if !(opts.mouse_smoothing >= 0.0 && opts.mouse_smoothing.is_finite()) {
+                        return Err("--mouse-smoothing must be a finite number >= 0".to_string());
+                    }
+                }
+                "--software" => opts.software = true,
+                "--check" => {
+                    opts.check = true;
+                    // Optional directory; a following flag isn't one
+                    opts.check_dir = args.next_if(|next| !next.starts_with('-'));
+                }
+                "--reindex" => opts.reindex = true,
+                "--out" => opts.out = Some(value(&mut args, &arg)?),
+                "--out-size" => opts.out_size = parse_size(&value(&mut args, &arg)?)?,
+                "--once" => opts.frames = Some(1),
+                "--repeat" => {
+                    // Optional frame count; a following shader name isn't one
+                    opts.frames = args.peek().and_then(|next| next.parse::<u32>().ok());
+                    if opts.frames.is_some() {
+                        args.next();
+                    }
+                    if opts.frames == Some(0) {
+                        return Err("--repeat count must be > 0".to_string());
+                    }
+                }
+                "--contact-sheet" => opts.contact_sheet = Some(value(&mut args, &arg)?),
+                "--cols" => {
+                    opts.cols = parsed(&mut args, &arg)?;
+                    if opts.cols == 0 {
+                        return Err("--cols must be > 0".to_string());
+                    }
+                }
+                "--thumbnails" => opts.thumbnails = Some(value(&mut args, &arg)?),
+                "--thumb-size" => opts.thumb_size = parse_size(&value(&mut args, &arg)?)?,
+                "--werror" => opts.werror = true,
+                "--shadertoy" => opts.shadertoy = Some(value(&mut args, &arg)?),
+                "--fullscreen" => opts.fullscreen = true,
+                "--kiosk" => opts.kiosk = true,
+                "--kiosk-interval" => {
+                    opts.kiosk_interval = parsed(&mut args, &arg)?;
+                    // NaN or infinity would never be reached, so kiosk mode would stop cycling
+                    if !(opts.kiosk_interval > 0.0 && opts.kiosk_interval.is_finite()) {
+                        return Err("--kiosk-interval must be a finite number > 0".to_string());
+                    }
+                }
+                "--overlay" => {
+                    opts.overlay_secs = parsed(&mut args, &arg)?;
+                    if opts.overlay_secs < 0.0 {
+                        return Err("--overlay must be >= 0".to_string());
+                    }
+                }
+                "--show-fps" => opts.show_fps = true,
+                "--start-time" => opts.start_time = parsed(&mut args, &arg)?,
+                "--render-scale" => {
+                    opts.render_scale = parsed(&mut args, &arg)?;
+                    if !(opts.render_scale > 0.0 && opts.render_scale <= 1.0) {
+                        return Err("--render-scale must be in (0, 1]".to_string());
+                    }
+                }
+                "--flip-y" => opts.flip_y = true,
+                "--frag-coord" => opts.frag_coord = parse_frag_coord(&value(&mut args, &arg)?)?,
+                "--no-vsync" => opts.no_vsync = true,
+                "--ignore-shader-resolution" => opts.shader_resolution = false,
+                "--aspect" => opts.aspect = Some(parse_aspect(&value(&mut args, &arg)?)?),
+                "--loop-time" => {
+                    let period: f64 = parsed(&mut args, &arg)?;
+                    if period <= 0.0 {
+                        return Err("--loop-time must be > 0".to_string());
+                    }
+                    opts.loop_time = Some(period);
+                }
+                "--entry" => {
+                    let name = value(&mut args, &arg)?;
+                    opts.entry_points = EntryPoints { vertex: name.clone(), fragment: name };
+                }
+                "--vert-entry" => opts.entry_points.vertex = value(&mut args, &arg)?,
+                "--frag-entry" => opts.entry_points.fragment = value(&mut args, &arg)?,
+                "-" => opts.frag_spv = Some("-".to_string()),
+                "--frag-spv" => opts.frag_spv = Some(value(&mut args, &arg)?),
+                "--vert-spv" => opts.vert_spv = Some(value(&mut args, &arg)?),
+                "--channel0" => opts.channel0 = Some(value(&mut args, &arg)?),
+                "--channel0-cube" => opts.channel0_cube = true,
+                "--channel0-wrap" => opts.channel0_sampler.wrap = parse_channel_wrap(&value(&mut args, &arg)?)?,
+                "--channel0-filter" => opts.channel0_sampler.filter = parse_channel_filter(&value(&mut args, &arg)?)?,
+                "--wide-gamut" => opts.wide_gamut = true,
+                "--dpi" => opts.dpi = parse_dpi(&value(&mut args, &arg)?)?,
+                "--monitor" => opts.monitor = Some(parsed(&mut args, &arg)?),
+                "--size" => opts.size = Some(parse_size(&value(&mut args, &arg)?)?),
+                "--refresh" => {
+                    let hz: f64 = parsed(&mut args, &arg)?;
+                    if hz <= 0.0 {
+                        return Err("--refresh must be > 0".to_string());
+                    }
+                    opts.refresh = Some(hz);
+                }
+                "--window-sizes" => opts.window_sizes = parse_window_sizes(&value(&mut args, &arg)?)?,
+                "--osc" => opts.osc_port = Some(parsed(&mut args, &arg)?),
+                "--midi" => opts.midi = Some(value(&mut args, &arg)?),
+                "--midi-cc" => opts.midi_cc = parse_midi_cc(&value(&mut args, &arg)?)?,
+                "--transparent" => opts.composite_alpha = CompositeAlpha::PreMultiplied,
+                "--composite-alpha" => {
+                    opts.composite_alpha = parse_composite_alpha(&value(&mut args, &arg)?)?;
+                }
+                flag if flag.starts_with("--") => {
+                    return Err(format!("Unknown option: {} (see --help)", flag));
+                }
+                // Process serial number older macOS adds to Finder launches
+                psn if psn.starts_with("-psn_") => {}
+                _ => {
+                    if shader.is_some() {
+                        return Err(format!("Unexpected argument: {}", arg));
+                    }
+                    shader = Some(arg);
+                }
+            }
+        }
+
+        if let Some(shader) = shader {
+            opts.shader = shader;
+        }
+        if opts.vert_spv.is_some() && opts.frag_spv.is_none() {
+            return Err("--vert-spv needs --frag-spv (or - for stdin)".to_string());
+        }
+        Ok(Parsed::Run(Box::new(opts)))
+    }
+}
+
+pub fn parse_tiling(value: &str) -> Result<RenderTiling, String> {
+    match value {
+        "linear" => Ok(RenderTiling::Linear),
+        "optimal" => Ok(RenderTiling::Optimal),
+        other => Err(format!("tiling expects linear|optimal, got '{}'", other)),
+    }
+}
+
+pub fn parse_render_format(value: &str) -> Result<RenderFormat, String> {
+    match value {
+        "rgba8" => Ok(RenderFormat::Rgba8),
+        "rgba16f" => Ok(RenderFormat::Rgba16f),
+        "rgba32f" => Ok(RenderFormat::Rgba32f),
+        other => Err(format!("format expects rgba8|rgba16f|rgba32f, got '{}'", other)),
+    }
+}
+
+pub fn parse_tonemap(value: &str) -> Result<Tonemap, String> {
+    match value {
+        "none" => Ok(Tonemap::None),
+        "reinhard" => Ok(Tonemap::Reinhard),
+        "aces" => Ok(Tonemap::Aces),
+        other => Err(format!("tonemap expects aces|reinhard|none, got '{}'", other)),
+    }
+}
+
+pub fn parse_frag_coord(value: &str) -> Result<FragCoord, String> {
+    match value {
+        "pixels" => Ok(FragCoord::Pixels),
+        "normalized" => Ok(FragCoord::Normalized),
+        other => Err(format!("frag-coord expects pixels|normalized, got '{}'", other)),
+    }
+}
+
+pub fn parse_composite_alpha(value: &str) -> Result<CompositeAlpha, String> {
+    match value {
+        "opaque" => Ok(CompositeAlpha::Opaque),
+        "premultiplied" => Ok(CompositeAlpha::PreMultiplied),
+        "postmultiplied" => Ok(CompositeAlpha::PostMultiplied),
+        other => Err(format!(
+            "composite-alpha expects opaque|premultiplied|postmultiplied, got '{}'", other
+        )),
+    }
+}
+
+pub fn parse_dpi(value: &str) -> Result<Dpi, String> {
+    match value {
+        "physical" => Ok(Dpi::Physical),
+        "logical" => Ok(Dpi::Logical),
+        other => Err(format!("dpi expects physical|logical, got '{}'", other)),
+    }
+}
+
+/// CC 1-8 to iParams[0..8], the first eight knobs on most controllers
+pub fn default_midi_cc() -> Vec<(u8, usize)> {
+    (0..PARAM_COUNT).map(|param| (param as u8 + 1, param)).collect()
+}
+
+/// `CC=PARAM[,CC=PARAM...]`, e.g. `74=0,71=1`
+pub fn parse_midi_cc(value: &str) -> Result<Vec<(u8, usize)>, String> {
+    value
+        .split(',')
+        .map(|pair| {
+            let parsed = pair.split_once('=').and_then(|(cc, param)| {
+                Some((cc.trim().parse::<u8>().ok()?, param.trim().parse::<usize>().ok()?))
+            });
+            match parsed {
+                Some((cc, param)) if cc < 128 && param < PARAM_COUNT => Ok((cc, param)),
+                _ => Err(format!(
+                    "midi cc map expects CC=PARAM pairs (CC 0-127, PARAM 0-{}), got '{}'",
+                    PARAM_COUNT - 1,
+                    pair
+                )),
+            }
+        })
+        .collect()
+}
+
+pub fn parse_channel_wrap(value: &str) -> Result<ChannelWrap, String> {
+    match value {
+        "repeat" => Ok(ChannelWrap::Repeat),
+        "clamp" => Ok(ChannelWrap::Clamp),
+        "mirror" => Ok(ChannelWrap::Mirror),
+        other => Err(format!("channel wrap expects repeat|clamp|mirror, got '{}'", other)),
+    }
+}
+
+pub fn parse_channel_filter(value: &str) -> Result<ChannelFilter, String> {
+    match value {
+        "mipmap" => Ok(ChannelFilter::Mipmap),
+        "linear" => Ok(ChannelFilter::Linear),
+        "nearest" => Ok(ChannelFilter::Nearest),
+        other => Err(format!("channel filter expects mipmap|linear|nearest, got '{}'", other)),
+    }
+}
+
+pub fn parse_aspect(value: &str) -> Result<(u32, u32), String> {
+    let parsed = value
+        .split_once(':')
+        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)));
+    match parsed {
+        Some((w, h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(format!("aspect expects W:H like 16:9, got '{}'", value)),
+    }
+}
+
+pub fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let parsed = value
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)));
+    match parsed {
+        Some((w, h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(format!("size expects WxH like 1920x1080, got '{}'", value)),
+    }
+}
+
+/// Keys 1-5 in a window: 16:9 from 1024x576 up to 4K
+pub fn default_window_sizes() -> Vec<(u32, u32)> {
+    vec![(1024, 576), (1280, 720), (1920, 1080), (2560, 1440), (3840, 2160)]
+}
+
+/// `WxH[,WxH...]`, one size per number key, e.g. `2560x1080,1080x1920`
+pub fn parse_window_sizes(value: &str) -> Result<Vec<(u32, u32)>, String> {
+    let sizes = value.split(',').map(parse_size).collect::<Result<Vec<_>, _>>()?;
+    if sizes.len() > 9 {
+        return Err(format!("window sizes: at most 9 (keys 1-9), got {}", sizes.len()));
+    }
+    Ok(sizes)
+}
+
+/// Render target size for an output of `width` x `height` at `scale`
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    let scaled = |n: u32| ((n as f32 * scale).round() as u32).clamp(1, n.max(1));
+    (scaled(width), scaled(height))
+}
+
+/// Fetch the value following a flag
+fn value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} requires a value", flag))
+}
+
+/// Fetch and parse the value following a flag
+fn parsed<T, I>(args: &mut I, flag: &str) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    I: Iterator<Item = String>,
+{
+    let raw = value(args, flag)?;
+    raw.parse()
+        .map_err(|_| format!("{}: invalid value '{}'", flag, raw))
+}
+
+/// Cargo features this build was compiled with
+const FEATURES: [(&str, bool); 10] = [
+    ("drm", cfg!(feature = "drm")),
+    ("window", cfg!(feature = "window")),
+    ("animation", cfg!(feature = "animation")),
+    ("osc", cfg!(feature = "osc")),
+    ("midi", cfg!(feature = "midi")),
+    ("exr", cfg!(feature = "exr")),
+    ("png", cfg!(feature = "png")),
+    ("wgsl", cfg!(feature = "wgsl")),
+    ("shaderc", cfg!(feature = "shaderc")),
+    ("rayon", cfg!(feature = "rayon")),
+];
+
+pub fn print_usage() {
+    println!("Usage: metalshader [options] [shader | -]");
+    println!();
+    println!("Options:");
+    println!("  --tiling linear|optimal   Offscreen render target tiling (Linux/Redox, default linear)");
+    println!("  --format FORMAT           rgba8|rgba16f|rgba32f render target (default rgba8)");
+    println!("  --tonemap MODE            aces|reinhard|none for float targets on the display (default none)");
+    println!("  --blend                   Alpha-blend shader output over the clear color");
+    println!("  --mouse-smoothing SECS    Mouse smoothing time constant, 0 = off (macOS, default 0.2)");
+    println!("  --software                Use lavapipe/SwiftShader instead of the GPU");
+    println!("  --shadertoy FILE          Import a ShaderToy JSON export and start with it");
+    println!("  --werror                  Treat shader compile warnings as errors");
+    println!("  --check [DIR]             Compile and load every .frag headlessly, report, and exit");
+    println!("  --reindex                 Write shaders.index into each search directory for fast startup, and exit");
+    println!("  --out FILE.exr            Render headlessly to OpenEXR and exit; numbered files with --repeat N (feature exr)");
+    println!("  --out-size WxH            Size of the --out frame (default 1920x1080)");
+    println!("  --contact-sheet FILE.png  Render every shader into one tiled, labeled PNG and exit");
+    println!("  --cols N                  Tiles per row of the contact sheet (default 4)");
+    println!("  --thumbnails DIR          Write DIR/<name>.png for each shader that changed since, and exit");
+    println!("  --thumb-size WxH          Size of thumbnails and contact sheet tiles (default 320x180)");
+    println!("  --once                    Render a single frame and exit (same as --repeat 1)");
+    println!("  --repeat [N]              Render N frames and exit; without N, loop until quit (the default)");
+    println!("  --fullscreen              Start fullscreen (macOS; DRM output always is)");
+    println!("  --kiosk                   Hide cursor, ignore Esc/Q (Ctrl+Alt+Q quits), cycle shaders");
+    println!("  --kiosk-interval SECS     Seconds per shader in kiosk mode (default 60)");
+    println!("  --overlay SECS            Show the shader name for SECS after a switch (default 3, 0 = off)");
+    println!("  --show-fps                Show the frame rate in the top-right corner");
+    println!("  --start-time SECS         Begin the animation at iTime = SECS");
+    println!("  --loop-time SECS          Wrap iTime to 0 every SECS seconds");
+    println!("  --render-scale F          Render at F x the output size and upscale (0 < F <= 1)");
+    println!("  --aspect W:H              Letterbox to a fixed aspect ratio, e.g. 16:9");
+    println!("  --ignore-shader-resolution");
+    println!("                            Don't apply the resolution from a shader's sidecar JSON");
+    println!("  --flip-y                  fragCoord origin at the bottom-left (ShaderToy) instead of top-left");
+    println!("  --frag-coord UNITS        pixels|normalized fragCoord from the built-in vertex shader (default pixels)");
+    println!("  --entry NAME              SPIR-V entry point of both stages (default main)");
+    println!("  --vert-entry NAME         Entry point of the vertex stage only");
+    println!("  --frag-entry NAME         Entry point of the fragment stage only");
+    println!("  -                         Read fragment SPIR-V from stdin (fullscreen vertex shader)");
+    println!("  --frag-spv FILE           Load this fragment SPIR-V directly instead of scanning");
+    println!("  --vert-spv FILE           Vertex SPIR-V to pair with --frag-spv");
+    println!("  --channel0 FILE[,...]     Bind a .dds/.ktx2 texture (RGBA8 or BC1-BC7) or a .gif/.png as iChannel0;");
+    println!("                            six comma-separated files (+X,-X,+Y,-Y,+Z,-Z) make a cubemap");
+    println!("  --channel0-cube           Convert an equirectangular --channel0 image to a cubemap");
+    println!("  --channel0-wrap MODE      repeat|clamp|mirror addressing of iChannel0 (default repeat)");
+    println!("  --channel0-filter MODE    mipmap|linear|nearest filtering of iChannel0 (default mipmap)");
+    println!("  --no-vsync                Uncapped frame rate for benchmarking (macOS)");
+    println!("  --wide-gamut              Present in Display P3 or extended sRGB when available (macOS)");
+    println!("  --dpi MODE                physical|logical render resolution on retina displays (macOS)");
+    println!("  --monitor N               Open and go fullscreen on monitor N (macOS, see startup list)");
+    println!("  --size WxH                Window size in pixels; in fullscreen the nearest display mode (macOS)");
+    println!("  --refresh HZ              Display modes at the refresh rate nearest HZ, not the highest (macOS)");
+    println!("  --window-sizes WxH[,...]  Window sizes for keys 1-9 while windowed (macOS, default 16:9 from 1024x576)");
+    println!("  --transparent             Transparent, undecorated window where alpha < 1 (macOS)");
+    println!("  --composite-alpha MODE    opaque|premultiplied|postmultiplied window compositing (macOS)");
+    println!("  --osc PORT                Set iParams and switch shaders over OSC/UDP (feature osc)");
+    println!("  --midi PORT               Map MIDI CCs to iParams, note-on = next shader (feature midi)");
+    println!("  --midi-cc CC=N[,...]      MIDI CC to iParams index map (default 1=0,...,8=7)");
+    println!("  -h, --help                Show this help");
+    println!("  -V, --version             Show version, git commit, Vulkan and enabled features");
+    println!();
+    match crate::config::config_path() {
+        Some(path) => println!("Defaults are read from {} if it exists.", path.display()),
+        None => println!("Defaults are read from config.toml in the platform config directory if it exists."),
+    }
+}
+
+/// `--version`: what a bug report needs to reproduce the build
+pub fn print_version() {
+    println!("metalshader {} ({})", env!("CARGO_PKG_VERSION"), env!("METALSHADER_GIT_HASH"));
+    let header = ash::vk::HEADER_VERSION_COMPLETE;
+    println!(
+        "Vulkan headers: {}.{}.{} (ash)",
+        ash::vk::api_version_major(header),
+        ash::vk::api_version_minor(header),
+        ash::vk::api_version_patch(header)
+    );
+    // The loader is looked up at runtime; a missing one is worth reporting too
+    let loader = crate::vulkan_loader::load_entry()
+        .map_err(|e| e.to_string())
+        .and_then(|entry| unsafe { entry.try_enumerate_instance_version() }.map_err(|e| e.to_string()));
+    match loader {
+        Ok(Some(version)) => println!(
+            "Vulkan loader: {}.{}.{}",
+            ash::vk::api_version_major(version),
+            ash::vk::api_version_minor(version),
+            ash::vk::api_version_patch(version)
+        ),
+        Ok(None) => println!("Vulkan loader: 1.0"),
+        Err(e) => println!("Vulkan loader: unavailable ({})", e),
+    }
+    let enabled: Vec<&str> = FEATURES.iter().filter(|(_, on)| *on).map(|(name, _)| *name).collect();
+    println!("Features: {}", if enabled.is_empty() { "none".to_string() } else { enabled.join(" ") });
+    println!("Target: {} {}", std::env::consts::OS, std::env::consts::ARCH);
+}
@@ -0,0 +1,115 @@
+// OSC remote control for VJ setups (`--osc PORT`, feature `osc`)
+//
+// A background thread receives OSC packets over UDP and applies them to a
+// `Remote`. Recognized addresses:
+//
+//   /param/N f      iParams[N] = f (N < PARAM_COUNT; int and double accepted)
+//   /shader/next    next shader
+//   /shader/prev    previous shader
+//   /shader/reload  recompile the current shader, like Ctrl+R
+//
+// Anything else is ignored, so a controller can broadcast freely.
+
+use crate::error::MetalshaderError;
+use crate::remote::{Remote, RemoteCommand};
+use crate::ubo::PARAM_COUNT;
+
+/// What an incoming address refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Route {
+    Param(usize),
+    Command(RemoteCommand),
+}
+
+fn route(addr: &str) -> Option<Route> {
+    match addr {
+        "/shader/next" => Some(Route::Command(RemoteCommand::Next)),
+        "/shader/prev" => Some(Route::Command(RemoteCommand::Previous)),
+        "/shader/reload" => Some(Route::Command(RemoteCommand::Reload)),
+        _ => {
+            let index: usize = addr.strip_prefix("/param/")?.parse().ok()?;
+            (index < PARAM_COUNT).then_some(Route::Param(index))
+        }
+    }
+}
+
+/// Apply one message; `value` is its first numeric argument, if any
+#[cfg_attr(not(feature = "osc"), allow(dead_code))]
+fn apply(remote: &Remote, addr: &str, value: Option<f32>) {
+    match route(addr) {
+        Some(Route::Param(index)) => {
+            if let Some(value) = value {
+                remote.set_param(index, value);
+            }
+        }
+        Some(Route::Command(command)) => remote.push(command),
+        None => {}
+    }
+}
+
+/// Listen on UDP `port` on all interfaces, feeding `remote` from a
+/// background thread for the rest of the run
+#[cfg(feature = "osc")]
+pub fn listen(port: u16, remote: Remote) -> Result<(), MetalshaderError> {
+    let socket = std::net::UdpSocket::bind(("0.0.0.0", port))?;
+    std::thread::spawn(move || {
+        let mut buf = [0u8; rosc::decoder::MTU];
+        while let Ok(len) = socket.recv(&mut buf) {
+            match rosc::decoder::decode_udp(&buf[..len]) {
+                Ok((_, packet)) => apply_packet(&remote, packet),
+                Err(e) => eprintln!("OSC: ignoring malformed packet ({:?})", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "osc"))]
+pub fn listen(_port: u16, _remote: Remote) -> Result<(), MetalshaderError> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "--osc needs the `osc` feature").into())
+}
+
+/// Bundles are applied in order, ignoring their time tags
+#[cfg(feature = "osc")]
+fn apply_packet(remote: &Remote, packet: rosc::OscPacket) {
+    match packet {
+        rosc::OscPacket::Message(message) => {
+            let value = message.args.iter().find_map(|arg| match *arg {
+                rosc::OscType::Float(f) => Some(f),
+                rosc::OscType::Double(d) => Some(d as f32),
+                rosc::OscType::Int(i) => Some(i as f32),
+                _ => None,
+            });
+            apply(remote, &message.addr, value);
+        }
+        rosc::OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                apply_packet(remote, packet);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes() {
+        let remote = Remote::new();
+        apply(&remote, "/param/2", Some(0.75));
+        apply(&remote, "/param/8", Some(1.0)); // out of range
+        apply(&remote, "/param/x", Some(1.0));
+        apply(&remote, "/param/3", None);
+        apply(&remote, "/shader/next", None);
+        apply(&remote, "/mixer/fader", Some(0.5));
+        apply(&remote, "/shader/prev", Some(1.0));
+        apply(&remote, "/shader/reload", None);
+        assert_eq!(remote.params(), [0.0, 0.0, 0.75, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(
+            remote.take_commands(),
+            vec![RemoteCommand::Next, RemoteCommand::Previous, RemoteCommand::Reload]
+        );
+        assert!(remote.take_commands().is_empty());
+    }
+}
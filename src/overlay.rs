@@ -0,0 +1,357 @@
+// On-screen shader name and FPS counter for fullscreen and kiosk use
+//
+// Text is laid out with a built-in 8x16 bitmap font as solid rectangles: a
+// dark backing box and the lit runs of each glyph row. The renderers clear
+// those rectangles with vkCmdClearAttachments at the end of the render pass,
+// so no font texture or extra pipeline is needed and the label stays put
+// with --flip-y.
+
+use std::time::{Duration, Instant};
+
+use ash::vk;
+
+/// Glyph cell in font pixels
+pub const GLYPH_WIDTH: u32 = 8;
+pub const GLYPH_HEIGHT: u32 = 16;
+
+/// Seconds the name stays up after a switch unless `--overlay` says otherwise
+pub const DEFAULT_SECS: f64 = 3.0;
+
+/// How often the FPS text changes. Slower than the frame rate so it stays
+/// readable, and so the DRM renderer doesn't re-record its commands per frame
+const FPS_REFRESH: Duration = Duration::from_millis(500);
+
+/// Printable ASCII (0x20..=0x7e), one byte per row with the leftmost pixel in
+/// the high bit, rasterized from DejaVu Sans Mono Bold at 13 px
+const FONT: [[u8; 16]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // '!'
+    [0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x00, 0x00, 0x00, 0x00, 0x1a, 0x16, 0x7f, 0x34, 0x24, 0xfe, 0x68, 0x48, 0x00, 0x00, 0x00, 0x00], // '#'
+    [0x00, 0x00, 0x08, 0x08, 0x3e, 0x6a, 0x68, 0x3e, 0x0f, 0x4b, 0x6b, 0x3e, 0x08, 0x08, 0x00, 0x00], // '$'
+    [0x00, 0x00, 0x00, 0x60, 0x90, 0x90, 0x62, 0x18, 0x46, 0x09, 0x09, 0x06, 0x00, 0x00, 0x00, 0x00], // '%'
+    [0x00, 0x00, 0x00, 0x1c, 0x34, 0x30, 0x18, 0x39, 0x6d, 0x6f, 0x77, 0x3f, 0x00, 0x00, 0x00, 0x00], // '&'
+    [0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x00, 0x08, 0x18, 0x10, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x10, 0x18, 0x08, 0x00, 0x00, 0x00], // '('
+    [0x00, 0x10, 0x18, 0x08, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x08, 0x18, 0x10, 0x00, 0x00, 0x00], // ')'
+    [0x00, 0x00, 0x00, 0x10, 0xd6, 0x7c, 0x7c, 0xd6, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '*'
+    [0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0xff, 0xff, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x10, 0x20, 0x00, 0x00], // ','
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // '.'
+    [0x00, 0x00, 0x00, 0x02, 0x04, 0x04, 0x08, 0x08, 0x18, 0x10, 0x10, 0x20, 0x20, 0x40, 0x00, 0x00], // '/'
+    [0x00, 0x00, 0x00, 0x1c, 0x36, 0x63, 0x6b, 0x6b, 0x63, 0x63, 0x36, 0x1c, 0x00, 0x00, 0x00, 0x00], // '0'
+    [0x00, 0x00, 0x00, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00, 0x00], // '1'
+    [0x00, 0x00, 0x00, 0x3e, 0x47, 0x03, 0x03, 0x06, 0x0c, 0x18, 0x70, 0x7f, 0x00, 0x00, 0x00, 0x00], // '2'
+    [0x00, 0x00, 0x00, 0x3e, 0x43, 0x03, 0x1e, 0x07, 0x03, 0x03, 0x47, 0x3e, 0x00, 0x00, 0x00, 0x00], // '3'
+    [0x00, 0x00, 0x00, 0x0e, 0x0e, 0x1e, 0x36, 0x66, 0x7f, 0x06, 0x06, 0x06, 0x00, 0x00, 0x00, 0x00], // '4'
+    [0x00, 0x00, 0x00, 0x7e, 0x60, 0x60, 0x7c, 0x47, 0x03, 0x03, 0x47, 0x3c, 0x00, 0x00, 0x00, 0x00], // '5'
+    [0x00, 0x00, 0x00, 0x1c, 0x32, 0x60, 0x7e, 0x77, 0x63, 0x63, 0x37, 0x3e, 0x00, 0x00, 0x00, 0x00], // '6'
+    [0x00, 0x00, 0x00, 0x7f, 0x03, 0x06, 0x06, 0x0c, 0x0c, 0x1c, 0x18, 0x38, 0x00, 0x00, 0x00, 0x00], // '7'
+    [0x00, 0x00, 0x00, 0x3e, 0x63, 0x63, 0x3e, 0x77, 0x63, 0x63, 0x77, 0x3e, 0x00, 0x00, 0x00, 0x00], // '8'
+    [0x00, 0x00, 0x00, 0x3e, 0x76, 0x63, 0x63, 0x77, 0x3f, 0x03, 0x26, 0x1c, 0x00, 0x00, 0x00, 0x00], // '9'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // ':'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x18, 0x18, 0x10, 0x20, 0x00, 0x00], // ';'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x0f, 0x7c, 0x70, 0x7c, 0x0f, 0x01, 0x00, 0x00, 0x00, 0x00], // '<'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7f, 0x7f, 0x00, 0x7f, 0x7f, 0x00, 0x00, 0x00, 0x00, 0x00], // '='
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x78, 0x1f, 0x07, 0x1f, 0x78, 0x40, 0x00, 0x00, 0x00, 0x00], // '>'
+    [0x00, 0x00, 0x00, 0x1c, 0x26, 0x06, 0x0c, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // '?'
+    [0x00, 0x00, 0x00, 0x3c, 0x66, 0xde, 0xb6, 0xa2, 0xa2, 0xa2, 0xb6, 0x5e, 0x62, 0x3e, 0x00, 0x00], // '@'
+    [0x00, 0x00, 0x00, 0x1c, 0x1c, 0x1c, 0x36, 0x36, 0x3e, 0x36, 0x63, 0x63, 0x00, 0x00, 0x00, 0x00], // 'A'
+    [0x00, 0x00, 0x00, 0x7e, 0x63, 0x63, 0x63, 0x7e, 0x63, 0x63, 0x63, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'B'
+    [0x00, 0x00, 0x00, 0x1e, 0x31, 0x60, 0x60, 0x60, 0x60, 0x60, 0x31, 0x1e, 0x00, 0x00, 0x00, 0x00], // 'C'
+    [0x00, 0x00, 0x00, 0x7c, 0x66, 0x63, 0x63, 0x63, 0x63, 0x63, 0x66, 0x7c, 0x00, 0x00, 0x00, 0x00], // 'D'
+    [0x00, 0x00, 0x00, 0x7f, 0x60, 0x60, 0x60, 0x7e, 0x60, 0x60, 0x60, 0x7f, 0x00, 0x00, 0x00, 0x00], // 'E'
+    [0x00, 0x00, 0x00, 0x7f, 0x60, 0x60, 0x60, 0x7e, 0x60, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00, 0x00], // 'F'
+    [0x00, 0x00, 0x00, 0x1e, 0x31, 0x60, 0x60, 0x67, 0x63, 0x63, 0x33, 0x1f, 0x00, 0x00, 0x00, 0x00], // 'G'
+    [0x00, 0x00, 0x00, 0x63, 0x63, 0x63, 0x63, 0x7f, 0x63, 0x63, 0x63, 0x63, 0x00, 0x00, 0x00, 0x00], // 'H'
+    [0x00, 0x00, 0x00, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'I'
+    [0x00, 0x00, 0x00, 0x0f, 0x03, 0x03, 0x03, 0x03, 0x03, 0x03, 0x43, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'J'
+    [0x00, 0x00, 0x00, 0x67, 0x66, 0x6c, 0x78, 0x7c, 0x7c, 0x66, 0x66, 0x63, 0x00, 0x00, 0x00, 0x00], // 'K'
+    [0x00, 0x00, 0x00, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x7f, 0x00, 0x00, 0x00, 0x00], // 'L'
+    [0x00, 0x00, 0x00, 0x77, 0x77, 0x77, 0x7f, 0x7f, 0x6b, 0x63, 0x63, 0x63, 0x00, 0x00, 0x00, 0x00], // 'M'
+    [0x00, 0x00, 0x00, 0x73, 0x73, 0x73, 0x7b, 0x6b, 0x6f, 0x67, 0x67, 0x67, 0x00, 0x00, 0x00, 0x00], // 'N'
+    [0x00, 0x00, 0x00, 0x1c, 0x36, 0x63, 0x63, 0x63, 0x63, 0x63, 0x36, 0x1c, 0x00, 0x00, 0x00, 0x00], // 'O'
+    [0x00, 0x00, 0x00, 0x7e, 0x67, 0x63, 0x63, 0x67, 0x7e, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00, 0x00], // 'P'
+    [0x00, 0x00, 0x00, 0x1c, 0x36, 0x63, 0x63, 0x63, 0x63, 0x63, 0x36, 0x1e, 0x06, 0x02, 0x00, 0x00], // 'Q'
+    [0x00, 0x00, 0x00, 0x7e, 0x67, 0x63, 0x63, 0x67, 0x7e, 0x66, 0x63, 0x63, 0x00, 0x00, 0x00, 0x00], // 'R'
+    [0x00, 0x00, 0x00, 0x3e, 0x61, 0x60, 0x78, 0x3e, 0x0f, 0x03, 0x43, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'S'
+    [0x00, 0x00, 0x00, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // 'T'
+    [0x00, 0x00, 0x00, 0x63, 0x63, 0x63, 0x63, 0x63, 0x63, 0x63, 0x63, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'U'
+    [0x00, 0x00, 0x00, 0x63, 0x63, 0x26, 0x36, 0x36, 0x36, 0x1c, 0x1c, 0x1c, 0x00, 0x00, 0x00, 0x00], // 'V'
+    [0x00, 0x00, 0x00, 0xc3, 0xc3, 0xdb, 0xdb, 0x5b, 0x7e, 0x7e, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // 'W'
+    [0x00, 0x00, 0x00, 0x63, 0x36, 0x3e, 0x1c, 0x1c, 0x1c, 0x3e, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // 'X'
+    [0x00, 0x00, 0x00, 0xc3, 0x66, 0x66, 0x3c, 0x3c, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // 'Y'
+    [0x00, 0x00, 0x00, 0x7f, 0x03, 0x06, 0x0c, 0x1c, 0x38, 0x30, 0x60, 0x7f, 0x00, 0x00, 0x00, 0x00], // 'Z'
+    [0x00, 0x1e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1e, 0x00, 0x00, 0x00], // '['
+    [0x00, 0x00, 0x00, 0x60, 0x20, 0x20, 0x30, 0x10, 0x18, 0x08, 0x0c, 0x04, 0x04, 0x06, 0x00, 0x00], // '\\'
+    [0x00, 0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x38, 0x00, 0x00, 0x00], // ']'
+    [0x00, 0x00, 0x00, 0x38, 0x38, 0x6c, 0xc6, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x00], // '_'
+    [0x00, 0x00, 0x30, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x1c, 0x26, 0x06, 0x3e, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'a'
+    [0x00, 0x60, 0x60, 0x60, 0x60, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x00, 0x00, 0x00, 0x00], // 'b'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x1c, 0x32, 0x60, 0x60, 0x60, 0x32, 0x1c, 0x00, 0x00, 0x00, 0x00], // 'c'
+    [0x00, 0x06, 0x06, 0x06, 0x06, 0x3e, 0x6e, 0x66, 0x66, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'd'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x66, 0x66, 0x7e, 0x60, 0x72, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'e'
+    [0x00, 0x0e, 0x18, 0x18, 0x18, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00], // 'f'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x6e, 0x66, 0x66, 0x66, 0x6e, 0x3e, 0x06, 0x26, 0x1c, 0x00], // 'g'
+    [0x00, 0x60, 0x60, 0x60, 0x60, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // 'h'
+    [0x00, 0x18, 0x18, 0x00, 0x00, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'i'
+    [0x00, 0x0c, 0x0c, 0x00, 0x00, 0x3c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x78, 0x00], // 'j'
+    [0x00, 0x60, 0x60, 0x60, 0x60, 0x6c, 0x6c, 0x78, 0x78, 0x6c, 0x6c, 0x66, 0x00, 0x00, 0x00, 0x00], // 'k'
+    [0x00, 0xf0, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x1e, 0x00, 0x00, 0x00, 0x00], // 'l'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xdb, 0xdb, 0xdb, 0xdb, 0xdb, 0xdb, 0x00, 0x00, 0x00, 0x00], // 'm'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00], // 'n'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00], // 'o'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x60, 0x60, 0x60, 0x00], // 'p'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x6e, 0x66, 0x66, 0x66, 0x66, 0x3e, 0x06, 0x06, 0x06, 0x00], // 'q'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x3a, 0x30, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00], // 'r'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x62, 0x70, 0x3c, 0x0e, 0x46, 0x3c, 0x00, 0x00, 0x00, 0x00], // 's'
+    [0x00, 0x00, 0x00, 0x18, 0x18, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x0e, 0x00, 0x00, 0x00, 0x00], // 't'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00, 0x00], // 'u'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x3c, 0x3c, 0x3c, 0x3c, 0x18, 0x00, 0x00, 0x00, 0x00], // 'v'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xc3, 0xc3, 0xdb, 0x5a, 0x7e, 0x7e, 0x66, 0x00, 0x00, 0x00, 0x00], // 'w'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x3c, 0x18, 0x18, 0x3c, 0x3c, 0x66, 0x00, 0x00, 0x00, 0x00], // 'x'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x3c, 0x3c, 0x3c, 0x18, 0x18, 0x18, 0x30, 0x70, 0x00], // 'y'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x7e, 0x00, 0x00, 0x00, 0x00], // 'z'
+    [0x00, 0x0e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x70, 0x38, 0x18, 0x18, 0x18, 0x0e, 0x00, 0x00, 0x00], // '{'
+    [0x00, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00], // '|'
+    [0x00, 0x70, 0x18, 0x18, 0x18, 0x18, 0x18, 0x0e, 0x1c, 0x18, 0x18, 0x18, 0x70, 0x00, 0x00, 0x00], // '}'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x79, 0x4f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+/// Pixel rectangle in the render target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Which corner of the render target a label sits in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+}
+
+/// A line of text laid out for one render target size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub backing: Rect,
+    pub glyph_runs: Vec<Rect>,
+}
+
+impl Label {
+    /// Lay out `text` in a corner of a `width` x `height` target, font
+    /// pixels scaled up by whole steps of 540 target rows. Glyphs past the
+    /// right edge are dropped; characters outside printable ASCII show as `?`
+    pub fn layout(text: &str, corner: Corner, width: u32, height: u32) -> Self {
+        Self::layout_at(text, corner, 0, width, height)
+    }
+
+    /// Like `layout`, `row` boxes down from the top, so labels stack into a
+    /// list; `None` if that row doesn't fit in the target
+    pub fn layout_row(text: &str, corner: Corner, row: u32, width: u32, height: u32) -> Option<Self> {
+        let label = Self::layout_at(text, corner, row, width, height);
+        (label.backing.y + label.backing.height < height).then_some(label)
+    }
+
+    fn layout_at(text: &str, corner: Corner, row: u32, width: u32, height: u32) -> Self {
+        let scale = (height / 540).max(1);
+        let (margin, padding) = (8 * scale, 4 * scale);
+        let top = margin + row * (GLYPH_HEIGHT * scale + 2 * padding);
+        let max_chars = (width.saturating_sub(2 * (margin + padding)) / (GLYPH_WIDTH * scale)) as usize;
+        let chars: Vec<char> = text.chars().take(max_chars).collect();
+
+        let text_width = chars.len() as u32 * GLYPH_WIDTH * scale;
+        let left = match corner {
+            Corner::TopLeft => margin,
+            Corner::TopRight => width.saturating_sub(margin + text_width + 2 * padding).max(margin),
+        };
+        let origin = (left + padding, top + padding);
+        let mut glyph_runs = Vec::new();
+        for (column, c) in chars.iter().enumerate() {
+            let glyph = &FONT[if (' '..='~').contains(c) { *c as usize - 0x20 } else { '?' as usize - 0x20 }];
+            let cell_x = origin.0 + column as u32 * GLYPH_WIDTH * scale;
+            for (row, bits) in glyph.iter().enumerate() {
+                // Each horizontal run of set bits becomes one rectangle
+                let mut x = 0;
+                while x < GLYPH_WIDTH {
+                    if bits & (0x80 >> x) == 0 {
+                        x += 1;
+                        continue;
+                    }
+                    let start = x;
+                    while x < GLYPH_WIDTH && bits & (0x80 >> x) != 0 {
+                        x += 1;
+                    }
+                    glyph_runs.push(Rect {
+                        x: cell_x + start * scale,
+                        y: origin.1 + row as u32 * scale,
+                        width: (x - start) * scale,
+                        height: scale,
+                    });
+                }
+            }
+        }
+
+        let backing = Rect {
+            x: left,
+            y: top,
+            width: (text_width + 2 * padding).min(width.saturating_sub(left)),
+            height: (GLYPH_HEIGHT * scale + 2 * padding).min(height.saturating_sub(top)),
+        };
+        Self { backing, glyph_runs }
+    }
+
+    /// Clear the backing box and then the glyph runs of the current subpass's
+    /// color attachment 0. Must be recorded inside the render pass.
+    pub(crate) unsafe fn record(&self, device: &ash::Device, cmd: vk::CommandBuffer) {
+        let clear_rect = |r: &Rect| vk::ClearRect {
+            rect: vk::Rect2D {
+                offset: vk::Offset2D { x: r.x as i32, y: r.y as i32 },
+                extent: vk::Extent2D { width: r.width, height: r.height },
+            },
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let attachment = |rgb: f32| vk::ClearAttachment {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            color_attachment: 0,
+            clear_value: vk::ClearValue { color: vk::ClearColorValue { float32: [rgb, rgb, rgb, 1.0] } },
+        };
+        device.cmd_clear_attachments(cmd, &[attachment(0.05)], &[clear_rect(&self.backing)]);
+        if !self.glyph_runs.is_empty() {
+            let runs: Vec<vk::ClearRect> = self.glyph_runs.iter().map(clear_rect).collect();
+            device.cmd_clear_attachments(cmd, &[attachment(1.0)], &runs);
+        }
+    }
+}
+
+/// When the shader name is on screen: for a few seconds after each switch,
+/// with a key to turn that off and on again. With `--show-fps`, an FPS
+/// counter stays in the top-right corner.
+pub struct Overlay {
+    enabled: bool,
+    duration: Duration,
+    text: String,
+    shown_at: Option<Instant>,
+    /// FPS text and when it last changed, `None` without --show-fps
+    fps: Option<(String, Option<Instant>)>,
+}
+
+impl Overlay {
+    /// `secs` is how long the name stays up; 0 starts with the overlay off
+    pub fn new(secs: f64) -> Self {
+        Self {
+            enabled: secs > 0.0,
+            duration: Duration::from_secs_f64(if secs > 0.0 { secs } else { DEFAULT_SECS }),
+            text: String::new(),
+            shown_at: None,
+            fps: None,
+        }
+    }
+
+    /// Also show an FPS counter
+    pub fn with_fps(mut self, show_fps: bool) -> Self {
+        self.fps = show_fps.then(|| (String::new(), None));
+        self
+    }
+
+    /// Put `text` up for the display duration
+    pub fn show(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.shown_at = Some(Instant::now());
+    }
+
+    /// Turn the overlay off or back on, showing the current text again when
+    /// it comes on. Returns whether it is now enabled
+    pub fn toggle(&mut self) -> bool {
+        self.enabled = !self.enabled;
+        self.shown_at = self.enabled.then(Instant::now);
+        self.enabled
+    }
+
+    /// Feed the rolling FPS estimate; the counter text follows it every
+    /// `FPS_REFRESH`
+    pub fn set_fps(&mut self, fps: f32) {
+        if let Some((text, updated_at)) = &mut self.fps {
+            if !updated_at.is_some_and(|at| at.elapsed() < FPS_REFRESH) {
+                *text = format!("{:.0} fps", fps);
+                *updated_at = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Labels to draw this frame on a `width` x `height` target, empty while
+    /// nothing is shown
+    pub fn labels(&self, width: u32, height: u32) -> Vec<Label> {
+        let mut labels = Vec::new();
+        let name_shown = self.enabled
+            && !self.text.is_empty()
+            && self.shown_at.is_some_and(|at| at.elapsed() < self.duration);
+        if name_shown {
+            labels.push(Label::layout(&self.text, Corner::TopLeft, width, height));
+        }
+        if let Some((text, _)) = self.fps.as_ref().filter(|(text, _)| !text.is_empty()) {
+            labels.push(Label::layout(text, Corner::TopRight, width, height));
+        }
+        labels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_layout() {
+        // 'I' in a 1080p target (scale 2): every run is inside the backing box
+        let label = Label::layout("I", Corner::TopLeft, 1920, 1080);
+        assert_eq!(label.backing, Rect { x: 16, y: 16, width: 8 * 2 + 16, height: 16 * 2 + 16 });
+        assert!(!label.glyph_runs.is_empty());
+        let b = label.backing;
+        for run in &label.glyph_runs {
+            assert!(run.x >= b.x && run.x + run.width <= b.x + b.width);
+            assert!(run.y >= b.y && run.y + run.height <= b.y + b.height);
+            assert_eq!(run.height, 2);
+        }
+        // Text wider than the target is cut at the right edge
+        let long = Label::layout(&"x".repeat(100), Corner::TopLeft, 200, 100);
+        assert!(long.backing.x + long.backing.width <= 200);
+        // Right-aligned: the box ends one margin from the right edge
+        let fps = Label::layout("60 fps", Corner::TopRight, 640, 480);
+        assert_eq!(fps.backing.x + fps.backing.width, 640 - 8);
+        // Rows stack box under box until the bottom edge
+        let second = Label::layout_row("b", Corner::TopLeft, 1, 640, 480).unwrap();
+        assert_eq!(second.backing.y, 8 + 24);
+        assert!(Label::layout_row("z", Corner::TopLeft, 19, 640, 480).is_none());
+    }
+
+    #[test]
+    fn test_overlay_toggle() {
+        let mut overlay = Overlay::new(DEFAULT_SECS);
+        assert!(overlay.labels(640, 480).is_empty());
+        overlay.show("plasma");
+        assert_eq!(overlay.labels(640, 480).len(), 1);
+        assert!(!overlay.toggle());
+        assert!(overlay.labels(640, 480).is_empty());
+        assert!(overlay.toggle());
+        assert_eq!(overlay.labels(640, 480).len(), 1);
+    }
+
+    #[test]
+    fn test_fps_counter_refresh() {
+        let mut overlay = Overlay::new(0.0).with_fps(true);
+        assert!(overlay.labels(640, 480).is_empty());
+        overlay.set_fps(59.7);
+        let labels = overlay.labels(640, 480);
+        assert_eq!(labels, vec![Label::layout("60 fps", Corner::TopRight, 640, 480)]);
+        // Within the refresh interval the text holds still
+        overlay.set_fps(12.0);
+        assert_eq!(overlay.labels(640, 480), labels);
+    }
+}
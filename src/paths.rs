@@ -0,0 +1,41 @@
+// Where metalshader keeps its own files, per platform
+//
+// Resolved with the `directories` crate: the XDG base directories on
+// Linux/Redox (~/.config, ~/.cache and ~/.local/share, or their $XDG_*
+// overrides) and ~/Library/Application Support and ~/Library/Caches on macOS.
+
+use std::fs;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+
+fn project() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "metalshader")
+}
+
+/// Directory `config.toml` is read from; None without a home directory
+pub fn config_dir() -> Option<PathBuf> {
+    project().map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Persistent state such as the macOS window geometry, and the per-user
+/// shader directory
+pub fn data_dir() -> Option<PathBuf> {
+    project().map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Shaders in the per-user data directory, searched after the working
+/// directory
+pub fn user_shader_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("shaders"))
+}
+
+/// Created on demand: SPIR-V that doesn't live next to a shader (stdin, the
+/// built-in modules). Falls back to the temp directory without a home
+pub fn cache_dir() -> std::io::Result<PathBuf> {
+    let dir = project()
+        .map(|dirs| dirs.cache_dir().to_path_buf())
+        .unwrap_or_else(|| std::env::temp_dir().join("metalshader"));
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
@@ -0,0 +1,127 @@
+// `/` shader picker: type to narrow the shader list, Enter to jump
+//
+// The query matches a name when its characters appear in it in order,
+// ignoring case ("plsm" finds "plasma"). Names where they sit together or
+// start words rank first. The list is drawn with the overlay font in the
+// top-left corner, replacing the name overlay while the picker is open.
+
+use crate::overlay::{Corner, Label};
+use crate::shader::ShaderManager;
+
+/// Matches listed below the query line
+pub const MAX_ROWS: usize = 12;
+
+/// How well `query` matches `name`, higher is better; `None` unless every
+/// query character occurs in `name` in order (case-insensitive)
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let found = next + name[next..].iter().position(|&c| c == q)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 4;
+        }
+        if found == 0 || !name[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    // Shorter names win ties: fewer characters that weren't asked for
+    Some(score * 100 - name.len() as i32)
+}
+
+/// Indices of `names` that match `query`, best first; all of them in their
+/// order for an empty query
+pub fn fuzzy_filter<'a>(query: &str, names: impl IntoIterator<Item = &'a str>) -> Vec<usize> {
+    let mut scored: Vec<(i32, usize)> = names
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, name)| fuzzy_score(query, name).map(|score| (score, index)))
+        .collect();
+    // Stable: equal scores keep the list order
+    if !query.is_empty() {
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    }
+    scored.into_iter().map(|(_, index)| index).collect()
+}
+
+/// Query and highlighted row of an open picker
+#[derive(Debug, Default)]
+pub struct Picker {
+    query: String,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Append typed text; control characters are ignored
+    pub fn push(&mut self, text: &str) {
+        self.query.extend(text.chars().filter(|c| !c.is_control()));
+        self.selected = 0;
+    }
+
+    /// Backspace
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Move the highlight `delta` rows, staying on the list
+    pub fn move_selection(&mut self, delta: isize, shaders: &ShaderManager) {
+        let last = self.matches(shaders).len().min(MAX_ROWS).saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Shader index of the highlighted row, if anything matches
+    pub fn selection(&self, shaders: &ShaderManager) -> Option<usize> {
+        self.matches(shaders).get(self.selected).copied()
+    }
+
+    fn matches(&self, shaders: &ShaderManager) -> Vec<usize> {
+        let names = (0..shaders.len()).filter_map(|index| shaders.get(index)).map(|shader| shader.name.as_str());
+        fuzzy_filter(&self.query, names)
+    }
+
+    /// The query line and the best matches, for a `width` x `height` target
+    pub fn labels(&self, shaders: &ShaderManager, width: u32, height: u32) -> Vec<Label> {
+        let matches = self.matches(shaders);
+        let mut lines = vec![format!("/{}_", self.query)];
+        if matches.is_empty() {
+            lines.push("  (no match)".to_string());
+        }
+        for (row, &index) in matches.iter().take(MAX_ROWS).enumerate() {
+            let marker = if row == self.selected { '>' } else { ' ' };
+            lines.push(format!("{} {}", marker, shaders.get(index).unwrap().name));
+        }
+        lines
+            .iter()
+            .enumerate()
+            .map_while(|(row, line)| Label::layout_row(line, Corner::TopLeft, row as u32, width, height))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_filter() {
+        let names = ["plasma", "tunnel", "simple_plasma", "palm_sea", "Polar"];
+        assert_eq!(fuzzy_filter("", names), vec![0, 1, 2, 3, 4]);
+        // A run of letters at the start first, scattered letters last
+        assert_eq!(fuzzy_filter("PLA", names), vec![0, 4, 2, 3]);
+        assert_eq!(fuzzy_filter("plasma", names), vec![0, 2]);
+        assert_eq!(fuzzy_filter("pol", names), vec![4]);
+        assert!(fuzzy_filter("xyz", names).is_empty());
+        // Shorter name wins a tie
+        assert_eq!(fuzzy_filter("tun", ["tunnel_cube", "tunnel"]), vec![1, 0]);
+    }
+}
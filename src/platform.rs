@@ -5,6 +5,7 @@
 // completely platform-agnostic while allowing different backends for
 // display presentation and keyboard input.
 
+use crate::viewport::Viewport;
 use std::error::Error;
 
 /// Platform-agnostic display backend trait
@@ -26,15 +27,28 @@ pub trait DisplayBackend {
 
     /// Set a new display mode/resolution
     ///
-    /// `mode` is a mode number (1-9) that maps to predefined resolutions
+    /// `mode` is a mode number (1-9) that maps to predefined resolutions;
+    /// 0 and numbers past the available modes are errors (see `mode_index`)
     /// Returns the new (width, height) after mode change
     fn set_mode(&mut self, mode: u8) -> Result<(u32, u32), Box<dyn Error>>;
 
+    /// The display's modes as (width, height, refresh rate in Hz if known);
+    /// the first is mode number 1 for `set_mode`
+    fn available_modes(&self) -> Vec<(u32, u32, Option<f32>)>;
+
+    /// Mode number for `set_mode` with exactly `size`, if the display has one
+    fn find_mode(&self, _size: (u32, u32)) -> Option<u8> {
+        None
+    }
+
     /// Present a rendered frame to the display
     ///
     /// `data` contains the pixel data in BGRA format
     /// `row_pitch` is the number of bytes per row (may differ from width * 4 due to alignment)
-    fn present(&mut self, data: &[u8], row_pitch: usize) -> Result<(), Box<dyn Error>>;
+    /// `size` is the frame's (width, height); it is scaled to fill `viewport`
+    /// (see `--render-scale`), and the display outside `viewport` is black
+    fn present(&mut self, data: &[u8], row_pitch: usize, size: (u32, u32), viewport: Viewport)
+        -> Result<(), Box<dyn Error>>;
 }
 
 /// Platform-agnostic input backend trait
@@ -53,6 +67,10 @@ pub trait InputBackend {
     /// Returns Some(KeyEvent) if an event is available, None otherwise
     /// This function should not block - it returns immediately
     fn poll_event(&mut self) -> Option<KeyEvent>;
+
+    /// Enable kiosk mode: Esc/Q no longer produce `KeyEvent::Quit`, only
+    /// the Ctrl+Alt+Q escape hatch does
+    fn set_kiosk(&mut self, kiosk: bool);
 }
 
 /// Platform-independent keyboard event types
@@ -62,16 +80,48 @@ pub trait InputBackend {
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyEvent {
-    /// Navigate to previous shader
+    /// Navigate to previous shader, or step iTime back while paused
     Left,
-    /// Navigate to next shader
+    /// Navigate to next shader, or step iTime forward while paused
     Right,
+    /// Left with Shift held (coarse time step while paused)
+    ShiftLeft,
+    /// Right with Shift held (coarse time step while paused)
+    ShiftRight,
+    /// Freeze/resume iTime
+    Pause,
     /// Toggle fullscreen mode
     Fullscreen,
     /// Quit the application
     Quit,
     /// Switch to a specific resolution mode (1-9)
     Resolution(u8),
+    /// Recompile the current shader from its source and reload it (Ctrl+R)
+    Reload,
+    /// Toggle the shader name overlay (N)
+    Overlay,
+    /// Seek iTime back by `clock::SEEK_STEP` (,)
+    SeekBack,
+    /// Seek iTime forward by `clock::SEEK_STEP` (.)
+    SeekForward,
+    /// Return to the previously viewed shader (Backspace)
+    Back,
+}
+
+impl KeyEvent {
+    /// Whether holding the key keeps acting (see `key_repeat`)
+    pub fn repeats(self) -> bool {
+        matches!(
+            self,
+            KeyEvent::Left | KeyEvent::Right | KeyEvent::ShiftLeft | KeyEvent::ShiftRight | KeyEvent::SeekBack | KeyEvent::SeekForward
+        )
+    }
+}
+
+/// Index into a list of `count` display modes for 1-based `mode_number`;
+/// None for 0 and for numbers past the end
+pub fn mode_index(mode_number: u8, count: usize) -> Option<usize> {
+    usize::from(mode_number).checked_sub(1).filter(|&index| index < count)
 }
 
 // Platform-specific implementations
@@ -83,3 +133,17 @@ pub mod redox;
 
 #[cfg(target_os = "macos")]
 pub mod macos;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_index() {
+        assert_eq!(mode_index(1, 3), Some(0));
+        assert_eq!(mode_index(3, 3), Some(2));
+        assert_eq!(mode_index(4, 3), None);
+        assert_eq!(mode_index(0, 3), None);
+        assert_eq!(mode_index(1, 0), None);
+    }
+}
@@ -1,14 +1,17 @@
 // Linux platform implementation using DRM/KMS and evdev
 #![cfg(target_os = "linux")]
 
-use crate::platform::{DisplayBackend, InputBackend, KeyEvent};
+use crate::error::MetalshaderError;
+use crate::frame_copy;
+use crate::platform::{mode_index, DisplayBackend, InputBackend, KeyEvent};
+use crate::viewport::Viewport;
 use std::error::Error;
 
 // ============================================================================
 // Display Backend - DRM/KMS
 // ============================================================================
 
-use drm::control::{connector, crtc, framebuffer, Device as ControlDevice, dumbbuffer::DumbBuffer};
+use drm::control::{connector, crtc, framebuffer, Device as ControlDevice, dumbbuffer::DumbBuffer, Event, PageFlipFlags};
 use drm::buffer::{Buffer, DrmFourcc};
 use drm::Device;
 use std::fs::{File, OpenOptions};
@@ -27,17 +30,35 @@ impl AsFd for DrmCard {
 impl Device for DrmCard {}
 impl ControlDevice for DrmCard {}
 
+/// Number of scanout buffers: one is displayed while the other is written
+const SCANOUT_BUFFERS: usize = 2;
+
+/// A CPU-writable dumb buffer registered as a DRM framebuffer
+struct ScanoutBuffer {
+    dumb_buffer: DumbBuffer,
+    fb_id: framebuffer::Handle,
+}
+
 pub struct LinuxDisplay {
     drm_fd: RawFd,
     drm_card: DrmCard,
-    dumb_buffer: DumbBuffer,
-    fb_id: framebuffer::Handle,
+    /// Double-buffered scanout; `front` is the one currently displayed
+    buffers: Vec<ScanoutBuffer>,
+    front: usize,
+    /// A page flip was queued and its completion event not yet consumed
+    flip_pending: bool,
+    /// Cleared when the driver rejects page_flip; we then swap via set_crtc
+    page_flip_supported: bool,
     crtc_id: crtc::Handle,
     connector_handle: connector::Handle,
     modes: Vec<drm::control::Mode>,
     current_mode_idx: usize,
     width: u32,
     height: u32,
+    /// CRTC state (console framebuffer and mode) before we took over
+    saved_crtc: Option<crtc::Info>,
+    /// The first present logs its buffer layout
+    debug_logged: bool,
 }
 
 impl DisplayBackend for LinuxDisplay {
@@ -68,7 +89,7 @@ impl DisplayBackend for LinuxDisplay {
                     None
                 }
             })
-            .ok_or("No connected display found")?;
+            .ok_or_else(|| MetalshaderError::DisplayInit("No connected display found".to_string()))?;
 
         let connector = drm_card.get_connector(connector_handle, true)?;
 
@@ -81,7 +102,7 @@ impl DisplayBackend for LinuxDisplay {
 
         let current_mode_idx = 0;
         let mode = modes.first()
-            .ok_or("No display mode available")?;
+            .ok_or_else(|| MetalshaderError::DisplayInit("No display mode available".to_string()))?;
 
         let (width, height) = mode.size();
         eprintln!("Selected mode: [1] {}x{}", width, height);
@@ -92,26 +113,25 @@ impl DisplayBackend for LinuxDisplay {
             .and_then(|enc_handle| drm_card.get_encoder(enc_handle).ok())
             .and_then(|enc| enc.crtc())
             .or_else(|| res.crtcs().first().copied())
-            .ok_or("No CRTC found")?;
+            .ok_or_else(|| MetalshaderError::DisplayInit("No CRTC found".to_string()))?;
 
-        eprintln!("Creating dumb buffer: {}x{}", width, height);
-        // Create DumbBuffer (CPU-accessible buffer for virtio-gpu)
-        let dumb_buffer = drm_card.create_dumb_buffer(
-            (width as u32, height as u32),
-            DrmFourcc::Xrgb8888,
-            32 // bpp
-        ).map_err(|e| format!("Failed to create dumb buffer {}x{}: {}", width, height, e))?;
+        // Remember what the console was showing so Drop can hand it back
+        let saved_crtc = drm_card.get_crtc(crtc_id).ok();
+        match saved_crtc.as_ref().and_then(|c| c.mode()) {
+            Some(m) => eprintln!("Original mode: {}x{} (restored on exit)", m.size().0, m.size().1),
+            None => eprintln!("Original mode: none (CRTC was disabled)"),
+        }
 
-        eprintln!("Creating framebuffer");
-        // Create framebuffer
-        let fb_id = drm_card.add_framebuffer(&dumb_buffer, 24, 32)
-            .map_err(|e| format!("Failed to add framebuffer: {}", e))?;
+        eprintln!("Creating {} scanout buffers: {}x{}", SCANOUT_BUFFERS, width, height);
+        let buffers = (0..SCANOUT_BUFFERS)
+            .map(|_| create_scanout_buffer(&drm_card, width as u32, height as u32))
+            .collect::<Result<Vec<_>, _>>()?;
 
         eprintln!("Setting CRTC");
         // Set CRTC
         drm_card.set_crtc(
             crtc_id,
-            Some(fb_id),
+            Some(buffers[0].fb_id),
             (0, 0),
             &[connector_handle],
             Some(*mode),
@@ -120,14 +140,18 @@ impl DisplayBackend for LinuxDisplay {
         Ok(Self {
             drm_fd,
             drm_card,
-            dumb_buffer,
-            fb_id,
+            buffers,
+            front: 0,
+            flip_pending: false,
+            page_flip_supported: true,
             crtc_id,
             connector_handle,
             modes,
             current_mode_idx,
             width: width as u32,
             height: height as u32,
+            saved_crtc,
+            debug_logged: false,
         })
     }
 
@@ -135,40 +159,55 @@ impl DisplayBackend for LinuxDisplay {
         (self.width, self.height)
     }
 
+    fn available_modes(&self) -> Vec<(u32, u32, Option<f32>)> {
+        self.modes
+            .iter()
+            .map(|mode| {
+                let (width, height) = mode.size();
+                (width as u32, height as u32, Some(mode.vrefresh() as f32))
+            })
+            .collect()
+    }
+
+    fn find_mode(&self, size: (u32, u32)) -> Option<u8> {
+        let index = self.modes.iter().position(|mode| {
+            let (width, height) = mode.size();
+            (width as u32, height as u32) == size
+        })?;
+        u8::try_from(index + 1).ok()
+    }
+
     fn set_mode(&mut self, mode_number: u8) -> Result<(u32, u32), Box<dyn Error>> {
-        let mode_idx = (mode_number - 1) as usize;
-        if mode_idx >= self.modes.len() {
-            return Err(format!("Mode {} not available (only {} modes)", mode_number, self.modes.len()).into());
-        }
+        let mode_idx = mode_index(mode_number, self.modes.len())
+            .ok_or_else(|| format!("Mode {} not available (modes 1-{})", mode_number, self.modes.len()))?;
 
-        let mode = &self.modes[mode_idx];
+        let mode = self.modes[mode_idx];
         let (width, height) = mode.size();
 
         eprintln!("\nSwitching to mode [{}]: {}x{}", mode_number, width, height);
 
-        // Remove old framebuffer
-        let _ = self.drm_card.destroy_framebuffer(self.fb_id);
+        // A queued flip still references the old buffers
+        self.wait_for_flip();
 
-        // Destroy old dumb buffer
-        let _ = self.drm_card.destroy_dumb_buffer(self.dumb_buffer);
-
-        // Create new dumb buffer at new resolution
-        self.dumb_buffer = self.drm_card.create_dumb_buffer(
-            (width as u32, height as u32),
-            DrmFourcc::Xrgb8888,
-            32
-        )?;
+        // Remove old framebuffers and dumb buffers
+        for buffer in self.buffers.drain(..) {
+            destroy_scanout_buffer(&self.drm_card, buffer);
+        }
 
-        // Create new framebuffer
-        self.fb_id = self.drm_card.add_framebuffer(&self.dumb_buffer, 24, 32)?;
+        // Create new buffers at new resolution
+        for _ in 0..SCANOUT_BUFFERS {
+            let buffer = create_scanout_buffer(&self.drm_card, width as u32, height as u32)?;
+            self.buffers.push(buffer);
+        }
+        self.front = 0;
 
         // Set CRTC to new mode
         self.drm_card.set_crtc(
             self.crtc_id,
-            Some(self.fb_id),
+            Some(self.buffers[0].fb_id),
             (0, 0),
             &[self.connector_handle],
-            Some(*mode),
+            Some(mode),
         )?;
 
         self.current_mode_idx = mode_idx;
@@ -178,61 +217,196 @@ impl DisplayBackend for LinuxDisplay {
         Ok((self.width, self.height))
     }
 
-    fn present(&mut self, frame_data: &[u8], src_row_pitch: usize) -> Result<(), Box<dyn Error>> {
+    fn present(&mut self, frame_data: &[u8], src_row_pitch: usize, size: (u32, u32), viewport: Viewport)
+        -> Result<(), Box<dyn Error>>
+    {
         let bytes_per_pixel = 4;
-        let row_size = self.width as usize * bytes_per_pixel;
-        let dst_stride = self.dumb_buffer.pitch() as usize;
+        let row_size = viewport.width as usize * bytes_per_pixel;
+
+        // Never write into a buffer that may still be on screen: the back
+        // buffer was the front one until the previous flip completed
+        self.wait_for_flip();
+        let back = (self.front + 1) % self.buffers.len();
+        let dst_stride = self.buffers[back].dumb_buffer.pitch() as usize;
 
         // Map DumbBuffer for CPU access
-        let mut mapping = self.drm_card.map_dumb_buffer(&mut self.dumb_buffer)?;
+        let mut mapping = self.drm_card.map_dumb_buffer(&mut self.buffers[back].dumb_buffer)?;
         let buffer_slice = mapping.as_mut();
 
-        static mut DEBUG_COUNT: u32 = 0;
-        unsafe {
-            if DEBUG_COUNT == 0 {
-                eprintln!("=== DISPLAY DEBUG ===");
-                eprintln!("Frame data len: {}, src_row_pitch: {}", frame_data.len(), src_row_pitch);
-                eprintln!("Buffer len: {}, dst_stride: {}", buffer_slice.len(), dst_stride);
-                eprintln!("Dimensions: {}x{}, row_size: {}", self.width, self.height, row_size);
-                eprintln!("First 16 bytes of source: {:02x?}", &frame_data[0..16.min(frame_data.len())]);
-            }
+        if !self.debug_logged {
+            eprintln!("=== DISPLAY DEBUG ===");
+            eprintln!("Frame data len: {}, src_row_pitch: {}", frame_data.len(), src_row_pitch);
+            eprintln!("Buffer len: {}, dst_stride: {}", buffer_slice.len(), dst_stride);
+            eprintln!("Dimensions: {}x{}, row_size: {}", self.width, self.height, row_size);
+            eprintln!("First 16 bytes of source: {:02x?}", &frame_data[0..16.min(frame_data.len())]);
         }
 
-        for y in 0..self.height as usize {
-            let dst_offset = y * dst_stride;
-            let src_offset = y * src_row_pitch;  // Use Vulkan's row pitch
-            let copy_len = row_size;
-            if dst_offset + copy_len <= buffer_slice.len() && src_offset + copy_len <= frame_data.len() {
-                buffer_slice[dst_offset..dst_offset + copy_len]
-                    .copy_from_slice(&frame_data[src_offset..src_offset + copy_len]);
-            }
+        // Letterbox bars; the buffers swap, so both need them every frame
+        if !viewport.is_full(self.width, self.height) {
+            frame_copy::clear_outside(buffer_slice, dst_stride, (self.width, self.height), viewport);
+        }
+
+        // Use Vulkan's row pitch for the source, the dumb buffer's for the destination
+        let offset = (viewport.y as usize * dst_stride + viewport.x as usize * bytes_per_pixel).min(buffer_slice.len());
+        let dst = &mut buffer_slice[offset..];
+        if size == (viewport.width, viewport.height) {
+            frame_copy::copy_rows(dst, dst_stride, frame_data, src_row_pitch, row_size, viewport.height as usize);
+        } else {
+            frame_copy::scale_rows(dst, dst_stride, (viewport.width, viewport.height), frame_data, src_row_pitch, size);
         }
 
-        unsafe {
-            if DEBUG_COUNT == 0 {
-                eprintln!("First 16 bytes of dest after copy: {:02x?}", &buffer_slice[0..16.min(buffer_slice.len())]);
-                DEBUG_COUNT = 1;
+        if !self.debug_logged {
+            eprintln!("First 16 bytes of dest after copy: {:02x?}", &buffer_slice[0..16.min(buffer_slice.len())]);
+            self.debug_logged = true;
+        }
+
+        drop(mapping);  // Unmap before handing the buffer to scanout
+        self.flip_to(back)?;
+
+        Ok(())
+    }
+}
+
+impl LinuxDisplay {
+    /// Make buffer `index` the displayed one
+    ///
+    /// Prefers a vblank-synchronized page flip; drivers without flip support
+    /// fall back to swapping the CRTC framebuffer directly.
+    fn flip_to(&mut self, index: usize) -> Result<(), Box<dyn Error>> {
+        let fb_id = self.buffers[index].fb_id;
+
+        if self.page_flip_supported {
+            match self.drm_card.page_flip(self.crtc_id, fb_id, PageFlipFlags::EVENT, None) {
+                Ok(()) => {
+                    self.flip_pending = true;
+                    self.front = index;
+                    return Ok(());
+                }
+                Err(e) => {
+                    eprintln!("page_flip unsupported ({}), swapping framebuffers via set_crtc", e);
+                    self.page_flip_supported = false;
+                }
             }
         }
 
+        self.drm_card.set_crtc(
+            self.crtc_id,
+            Some(fb_id),
+            (0, 0),
+            &[self.connector_handle],
+            Some(self.modes[self.current_mode_idx]),
+        )?;
+
         // CRITICAL: Mark framebuffer as dirty so DRM actually displays it!
-        drop(mapping);  // Unmap before dirty call
         use drm::control::ClipRect;
         let clip = ClipRect::new(0, 0, self.width as u16, self.height as u16);
-        self.drm_card.dirty_framebuffer(self.fb_id, &[clip])?;
+        self.drm_card.dirty_framebuffer(fb_id, &[clip])?;
 
+        self.front = index;
         Ok(())
     }
+
+    /// Block until the queued page flip has completed (no-op if none pending)
+    fn wait_for_flip(&mut self) {
+        while self.flip_pending {
+            match self.drm_card.receive_events() {
+                Ok(events) => {
+                    for event in events {
+                        if let Event::PageFlip(_) = event {
+                            self.flip_pending = false;
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to read DRM events: {}", e);
+                    self.flip_pending = false;
+                }
+            }
+        }
+    }
+
+    /// Restore the CRTC configuration that was active before metalshader started
+    ///
+    /// Mirrors `ResolutionManager::restore` on macOS. If the CRTC had no mode
+    /// (console disabled), it is switched off again instead.
+    pub fn restore(&self) {
+        let Some(saved) = &self.saved_crtc else {
+            return;
+        };
+
+        let result = match (saved.framebuffer(), saved.mode()) {
+            (Some(fb), Some(mode)) => self.drm_card.set_crtc(
+                self.crtc_id,
+                Some(fb),
+                saved.position(),
+                &[self.connector_handle],
+                Some(mode),
+            ),
+            _ => self.drm_card.set_crtc(self.crtc_id, None, (0, 0), &[], None),
+        };
+
+        match result {
+            Ok(()) => eprintln!("Display mode restored"),
+            Err(e) => eprintln!("Failed to restore display mode: {}", e),
+        }
+    }
+}
+
+impl Drop for LinuxDisplay {
+    fn drop(&mut self) {
+        self.wait_for_flip();
+
+        // Put the console's framebuffer and mode back before releasing ours,
+        // otherwise the CRTC keeps scanning out a destroyed buffer
+        self.restore();
+
+        for buffer in self.buffers.drain(..) {
+            destroy_scanout_buffer(&self.drm_card, buffer);
+        }
+    }
+}
+
+fn create_scanout_buffer(card: &DrmCard, width: u32, height: u32) -> Result<ScanoutBuffer, Box<dyn Error>> {
+    // Create DumbBuffer (CPU-accessible buffer for virtio-gpu)
+    let dumb_buffer = card.create_dumb_buffer(
+        (width, height),
+        DrmFourcc::Xrgb8888,
+        32 // bpp
+    ).map_err(|e| format!("Failed to create dumb buffer {}x{}: {}", width, height, e))?;
+
+    let fb_id = match card.add_framebuffer(&dumb_buffer, 24, 32) {
+        Ok(fb_id) => fb_id,
+        Err(e) => {
+            let _ = card.destroy_dumb_buffer(dumb_buffer);
+            return Err(format!("Failed to add framebuffer: {}", e).into());
+        }
+    };
+
+    Ok(ScanoutBuffer { dumb_buffer, fb_id })
+}
+
+fn destroy_scanout_buffer(card: &DrmCard, buffer: ScanoutBuffer) {
+    let _ = card.destroy_framebuffer(buffer.fb_id);
+    let _ = card.destroy_dumb_buffer(buffer.dumb_buffer);
 }
 
 // ============================================================================
 // Input Backend - evdev
 // ============================================================================
 
+use crate::key_repeat::KeyRepeat;
 use input_linux::{EventKind, InputEvent, Key, GenericEvent};
+use std::time::Instant;
 
 pub struct LinuxInput {
     device: Option<File>,
+    kiosk: bool,
+    /// Modifier state for the kiosk escape hatch (Ctrl+Alt+Q)
+    ctrl_held: bool,
+    alt_held: bool,
+    shift_held: bool,
+    /// Thins out autorepeats of held navigation keys
+    repeat: KeyRepeat,
 }
 
 impl InputBackend for LinuxInput {
@@ -251,41 +425,40 @@ impl InputBackend for LinuxInput {
                 eprintln!("  {}: {}", path, name);
                 if name.to_lowercase().contains("keyboard") || name.to_lowercase().contains("input") {
                     println!("Using input: {} ({})", path, name);
-                    return Ok(Self { device: Some(file) });
+                    return Ok(Self { device: Some(file), kiosk: false, ctrl_held: false, alt_held: false, shift_held: false, repeat: KeyRepeat::default() });
                 }
             }
         }
 
         println!("Warning: No keyboard input found, arrow key navigation disabled");
-        Ok(Self { device: None })
+        Ok(Self { device: None, kiosk: false, ctrl_held: false, alt_held: false, shift_held: false, repeat: KeyRepeat::default() })
     }
 
-    fn poll_event(&mut self) -> Option<KeyEvent> {
-        let device = self.device.as_mut()?;
+    fn set_kiosk(&mut self, kiosk: bool) {
+        self.kiosk = kiosk;
+    }
 
+    fn poll_event(&mut self) -> Option<KeyEvent> {
         // Read events in non-blocking mode
         loop {
             let mut event = InputEvent::zeroed();
-            match read_input_event(device, &mut event) {
+            match read_input_event(self.device.as_mut()?, &mut event) {
                 Ok(true) => {
-                    // Check for key press events (value == 1 means press, not release)
-                    if event.kind == EventKind::Key && event.value() == 1 {
-                        // Check for number keys using raw codes (KEY_1 = 2, KEY_2 = 3, etc.)
-                        if event.code >= 2 && event.code <= 10 {
-                            let mode_num = if event.code == 10 { 0 } else { event.code - 1 } as u8;
-                            if mode_num >= 1 && mode_num <= 9 {
-                                return Some(KeyEvent::Resolution(mode_num));
-                            }
+                    // Track modifiers on press (1), repeat (2) and release (0)
+                    if event.kind == EventKind::Key {
+                        match Key::from_code(event.code) {
+                            Ok(Key::LeftCtrl | Key::RightCtrl) => self.ctrl_held = event.value() != 0,
+                            Ok(Key::LeftAlt | Key::RightAlt) => self.alt_held = event.value() != 0,
+                            Ok(Key::LeftShift | Key::RightShift) => self.shift_held = event.value() != 0,
+                            _ => {}
                         }
+                    }
 
-                        // Get key code from event for named keys
-                        if let Ok(key) = Key::from_code(event.code) {
-                            match key {
-                                Key::Left => return Some(KeyEvent::Left),
-                                Key::Right => return Some(KeyEvent::Right),
-                                Key::F => return Some(KeyEvent::Fullscreen),
-                                Key::Esc | Key::Q => return Some(KeyEvent::Quit),
-                                _ => {}
+                    // 1 is a press, 2 an autorepeat of a held key, 0 a release
+                    if event.kind == EventKind::Key && (event.value() == 1 || event.value() == 2) {
+                        if let Some(key_event) = self.key_event(event.code) {
+                            if self.repeat.accept(key_event.repeats(), event.value() == 2, Instant::now()) {
+                                return Some(key_event);
                             }
                         }
                     }
@@ -297,8 +470,44 @@ impl InputBackend for LinuxInput {
     }
 }
 
+impl LinuxInput {
+    /// The action of key `code` with the current modifiers
+    fn key_event(&self, code: u16) -> Option<KeyEvent> {
+        if let Some(mode) = number_key_mode(code) {
+            return Some(KeyEvent::Resolution(mode));
+        }
+
+        // Get key code from event for named keys
+        match Key::from_code(code).ok()? {
+            Key::Left if self.shift_held => Some(KeyEvent::ShiftLeft),
+            Key::Right if self.shift_held => Some(KeyEvent::ShiftRight),
+            Key::Left => Some(KeyEvent::Left),
+            Key::Right => Some(KeyEvent::Right),
+            Key::Space => Some(KeyEvent::Pause),
+            Key::F => Some(KeyEvent::Fullscreen),
+            Key::R if self.ctrl_held => Some(KeyEvent::Reload),
+            Key::N => Some(KeyEvent::Overlay),
+            Key::Comma => Some(KeyEvent::SeekBack),
+            Key::Dot => Some(KeyEvent::SeekForward),
+            Key::Backspace => Some(KeyEvent::Back),
+            Key::Q if self.ctrl_held && self.alt_held => Some(KeyEvent::Quit),
+            Key::Esc | Key::Q if !self.kiosk => Some(KeyEvent::Quit),
+            _ => None,
+        }
+    }
+}
+
 // Helper functions for Linux input
 
+/// Mode number of a number key's evdev code: KEY_1 (2) to KEY_9 (10) are
+/// modes 1-9. KEY_0 (11) selects none, there being at most nine modes
+fn number_key_mode(code: u16) -> Option<u8> {
+    match code {
+        2..=10 => Some((code - 1) as u8),
+        _ => None,
+    }
+}
+
 fn get_device_name(fd: i32) -> String {
     // EVIOCGNAME ioctl: _IOC(_IOC_READ, 'E', 0x06, len)
     // Properly construct ioctl number for aarch64
@@ -343,3 +552,18 @@ fn read_input_event(file: &mut File, event: &mut InputEvent) -> std::io::Result<
         Err(e) => Err(e),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_key_mode() {
+        assert_eq!(number_key_mode(Key::Num1 as u16), Some(1));
+        assert_eq!(number_key_mode(Key::Num5 as u16), Some(5));
+        assert_eq!(number_key_mode(Key::Num9 as u16), Some(9));
+        assert_eq!(number_key_mode(Key::Num0 as u16), None);
+        assert_eq!(number_key_mode(Key::Esc as u16), None);
+        assert_eq!(number_key_mode(Key::Q as u16), None);
+    }
+}
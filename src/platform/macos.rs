@@ -1,7 +1,8 @@
 // macOS platform implementation using winit for windowing and input
 #![cfg(target_os = "macos")]
 
-use crate::platform::{DisplayBackend, InputBackend, KeyEvent};
+use crate::platform::{mode_index, DisplayBackend, InputBackend, KeyEvent};
+use crate::viewport::Viewport;
 use std::error::Error;
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
@@ -19,6 +20,19 @@ struct SharedState {
     pending_events: VecDeque<KeyEvent>,
 }
 
+/// Predefined resolution modes
+const RESOLUTIONS: [(u32, u32); 9] = [
+    (1280, 800),
+    (1920, 1080),
+    (2560, 1440),
+    (3840, 2160),
+    (1024, 768),
+    (1366, 768),
+    (1600, 900),
+    (2048, 1152),
+    (3440, 1440),
+];
+
 pub struct MacOSDisplay {
     width: u32,
     height: u32,
@@ -44,31 +58,16 @@ impl DisplayBackend for MacOSDisplay {
         (self.width, self.height)
     }
 
+    fn available_modes(&self) -> Vec<(u32, u32, Option<f32>)> {
+        RESOLUTIONS.iter().map(|&(width, height)| (width, height, None)).collect()
+    }
+
     fn set_mode(&mut self, mode_number: u8) -> Result<(u32, u32), Box<dyn Error>> {
-        // Predefined resolution modes
-        let resolutions = [
-            (1280, 800),
-            (1920, 1080),
-            (2560, 1440),
-            (3840, 2160),
-            (1024, 768),
-            (1366, 768),
-            (1600, 900),
-            (2048, 1152),
-            (3440, 1440),
-        ];
-
-        let mode_idx = (mode_number - 1) as usize;
-        if mode_idx >= resolutions.len() {
-            return Err(format!(
-                "Mode {} not available (only {} modes)",
-                mode_number,
-                resolutions.len()
-            )
-            .into());
-        }
+        let mode_idx = mode_index(mode_number, RESOLUTIONS.len()).ok_or_else(|| {
+            format!("Mode {} not available (modes 1-{})", mode_number, RESOLUTIONS.len())
+        })?;
 
-        let (width, height) = resolutions[mode_idx];
+        let (width, height) = RESOLUTIONS[mode_idx];
         self.width = width;
         self.height = height;
 
@@ -76,7 +75,9 @@ impl DisplayBackend for MacOSDisplay {
         Ok((self.width, self.height))
     }
 
-    fn present(&mut self, _data: &[u8], _row_pitch: usize) -> Result<(), Box<dyn Error>> {
+    fn present(&mut self, _data: &[u8], _row_pitch: usize, _size: (u32, u32), _viewport: Viewport)
+        -> Result<(), Box<dyn Error>>
+    {
         // For now, just verify data is present
         // Full windowed rendering would require swapchain integration
         // Rendering happens in memory only (headless mode)
@@ -109,6 +110,10 @@ impl InputBackend for MacOSInput {
             None
         }
     }
+
+    fn set_kiosk(&mut self, _kiosk: bool) {
+        // Kiosk handling lives in main_macos.rs for the windowed app
+    }
 }
 
 #[allow(dead_code)]
@@ -1,7 +1,10 @@
 // Redox OS platform implementation using schemes
 #![cfg(target_os = "redox")]
 
+use crate::error::MetalshaderError;
+use crate::frame_copy;
 use crate::platform::{DisplayBackend, InputBackend, KeyEvent};
+use crate::viewport::Viewport;
 use std::error::Error;
 use std::fs::File;
 
@@ -73,7 +76,7 @@ impl DisplayBackend for RedoxDisplay {
         };
 
         if fb_ptr == libc::MAP_FAILED {
-            return Err(format!("mmap failed: {}", std::io::Error::last_os_error()).into());
+            return Err(MetalshaderError::DisplayInit(format!("mmap failed: {}", std::io::Error::last_os_error())).into());
         }
 
         eprintln!("Framebuffer mapped at {:?}, size {}", fb_ptr, fb_size);
@@ -91,29 +94,39 @@ impl DisplayBackend for RedoxDisplay {
         (self.width, self.height)
     }
 
+    fn available_modes(&self) -> Vec<(u32, u32, Option<f32>)> {
+        // Only the mode the display scheme was opened at (see `set_mode`)
+        vec![(self.width, self.height, None)]
+    }
+
     fn set_mode(&mut self, mode_number: u8) -> Result<(u32, u32), Box<dyn Error>> {
         // Resolution switching on Redox V1 API is not directly supported
         // Would need to close and reopen with different path, or use V2 API
         Err(format!("Resolution switching not implemented for Redox yet (requested mode {})", mode_number).into())
     }
 
-    fn present(&mut self, data: &[u8], row_pitch: usize) -> Result<(), Box<dyn Error>> {
+    fn present(&mut self, data: &[u8], row_pitch: usize, size: (u32, u32), viewport: Viewport)
+        -> Result<(), Box<dyn Error>>
+    {
         // Copy frame data to mapped framebuffer
         let bytes_per_pixel = 4;
-        let row_size = self.width as usize * bytes_per_pixel;
+        let fb_stride = self.width as usize * bytes_per_pixel;
+        let row_size = viewport.width as usize * bytes_per_pixel;
 
         unsafe {
             let fb = std::slice::from_raw_parts_mut(self.fb_ptr, self.fb_size);
 
-            // Handle row pitch differences
-            for y in 0..self.height as usize {
-                let dst_offset = y * row_size;
-                let src_offset = y * row_pitch;
+            if !viewport.is_full(self.width, self.height) {
+                frame_copy::clear_outside(fb, fb_stride, (self.width, self.height), viewport);
+            }
 
-                if dst_offset + row_size <= fb.len() && src_offset + row_size <= data.len() {
-                    fb[dst_offset..dst_offset + row_size]
-                        .copy_from_slice(&data[src_offset..src_offset + row_size]);
-                }
+            // Handle row pitch differences (framebuffer rows are tightly packed)
+            let offset = (viewport.y as usize * fb_stride + viewport.x as usize * bytes_per_pixel).min(fb.len());
+            let dst = &mut fb[offset..];
+            if size == (viewport.width, viewport.height) {
+                frame_copy::copy_rows(dst, fb_stride, data, row_pitch, row_size, viewport.height as usize);
+            } else {
+                frame_copy::scale_rows(dst, fb_stride, (viewport.width, viewport.height), data, row_pitch, size);
             }
         }
 
@@ -171,6 +184,11 @@ impl Drop for RedoxDisplay {
 
 pub struct RedoxInput {
     file: File,
+    kiosk: bool,
+    /// Modifier state for the kiosk escape hatch (Ctrl+Alt+Q)
+    ctrl_held: bool,
+    alt_held: bool,
+    shift_held: bool,
 }
 
 impl InputBackend for RedoxInput {
@@ -194,7 +212,11 @@ impl InputBackend for RedoxInput {
 
         eprintln!("Input device opened: input:consumer");
 
-        Ok(Self { file })
+        Ok(Self { file, kiosk: false, ctrl_held: false, alt_held: false, shift_held: false })
+    }
+
+    fn set_kiosk(&mut self, kiosk: bool) {
+        self.kiosk = kiosk;
     }
 
     fn poll_event(&mut self) -> Option<KeyEvent> {
@@ -221,6 +243,13 @@ impl InputBackend for RedoxInput {
         // Process events
         for event in &events[..count] {
             if let EventOption::Key(key_event) = event.to_option() {
+                match key_event.scancode {
+                    0x1D => self.ctrl_held = key_event.pressed, // Left Ctrl
+                    0x38 => self.alt_held = key_event.pressed,  // Left Alt
+                    0x2A | 0x36 => self.shift_held = key_event.pressed, // Left/Right Shift
+                    _ => {}
+                }
+
                 // Only process key press events
                 if !key_event.pressed {
                     continue;
@@ -229,11 +258,20 @@ impl InputBackend for RedoxInput {
                 // Map PS/2 scan codes to KeyEvent
                 // Scan codes based on PS/2 Set 1
                 match key_event.scancode {
+                    0x4B if self.shift_held => return Some(KeyEvent::ShiftLeft),
+                    0x4D if self.shift_held => return Some(KeyEvent::ShiftRight),
                     0x4B => return Some(KeyEvent::Left),       // Left arrow
                     0x4D => return Some(KeyEvent::Right),      // Right arrow
+                    0x39 => return Some(KeyEvent::Pause),      // Space
                     0x21 => return Some(KeyEvent::Fullscreen), // F key
-                    0x01 => return Some(KeyEvent::Quit),       // ESC
-                    0x10 => return Some(KeyEvent::Quit),       // Q key
+                    0x13 if self.ctrl_held => return Some(KeyEvent::Reload), // Ctrl+R
+                    0x31 => return Some(KeyEvent::Overlay),    // N key
+                    0x33 => return Some(KeyEvent::SeekBack),   // Comma
+                    0x34 => return Some(KeyEvent::SeekForward), // Period
+                    0x0E => return Some(KeyEvent::Back),       // Backspace
+                    0x10 if self.ctrl_held && self.alt_held => return Some(KeyEvent::Quit), // Ctrl+Alt+Q
+                    0x01 if !self.kiosk => return Some(KeyEvent::Quit), // ESC
+                    0x10 if !self.kiosk => return Some(KeyEvent::Quit), // Q key
                     0x02..=0x0A => {
                         // Number keys 1-9
                         // 0x02 = '1', 0x03 = '2', ..., 0x0A = '9'
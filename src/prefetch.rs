@@ -0,0 +1,149 @@
+// Background preparation of the shaders next to the current one
+//
+// Switching shaders reads two .spv files, and recompiles them first when the
+// source is newer. `Prefetcher` does that on a worker thread for the previous
+// and next shaders while the current one plays, so a switch only has to build
+// the pipeline. Results come back over a bounded channel and are kept until
+// they are taken or the neighbours change.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::error::MetalshaderError;
+use crate::shader::ShaderInfo;
+use crate::shader_compiler::{CompiledShader, ShaderCompiler};
+
+/// Finished shaders the worker may get ahead of the render loop by
+const RESULT_DEPTH: usize = 4;
+
+pub struct Prefetcher {
+    requests: Sender<ShaderInfo>,
+    /// Keyed by `frag_path`; None if the shader couldn't be prepared
+    results: Receiver<(PathBuf, Option<CompiledShader>)>,
+    /// Sent to the worker and not back yet
+    pending: HashSet<PathBuf>,
+    /// Shaders from the last `prefetch`
+    wanted: HashSet<PathBuf>,
+    ready: HashMap<PathBuf, CompiledShader>,
+}
+
+impl Prefetcher {
+    /// Start the worker; it compiles stale sources with `compiler`
+    pub fn new(compiler: ShaderCompiler) -> Self {
+        let (requests, jobs) = mpsc::channel::<ShaderInfo>();
+        let (done, results) = mpsc::sync_channel(RESULT_DEPTH);
+        std::thread::spawn(move || {
+            for shader in jobs {
+                // Errors surface when the shader is loaded the usual way
+                let compiled = prepare(&compiler, &shader).ok();
+                if done.send((shader.frag_path, compiled)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            results,
+            pending: HashSet::new(),
+            wanted: HashSet::new(),
+            ready: HashMap::new(),
+        }
+    }
+
+    /// Prepare `shaders` in the background, dropping whatever was prepared
+    /// for shaders no longer among them
+    pub fn prefetch<'a>(&mut self, shaders: impl IntoIterator<Item = &'a ShaderInfo>) {
+        self.drain();
+        self.wanted.clear();
+        for shader in shaders {
+            if !self.wanted.insert(shader.frag_path.clone()) {
+                continue;
+            }
+            if !self.ready.contains_key(&shader.frag_path)
+                && !self.pending.contains(&shader.frag_path)
+                && self.requests.send(shader.clone()).is_ok()
+            {
+                self.pending.insert(shader.frag_path.clone());
+            }
+        }
+        self.ready.retain(|path, _| self.wanted.contains(path));
+    }
+
+    /// The SPIR-V prepared for `shader`, if the worker has finished it; load
+    /// it with `load_shader_spirv`
+    pub fn take(&mut self, shader: &ShaderInfo) -> Option<CompiledShader> {
+        self.drain();
+        self.ready.remove(&shader.frag_path)
+    }
+
+    fn drain(&mut self) {
+        while let Ok((path, compiled)) = self.results.try_recv() {
+            self.pending.remove(&path);
+            if let Some(compiled) = compiled.filter(|_| self.wanted.contains(&path)) {
+                self.ready.insert(path, compiled);
+            }
+        }
+    }
+}
+
+/// A shader's SPIR-V, recompiled first if its source is newer than the .spv
+fn prepare(compiler: &ShaderCompiler, shader: &ShaderInfo) -> Result<CompiledShader, MetalshaderError> {
+    if let Some(source) = shader.source_path().filter(|source| is_newer(source, &shader.frag_path)) {
+        compiler.compile(&source)?;
+    }
+    Ok(CompiledShader {
+        vertex: fs::read(&shader.vert_path)?,
+        fragment: fs::read(&shader.frag_path)?,
+    })
+}
+
+/// Whether `source` was modified after `output`; a missing output counts
+fn is_newer(source: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(source), modified(output)) {
+        (Some(source), Some(output)) => source > output,
+        (_, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_prefetch_and_take() {
+        let dir = std::env::temp_dir().join(format!("metalshader-prefetch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let shader = |name: &str| {
+            let info = ShaderInfo {
+                name: name.to_string(),
+                vert_path: dir.join(format!("{}.vert.spv", name)),
+                frag_path: dir.join(format!("{}.frag.spv", name)),
+                resolution: None,
+            };
+            fs::write(&info.vert_path, b"vert").unwrap();
+            fs::write(&info.frag_path, name).unwrap();
+            info
+        };
+        let (a, b) = (shader("a"), shader("b"));
+
+        let mut prefetcher = Prefetcher::new(ShaderCompiler::new());
+        prefetcher.prefetch([&a, &b]);
+        let start = Instant::now();
+        while prefetcher.ready.len() < 2 && start.elapsed() < Duration::from_secs(5) {
+            prefetcher.drain();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let compiled = prefetcher.take(&a).unwrap();
+        assert_eq!((compiled.vertex.as_slice(), compiled.fragment.as_slice()), (&b"vert"[..], &b"a"[..]));
+        assert!(prefetcher.take(&a).is_none());
+        // Moving on drops b
+        prefetcher.prefetch([&a]);
+        assert!(prefetcher.take(&b).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+// Requests for the render loop from other threads: parameters and navigation
+// from remote controllers (`--osc`, `--midi`) and files opened from Finder
+//
+// Listener threads write into one `Remote` behind a mutex; the render loop
+// reads `iParams` and drains the commands once per frame. Every source shares
+// it, so they can all drive the same parameters and shader selection.
+
+use std::sync::{Arc, Mutex};
+
+use crate::ubo::PARAM_COUNT;
+
+/// Navigation or reload requested from outside the render loop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteCommand {
+    Next,
+    Previous,
+    /// Recompile the current shader from source, like Ctrl+R
+    Reload,
+    /// Switch to the shader compiled from this file (any of its source or
+    /// SPIR-V paths)
+    Open(String),
+}
+
+#[derive(Default)]
+struct RemoteState {
+    params: [f32; PARAM_COUNT],
+    commands: Vec<RemoteCommand>,
+}
+
+/// Handle to the shared state; clones refer to the same parameters
+#[derive(Clone, Default)]
+pub struct Remote {
+    state: Arc<Mutex<RemoteState>>,
+}
+
+impl Remote {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current `iParams` values
+    pub fn params(&self) -> [f32; PARAM_COUNT] {
+        self.state.lock().unwrap().params
+    }
+
+    /// Navigation received since the last call, oldest first
+    pub fn take_commands(&self) -> Vec<RemoteCommand> {
+        std::mem::take(&mut self.state.lock().unwrap().commands)
+    }
+
+    /// Out-of-range indices are ignored
+    pub fn set_param(&self, index: usize, value: f32) {
+        if let Some(param) = self.state.lock().unwrap().params.get_mut(index) {
+            *param = value;
+        }
+    }
+
+    pub fn push(&self, command: RemoteCommand) {
+        self.state.lock().unwrap().commands.push(command);
+    }
+}
@@ -1,11 +1,98 @@
 // Vulkan rendering engine
 
 use ash::vk;
-use std::ffi::CStr;
-use std::fs::File;
-use std::io::Read;
+use std::ffi::{CStr, CString};
 use std::path::Path;
 
+pub use crate::options::{ChannelSampler, EntryPoints, FragCoord, RenderFormat, RenderTiling};
+use crate::channel_texture::{self, ChannelTexture};
+use crate::error::MetalshaderError;
+use crate::mapped_memory::MappedMemory;
+use crate::overlay::Label;
+use crate::shader_compiler::{FULLSCREEN_NORMALIZED_VERT_SPV, FULLSCREEN_VERT_SPV};
+use crate::shader_layout::{LayoutKey, ShaderLayouts};
+use crate::spirv_reflect::{reflect, words_from_bytes, BindingKind};
+use crate::texture_file::TextureData;
+use crate::ubo::{ShaderToyUBO, USER_BINDING, USER_UNIFORMS_BYTES, USER_UNIFORMS_OFFSET};
+
+/// Construction-time settings for the offscreen renderer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderConfig {
+    pub tiling: RenderTiling,
+    /// Render target pixel format; `get_frame_buffer` rows hold this
+    pub format: RenderFormat,
+    /// Standard src-alpha / one-minus-src-alpha blending over the clear color
+    pub blend: bool,
+    /// Prefer a CPU Vulkan device (lavapipe) even when a GPU is present
+    pub software: bool,
+    /// Negative-height viewport: `fragCoord.y = 0` at the bottom row
+    pub flip_y: bool,
+    /// `fragCoord` units from the built-in fullscreen vertex shader
+    pub frag_coord: FragCoord,
+    /// Wrap and filter of the iChannel0 sampler
+    pub channel0_sampler: ChannelSampler,
+    /// Allocate the render target so `export_render_target_fd` can share it
+    /// with another Vulkan context (`VK_KHR_external_memory_fd`); also makes
+    /// it sampleable
+    pub exportable: bool,
+}
+
+fn vk_format(format: RenderFormat) -> vk::Format {
+    match format {
+        RenderFormat::Rgba8 => vk::Format::B8G8R8A8_UNORM,
+        RenderFormat::Rgba16f => vk::Format::R16G16B16A16_SFLOAT,
+        RenderFormat::Rgba32f => vk::Format::R32G32B32A32_SFLOAT,
+    }
+}
+
+/// Push-constant range every Vulkan implementation must support
+const PUSH_CONSTANT_BYTES: u32 = 128;
+
+/// Size of the uniform buffer bound at set 0, binding 0; room for
+/// `ShaderToyUBO` including `iParams`
+const UNIFORM_BUFFER_BYTES: u32 = 128;
+
+/// A frame that hasn't finished after this long is treated as a GPU hang
+const FRAME_TIMEOUT_NS: u64 = 2_000_000_000;
+
+/// Raw handles and parameters of the render target, for code that samples
+/// it from the same device, or imports `export_render_target_fd` into
+/// another one. Valid until the renderer is resized or dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderTargetHandle {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    /// Size of `memory`, which an importer has to allocate
+    pub allocation_size: vk::DeviceSize,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub tiling: vk::ImageTiling,
+    pub usage: vk::ImageUsageFlags,
+    /// Layout the image is in once `render_frame` has returned
+    pub layout: vk::ImageLayout,
+}
+
+/// Host-visible buffer the OPTIMAL render target is copied into each frame
+struct ReadbackBuffer {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+}
+
+/// Everything sized to the frame, recreated by `resize`
+struct RenderTarget {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    framebuffer: vk::Framebuffer,
+    /// The image (LINEAR) or the readback buffer (OPTIMAL), which
+    /// `get_frame_buffer` reads the frame from
+    frame_memory: MappedMemory,
+    readback: Option<ReadbackBuffer>,
+    row_pitch: usize,
+    /// What `render_target` returns
+    handle: RenderTargetHandle,
+}
+
 pub struct VulkanRenderer {
     #[allow(dead_code)]
     entry: ash::Entry,
@@ -13,44 +100,54 @@ pub struct VulkanRenderer {
     device: ash::Device,
     physical_device: vk::PhysicalDevice,
     queue: vk::Queue,
+    queue_family_index: u32,
 
-    render_target_image: vk::Image,
-    render_target_memory: vk::DeviceMemory,
-    render_target_view: vk::ImageView,
-    render_target_ptr: *mut u8,
-    render_target_size: usize,
+    mem_properties: vk::PhysicalDeviceMemoryProperties,
+    target: RenderTarget,
+    /// Tiling in use, after falling back from LINEAR for unsupported formats
+    tiling: RenderTiling,
+    exportable: bool,
 
     texture_image: vk::Image,
     texture_memory: vk::DeviceMemory,
     texture_view: vk::ImageView,
     sampler: vk::Sampler,
+    /// Texture from `set_channel0`, replacing the checkerboard in the descriptor
+    channel0: Option<ChannelTexture>,
+    /// Shader name and FPS drawn over the frame, recorded with the frame commands
+    overlay: Vec<Label>,
 
     render_pass: vk::RenderPass,
-    framebuffer: vk::Framebuffer,
 
-    descriptor_pool: vk::DescriptorPool,
-    descriptor_set_layout: vk::DescriptorSetLayout,
-    descriptor_set: vk::DescriptorSet,
-    pipeline_layout: vk::PipelineLayout,
+    layouts: ShaderLayouts,
+    /// Layout variant the current pipeline was built against
+    layout_key: LayoutKey,
 
     uniform_buffer: vk::Buffer,
     uniform_memory: vk::DeviceMemory,
-    uniform_ptr: *mut u8,
+    uniforms: MappedMemory,
+    /// Bytes `set_uniforms` wrote at `USER_UNIFORMS_OFFSET`
+    user_uniform_bytes: usize,
 
     pipeline: Option<vk::Pipeline>,
     command_pool: vk::CommandPool,
     command_buffer: vk::CommandBuffer,
     fence: vk::Fence,
+    /// A submitted frame timed out and may still be executing
+    frame_pending: bool,
 
     width: u32,
     height: u32,
-    row_pitch: usize,
+    format: RenderFormat,
+    blend: bool,
+    flip_y: bool,
+    frag_coord: FragCoord,
 }
 
 impl VulkanRenderer {
-    pub fn new(width: u32, height: u32) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(width: u32, height: u32, config: RenderConfig) -> Result<Self, MetalshaderError> {
         unsafe {
-            let entry = ash::Entry::load()?;
+            let entry = crate::vulkan_loader::load_entry()?;
 
             // Create instance with MoltenVK portability extensions for macOS
             let app_info = vk::ApplicationInfo::default()
@@ -76,18 +173,24 @@ impl VulkanRenderer {
                 .enabled_extension_names(&extension_names)
                 .flags(create_flags);
 
-            let instance = entry.create_instance(&create_info, None)?;
+            let instance = entry.create_instance(&create_info, None)
+                .map_err(crate::vulkan_loader::instance_error)?;
 
-            // Get physical device
-            let physical_devices = instance.enumerate_physical_devices()?;
-            let physical_device = *physical_devices.first()
-                .ok_or("No Vulkan physical device found")?;
+            // Get the preferred physical device that can run graphics
+            let (physical_device, queue_family_index) =
+                crate::vulkan_loader::ranked_physical_devices(&instance, config.software)?
+                    .into_iter()
+                    .find_map(|pd| {
+                        crate::vulkan_loader::graphics_queue_family(&instance, pd).map(|family| (pd, family))
+                    })
+                    .ok_or(MetalshaderError::NoDevice)?;
+            crate::vulkan_loader::print_device_info(&entry, &instance, physical_device);
 
             let mem_properties = instance.get_physical_device_memory_properties(physical_device);
 
             // Create device with portability subset for MoltenVK
             let queue_info = vk::DeviceQueueCreateInfo::default()
-                .queue_family_index(0)
+                .queue_family_index(queue_family_index)
                 .queue_priorities(&[1.0]);
 
             #[cfg(target_os = "macos")]
@@ -96,95 +199,50 @@ impl VulkanRenderer {
             ];
 
             #[cfg(not(target_os = "macos"))]
-            let device_extensions: Vec<*const i8> = vec![];
+            let device_extensions: Vec<*const i8> = if config.exportable {
+                vec![ash::khr::external_memory_fd::NAME.as_ptr()]
+            } else {
+                vec![]
+            };
 
             let device_create_info = vk::DeviceCreateInfo::default()
                 .queue_create_infos(std::slice::from_ref(&queue_info))
                 .enabled_extension_names(&device_extensions);
 
             let device = instance.create_device(physical_device, &device_create_info, None)?;
-            let queue = device.get_device_queue(0, 0);
-
-            // Create render target image (LINEAR + HOST_VISIBLE)
-            let rt_image_info = vk::ImageCreateInfo::default()
-                .image_type(vk::ImageType::TYPE_2D)
-                .format(vk::Format::B8G8R8A8_UNORM)
-                .extent(vk::Extent3D { width, height, depth: 1 })
-                .mip_levels(1)
-                .array_layers(1)
-                .samples(vk::SampleCountFlags::TYPE_1)
-                .tiling(vk::ImageTiling::LINEAR)
-                .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
-                .initial_layout(vk::ImageLayout::UNDEFINED);
-
-            let render_target_image = device.create_image(&rt_image_info, None)?;
-            let rt_mem_req = device.get_image_memory_requirements(render_target_image);
-
-            let rt_mem_type = find_memory_type(
-                &mem_properties,
-                rt_mem_req.memory_type_bits,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            )?;
-
-            let rt_alloc_info = vk::MemoryAllocateInfo::default()
-                .allocation_size(rt_mem_req.size)
-                .memory_type_index(rt_mem_type);
-
-            let render_target_memory = device.allocate_memory(&rt_alloc_info, None)?;
-            device.bind_image_memory(render_target_image, render_target_memory, 0)?;
-
-            let render_target_ptr = device.map_memory(
-                render_target_memory,
-                0,
-                vk::WHOLE_SIZE,
-                vk::MemoryMapFlags::empty(),
-            )? as *mut u8;
-
-            let rt_view_info = vk::ImageViewCreateInfo::default()
-                .image(render_target_image)
-                .view_type(vk::ImageViewType::TYPE_2D)
-                .format(vk::Format::B8G8R8A8_UNORM)
-                .subresource_range(vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                });
-
-            let render_target_view = device.create_image_view(&rt_view_info, None)?;
-
-            // Get layout for row pitch
-            let subresource = vk::ImageSubresource {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                mip_level: 0,
-                array_layer: 0,
+            let queue = device.get_device_queue(queue_family_index, 0);
+
+            // Float targets often can't be LINEAR color attachments (or,
+            // to export, sampled images)
+            let rt_format = vk_format(config.format);
+            let linear_features = instance
+                .get_physical_device_format_properties(physical_device, rt_format)
+                .linear_tiling_features;
+            let needed_features = if config.exportable {
+                vk::FormatFeatureFlags::COLOR_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE
+            } else {
+                vk::FormatFeatureFlags::COLOR_ATTACHMENT
+            };
+            let tiling = if config.tiling == RenderTiling::Linear && !linear_features.contains(needed_features) {
+                println!("{:?} can't be rendered with linear tiling here; using optimal", config.format);
+                RenderTiling::Optimal
+            } else {
+                config.tiling
             };
-            let layout = device.get_image_subresource_layout(render_target_image, subresource);
-            let row_pitch = layout.row_pitch as usize;
-
-            // Create texture
-            let (texture_image, texture_memory, texture_view) =
-                Self::create_texture(&device, &mem_properties)?;
-
-            // Create sampler
-            let sampler_info = vk::SamplerCreateInfo::default()
-                .mag_filter(vk::Filter::LINEAR)
-                .min_filter(vk::Filter::LINEAR)
-                .address_mode_u(vk::SamplerAddressMode::REPEAT)
-                .address_mode_v(vk::SamplerAddressMode::REPEAT)
-                .address_mode_w(vk::SamplerAddressMode::REPEAT);
 
-            let sampler = device.create_sampler(&sampler_info, None)?;
+            // Create render pass. The OPTIMAL target ends ready for the copy-out.
+            let final_layout = match tiling {
+                RenderTiling::Linear => vk::ImageLayout::GENERAL,
+                RenderTiling::Optimal => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            };
 
-            // Create render pass
             let attachment = vk::AttachmentDescription::default()
-                .format(vk::Format::B8G8R8A8_UNORM)
+                .format(rt_format)
                 .samples(vk::SampleCountFlags::TYPE_1)
                 .load_op(vk::AttachmentLoadOp::CLEAR)
                 .store_op(vk::AttachmentStoreOp::STORE)
                 .initial_layout(vk::ImageLayout::UNDEFINED)
-                .final_layout(vk::ImageLayout::GENERAL);
+                .final_layout(final_layout);
 
             let color_ref = vk::AttachmentReference::default()
                 .attachment(0)
@@ -194,24 +252,45 @@ impl VulkanRenderer {
                 .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
                 .color_attachments(std::slice::from_ref(&color_ref));
 
+            // Make color writes visible to the transfer that copies the target out
+            let copy_dependency = vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+
+            let dependencies: &[vk::SubpassDependency] = match tiling {
+                RenderTiling::Linear => &[],
+                RenderTiling::Optimal => std::slice::from_ref(&copy_dependency),
+            };
+
             let render_pass_info = vk::RenderPassCreateInfo::default()
                 .attachments(std::slice::from_ref(&attachment))
-                .subpasses(std::slice::from_ref(&subpass));
+                .subpasses(std::slice::from_ref(&subpass))
+                .dependencies(dependencies);
 
             let render_pass = device.create_render_pass(&render_pass_info, None)?;
 
-            // Create framebuffer
-            let fb_info = vk::FramebufferCreateInfo::default()
-                .render_pass(render_pass)
-                .attachments(std::slice::from_ref(&render_target_view))
-                .width(width)
-                .height(height)
-                .layers(1);
+            let target = RenderTarget::create(
+                &device,
+                &mem_properties,
+                render_pass,
+                (width, height),
+                tiling,
+                config.format,
+                config.exportable,
+            )?;
+
+            // Create texture
+            let (texture_image, texture_memory, texture_view) =
+                Self::create_texture(&device, &mem_properties)?;
 
-            let framebuffer = device.create_framebuffer(&fb_info, None)?;
+            let sampler = channel_texture::create_sampler(&device, config.channel0_sampler)?;
 
-            // Create uniform buffer
-            let ubo_size = 64;
+            // Create uniform buffer: the UBO, then the sidecar uniform block
+            let ubo_size = (USER_UNIFORMS_OFFSET + USER_UNIFORMS_BYTES) as u64;
             let ubo_info = vk::BufferCreateInfo::default()
                 .size(ubo_size)
                 .usage(vk::BufferUsageFlags::UNIFORM_BUFFER);
@@ -232,91 +311,24 @@ impl VulkanRenderer {
             let uniform_memory = device.allocate_memory(&ubo_alloc, None)?;
             device.bind_buffer_memory(uniform_buffer, uniform_memory, 0)?;
 
-            let uniform_ptr = device.map_memory(
-                uniform_memory,
-                0,
-                ubo_size,
-                vk::MemoryMapFlags::empty(),
-            )? as *mut u8;
-
-            // Create descriptors
-            let bindings = [
-                vk::DescriptorSetLayoutBinding::default()
-                    .binding(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .descriptor_count(1)
-                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT),
-                vk::DescriptorSetLayoutBinding::default()
-                    .binding(1)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .descriptor_count(1)
-                    .stage_flags(vk::ShaderStageFlags::FRAGMENT),
-            ];
-
-            let desc_layout_info = vk::DescriptorSetLayoutCreateInfo::default()
-                .bindings(&bindings);
-
-            let descriptor_set_layout = device.create_descriptor_set_layout(&desc_layout_info, None)?;
-
-            let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
-                .set_layouts(std::slice::from_ref(&descriptor_set_layout));
+            let uniforms = MappedMemory::map(&device, uniform_memory, ubo_size as usize)?;
 
-            let pipeline_layout = device.create_pipeline_layout(&pipeline_layout_info, None)?;
-
-            // Create descriptor pool
-            let pool_sizes = [
-                vk::DescriptorPoolSize {
-                    ty: vk::DescriptorType::UNIFORM_BUFFER,
-                    descriptor_count: 1,
-                },
-                vk::DescriptorPoolSize {
-                    ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    descriptor_count: 1,
-                },
-            ];
-
-            let pool_info = vk::DescriptorPoolCreateInfo::default()
-                .max_sets(1)
-                .pool_sizes(&pool_sizes);
-
-            let descriptor_pool = device.create_descriptor_pool(&pool_info, None)?;
-
-            let alloc_info = vk::DescriptorSetAllocateInfo::default()
-                .descriptor_pool(descriptor_pool)
-                .set_layouts(std::slice::from_ref(&descriptor_set_layout));
-
-            let descriptor_sets = device.allocate_descriptor_sets(&alloc_info)?;
-            let descriptor_set = descriptor_sets[0];
-
-            // Update descriptors
-            let buffer_info = vk::DescriptorBufferInfo::default()
-                .buffer(uniform_buffer)
-                .offset(0)
-                .range(64);
-
-            let image_info = vk::DescriptorImageInfo::default()
-                .sampler(sampler)
-                .image_view(texture_view)
-                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
-
-            let writes = [
-                vk::WriteDescriptorSet::default()
-                    .dst_set(descriptor_set)
-                    .dst_binding(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(std::slice::from_ref(&buffer_info)),
-                vk::WriteDescriptorSet::default()
-                    .dst_set(descriptor_set)
-                    .dst_binding(1)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(std::slice::from_ref(&image_info)),
-            ];
-
-            device.update_descriptor_sets(&writes, &[]);
+            // Descriptor sets and pipeline layouts for every combination of
+            // sampler / push constants a shader may need
+            let layouts = ShaderLayouts::new(
+                &device,
+                uniform_buffer,
+                UNIFORM_BUFFER_BYTES as u64,
+                sampler,
+                texture_view,
+                PUSH_CONSTANT_BYTES,
+            )?;
 
             // Create command pool
+            // RESET_COMMAND_BUFFER: the frame commands are re-recorded whenever a shader loads
             let pool_info = vk::CommandPoolCreateInfo::default()
-                .queue_family_index(0);
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                .queue_family_index(queue_family_index);
 
             let command_pool = device.create_command_pool(&pool_info, None)?;
 
@@ -346,31 +358,35 @@ impl VulkanRenderer {
                 device,
                 physical_device,
                 queue,
-                render_target_image,
-                render_target_memory,
-                render_target_view,
-                render_target_ptr,
-                render_target_size: (height as usize * row_pitch),
+                queue_family_index,
+                mem_properties,
+                target,
+                tiling,
+                exportable: config.exportable,
                 texture_image,
                 texture_memory,
                 texture_view,
                 sampler,
+                channel0: None,
+                overlay: Vec::new(),
                 render_pass,
-                framebuffer,
-                descriptor_pool,
-                descriptor_set_layout,
-                descriptor_set,
-                pipeline_layout,
+                layouts,
+                layout_key: LayoutKey::default(),
                 uniform_buffer,
                 uniform_memory,
-                uniform_ptr,
+                uniforms,
+                user_uniform_bytes: 0,
                 pipeline: None,
                 command_pool,
                 command_buffer,
                 fence,
+                frame_pending: false,
                 width,
                 height,
-                row_pitch,
+                format: config.format,
+                blend: config.blend,
+                flip_y: config.flip_y,
+                frag_coord: config.frag_coord,
             })
         }
     }
@@ -384,19 +400,77 @@ impl VulkanRenderer {
         }
     }
 
-    pub fn load_shader(&mut self, vert_path: &Path, frag_path: &Path)
-        -> Result<(), Box<dyn std::error::Error>>
+    pub fn load_shader(&mut self, vert_path: &Path, frag_path: &Path, entry_points: &EntryPoints)
+        -> Result<(), MetalshaderError>
+    {
+        let vert = std::fs::read(vert_path)?;
+        let frag = std::fs::read(frag_path)?;
+        self.load_shader_spirv(&vert, &frag, entry_points)
+    }
+
+    /// Like `load_shader`, from SPIR-V already in memory (see
+    /// `ShaderCompiler::compile_to_spirv`)
+    pub fn load_shader_spirv(&mut self, vert: &[u8], frag: &[u8], entry_points: &EntryPoints)
+        -> Result<(), MetalshaderError>
     {
         unsafe {
-            // Destroy old pipeline if exists
+            // A hung frame may still be using the pipeline and command buffer
+            self.finish_pending_frame()?;
+
+            let vert_code = if self.frag_coord == FragCoord::Normalized && vert == FULLSCREEN_VERT_SPV {
+                words_from_bytes(FULLSCREEN_NORMALIZED_VERT_SPV)
+            } else {
+                words_from_bytes(vert)
+            };
+            let frag_code = words_from_bytes(frag);
+
+            let vert_reflection = reflect(&vert_code).map_err(MetalshaderError::InvalidShader)?;
+            let frag_reflection = reflect(&frag_code).map_err(MetalshaderError::InvalidShader)?;
+            vert_reflection.expect_entry_point(&entry_points.vertex, "Vertex").map_err(MetalshaderError::InvalidShader)?;
+            frag_reflection.expect_entry_point(&entry_points.fragment, "Fragment").map_err(MetalshaderError::InvalidShader)?;
+            // Mismatched varyings otherwise surface as an opaque pipeline error
+            for warning in vert_reflection.check_stage_interface(&frag_reflection).map_err(MetalshaderError::InvalidShader)? {
+                eprintln!("Warning: {}", warning);
+            }
+            let reflection = vert_reflection.merge(&frag_reflection);
+            // A binding the layout lacks renders black or crashes at bind time
+            // instead of failing pipeline creation
+            reflection.check_descriptor_layout(UNIFORM_BUFFER_BYTES).map_err(MetalshaderError::InvalidShader)?;
+            let cube_bound = self.channel0.as_ref().is_some_and(|channel0| channel0.cube);
+            reflection.check_channel0(cube_bound).map_err(MetalshaderError::InvalidShader)?;
+            let uses_push_constants = match reflection.push_constant_size {
+                Some(size) if size > PUSH_CONSTANT_BYTES => {
+                    return Err(MetalshaderError::InvalidShader(format!(
+                        "Push-constant block is {} bytes, at most {} are supported", size, PUSH_CONSTANT_BYTES
+                    )));
+                }
+                Some(size) => {
+                    println!("Shader uses push constants ({} bytes)", size);
+                    true
+                }
+                None => false,
+            };
+            if let Some(BindingKind::UniformBuffer { size }) = reflection.binding(0, USER_BINDING).map(|b| b.kind) {
+                if size as usize > self.user_uniform_bytes {
+                    eprintln!(
+                        "Warning: uniform block at binding {} is {} bytes, the sidecar fills {}; the rest reads as zero",
+                        USER_BINDING, size, self.user_uniform_bytes
+                    );
+                }
+            }
+            // Shaders without iChannel0 get a layout without the sampler binding
+            let layout_key = LayoutKey {
+                sampler: reflection.binding(0, 1).is_some(),
+                user_uniforms: reflection.binding(0, USER_BINDING).is_some(),
+                push_constants: uses_push_constants,
+            };
+            let layout = self.layouts.pipeline_layout(layout_key);
+
+            // Destroy old pipeline if exists; a shader rejected above keeps it
             if let Some(pipeline) = self.pipeline.take() {
                 self.device.destroy_pipeline(pipeline, None);
             }
 
-            // Load shader code
-            let vert_code = load_shader_code(vert_path)?;
-            let frag_code = load_shader_code(frag_path)?;
-
             // Create shader modules
             let vert_info = vk::ShaderModuleCreateInfo::default()
                 .code(&vert_code);
@@ -406,17 +480,18 @@ impl VulkanRenderer {
                 .code(&frag_code);
             let frag_module = self.device.create_shader_module(&frag_info, None)?;
 
-            let entry_name = CStr::from_bytes_with_nul(b"main\0")?;
+            let vert_entry = CString::new(entry_points.vertex.as_str()).map_err(|e| MetalshaderError::InvalidShader(e.to_string()))?;
+            let frag_entry = CString::new(entry_points.fragment.as_str()).map_err(|e| MetalshaderError::InvalidShader(e.to_string()))?;
 
             let stages = [
                 vk::PipelineShaderStageCreateInfo::default()
                     .stage(vk::ShaderStageFlags::VERTEX)
                     .module(vert_module)
-                    .name(entry_name),
+                    .name(&vert_entry),
                 vk::PipelineShaderStageCreateInfo::default()
                     .stage(vk::ShaderStageFlags::FRAGMENT)
                     .module(frag_module)
-                    .name(entry_name),
+                    .name(&frag_entry),
             ];
 
             let vertex_input = vk::PipelineVertexInputStateCreateInfo::default();
@@ -424,11 +499,18 @@ impl VulkanRenderer {
             let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
                 .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
 
+            // --flip-y: a negative height (core since Vulkan 1.1) starts the
+            // viewport at the bottom edge, so clip y = -1 is the last row
+            let (viewport_y, viewport_height) = if self.flip_y {
+                (self.height as f32, -(self.height as f32))
+            } else {
+                (0.0, self.height as f32)
+            };
             let viewport = vk::Viewport {
                 x: 0.0,
-                y: 0.0,
+                y: viewport_y,
                 width: self.width as f32,
-                height: self.height as f32,
+                height: viewport_height,
                 min_depth: 0.0,
                 max_depth: 1.0,
             };
@@ -453,8 +535,20 @@ impl VulkanRenderer {
             let multisampling = vk::PipelineMultisampleStateCreateInfo::default()
                 .rasterization_samples(vk::SampleCountFlags::TYPE_1);
 
-            let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
-                .color_write_mask(vk::ColorComponentFlags::RGBA);
+            let color_blend_attachment = if self.blend {
+                vk::PipelineColorBlendAttachmentState::default()
+                    .color_write_mask(vk::ColorComponentFlags::RGBA)
+                    .blend_enable(true)
+                    .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+                    .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                    .color_blend_op(vk::BlendOp::ADD)
+                    .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                    .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                    .alpha_blend_op(vk::BlendOp::ADD)
+            } else {
+                vk::PipelineColorBlendAttachmentState::default()
+                    .color_write_mask(vk::ColorComponentFlags::RGBA)
+            };
 
             let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
                 .attachments(std::slice::from_ref(&color_blend_attachment));
@@ -467,7 +561,7 @@ impl VulkanRenderer {
                 .rasterization_state(&rasterizer)
                 .multisample_state(&multisampling)
                 .color_blend_state(&color_blending)
-                .layout(self.pipeline_layout)
+                .layout(layout)
                 .render_pass(self.render_pass)
                 .subpass(0);
 
@@ -477,135 +571,374 @@ impl VulkanRenderer {
                 None,
             ).map_err(|e| e.1)?;
 
-            self.pipeline = Some(pipelines[0]);
-
             // Clean up shader modules
             self.device.destroy_shader_module(vert_module, None);
             self.device.destroy_shader_module(frag_module, None);
 
+            // Replaces the recording that referenced the old pipeline. Push
+            // constants are recorded into the command buffer, so those shaders
+            // are re-recorded every frame instead
+            self.layout_key = layout_key;
+            if !uses_push_constants {
+                if let Err(e) = self.record_frame_commands(pipelines[0], None) {
+                    self.device.destroy_pipeline(pipelines[0], None);
+                    return Err(e);
+                }
+            }
+            self.pipeline = Some(pipelines[0]);
+
             Ok(())
         }
     }
 
-    pub fn render_frame(&mut self, ubo: &crate::ShaderToyUBO)
-        -> Result<(), Box<dyn std::error::Error>>
-    {
+    /// The render target's handles, for sampling it outside the renderer
+    pub fn render_target(&self) -> RenderTargetHandle {
+        self.target.handle
+    }
+
+    /// The logical device the render target and its memory belong to
+    pub fn device(&self) -> &ash::Device {
+        &self.device
+    }
+
+    /// Export the render target's memory as an opaque file descriptor for
+    /// `VkImportMemoryFdInfoKHR` in another Vulkan context on the same GPU;
+    /// the caller owns it. Needs `RenderConfig::exportable`. Recreate the
+    /// image there with the `render_target` parameters and the
+    /// `OPAQUE_FD` external handle type.
+    #[cfg(unix)]
+    pub fn export_render_target_fd(&self) -> Result<std::os::fd::OwnedFd, MetalshaderError> {
+        use std::os::fd::FromRawFd;
+        if !self.exportable {
+            return Err(MetalshaderError::NotExportable);
+        }
+        let get_fd_info = vk::MemoryGetFdInfoKHR::default()
+            .memory(self.target.memory)
+            .handle_type(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
         unsafe {
-            let pipeline = self.pipeline.ok_or("No shader loaded")?;
+            let fd = ash::khr::external_memory_fd::Device::new(&self.instance, &self.device).get_memory_fd(&get_fd_info)?;
+            Ok(std::os::fd::OwnedFd::from_raw_fd(fd))
+        }
+    }
 
-            // Update UBO
-            std::ptr::copy_nonoverlapping(
-                ubo as *const _ as *const u8,
-                self.uniform_ptr,
-                std::mem::size_of::<crate::ShaderToyUBO>(),
-            );
+    /// Mip levels of the bound iChannel0 texture (1 for the checkerboard)
+    pub fn channel0_mip_levels(&self) -> u32 {
+        self.channel0.as_ref().map_or(1, |channel0| channel0.mip_levels)
+    }
+
+    /// Bind `texture` as iChannel0 in place of the built-in checkerboard
+    pub fn set_channel0(&mut self, texture: &TextureData) -> Result<(), MetalshaderError> {
+        unsafe {
+            self.finish_pending_frame()?;
+            let channel0 = ChannelTexture::upload(
+                &self.instance,
+                self.physical_device,
+                &self.device,
+                self.queue,
+                self.queue_family_index,
+                texture,
+            )?;
+            self.layouts.set_texture(&self.device, self.sampler, channel0.view);
+            if let Some(old) = self.channel0.replace(channel0) {
+                old.destroy(&self.device);
+            }
+            // Updating a bound descriptor set invalidates the recorded commands
+            if let Some(pipeline) = self.pipeline {
+                self.record_frame_commands(pipeline, None)?;
+            }
+            Ok(())
+        }
+    }
 
-            // Record commands
-            let begin_info = vk::CommandBufferBeginInfo::default()
-                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+    /// Show another frame of an animated channel: overwrites the bound
+    /// texture in place when `texture` has its size and format, otherwise
+    /// binds it like `set_channel0`
+    pub fn update_channel0(&mut self, texture: &TextureData) -> Result<(), MetalshaderError> {
+        if !self.channel0.as_ref().is_some_and(|channel0| channel0.can_update(texture)) {
+            return self.set_channel0(texture);
+        }
+        unsafe {
+            self.finish_pending_frame()?;
+            // The descriptor still points at the same view; nothing to re-record
+            if let Some(channel0) = &self.channel0 {
+                channel0.update(&self.instance, self.physical_device, &self.device, self.queue, self.queue_family_index, texture)?;
+            }
+            Ok(())
+        }
+    }
 
-            self.device.begin_command_buffer(self.command_buffer, &begin_info)?;
+    /// Draw `labels` over the following frames
+    pub fn set_overlay(&mut self, labels: Vec<Label>) -> Result<(), MetalshaderError> {
+        if labels == self.overlay {
+            return Ok(());
+        }
+        unsafe {
+            self.finish_pending_frame()?;
+            self.overlay = labels;
+            // Push-constant shaders re-record every frame anyway
+            if let (Some(pipeline), false) = (self.pipeline, self.layout_key.push_constants) {
+                self.record_frame_commands(pipeline, None)?;
+            }
+            Ok(())
+        }
+    }
 
-            let clear_value = vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 1.0],
-                },
-            };
+    /// Fill the uniform block at `USER_BINDING` (`ShaderInfo::uniforms`),
+    /// zeroing the rest; call before `load_shader`
+    pub fn set_uniforms(&mut self, bytes: &[u8]) -> Result<(), MetalshaderError> {
+        if bytes.len() > USER_UNIFORMS_BYTES {
+            return Err(MetalshaderError::InvalidShader(format!(
+                "Uniforms are {} bytes, at most {} are supported", bytes.len(), USER_UNIFORMS_BYTES
+            )));
+        }
+        unsafe {
+            self.finish_pending_frame()?;
+        }
+        let block = &mut self.uniforms.as_mut_slice()[USER_UNIFORMS_OFFSET..];
+        block[..bytes.len()].copy_from_slice(bytes);
+        block[bytes.len()..].fill(0);
+        self.user_uniform_bytes = bytes.len();
+        Ok(())
+    }
 
-            let render_pass_info = vk::RenderPassBeginInfo::default()
-                .render_pass(self.render_pass)
-                .framebuffer(self.framebuffer)
-                .render_area(vk::Rect2D {
-                    offset: vk::Offset2D { x: 0, y: 0 },
-                    extent: vk::Extent2D {
-                        width: self.width,
-                        height: self.height,
-                    },
-                })
-                .clear_values(std::slice::from_ref(&clear_value));
-
-            self.device.cmd_begin_render_pass(
-                self.command_buffer,
-                &render_pass_info,
-                vk::SubpassContents::INLINE,
-            );
+    /// Render at `width`x`height` from now on. Only the render target, its
+    /// readback mapping and framebuffer are recreated; the instance, device,
+    /// render pass and textures are kept. The viewport is baked into the
+    /// pipeline, so load a shader again before the next `render_frame`
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), MetalshaderError> {
+        unsafe {
+            self.finish_pending_frame()?;
+            if let Some(pipeline) = self.pipeline.take() {
+                self.device.destroy_pipeline(pipeline, None);
+            }
+            let target = RenderTarget::create(
+                &self.device,
+                &self.mem_properties,
+                self.render_pass,
+                (width, height),
+                self.tiling,
+                self.format,
+                self.exportable,
+            )?;
+            std::mem::replace(&mut self.target, target).destroy(&self.device);
+        }
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
 
-            self.device.cmd_bind_pipeline(
-                self.command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                pipeline,
-            );
+    pub fn render_frame(&mut self, ubo: &ShaderToyUBO)
+        -> Result<(), MetalshaderError>
+    {
+        unsafe {
+            let pipeline = self.pipeline.ok_or(MetalshaderError::NoShaderLoaded)?;
+            self.finish_pending_frame()?;
 
-            self.device.cmd_bind_descriptor_sets(
-                self.command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
-                0,
-                &[self.descriptor_set],
-                &[],
-            );
+            // Update UBO
+            self.uniforms.write(ubo);
 
-            self.device.cmd_draw(self.command_buffer, 6, 1, 0, 0);
-            self.device.cmd_end_render_pass(self.command_buffer);
-            self.device.end_command_buffer(self.command_buffer)?;
+            if self.layout_key.push_constants {
+                // The push block mirrors the start of the UBO layout
+                let len = std::mem::size_of::<ShaderToyUBO>().min(PUSH_CONSTANT_BYTES as usize) & !3;
+                let bytes = std::slice::from_raw_parts(ubo as *const _ as *const u8, len);
+                self.record_frame_commands(pipeline, Some(bytes))?;
+            }
 
-            // Submit and wait
+            // Submit the commands recorded by load_shader; the UBO is read
+            // through the descriptor set, so no re-recording is needed
             let submit_info = vk::SubmitInfo::default()
                 .command_buffers(std::slice::from_ref(&self.command_buffer));
 
             self.device.queue_submit(self.queue, &[submit_info], self.fence)?;
-            self.device.wait_for_fences(&[self.fence], true, u64::MAX)?;
-            self.device.reset_fences(&[self.fence])?;
-
-            Ok(())
+            self.frame_pending = true;
+            self.finish_pending_frame()
         }
     }
 
-    pub fn get_frame_buffer(&self) -> &[u8] {
-        unsafe {
-            let buffer = std::slice::from_raw_parts(self.render_target_ptr, self.render_target_size);
-
-            // Debug: check first few pixels
-            if buffer.len() >= 16 {
-                let first_pixels: Vec<u8> = buffer[0..16].to_vec();
-                eprintln!("First 16 bytes of framebuffer: {:02x?}", first_pixels);
-                eprintln!("Row pitch: {}, Width: {}, Expected: {}",
-                    self.row_pitch, self.width, self.width * 4);
+    /// Wait (bounded by `FRAME_TIMEOUT_NS`) for the last submitted frame
+    unsafe fn finish_pending_frame(&mut self) -> Result<(), MetalshaderError> {
+        if !self.frame_pending {
+            return Ok(());
+        }
+        match self.device.wait_for_fences(&[self.fence], true, FRAME_TIMEOUT_NS) {
+            Ok(()) => {}
+            Err(vk::Result::TIMEOUT) => {
+                return Err(MetalshaderError::FrameTimeout(FRAME_TIMEOUT_NS / 1_000_000_000));
             }
+            Err(e) => return Err(e.into()),
+        }
+        self.device.reset_fences(&[self.fence])?;
+        self.frame_pending = false;
+        Ok(())
+    }
+
+    /// Record the per-frame commands once for `pipeline`
+    ///
+    /// Everything that changes between frames lives in host-mapped memory (the
+    /// UBO), so the same command buffer is resubmitted until the next shader load.
+    /// Push-constant shaders pass their per-frame bytes and re-record each frame.
+    unsafe fn record_frame_commands(&self, pipeline: vk::Pipeline, push_constants: Option<&[u8]>)
+        -> Result<(), MetalshaderError>
+    {
+        let layout = self.layouts.pipeline_layout(self.layout_key);
+
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::empty());
+
+        self.device.begin_command_buffer(self.command_buffer, &begin_info)?;
+
+        let clear_value = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            },
+        };
+
+        let render_pass_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.render_pass)
+            .framebuffer(self.target.framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D {
+                    width: self.width,
+                    height: self.height,
+                },
+            })
+            .clear_values(std::slice::from_ref(&clear_value));
+
+        self.device.cmd_begin_render_pass(
+            self.command_buffer,
+            &render_pass_info,
+            vk::SubpassContents::INLINE,
+        );
+
+        self.device.cmd_bind_pipeline(
+            self.command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline,
+        );
+
+        self.device.cmd_bind_descriptor_sets(
+            self.command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            layout,
+            0,
+            &[self.layouts.descriptor_set(self.layout_key)],
+            &[],
+        );
+
+        if let Some(bytes) = push_constants {
+            self.device.cmd_push_constants(
+                self.command_buffer,
+                layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytes,
+            );
+        }
+
+        self.device.cmd_draw(self.command_buffer, 6, 1, 0, 0);
+        for label in &self.overlay {
+            label.record(&self.device, self.command_buffer);
+        }
+        self.device.cmd_end_render_pass(self.command_buffer);
 
-            buffer
+        if let Some(readback) = &self.target.readback {
+            self.record_copy_out(readback.buffer);
         }
+
+        self.device.end_command_buffer(self.command_buffer)?;
+
+        Ok(())
+    }
+
+    /// Copy the OPTIMAL render target into the readback buffer and make it host-visible
+    unsafe fn record_copy_out(&self, buffer: vk::Buffer) {
+        let region = vk::BufferImageCopy::default()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D {
+                width: self.width,
+                height: self.height,
+                depth: 1,
+            });
+
+        self.device.cmd_copy_image_to_buffer(
+            self.command_buffer,
+            self.target.image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            buffer,
+            &[region],
+        );
+
+        let barrier = vk::BufferMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE);
+
+        self.device.cmd_pipeline_barrier(
+            self.command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[barrier],
+            &[],
+        );
+    }
+
+    pub fn get_frame_buffer(&self) -> &[u8] {
+        self.target.frame_memory.as_slice()
     }
 
     pub fn get_row_pitch(&self) -> usize {
-        self.row_pitch
+        self.target.row_pitch
+    }
+
+    /// (width, height) of the render target
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Pixel format of the `get_frame_buffer` rows
+    pub fn format(&self) -> RenderFormat {
+        self.format
     }
 
     // DEBUG: Fill framebuffer with test pattern
     pub fn fill_test_pattern(&mut self) {
-        unsafe {
-            let buffer = std::slice::from_raw_parts_mut(self.render_target_ptr, self.render_target_size);
-            for y in 0..self.height as usize {
-                for x in 0..self.width as usize {
-                    let offset = y * self.row_pitch + x * 4;
-                    if offset + 3 < buffer.len() {
-                        // Checkerboard pattern
-                        let checker = ((x / 64) + (y / 64)) % 2;
-                        buffer[offset + 0] = if checker == 1 { 255 } else { 0 }; // B
-                        buffer[offset + 1] = if checker == 1 { 0 } else { 255 }; // G
-                        buffer[offset + 2] = 0; // R
-                        buffer[offset + 3] = 255; // A
-                    }
+        let buffer = self.target.frame_memory.as_mut_slice();
+        for y in 0..self.height as usize {
+            for x in 0..self.width as usize {
+                let offset = y * self.target.row_pitch + x * 4;
+                if offset + 3 < buffer.len() {
+                    // Checkerboard pattern
+                    let checker = ((x / 64) + (y / 64)) % 2;
+                    buffer[offset + 0] = if checker == 1 { 255 } else { 0 }; // B
+                    buffer[offset + 1] = if checker == 1 { 0 } else { 255 }; // G
+                    buffer[offset + 2] = 0; // R
+                    buffer[offset + 3] = 255; // A
                 }
             }
-            eprintln!("Filled test pattern: {}x{} with row_pitch {}", self.width, self.height, self.row_pitch);
         }
+        eprintln!("Filled test pattern: {}x{} with row_pitch {}", self.width, self.height, self.target.row_pitch);
     }
 
     fn create_texture(
         device: &ash::Device,
         mem_props: &vk::PhysicalDeviceMemoryProperties,
-    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), Box<dyn std::error::Error>> {
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), MetalshaderError> {
         unsafe {
             let tex_info = vk::ImageCreateInfo::default()
                 .image_type(vk::ImageType::TYPE_2D)
@@ -635,12 +968,7 @@ impl VulkanRenderer {
             device.bind_image_memory(texture_image, texture_memory, 0)?;
 
             // Upload texture data
-            let ptr = device.map_memory(
-                texture_memory,
-                0,
-                vk::WHOLE_SIZE,
-                vk::MemoryMapFlags::empty(),
-            )? as *mut u8;
+            let mut mapped = MappedMemory::map(device, texture_memory, tex_req.size as usize)?;
 
             let subresource = vk::ImageSubresource {
                 aspect_mask: vk::ImageAspectFlags::COLOR,
@@ -662,13 +990,12 @@ impl VulkanRenderer {
                 }
             }
 
-            for y in 0..256 {
-                let dst = ptr.add(y * layout.row_pitch as usize);
-                let src = tex_data.as_ptr().add(y * 256 * 4);
-                std::ptr::copy_nonoverlapping(src, dst, 256 * 4);
+            for (y, row) in tex_data.chunks_exact(256 * 4).enumerate() {
+                let dst = layout.offset as usize + y * layout.row_pitch as usize;
+                mapped.as_mut_slice()[dst..dst + row.len()].copy_from_slice(row);
             }
 
-            device.unmap_memory(texture_memory);
+            mapped.unmap(device);
 
             let view_info = vk::ImageViewCreateInfo::default()
                 .image(texture_image)
@@ -688,12 +1015,46 @@ impl VulkanRenderer {
         }
     }
 
+    fn create_readback_buffer(
+        device: &ash::Device,
+        mem_props: &vk::PhysicalDeviceMemoryProperties,
+        size: vk::DeviceSize,
+    ) -> Result<ReadbackBuffer, MetalshaderError> {
+        unsafe {
+            let buffer_info = vk::BufferCreateInfo::default()
+                .size(size)
+                .usage(vk::BufferUsageFlags::TRANSFER_DST)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+            let buffer = device.create_buffer(&buffer_info, None)?;
+            let mem_req = device.get_buffer_memory_requirements(buffer);
+
+            // Prefer cached memory: the CPU reads every byte of this buffer per frame
+            let host_flags = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+            let mem_type = find_memory_type(
+                mem_props,
+                mem_req.memory_type_bits,
+                host_flags | vk::MemoryPropertyFlags::HOST_CACHED,
+            )
+            .or_else(|_| find_memory_type(mem_props, mem_req.memory_type_bits, host_flags))?;
+
+            let alloc_info = vk::MemoryAllocateInfo::default()
+                .allocation_size(mem_req.size)
+                .memory_type_index(mem_type);
+
+            let memory = device.allocate_memory(&alloc_info, None)?;
+            device.bind_buffer_memory(buffer, memory, 0)?;
+
+            Ok(ReadbackBuffer { buffer, memory })
+        }
+    }
+
     fn transition_texture_layout(
         device: &ash::Device,
         cmd: vk::CommandBuffer,
         queue: vk::Queue,
         image: vk::Image,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), MetalshaderError> {
         unsafe {
             let begin_info = vk::CommandBufferBeginInfo::default();
             device.begin_command_buffer(cmd, &begin_info)?;
@@ -739,6 +1100,166 @@ impl VulkanRenderer {
     }
 }
 
+impl RenderTarget {
+    /// Create the render target image and its framebuffer for `render_pass`.
+    /// LINEAR images are HOST_VISIBLE and mapped directly; OPTIMAL ones are
+    /// DEVICE_LOCAL and copied into a host-visible readback buffer
+    unsafe fn create(
+        device: &ash::Device,
+        mem_properties: &vk::PhysicalDeviceMemoryProperties,
+        render_pass: vk::RenderPass,
+        (width, height): (u32, u32),
+        tiling: RenderTiling,
+        format: RenderFormat,
+        exportable: bool,
+    ) -> Result<Self, MetalshaderError> {
+        let rt_format = vk_format(format);
+        let (rt_tiling, mut rt_usage) = match tiling {
+            RenderTiling::Linear => (vk::ImageTiling::LINEAR, vk::ImageUsageFlags::COLOR_ATTACHMENT),
+            RenderTiling::Optimal => (
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            ),
+        };
+        if exportable {
+            rt_usage |= vk::ImageUsageFlags::SAMPLED;
+        }
+
+        let mut rt_external_info = vk::ExternalMemoryImageCreateInfo::default()
+            .handle_types(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+        let mut rt_image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(rt_format)
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(rt_tiling)
+            .usage(rt_usage)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        if exportable {
+            rt_image_info = rt_image_info.push_next(&mut rt_external_info);
+        }
+
+        let image = device.create_image(&rt_image_info, None)?;
+        let rt_mem_req = device.get_image_memory_requirements(image);
+
+        let rt_mem_type = match tiling {
+            RenderTiling::Linear => find_memory_type(
+                mem_properties,
+                rt_mem_req.memory_type_bits,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )?,
+            RenderTiling::Optimal => find_memory_type(
+                mem_properties,
+                rt_mem_req.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+            .or_else(|_| find_memory_type(
+                mem_properties,
+                rt_mem_req.memory_type_bits,
+                vk::MemoryPropertyFlags::empty(),
+            ))?,
+        };
+
+        // Exported images get memory of their own, which some drivers require
+        let mut rt_export_info = vk::ExportMemoryAllocateInfo::default()
+            .handle_types(vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD);
+        let mut rt_dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+        let mut rt_alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(rt_mem_req.size)
+            .memory_type_index(rt_mem_type);
+        if exportable {
+            rt_alloc_info = rt_alloc_info.push_next(&mut rt_export_info).push_next(&mut rt_dedicated_info);
+        }
+
+        let memory = device.allocate_memory(&rt_alloc_info, None)?;
+        device.bind_image_memory(image, memory, 0)?;
+
+        let rt_view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(rt_format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+
+        let view = device.create_image_view(&rt_view_info, None)?;
+
+        let (frame_memory, row_pitch, readback, layout) = match tiling {
+            RenderTiling::Linear => {
+                // Get layout for row pitch
+                let subresource = vk::ImageSubresource {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: 0,
+                    array_layer: 0,
+                };
+                let layout = device.get_image_subresource_layout(image, subresource);
+                let row_pitch = layout.row_pitch as usize;
+                let mapped = MappedMemory::map(device, memory, row_pitch * height as usize)?;
+                (mapped, row_pitch, None, vk::ImageLayout::GENERAL)
+            }
+            RenderTiling::Optimal => {
+                // Tightly packed rows: cmd_copy_image_to_buffer writes whole pixels per row
+                let row_pitch = width as usize * format.bytes_per_pixel();
+                let readback = VulkanRenderer::create_readback_buffer(
+                    device,
+                    mem_properties,
+                    (row_pitch * height as usize) as vk::DeviceSize,
+                )?;
+                let mapped = MappedMemory::map(device, readback.memory, row_pitch * height as usize)?;
+                (mapped, row_pitch, Some(readback), vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            }
+        };
+
+        let fb_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(std::slice::from_ref(&view))
+            .width(width)
+            .height(height)
+            .layers(1);
+
+        let framebuffer = device.create_framebuffer(&fb_info, None)?;
+
+        Ok(Self {
+            image,
+            memory,
+            view,
+            framebuffer,
+            frame_memory,
+            readback,
+            row_pitch,
+            handle: RenderTargetHandle {
+                image,
+                memory,
+                allocation_size: rt_mem_req.size,
+                format: rt_format,
+                extent: vk::Extent2D { width, height },
+                tiling: rt_tiling,
+                usage: rt_usage,
+                layout,
+            },
+        })
+    }
+
+    /// The GPU must be done with the target
+    unsafe fn destroy(&mut self, device: &ash::Device) {
+        self.frame_memory.unmap(device);
+        device.destroy_framebuffer(self.framebuffer, None);
+        if let Some(readback) = self.readback.take() {
+            device.destroy_buffer(readback.buffer, None);
+            device.free_memory(readback.memory, None);
+        }
+        device.destroy_image_view(self.view, None);
+        device.destroy_image(self.image, None);
+        device.free_memory(self.memory, None);
+    }
+}
+
 impl Drop for VulkanRenderer {
     fn drop(&mut self) {
         unsafe {
@@ -750,20 +1271,19 @@ impl Drop for VulkanRenderer {
 
             self.device.destroy_fence(self.fence, None);
             self.device.destroy_command_pool(self.command_pool, None);
-            self.device.destroy_descriptor_pool(self.descriptor_pool, None);
-            self.device.destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.layouts.destroy(&self.device);
+            self.uniforms.unmap(&self.device);
             self.device.destroy_buffer(self.uniform_buffer, None);
             self.device.free_memory(self.uniform_memory, None);
-            self.device.destroy_framebuffer(self.framebuffer, None);
+            self.target.destroy(&self.device);
             self.device.destroy_render_pass(self.render_pass, None);
             self.device.destroy_sampler(self.sampler, None);
             self.device.destroy_image_view(self.texture_view, None);
             self.device.destroy_image(self.texture_image, None);
             self.device.free_memory(self.texture_memory, None);
-            self.device.destroy_image_view(self.render_target_view, None);
-            self.device.destroy_image(self.render_target_image, None);
-            self.device.free_memory(self.render_target_memory, None);
+            if let Some(channel0) = self.channel0.take() {
+                channel0.destroy(&self.device);
+            }
             self.device.destroy_device(None);
             self.instance.destroy_instance(None);
         }
@@ -774,7 +1294,7 @@ fn find_memory_type(
     mem_props: &vk::PhysicalDeviceMemoryProperties,
     type_bits: u32,
     flags: vk::MemoryPropertyFlags,
-) -> Result<u32, Box<dyn std::error::Error>> {
+) -> Result<u32, MetalshaderError> {
     for i in 0..mem_props.memory_type_count {
         if (type_bits & (1 << i)) != 0
             && mem_props.memory_types[i as usize].property_flags.contains(flags)
@@ -782,19 +1302,56 @@ fn find_memory_type(
             return Ok(i);
         }
     }
-    Err("No suitable memory type found".into())
+    Err(MetalshaderError::NoMemoryType)
 }
 
-fn load_shader_code(path: &Path) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
-    let mut file = File::open(path)?;
-    let mut bytes = Vec::new();
-    file.read_to_end(&mut bytes)?;
 
-    // Convert bytes to u32
-    let code: Vec<u32> = bytes
-        .chunks_exact(4)
-        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
-        .collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Teardown has to release everything creation made, or repeated
+    /// renderers run out of device memory. Skipped without a Vulkan device;
+    /// run with VK_INSTANCE_LAYERS=VK_LAYER_KHRONOS_validation to have the
+    /// layers report objects left alive
+    #[test]
+    fn test_create_and_drop_repeatedly() {
+        for round in 0..32 {
+            let mut renderer = match VulkanRenderer::new(64, 64, RenderConfig::default()) {
+                Ok(renderer) => renderer,
+                Err(e) if round == 0 => {
+                    eprintln!("Skipping: {}", e);
+                    return;
+                }
+                Err(e) => panic!("round {}: {}", round, e),
+            };
+            renderer.set_uniforms(&[0; 16]).unwrap();
+            renderer
+                .load_shader_spirv(FULLSCREEN_VERT_SPV, crate::shader::BUILTIN_FRAG_SPV, &EntryPoints::default())
+                .unwrap();
+            renderer.render_frame(&ShaderToyUBO::default()).unwrap();
+            assert_eq!(renderer.get_frame_buffer().len(), 64 * renderer.get_row_pitch());
+        }
+    }
 
-    Ok(code)
+    #[test]
+    fn test_resize() {
+        let mut renderer = match VulkanRenderer::new(64, 64, RenderConfig::default()) {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                eprintln!("Skipping: {}", e);
+                return;
+            }
+        };
+        for (width, height) in [(128, 32), (17, 9), (64, 64)] {
+            renderer.resize(width, height).unwrap();
+            assert!(matches!(renderer.render_frame(&ShaderToyUBO::default()), Err(MetalshaderError::NoShaderLoaded)));
+            renderer
+                .load_shader_spirv(FULLSCREEN_VERT_SPV, crate::shader::BUILTIN_FRAG_SPV, &EntryPoints::default())
+                .unwrap();
+            renderer.render_frame(&ShaderToyUBO::default()).unwrap();
+            assert_eq!(renderer.render_target().extent, vk::Extent2D { width, height });
+            assert_eq!(renderer.get_frame_buffer().len(), height as usize * renderer.get_row_pitch());
+        }
+    }
 }
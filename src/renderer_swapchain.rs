@@ -3,51 +3,124 @@
 
 use ash::vk;
 use std::ffi::CStr;
-use std::fs::File;
-use std::io::Read;
 use std::sync::Arc;
 use winit::window::Window;
 
+pub use crate::options::{ChannelSampler, CompositeAlpha, Dpi, EntryPoints, FragCoord, RenderFormat};
+use crate::channel_texture::{self, ChannelTexture};
+use crate::error::MetalshaderError;
+use crate::mapped_memory::MappedMemory;
+use crate::overlay::Label;
+use crate::shader_compiler::{FULLSCREEN_NORMALIZED_VERT_SPV, FULLSCREEN_VERT_SPV};
+use crate::shader_layout::{LayoutKey, ShaderLayouts};
+use crate::viewport::Viewport;
+use crate::spirv_reflect::{reflect, words_from_bytes, BindingKind};
+use crate::texture_file::TextureData;
+use crate::ubo::{USER_BINDING, USER_UNIFORMS_BYTES, USER_UNIFORMS_OFFSET};
+
+/// Construction-time settings for the swapchain renderer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SwapchainConfig {
+    pub composite_alpha: CompositeAlpha,
+    /// Standard src-alpha / one-minus-src-alpha blending over the clear color
+    pub blend: bool,
+    /// Prefer a CPU Vulkan device (lavapipe) even when a GPU is present
+    pub software: bool,
+    /// Render at this fraction of the window size and upscale; 1.0 (or 0.0,
+    /// the `Default`) renders straight into the swapchain
+    pub render_scale: f32,
+    /// Letterbox to this aspect ratio (W, H)
+    pub aspect: Option<(u32, u32)>,
+    /// Float formats render into an offscreen target; the blit onto the
+    /// swapchain clamps them to its 8-bit format
+    pub format: RenderFormat,
+    /// Negative-height viewport: `fragCoord.y = 0` at the bottom row
+    pub flip_y: bool,
+    /// `fragCoord` units from the built-in fullscreen vertex shader
+    pub frag_coord: FragCoord,
+    /// Present with IMMEDIATE (tearing, uncapped) for benchmarking
+    pub no_vsync: bool,
+    /// Prefer a Display P3 / extended sRGB surface format over sRGB
+    pub wide_gamut: bool,
+    /// Swapchain in device pixels or points. The caller sets the window
+    /// layer's contentsScale to match; this covers surfaces that leave the
+    /// extent to the swapchain
+    pub dpi: Dpi,
+    /// Wrap and filter of the iChannel0 sampler
+    pub channel0_sampler: ChannelSampler,
+}
+
+/// Render target for `--render-scale` / `--aspect`, blitted onto `viewport`
+/// of the swapchain image every frame
+struct OffscreenTarget {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    /// Destination rectangle in the swapchain image; black outside
+    viewport: Viewport,
+}
+
+const COLOR_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
 pub struct SwapchainRenderer {
     #[allow(dead_code)]
     entry: ash::Entry,
     instance: ash::Instance,
     surface: vk::SurfaceKHR,
     surface_loader: ash::khr::surface::Instance,
-    #[allow(dead_code)]
     physical_device: vk::PhysicalDevice,
     device: ash::Device,
     queue: vk::Queue,
-    #[allow(dead_code)]
     queue_family_index: u32,
 
     swapchain: vk::SwapchainKHR,
     swapchain_loader: ash::khr::swapchain::Device,
-    #[allow(dead_code)]
     swapchain_images: Vec<vk::Image>,
     swapchain_image_views: Vec<vk::ImageView>,
     swapchain_extent: vk::Extent2D,
     #[allow(dead_code)]
     swapchain_format: vk::Format,
+    /// Composite alpha actually in use after validation against the surface
+    composite_alpha: CompositeAlpha,
+    config: SwapchainConfig,
 
     render_pass: vk::RenderPass,
     framebuffers: Vec<vk::Framebuffer>,
-
-    descriptor_set_layout: vk::DescriptorSetLayout,
-    pipeline_layout: vk::PipelineLayout,
+    /// Render pass into `offscreen_target`; null when rendering at window size
+    offscreen_render_pass: vk::RenderPass,
+    offscreen_target: Option<OffscreenTarget>,
+    mem_properties: vk::PhysicalDeviceMemoryProperties,
+
+    layouts: ShaderLayouts,
+    /// Layout variant the current pipeline was built against
+    layout_key: LayoutKey,
     pipeline: Option<vk::Pipeline>,
+    /// Replaced pipelines that in-flight frames may still use, with the number
+    /// of frame fences left to wait on before they can be destroyed
+    retired_pipelines: Vec<(vk::Pipeline, usize)>,
 
     uniform_buffer: vk::Buffer,
     uniform_memory: vk::DeviceMemory,
-    uniform_ptr: *mut u8,
+    uniforms: MappedMemory,
+    /// Bytes `set_uniforms` wrote at `USER_UNIFORMS_OFFSET`
+    user_uniform_bytes: usize,
 
     texture_image: vk::Image,
     texture_memory: vk::DeviceMemory,
     texture_view: vk::ImageView,
     sampler: vk::Sampler,
-
-    descriptor_pool: vk::DescriptorPool,
-    descriptor_set: vk::DescriptorSet,
+    /// Texture from `set_channel0`, replacing the checkerboard in the descriptor
+    channel0: Option<ChannelTexture>,
+    /// Shader name and FPS drawn over the frame, in render target pixels
+    overlay: Vec<Label>,
 
     command_pool: vk::CommandPool,
     command_buffers: Vec<vk::CommandBuffer>,
@@ -64,42 +137,61 @@ pub struct SwapchainRenderer {
 
 const MAX_FRAMES_IN_FLIGHT: usize = 2;
 
+/// Push-constant range every Vulkan implementation must support
+const PUSH_CONSTANT_BYTES: u32 = 128;
+
+/// Size of the uniform buffer bound at set 0, binding 0; room for the macOS
+/// viewer's extended block (80 bytes) and `iParams` after it
+const UNIFORM_BUFFER_BYTES: u32 = 128;
+
 impl SwapchainRenderer {
-    pub fn new(window: Arc<Window>) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(window: Arc<Window>, config: SwapchainConfig) -> Result<Self, MetalshaderError> {
         unsafe {
-            let entry = ash::Entry::load()?;
+            let entry = crate::vulkan_loader::load_entry()?;
 
             // Create instance with surface extensions
             let app_info = vk::ApplicationInfo::default()
                 .api_version(vk::make_api_version(0, 1, 2, 0));
 
-            let extension_names = vec![
+            let mut extension_names = vec![
                 ash::khr::surface::NAME.as_ptr(),
                 ash::ext::metal_surface::NAME.as_ptr(),
                 b"VK_KHR_portability_enumeration\0".as_ptr() as *const i8,
                 b"VK_KHR_get_physical_device_properties2\0".as_ptr() as *const i8,
             ];
+            // Surfaces only report color spaces beyond sRGB with this enabled
+            if config.wide_gamut {
+                let available = entry.enumerate_instance_extension_properties(None)?;
+                let colorspace = ash::ext::swapchain_colorspace::NAME;
+                if available.iter().any(|ext| ext.extension_name_as_c_str() == Ok(colorspace)) {
+                    extension_names.push(colorspace.as_ptr());
+                } else {
+                    eprintln!("Warning: {:?} not available, --wide-gamut stays sRGB", colorspace);
+                }
+            }
 
             let create_info = vk::InstanceCreateInfo::default()
                 .application_info(&app_info)
                 .enabled_extension_names(&extension_names)
                 .flags(vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR);
 
-            let instance = entry.create_instance(&create_info, None)?;
+            let instance = entry.create_instance(&create_info, None)
+                .map_err(crate::vulkan_loader::instance_error)?;
 
             // Create surface
             use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+            let handle_error = |e: raw_window_handle::HandleError| MetalshaderError::WindowHandle(e.to_string());
             let surface = ash_window::create_surface(
                 &entry,
                 &instance,
-                window.display_handle()?.as_raw(),
-                window.window_handle()?.as_raw(),
+                window.display_handle().map_err(handle_error)?.as_raw(),
+                window.window_handle().map_err(handle_error)?.as_raw(),
                 None,
             )?;
             let surface_loader = ash::khr::surface::Instance::new(&entry, &instance);
 
             // Select physical device
-            let physical_devices = instance.enumerate_physical_devices()?;
+            let physical_devices = crate::vulkan_loader::ranked_physical_devices(&instance, config.software)?;
             let (physical_device, queue_family_index) = physical_devices
                 .iter()
                 .find_map(|&pd| {
@@ -117,8 +209,9 @@ impl SwapchainRenderer {
                         }
                     })
                 })
-                .ok_or("No suitable physical device found")?;
+                .ok_or(MetalshaderError::NoDevice)?;
 
+            crate::vulkan_loader::print_device_info(&entry, &instance, physical_device);
             let device_props = instance.get_physical_device_properties(physical_device);
             let device_name = CStr::from_ptr(device_props.device_name.as_ptr())
                 .to_string_lossy()
@@ -146,7 +239,7 @@ impl SwapchainRenderer {
             let swapchain_loader = ash::khr::swapchain::Device::new(&instance, &device);
 
             // Create swapchain
-            let (swapchain, swapchain_images, swapchain_extent, swapchain_format) =
+            let (swapchain, swapchain_images, swapchain_extent, swapchain_format, composite_alpha) =
                 Self::create_swapchain(
                     &surface_loader,
                     &swapchain_loader,
@@ -154,6 +247,7 @@ impl SwapchainRenderer {
                     surface,
                     &window,
                     vk::SwapchainKHR::null(),
+                    &config,
                 )?;
 
             // Create image views
@@ -207,6 +301,33 @@ impl SwapchainRenderer {
 
             let render_pass = device.create_render_pass(&render_pass_info, None)?;
 
+            // --render-scale / --aspect: render into a separate image and blit
+            // it to the swapchain, which needs TRANSFER_DST on the swapchain images
+            let offscreen_requested =
+                (config.render_scale > 0.0 && config.render_scale < 1.0)
+                    || config.aspect.is_some()
+                    || config.format.is_float();
+            let blit_supported = surface_loader
+                .get_physical_device_surface_capabilities(physical_device, surface)?
+                .supported_usage_flags
+                .contains(vk::ImageUsageFlags::TRANSFER_DST);
+            if offscreen_requested && !blit_supported {
+                eprintln!("Warning: surface can't be a blit target, ignoring --render-scale, --aspect and --format");
+            }
+            let offscreen_render_pass = if offscreen_requested && blit_supported {
+                Self::create_offscreen_render_pass(&device, Self::offscreen_format(&config, swapchain_format))?
+            } else {
+                vk::RenderPass::null()
+            };
+            let offscreen_target = Self::create_offscreen_target(
+                &device,
+                &mem_properties,
+                offscreen_render_pass,
+                Self::offscreen_format(&config, swapchain_format),
+                swapchain_extent,
+                &config,
+            )?;
+
             // Create framebuffers
             let framebuffers = swapchain_image_views
                 .iter()
@@ -222,8 +343,8 @@ impl SwapchainRenderer {
                 })
                 .collect::<Result<Vec<_>, _>>()?;
 
-            // Create uniform buffer
-            let ubo_size = 64;
+            // Create uniform buffer: the UBO, then the sidecar uniform block
+            let ubo_size = (USER_UNIFORMS_OFFSET + USER_UNIFORMS_BYTES) as u64;
             let ubo_info = vk::BufferCreateInfo::default()
                 .size(ubo_size)
                 .usage(vk::BufferUsageFlags::UNIFORM_BUFFER);
@@ -244,101 +365,24 @@ impl SwapchainRenderer {
             let uniform_memory = device.allocate_memory(&ubo_alloc, None)?;
             device.bind_buffer_memory(uniform_buffer, uniform_memory, 0)?;
 
-            let uniform_ptr = device.map_memory(
-                uniform_memory,
-                0,
-                ubo_size,
-                vk::MemoryMapFlags::empty(),
-            )? as *mut u8;
+            let uniforms = MappedMemory::map(&device, uniform_memory, ubo_size as usize)?;
 
             // Create texture
             let (texture_image, texture_memory, texture_view) =
                 Self::create_texture(&device, &mem_properties)?;
 
-            let sampler_info = vk::SamplerCreateInfo::default()
-                .mag_filter(vk::Filter::LINEAR)
-                .min_filter(vk::Filter::LINEAR)
-                .address_mode_u(vk::SamplerAddressMode::REPEAT)
-                .address_mode_v(vk::SamplerAddressMode::REPEAT)
-                .address_mode_w(vk::SamplerAddressMode::REPEAT);
-
-            let sampler = device.create_sampler(&sampler_info, None)?;
-
-            // Create descriptor set layout
-            let bindings = [
-                vk::DescriptorSetLayoutBinding::default()
-                    .binding(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .descriptor_count(1)
-                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT),
-                vk::DescriptorSetLayoutBinding::default()
-                    .binding(1)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .descriptor_count(1)
-                    .stage_flags(vk::ShaderStageFlags::FRAGMENT),
-            ];
-
-            let desc_layout_info = vk::DescriptorSetLayoutCreateInfo::default()
-                .bindings(&bindings);
-
-            let descriptor_set_layout = device.create_descriptor_set_layout(&desc_layout_info, None)?;
-
-            let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
-                .set_layouts(std::slice::from_ref(&descriptor_set_layout));
-
-            let pipeline_layout = device.create_pipeline_layout(&pipeline_layout_info, None)?;
-
-            // Create descriptor pool
-            let pool_sizes = [
-                vk::DescriptorPoolSize {
-                    ty: vk::DescriptorType::UNIFORM_BUFFER,
-                    descriptor_count: 1,
-                },
-                vk::DescriptorPoolSize {
-                    ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    descriptor_count: 1,
-                },
-            ];
-
-            let pool_info = vk::DescriptorPoolCreateInfo::default()
-                .pool_sizes(&pool_sizes)
-                .max_sets(1);
-
-            let descriptor_pool = device.create_descriptor_pool(&pool_info, None)?;
-
-            // Allocate descriptor set
-            let alloc_info = vk::DescriptorSetAllocateInfo::default()
-                .descriptor_pool(descriptor_pool)
-                .set_layouts(std::slice::from_ref(&descriptor_set_layout));
-
-            let descriptor_sets = device.allocate_descriptor_sets(&alloc_info)?;
-            let descriptor_set = descriptor_sets[0];
-
-            // Update descriptor set
-            let buffer_info = vk::DescriptorBufferInfo::default()
-                .buffer(uniform_buffer)
-                .offset(0)
-                .range(ubo_size);
-
-            let image_info = vk::DescriptorImageInfo::default()
-                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .image_view(texture_view)
-                .sampler(sampler);
-
-            let descriptor_writes = [
-                vk::WriteDescriptorSet::default()
-                    .dst_set(descriptor_set)
-                    .dst_binding(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(std::slice::from_ref(&buffer_info)),
-                vk::WriteDescriptorSet::default()
-                    .dst_set(descriptor_set)
-                    .dst_binding(1)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(std::slice::from_ref(&image_info)),
-            ];
+            let sampler = channel_texture::create_sampler(&device, config.channel0_sampler)?;
 
-            device.update_descriptor_sets(&descriptor_writes, &[]);
+            // Descriptor sets and pipeline layouts for every combination of
+            // sampler / push constants a shader may need
+            let layouts = ShaderLayouts::new(
+                &device,
+                uniform_buffer,
+                UNIFORM_BUFFER_BYTES as u64,
+                sampler,
+                texture_view,
+                PUSH_CONSTANT_BYTES,
+            )?;
 
             // Create command pool
             let pool_info = vk::CommandPoolCreateInfo::default()
@@ -385,20 +429,27 @@ impl SwapchainRenderer {
                 swapchain_image_views,
                 swapchain_extent,
                 swapchain_format,
+                composite_alpha,
+                config,
                 render_pass,
                 framebuffers,
-                descriptor_set_layout,
-                pipeline_layout,
+                offscreen_render_pass,
+                offscreen_target,
+                mem_properties,
+                layouts,
+                layout_key: LayoutKey::default(),
                 pipeline: None,
+                retired_pipelines: Vec::new(),
                 uniform_buffer,
                 uniform_memory,
-                uniform_ptr,
+                uniforms,
+                user_uniform_bytes: 0,
                 texture_image,
                 texture_memory,
                 texture_view,
                 sampler,
-                descriptor_pool,
-                descriptor_set,
+                channel0: None,
+                overlay: Vec::new(),
                 command_pool,
                 command_buffers,
                 image_available_semaphores,
@@ -418,7 +469,8 @@ impl SwapchainRenderer {
         surface: vk::SurfaceKHR,
         window: &Window,
         old_swapchain: vk::SwapchainKHR,
-    ) -> Result<(vk::SwapchainKHR, Vec<vk::Image>, vk::Extent2D, vk::Format), Box<dyn std::error::Error>> {
+        config: &SwapchainConfig,
+    ) -> Result<(vk::SwapchainKHR, Vec<vk::Image>, vk::Extent2D, vk::Format, CompositeAlpha), MetalshaderError> {
         unsafe {
             let capabilities = surface_loader
                 .get_physical_device_surface_capabilities(physical_device, surface)?;
@@ -429,21 +481,28 @@ impl SwapchainRenderer {
             let present_modes = surface_loader
                 .get_physical_device_surface_present_modes(physical_device, surface)?;
 
-            let surface_format = formats
-                .iter()
-                .find(|f| {
-                    f.format == vk::Format::B8G8R8A8_UNORM
-                        && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-                })
-                .unwrap_or(&formats[0]);
+            let first_creation = old_swapchain == vk::SwapchainKHR::null();
+            let surface_format = Self::choose_surface_format(&formats, config.wide_gamut, first_creation);
 
-            let present_mode = if present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
+            let immediate = config.no_vsync && present_modes.contains(&vk::PresentModeKHR::IMMEDIATE);
+            if config.no_vsync && !immediate && first_creation {
+                eprintln!(
+                    "Warning: surface has no IMMEDIATE present mode ({:?}), FPS stays refresh-limited",
+                    present_modes
+                );
+            }
+            let present_mode = if immediate {
+                vk::PresentModeKHR::IMMEDIATE
+            } else if present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
                 vk::PresentModeKHR::MAILBOX
             } else {
                 vk::PresentModeKHR::FIFO
             };
 
-            let size = window.inner_size();
+            let size = match config.dpi {
+                Dpi::Physical => window.inner_size(),
+                Dpi::Logical => window.inner_size().to_logical(window.scale_factor()),
+            };
             let extent = if capabilities.current_extent.width != u32::MAX {
                 capabilities.current_extent
             } else {
@@ -459,6 +518,27 @@ impl SwapchainRenderer {
                 }
             };
 
+            // Validate the requested composite alpha; fall back to opaque if unsupported
+            let requested_flag = match config.composite_alpha {
+                CompositeAlpha::Opaque => vk::CompositeAlphaFlagsKHR::OPAQUE,
+                CompositeAlpha::PreMultiplied => vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+                CompositeAlpha::PostMultiplied => vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+            };
+            let (composite_alpha, composite_flag) =
+                if capabilities.supported_composite_alpha.contains(requested_flag) {
+                    (config.composite_alpha, requested_flag)
+                } else {
+                    eprintln!(
+                        "Warning: {:?} composite alpha not supported by surface ({:?}), using opaque",
+                        config.composite_alpha, capabilities.supported_composite_alpha
+                    );
+                    (CompositeAlpha::Opaque, vk::CompositeAlphaFlagsKHR::OPAQUE)
+                };
+
+            // TRANSFER_DST lets --render-scale / --aspect blit into the images
+            let image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | (capabilities.supported_usage_flags & vk::ImageUsageFlags::TRANSFER_DST);
+
             let image_count = (capabilities.min_image_count + 1).min(
                 if capabilities.max_image_count > 0 {
                     capabilities.max_image_count
@@ -474,10 +554,10 @@ impl SwapchainRenderer {
                 .image_color_space(surface_format.color_space)
                 .image_extent(extent)
                 .image_array_layers(1)
-                .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+                .image_usage(image_usage)
                 .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
                 .pre_transform(capabilities.current_transform)
-                .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+                .composite_alpha(composite_flag)
                 .present_mode(present_mode)
                 .clipped(true)
                 .old_swapchain(old_swapchain);
@@ -485,7 +565,7 @@ impl SwapchainRenderer {
             let swapchain = swapchain_loader.create_swapchain(&create_info, None)?;
             let images = swapchain_loader.get_swapchain_images(swapchain)?;
 
-            Ok((swapchain, images, extent, surface_format.format))
+            Ok((swapchain, images, extent, surface_format.format, composite_alpha))
         }
     }
 
@@ -493,31 +573,88 @@ impl SwapchainRenderer {
         &mut self,
         vert_path: &str,
         frag_path: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        unsafe {
-            self.device.device_wait_idle()?;
+        entry_points: &EntryPoints,
+    ) -> Result<(), MetalshaderError> {
+        let vert = std::fs::read(vert_path)?;
+        let frag = std::fs::read(frag_path)?;
+        self.load_shader_spirv(&vert, &frag, entry_points)
+    }
 
-            if let Some(pipeline) = self.pipeline.take() {
-                self.device.destroy_pipeline(pipeline, None);
+    /// Like `load_shader`, from SPIR-V already in memory (see
+    /// `ShaderCompiler::compile_to_spirv`)
+    pub fn load_shader_spirv(
+        &mut self,
+        vert: &[u8],
+        frag: &[u8],
+        entry_points: &EntryPoints,
+    ) -> Result<(), MetalshaderError> {
+        unsafe {
+            // No device_wait_idle here: the old pipeline stays alive until the
+            // frames that may reference it have finished (see render_frame)
+            let vert_code = if self.config.frag_coord == FragCoord::Normalized && vert == FULLSCREEN_VERT_SPV {
+                words_from_bytes(FULLSCREEN_NORMALIZED_VERT_SPV)
+            } else {
+                words_from_bytes(vert)
+            };
+            let frag_code = words_from_bytes(frag);
+
+            let vert_reflection = reflect(&vert_code).map_err(MetalshaderError::InvalidShader)?;
+            let frag_reflection = reflect(&frag_code).map_err(MetalshaderError::InvalidShader)?;
+            vert_reflection.expect_entry_point(&entry_points.vertex, "Vertex").map_err(MetalshaderError::InvalidShader)?;
+            frag_reflection.expect_entry_point(&entry_points.fragment, "Fragment").map_err(MetalshaderError::InvalidShader)?;
+            // Mismatched varyings otherwise surface as an opaque pipeline error
+            for warning in vert_reflection.check_stage_interface(&frag_reflection).map_err(MetalshaderError::InvalidShader)? {
+                eprintln!("Warning: {}", warning);
             }
-
-            let vert_code = Self::read_shader_file(vert_path)?;
-            let frag_code = Self::read_shader_file(frag_path)?;
+            let reflection = vert_reflection.merge(&frag_reflection);
+            // A binding the layout lacks renders black or crashes at bind time
+            // instead of failing pipeline creation
+            reflection.check_descriptor_layout(UNIFORM_BUFFER_BYTES).map_err(MetalshaderError::InvalidShader)?;
+            let cube_bound = self.channel0.as_ref().is_some_and(|channel0| channel0.cube);
+            reflection.check_channel0(cube_bound).map_err(MetalshaderError::InvalidShader)?;
+            let uses_push_constants = match reflection.push_constant_size {
+                Some(size) if size > PUSH_CONSTANT_BYTES => {
+                    return Err(MetalshaderError::InvalidShader(format!(
+                        "Push-constant block is {} bytes, at most {} are supported", size, PUSH_CONSTANT_BYTES
+                    )));
+                }
+                Some(size) => {
+                    println!("Shader uses push constants ({} bytes)", size);
+                    true
+                }
+                None => false,
+            };
+            if let Some(BindingKind::UniformBuffer { size }) = reflection.binding(0, USER_BINDING).map(|b| b.kind) {
+                if size as usize > self.user_uniform_bytes {
+                    eprintln!(
+                        "Warning: uniform block at binding {} is {} bytes, the sidecar fills {}; the rest reads as zero",
+                        USER_BINDING, size, self.user_uniform_bytes
+                    );
+                }
+            }
+            // Shaders without iChannel0 get a layout without the sampler binding
+            let layout_key = LayoutKey {
+                sampler: reflection.binding(0, 1).is_some(),
+                user_uniforms: reflection.binding(0, USER_BINDING).is_some(),
+                push_constants: uses_push_constants,
+            };
+            let layout = self.layouts.pipeline_layout(layout_key);
 
             let vert_module = Self::create_shader_module(&self.device, &vert_code)?;
             let frag_module = Self::create_shader_module(&self.device, &frag_code)?;
 
-            let entry_name = std::ffi::CString::new("main").unwrap();
+            let vert_entry = std::ffi::CString::new(entry_points.vertex.as_str()).map_err(|e| MetalshaderError::InvalidShader(e.to_string()))?;
+            let frag_entry = std::ffi::CString::new(entry_points.fragment.as_str()).map_err(|e| MetalshaderError::InvalidShader(e.to_string()))?;
 
             let vert_stage = vk::PipelineShaderStageCreateInfo::default()
                 .stage(vk::ShaderStageFlags::VERTEX)
                 .module(vert_module)
-                .name(&entry_name);
+                .name(&vert_entry);
 
             let frag_stage = vk::PipelineShaderStageCreateInfo::default()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
                 .module(frag_module)
-                .name(&entry_name);
+                .name(&frag_entry);
 
             let stages = [vert_stage, frag_stage];
 
@@ -544,9 +681,8 @@ impl SwapchainRenderer {
             let multisampling = vk::PipelineMultisampleStateCreateInfo::default()
                 .rasterization_samples(vk::SampleCountFlags::TYPE_1);
 
-            let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
-                .color_write_mask(vk::ColorComponentFlags::RGBA)
-                .blend_enable(false);
+            let color_blend_attachment =
+                Self::color_blend_attachment(self.composite_alpha, self.config.blend);
 
             let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
                 .attachments(std::slice::from_ref(&color_blend_attachment));
@@ -560,8 +696,8 @@ impl SwapchainRenderer {
                 .multisample_state(&multisampling)
                 .color_blend_state(&color_blending)
                 .dynamic_state(&dynamic_state)
-                .layout(self.pipeline_layout)
-                .render_pass(self.render_pass)
+                .layout(layout)
+                .render_pass(self.shader_render_pass())
                 .subpass(0);
 
             let pipelines = self.device.create_graphics_pipelines(
@@ -570,7 +706,10 @@ impl SwapchainRenderer {
                 None,
             ).map_err(|(_, e)| e)?;
 
-            self.pipeline = Some(pipelines[0]);
+            if let Some(old) = self.pipeline.replace(pipelines[0]) {
+                self.retired_pipelines.push((old, MAX_FRAMES_IN_FLIGHT));
+            }
+            self.layout_key = layout_key;
 
             self.device.destroy_shader_module(vert_module, None);
             self.device.destroy_shader_module(frag_module, None);
@@ -579,7 +718,114 @@ impl SwapchainRenderer {
         }
     }
 
-    pub fn recreate_swapchain(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Show another frame of an animated channel: overwrites the bound
+    /// texture in place when `texture` has its size and format, otherwise
+    /// binds it like `set_channel0`
+    pub fn update_channel0(&mut self, texture: &TextureData) -> Result<(), MetalshaderError> {
+        match &self.channel0 {
+            Some(channel0) if channel0.can_update(texture) => unsafe {
+                // Only the frames still in flight sample the image being
+                // overwritten; unlike device_wait_idle this leaves the rest
+                // of the queue running while an animation plays
+                self.device.wait_for_fences(&self.in_flight_fences, true, u64::MAX)?;
+                channel0.update(
+                    &self.instance,
+                    self.physical_device,
+                    &self.device,
+                    self.queue,
+                    self.queue_family_index,
+                    texture,
+                )
+            },
+            _ => self.set_channel0(texture),
+        }
+    }
+
+    /// Draw `labels` over the following frames
+    pub fn set_overlay(&mut self, labels: Vec<Label>) {
+        self.overlay = labels;
+    }
+
+    /// Fill the uniform block at `USER_BINDING` (`ShaderInfo::uniforms`),
+    /// zeroing the rest; call before `load_shader`
+    pub fn set_uniforms(&mut self, bytes: &[u8]) -> Result<(), MetalshaderError> {
+        if bytes.len() > USER_UNIFORMS_BYTES {
+            return Err(MetalshaderError::InvalidShader(format!(
+                "Uniforms are {} bytes, at most {} are supported", bytes.len(), USER_UNIFORMS_BYTES
+            )));
+        }
+        // In-flight frames read the block being overwritten
+        unsafe { self.device.device_wait_idle()? };
+        let block = &mut self.uniforms.as_mut_slice()[USER_UNIFORMS_OFFSET..];
+        block[..bytes.len()].copy_from_slice(bytes);
+        block[bytes.len()..].fill(0);
+        self.user_uniform_bytes = bytes.len();
+        Ok(())
+    }
+
+    /// Mip levels of the bound iChannel0 texture (1 for the checkerboard)
+    pub fn channel0_mip_levels(&self) -> u32 {
+        self.channel0.as_ref().map_or(1, |channel0| channel0.mip_levels)
+    }
+
+    /// Bind `texture` as iChannel0 in place of the built-in checkerboard
+    pub fn set_channel0(&mut self, texture: &TextureData) -> Result<(), MetalshaderError> {
+        unsafe {
+            // In-flight frames read the descriptor set being rewritten
+            self.device.device_wait_idle()?;
+            let channel0 = ChannelTexture::upload(
+                &self.instance,
+                self.physical_device,
+                &self.device,
+                self.queue,
+                self.queue_family_index,
+                texture,
+            )?;
+            self.layouts.set_texture(&self.device, self.sampler, channel0.view);
+            if let Some(old) = self.channel0.replace(channel0) {
+                old.destroy(&self.device);
+            }
+            Ok(())
+        }
+    }
+
+    /// B8G8R8A8_UNORM in sRGB, or with `wide_gamut` the first of Display P3
+    /// (8-bit, sRGB transfer) and extended sRGB (half float, values outside
+    /// [0, 1] allowed) the surface offers. `report` prints the outcome
+    fn choose_surface_format(
+        formats: &[vk::SurfaceFormatKHR],
+        wide_gamut: bool,
+        report: bool,
+    ) -> vk::SurfaceFormatKHR {
+        const WIDE: [(vk::Format, vk::ColorSpaceKHR); 3] = [
+            (vk::Format::B8G8R8A8_UNORM, vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT),
+            (vk::Format::R8G8B8A8_UNORM, vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT),
+            (vk::Format::R16G16B16A16_SFLOAT, vk::ColorSpaceKHR::EXTENDED_SRGB_NONLINEAR_EXT),
+        ];
+        let offered = |(format, color_space): (vk::Format, vk::ColorSpaceKHR)| {
+            formats.iter().find(|f| f.format == format && f.color_space == color_space).copied()
+        };
+        if wide_gamut {
+            if let Some(wide) = WIDE.into_iter().find_map(offered) {
+                if report {
+                    println!("Wide gamut: {:?} in {:?}", wide.format, wide.color_space);
+                }
+                return wide;
+            }
+            if report {
+                eprintln!("Warning: surface offers no Display P3 or extended sRGB format, using sRGB");
+            }
+        }
+        offered((vk::Format::B8G8R8A8_UNORM, vk::ColorSpaceKHR::SRGB_NONLINEAR)).unwrap_or(formats[0])
+    }
+
+    pub fn recreate_swapchain(&mut self) -> Result<(), MetalshaderError> {
+        // A minimized window has no extent; keep the old swapchain until the
+        // window is restored and resized again
+        let size = self.window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(());
+        }
         unsafe {
             self.device.device_wait_idle()?;
 
@@ -593,10 +839,14 @@ impl SwapchainRenderer {
                 self.device.destroy_image_view(view, None);
             }
 
+            if let Some(target) = self.offscreen_target.take() {
+                Self::destroy_offscreen_target(&self.device, &target);
+            }
+
             let old_swapchain = self.swapchain;
 
             // Create new swapchain
-            let (swapchain, swapchain_images, swapchain_extent, swapchain_format) =
+            let (swapchain, swapchain_images, swapchain_extent, swapchain_format, composite_alpha) =
                 Self::create_swapchain(
                     &self.surface_loader,
                     &self.swapchain_loader,
@@ -604,6 +854,7 @@ impl SwapchainRenderer {
                     self.surface,
                     &self.window,
                     old_swapchain,
+                    &self.config,
                 )?;
 
             // Destroy old swapchain
@@ -614,6 +865,7 @@ impl SwapchainRenderer {
             self.swapchain_images = swapchain_images.clone();
             self.swapchain_extent = swapchain_extent;
             self.swapchain_format = swapchain_format;
+            self.composite_alpha = composite_alpha;
 
             // Create new image views
             self.swapchain_image_views = swapchain_images
@@ -649,6 +901,15 @@ impl SwapchainRenderer {
                 })
                 .collect::<Result<Vec<_>, _>>()?;
 
+            self.offscreen_target = Self::create_offscreen_target(
+                &self.device,
+                &self.mem_properties,
+                self.offscreen_render_pass,
+                Self::offscreen_format(&self.config, swapchain_format),
+                swapchain_extent,
+                &self.config,
+            )?;
+
             // Recreate pipeline with new viewport if a shader is loaded
             if self.pipeline.is_some() {
                 // Pipeline recreation will be triggered by setting pipeline to None
@@ -659,10 +920,11 @@ impl SwapchainRenderer {
         }
     }
 
-    pub fn render_frame<T: Copy>(&mut self, ubo_data: &T) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn render_frame<T: Copy>(&mut self, ubo_data: &T) -> Result<(), MetalshaderError> {
         unsafe {
             let fence = self.in_flight_fences[self.current_frame];
             self.device.wait_for_fences(&[fence], true, u64::MAX)?;
+            self.release_retired_pipelines();
 
             let (image_index, _suboptimal) = match self.swapchain_loader.acquire_next_image(
                 self.swapchain,
@@ -681,11 +943,7 @@ impl SwapchainRenderer {
             self.device.reset_fences(&[fence])?;
 
             // Update uniform buffer
-            std::ptr::copy_nonoverlapping(
-                ubo_data as *const T as *const u8,
-                self.uniform_ptr,
-                std::mem::size_of::<T>(),
-            );
+            self.uniforms.write(ubo_data);
 
             // Record command buffer
             let cmd_buf = self.command_buffers[self.current_frame];
@@ -695,18 +953,27 @@ impl SwapchainRenderer {
             let begin_info = vk::CommandBufferBeginInfo::default();
             self.device.begin_command_buffer(cmd_buf, &begin_info)?;
 
+            // Clear to transparent black when the window composites our alpha
+            let clear_alpha = if self.composite_alpha == CompositeAlpha::Opaque { 1.0 } else { 0.0 };
             let clear_color = vk::ClearValue {
                 color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 1.0],
+                    float32: [0.0, 0.0, 0.0, clear_alpha],
                 },
             };
 
+            // Scaled rendering goes to the small target, blitted over below
+            let (render_pass, framebuffer) = match &self.offscreen_target {
+                Some(target) => (self.offscreen_render_pass, target.framebuffer),
+                None => (self.render_pass, self.framebuffers[image_index as usize]),
+            };
+            let render_extent = self.render_extent();
+
             let render_pass_info = vk::RenderPassBeginInfo::default()
-                .render_pass(self.render_pass)
-                .framebuffer(self.framebuffers[image_index as usize])
+                .render_pass(render_pass)
+                .framebuffer(framebuffer)
                 .render_area(vk::Rect2D {
                     offset: vk::Offset2D { x: 0, y: 0 },
-                    extent: self.swapchain_extent,
+                    extent: render_extent,
                 })
                 .clear_values(std::slice::from_ref(&clear_color));
 
@@ -723,41 +990,73 @@ impl SwapchainRenderer {
                     pipeline,
                 );
 
-                // Set dynamic viewport and scissor to match current swapchain extent
+                // Set dynamic viewport and scissor to match the render extent;
+                // --flip-y starts the viewport at the bottom edge with a
+                // negative height (core since Vulkan 1.1)
+                let (viewport_y, viewport_height) = if self.config.flip_y {
+                    (render_extent.height as f32, -(render_extent.height as f32))
+                } else {
+                    (0.0, render_extent.height as f32)
+                };
                 let viewport = vk::Viewport {
                     x: 0.0,
-                    y: 0.0,
-                    width: self.swapchain_extent.width as f32,
-                    height: self.swapchain_extent.height as f32,
+                    y: viewport_y,
+                    width: render_extent.width as f32,
+                    height: viewport_height,
                     min_depth: 0.0,
                     max_depth: 1.0,
                 };
                 let scissor = vk::Rect2D {
                     offset: vk::Offset2D { x: 0, y: 0 },
-                    extent: self.swapchain_extent,
+                    extent: render_extent,
                 };
                 self.device.cmd_set_viewport(cmd_buf, 0, &[viewport]);
                 self.device.cmd_set_scissor(cmd_buf, 0, &[scissor]);
 
+                let layout = self.layouts.pipeline_layout(self.layout_key);
+
                 self.device.cmd_bind_descriptor_sets(
                     cmd_buf,
                     vk::PipelineBindPoint::GRAPHICS,
-                    self.pipeline_layout,
+                    layout,
                     0,
-                    &[self.descriptor_set],
+                    &[self.layouts.descriptor_set(self.layout_key)],
                     &[],
                 );
 
+                if self.layout_key.push_constants {
+                    // The push block mirrors the start of the UBO layout
+                    let len = std::mem::size_of::<T>().min(PUSH_CONSTANT_BYTES as usize) & !3;
+                    let bytes = std::slice::from_raw_parts(ubo_data as *const T as *const u8, len);
+                    self.device.cmd_push_constants(
+                        cmd_buf,
+                        layout,
+                        vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                        0,
+                        bytes,
+                    );
+                }
+
                 self.device.cmd_draw(cmd_buf, 6, 1, 0, 0);
 
+                for label in &self.overlay {
+                    label.record(&self.device, cmd_buf);
+                }
+
                 self.device.cmd_end_render_pass(cmd_buf);
+
+                if let Some(target) = &self.offscreen_target {
+                    self.record_present_blit(cmd_buf, target, self.swapchain_images[image_index as usize]);
+                }
             }
 
             self.device.end_command_buffer(cmd_buf)?;
 
             // Submit
             let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
-            let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+            // The blit (and letterbox clear) is the first write to the image
+            // when rendering offscreen
+            let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER];
             let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
             let command_buffers = [cmd_buf];
 
@@ -792,29 +1091,310 @@ impl SwapchainRenderer {
         }
     }
 
-    pub fn get_device_name(&self) -> &str {
-        &self.device_name
+    /// Count one more completed frame fence against each retired pipeline and
+    /// destroy those no frame in flight can still be using
+    ///
+    /// Each frame slot's fence has been waited on once after retirement when the
+    /// count reaches zero, so every submission recorded with the old pipeline is done.
+    unsafe fn release_retired_pipelines(&mut self) {
+        let device = &self.device;
+        self.retired_pipelines.retain_mut(|(pipeline, fences_left)| {
+            *fences_left -= 1;
+            if *fences_left == 0 {
+                device.destroy_pipeline(*pipeline, None);
+                false
+            } else {
+                true
+            }
+        });
     }
 
-    fn read_shader_file(path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let mut file = File::open(path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-        Ok(buffer)
+    /// Size the shader renders at: the viewport size times `--render-scale`
+    pub fn render_extent(&self) -> vk::Extent2D {
+        self.offscreen_target.as_ref().map_or(self.swapchain_extent, |target| target.extent)
     }
 
-    fn create_shader_module(
+    /// Part of the window the frame is shown in, in window pixels
+    pub fn viewport(&self) -> Viewport {
+        self.offscreen_target.as_ref().map_or(
+            Viewport::full(self.swapchain_extent.width, self.swapchain_extent.height),
+            |target| target.viewport,
+        )
+    }
+
+    /// Render pass the shader draws in; pipelines must match its format
+    fn shader_render_pass(&self) -> vk::RenderPass {
+        if self.offscreen_render_pass == vk::RenderPass::null() {
+            self.render_pass
+        } else {
+            self.offscreen_render_pass
+        }
+    }
+
+    /// Offscreen target format: the swapchain's, or the float `--format`
+    fn offscreen_format(config: &SwapchainConfig, swapchain_format: vk::Format) -> vk::Format {
+        match config.format {
+            RenderFormat::Rgba8 => swapchain_format,
+            RenderFormat::Rgba16f => vk::Format::R16G16B16A16_SFLOAT,
+            RenderFormat::Rgba32f => vk::Format::R32G32B32A32_SFLOAT,
+        }
+    }
+
+    /// Like the swapchain render pass, but leaves the image ready to blit from.
+    /// The incoming dependency keeps the next frame's clear from overwriting
+    /// the image while the previous frame's blit still reads it.
+    unsafe fn create_offscreen_render_pass(device: &ash::Device, format: vk::Format)
+        -> Result<vk::RenderPass, MetalshaderError>
+    {
+        let attachment = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+
+        let color_ref = vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+        let subpass = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(std::slice::from_ref(&color_ref));
+
+        let dependencies = [
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .src_access_mask(vk::AccessFlags::empty())
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ),
+        ];
+
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(std::slice::from_ref(&attachment))
+            .subpasses(std::slice::from_ref(&subpass))
+            .dependencies(&dependencies);
+
+        Ok(device.create_render_pass(&render_pass_info, None)?)
+    }
+
+    /// The offscreen target for a swapchain of `extent`, or `None` when
+    /// rendering straight into the swapchain (`render_pass` is null)
+    unsafe fn create_offscreen_target(
         device: &ash::Device,
-        code: &[u8],
-    ) -> Result<vk::ShaderModule, Box<dyn std::error::Error>> {
-        unsafe {
-            let code_aligned = std::slice::from_raw_parts(
-                code.as_ptr() as *const u32,
-                code.len() / 4,
+        mem_properties: &vk::PhysicalDeviceMemoryProperties,
+        render_pass: vk::RenderPass,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        config: &SwapchainConfig,
+    ) -> Result<Option<OffscreenTarget>, MetalshaderError> {
+        if render_pass == vk::RenderPass::null() {
+            return Ok(None);
+        }
+        let viewport = Viewport::letterbox(extent.width, extent.height, config.aspect);
+        let scale = if config.render_scale > 0.0 { config.render_scale } else { 1.0 };
+        let (width, height) = crate::options::scaled_size(viewport.width, viewport.height, scale);
+
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let image = device.create_image(&image_info, None)?;
+
+        let mem_req = device.get_image_memory_requirements(image);
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_req.size)
+            .memory_type_index(Self::find_memory_type(
+                mem_properties,
+                mem_req.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?);
+        let memory = device.allocate_memory(&alloc_info, None)?;
+        device.bind_image_memory(image, memory, 0)?;
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(COLOR_RANGE);
+        let view = device.create_image_view(&view_info, None)?;
+
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(std::slice::from_ref(&view))
+            .width(width)
+            .height(height)
+            .layers(1);
+        let framebuffer = device.create_framebuffer(&framebuffer_info, None)?;
+
+        Ok(Some(OffscreenTarget {
+            image,
+            memory,
+            view,
+            framebuffer,
+            extent: vk::Extent2D { width, height },
+            viewport,
+        }))
+    }
+
+    unsafe fn destroy_offscreen_target(device: &ash::Device, target: &OffscreenTarget) {
+        device.destroy_framebuffer(target.framebuffer, None);
+        device.destroy_image_view(target.view, None);
+        device.destroy_image(target.image, None);
+        device.free_memory(target.memory, None);
+    }
+
+    /// Blit the offscreen target onto its viewport of the swapchain image,
+    /// clear the letterbox bars, and leave the image ready to present
+    unsafe fn record_present_blit(&self, cmd_buf: vk::CommandBuffer, target: &OffscreenTarget, swapchain_image: vk::Image) {
+        let to_transfer = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(swapchain_image)
+            .subresource_range(COLOR_RANGE);
+        self.device.cmd_pipeline_barrier(
+            cmd_buf,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_transfer],
+        );
+
+        // Letterbox bars: black, transparent unless the window is opaque
+        if !target.viewport.is_full(self.swapchain_extent.width, self.swapchain_extent.height) {
+            let clear_alpha = if self.composite_alpha == CompositeAlpha::Opaque { 1.0 } else { 0.0 };
+            let black = vk::ClearColorValue { float32: [0.0, 0.0, 0.0, clear_alpha] };
+            self.device.cmd_clear_color_image(
+                cmd_buf,
+                swapchain_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &black,
+                &[COLOR_RANGE],
+            );
+            // Order the clear before the blit over the viewport
+            let clear_done = vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+            self.device.cmd_pipeline_barrier(
+                cmd_buf,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[clear_done],
+                &[],
+                &[],
             );
+        }
+
+        let layers = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let viewport = target.viewport;
+        let blit = vk::ImageBlit::default()
+            .src_subresource(layers)
+            .src_offsets([
+                vk::Offset3D::default(),
+                vk::Offset3D { x: target.extent.width as i32, y: target.extent.height as i32, z: 1 },
+            ])
+            .dst_subresource(layers)
+            .dst_offsets([
+                vk::Offset3D { x: viewport.x as i32, y: viewport.y as i32, z: 0 },
+                vk::Offset3D {
+                    x: (viewport.x + viewport.width) as i32,
+                    y: (viewport.y + viewport.height) as i32,
+                    z: 1,
+                },
+            ]);
+        self.device.cmd_blit_image(
+            cmd_buf,
+            target.image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            swapchain_image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[blit],
+            vk::Filter::LINEAR,
+        );
+
+        let to_present = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::empty())
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(swapchain_image)
+            .subresource_range(COLOR_RANGE);
+        self.device.cmd_pipeline_barrier(
+            cmd_buf,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_present],
+        );
+    }
+
+    /// Blend state for the requested blending and how the compositor interprets
+    /// the swapchain alpha
+    fn color_blend_attachment(
+        composite_alpha: CompositeAlpha,
+        blend: bool,
+    ) -> vk::PipelineColorBlendAttachmentState {
+        let attachment = vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(vk::ColorComponentFlags::RGBA);
+
+        // Premultiplied compositing needs blending over the transparent clear
+        // so the stored color is premultiplied; otherwise straight color is
+        // written as-is and the compositor (if any) applies alpha
+        if !blend && composite_alpha != CompositeAlpha::PreMultiplied {
+            attachment.blend_enable(false)
+        } else {
+            attachment
+                .blend_enable(true)
+                .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .color_blend_op(vk::BlendOp::ADD)
+                .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .alpha_blend_op(vk::BlendOp::ADD)
+        }
+    }
 
-            let create_info = vk::ShaderModuleCreateInfo::default().code(code_aligned);
+    pub fn get_device_name(&self) -> &str {
+        &self.device_name
+    }
 
+    fn create_shader_module(
+        device: &ash::Device,
+        code: &[u32],
+    ) -> Result<vk::ShaderModule, MetalshaderError> {
+        unsafe {
+            let create_info = vk::ShaderModuleCreateInfo::default().code(code);
             Ok(device.create_shader_module(&create_info, None)?)
         }
     }
@@ -823,7 +1403,7 @@ impl SwapchainRenderer {
         mem_properties: &vk::PhysicalDeviceMemoryProperties,
         type_filter: u32,
         properties: vk::MemoryPropertyFlags,
-    ) -> Result<u32, Box<dyn std::error::Error>> {
+    ) -> Result<u32, MetalshaderError> {
         for i in 0..mem_properties.memory_type_count {
             if (type_filter & (1 << i)) != 0
                 && mem_properties.memory_types[i as usize]
@@ -833,13 +1413,13 @@ impl SwapchainRenderer {
                 return Ok(i);
             }
         }
-        Err("Failed to find suitable memory type".into())
+        Err(MetalshaderError::NoMemoryType)
     }
 
     fn create_texture(
         device: &ash::Device,
         mem_properties: &vk::PhysicalDeviceMemoryProperties,
-    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), Box<dyn std::error::Error>> {
+    ) -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), MetalshaderError> {
         unsafe {
             let width = 256u32;
             let height = 256u32;
@@ -876,8 +1456,8 @@ impl SwapchainRenderer {
             device.bind_image_memory(image, memory, 0)?;
 
             // Fill texture with checkerboard pattern
-            let ptr = device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?;
-            let pixels = std::slice::from_raw_parts_mut(ptr as *mut u8, (width * height * 4) as usize);
+            let mut mapped = MappedMemory::map(device, memory, (width * height * 4) as usize)?;
+            let pixels = mapped.as_mut_slice();
 
             for y in 0..height {
                 for x in 0..width {
@@ -891,7 +1471,7 @@ impl SwapchainRenderer {
                 }
             }
 
-            device.unmap_memory(memory);
+            mapped.unmap(device);
 
             let view_info = vk::ImageViewCreateInfo::default()
                 .image(image)
@@ -913,6 +1493,8 @@ impl SwapchainRenderer {
 }
 
 impl Drop for SwapchainRenderer {
+    // Reverse creation order: everything that refers to an object goes
+    // before it, images before their memory, the device before the surface
     fn drop(&mut self) {
         unsafe {
             let _ = self.device.device_wait_idle();
@@ -926,37 +1508,46 @@ impl Drop for SwapchainRenderer {
             for &fence in &self.in_flight_fences {
                 self.device.destroy_fence(fence, None);
             }
-
+            // Frees the command buffers with it
             self.device.destroy_command_pool(self.command_pool, None);
 
-            for &framebuffer in &self.framebuffers {
-                self.device.destroy_framebuffer(framebuffer, None);
-            }
-
-            for &view in &self.swapchain_image_views {
-                self.device.destroy_image_view(view, None);
+            if let Some(pipeline) = self.pipeline.take() {
+                self.device.destroy_pipeline(pipeline, None);
             }
-
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None);
-
-            if let Some(pipeline) = self.pipeline {
+            for (pipeline, _) in self.retired_pipelines.drain(..) {
                 self.device.destroy_pipeline(pipeline, None);
             }
+            self.layouts.destroy(&self.device);
 
-            self.device.destroy_descriptor_pool(self.descriptor_pool, None);
+            if let Some(channel0) = self.channel0.take() {
+                channel0.destroy(&self.device);
+            }
             self.device.destroy_sampler(self.sampler, None);
             self.device.destroy_image_view(self.texture_view, None);
             self.device.destroy_image(self.texture_image, None);
             self.device.free_memory(self.texture_memory, None);
-            self.device.unmap_memory(self.uniform_memory);
+
+            self.uniforms.unmap(&self.device);
             self.device.destroy_buffer(self.uniform_buffer, None);
             self.device.free_memory(self.uniform_memory, None);
-            self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device
-                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+
+            for &framebuffer in &self.framebuffers {
+                self.device.destroy_framebuffer(framebuffer, None);
+            }
+            if let Some(target) = self.offscreen_target.take() {
+                Self::destroy_offscreen_target(&self.device, &target);
+            }
+            if self.offscreen_render_pass != vk::RenderPass::null() {
+                self.device.destroy_render_pass(self.offscreen_render_pass, None);
+            }
             self.device.destroy_render_pass(self.render_pass, None);
+
+            for &view in &self.swapchain_image_views {
+                self.device.destroy_image_view(view, None);
+            }
+            // The swapchain owns its images
+            self.swapchain_loader.destroy_swapchain(self.swapchain, None);
+
             self.device.destroy_device(None);
             self.surface_loader.destroy_surface(self.surface, None);
             self.instance.destroy_instance(None);
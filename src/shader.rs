@@ -1,13 +1,152 @@
 // Shader discovery and management
 
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
+use serde::{Deserialize, Serialize};
+
+use crate::shader_compiler::ShaderCompiler;
+use crate::ubo::{pack_user_uniforms, UserUniform};
+
+/// First word of every SPIR-V module
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// Shader sources the scan lists once they have been compiled
+pub const SOURCE_EXTENSIONS: [&str; 2] = ["frag", "wgsl"];
+
+/// Name the built-in test pattern is listed under
+pub const BUILTIN_NAME: &str = "builtin";
+
+// SPIR-V of src/builtin/test_pattern.frag; regenerate with
+// `glslangValidator -V test_pattern.frag -o test_pattern.frag.spv`
+pub(crate) const BUILTIN_FRAG_SPV: &[u8] = include_bytes!("builtin/test_pattern.frag.spv");
+
 #[derive(Clone, Debug)]
 pub struct ShaderInfo {
     pub name: String,
     pub vert_path: PathBuf,
     pub frag_path: PathBuf,
+    /// Size the shader was written for, from its sidecar JSON
+    pub resolution: Option<(u32, u32)>,
+}
+
+/// Manifest `--reindex` writes into a shader directory
+pub const INDEX_FILE: &str = "shaders.index";
+
+/// One compiled shader in a directory, as `scan_shaders` lists it
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct IndexEntry {
+    name: String,
+    /// Has its own `<name>.vert.spv`; otherwise the fullscreen one is used
+    vertex: bool,
+    resolution: Option<(u32, u32)>,
+}
+
+/// `shaders.index`: a directory's shaders, valid while the directory's
+/// mtime (which changes when files are added, removed or renamed) matches
+#[derive(Debug, Serialize, Deserialize)]
+struct Index {
+    /// Seconds and nanoseconds since the Unix epoch
+    mtime: Option<(u64, u32)>,
+    shaders: Vec<IndexEntry>,
+}
+
+impl Index {
+    /// The index in `dir`, if there is one and the directory hasn't changed
+    /// since it was written
+    fn read(dir: &Path) -> Option<Self> {
+        let json = fs::read(dir.join(INDEX_FILE)).ok()?;
+        let index: Self = serde_json::from_slice(&json).ok()?;
+        (index.mtime.is_some() && index.mtime == dir_mtime(dir)).then_some(index)
+    }
+}
+
+fn dir_mtime(dir: &Path) -> Option<(u64, u32)> {
+    let since_epoch = fs::metadata(dir).ok()?.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some((since_epoch.as_secs(), since_epoch.subsec_nanos()))
+}
+
+/// The compiled shaders in `dir`: every `.frag`/`.wgsl` source with a
+/// `<name>.frag.spv` next to it. `<name>.frag` and `<name>.wgsl` side by side
+/// share one compiled module
+fn walk(dir: &Path) -> Vec<IndexEntry> {
+    let mut shaders: Vec<IndexEntry> = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return shaders;
+    };
+    for entry in entries.flatten() {
+        if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+            continue;
+        }
+
+        let path = entry.path();
+        if !path.extension().is_some_and(|ext| SOURCE_EXTENSIONS.iter().any(|e| ext == *e)) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if !dir.join(format!("{}.frag.spv", name)).exists() || shaders.iter().any(|s| s.name == name) {
+            continue;
+        }
+        shaders.push(IndexEntry {
+            name: name.to_string(),
+            vertex: dir.join(format!("{}.vert.spv", name)).exists(),
+            resolution: Sidecar::read(&dir.join(format!("{}.json", name))).resolution,
+        });
+    }
+    shaders
+}
+
+/// Optional `<name>.json` next to a shader:
+///
+/// ```json
+/// { "resolution": [800, 600], "uniforms": [{ "type": "vec4", "value": [1, 0, 0, 1] }] }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Sidecar {
+    resolution: Option<(u32, u32)>,
+    /// Members of the uniform block at `ubo::USER_BINDING`
+    uniforms: Vec<UserUniform>,
+}
+
+impl Sidecar {
+    /// The sidecar at `path`; a missing file is empty, a broken one is
+    /// reported and ignored so the shader still loads
+    fn read(path: &Path) -> Self {
+        let Ok(json) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str::<Self>(&json)
+            .map(|sidecar| Self { resolution: sidecar.resolution.filter(|&(w, h)| w > 0 && h > 0), ..sidecar })
+            .unwrap_or_else(|e| {
+                eprintln!("Warning: ignoring {}: {}", path.display(), e);
+                Self::default()
+            })
+    }
+}
+
+impl ShaderInfo {
+    /// Contents of the uniform block at `ubo::USER_BINDING`, from the
+    /// sidecar's `"uniforms"`; empty if it has none or they don't pack
+    pub fn uniforms(&self) -> Vec<u8> {
+        let path = self.frag_path.with_file_name(format!("{}.json", self.name));
+        pack_user_uniforms(&Sidecar::read(&path).uniforms).unwrap_or_else(|e| {
+            eprintln!("Warning: ignoring uniforms in {}: {}", path.display(), e);
+            Vec::new()
+        })
+    }
+
+    /// GLSL or WGSL source next to the fragment SPIR-V (`<name>.frag` or
+    /// `<name>.wgsl`), if there is one
+    pub fn source_path(&self) -> Option<PathBuf> {
+        SOURCE_EXTENSIONS
+            .iter()
+            .map(|ext| self.frag_path.with_file_name(format!("{}.{}", self.name, ext)))
+            .find(|source| source.exists())
+    }
 }
 
 pub struct ShaderManager {
@@ -21,48 +160,80 @@ impl ShaderManager {
         }
     }
 
+    /// A manager holding only `shader`, for SPIR-V loaded without scanning
+    pub fn single(shader: ShaderInfo) -> Self {
+        Self {
+            shaders: vec![shader],
+        }
+    }
+
+    /// List the compiled shaders in `dirs`, from each directory's
+    /// `shaders.index` when it is current (see `write_index`), else by
+    /// walking the directory
     pub fn scan_shaders(&mut self, dirs: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
         self.shaders.clear();
+        let mut fullscreen_vert: Option<PathBuf> = None;
 
         for dir in dirs {
-            if let Ok(entries) = fs::read_dir(dir) {
-                for entry in entries.flatten() {
-                    if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
-                        continue;
+            let dir = Path::new(dir);
+            let entries = match Index::read(dir) {
+                Some(index) => index.shaders,
+                None => walk(dir),
+            };
+            for entry in entries {
+                let frag_path = dir.join(format!("{}.frag.spv", entry.name));
+                let vert_path = if entry.vertex {
+                    dir.join(format!("{}.vert.spv", entry.name))
+                } else {
+                    match &fullscreen_vert {
+                        Some(path) => path.clone(),
+                        None => fullscreen_vert.insert(fullscreen_vertex()?).clone(),
                     }
+                };
+                self.shaders.push(ShaderInfo { name: entry.name, vert_path, frag_path, resolution: entry.resolution });
+            }
+        }
 
-                    let path = entry.path();
-                    if let Some(ext) = path.extension() {
-                        if ext != "frag" {
-                            continue;
-                        }
-                    } else {
-                        continue;
-                    }
+        Ok(())
+    }
 
-                    // Extract base name
-                    let file_name = path.file_stem().and_then(|s| s.to_str());
-                    if file_name.is_none() {
-                        continue;
-                    }
-                    let base_name = file_name.unwrap();
-
-                    // Build shader paths
-                    let vert_path = Path::new(dir).join(format!("{}.vert.spv", base_name));
-                    let frag_path = Path::new(dir).join(format!("{}.frag.spv", base_name));
-
-                    // Check if both compiled shaders exist
-                    if vert_path.exists() && frag_path.exists() {
-                        self.shaders.push(ShaderInfo {
-                            name: base_name.to_string(),
-                            vert_path,
-                            frag_path,
-                        });
-                    }
-                }
+    /// Walk `dirs` and write a `shaders.index` into each, so later scans
+    /// skip the walk until the directory changes. Returns the number of
+    /// shaders indexed
+    pub fn write_index(dirs: &[&str]) -> std::io::Result<usize> {
+        let mut count = 0;
+        for dir in dirs {
+            let dir = Path::new(dir);
+            if !dir.is_dir() {
+                continue;
             }
+            let shaders = walk(dir);
+            count += shaders.len();
+            let path = dir.join(INDEX_FILE);
+            // Creating the index changes the directory's mtime, so record
+            // the mtime after it exists; rewriting it in place doesn't
+            let mut index = Index { mtime: None, shaders };
+            fs::write(&path, serde_json::to_vec(&index)?)?;
+            index.mtime = dir_mtime(dir);
+            fs::write(&path, serde_json::to_vec_pretty(&index)?)?;
+            println!("Indexed {} shader(s) in {}", index.shaders.len(), path.display());
         }
+        Ok(count)
+    }
 
+    /// Add the test pattern compiled into the binary, so there is something
+    /// to show when no shaders are found. The renderers load SPIR-V from
+    /// files, so the modules are written to the temp directory first
+    pub fn add_builtin(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let vert_path = fullscreen_vertex()?;
+        let frag_path = work_dir()?.join(format!("{}.frag.spv", BUILTIN_NAME));
+        fs::write(&frag_path, BUILTIN_FRAG_SPV)?;
+        self.shaders.push(ShaderInfo {
+            name: BUILTIN_NAME.to_string(),
+            vert_path,
+            frag_path,
+            resolution: None,
+        });
         Ok(())
     }
 
@@ -83,6 +254,18 @@ impl ShaderManager {
         self.shaders.iter().position(|s| s.name == name)
     }
 
+    /// The listed name nearest to `name` by edit distance, if it is close
+    /// enough to be a typo of it, for "did you mean" hints
+    pub fn closest_name(&self, name: &str) -> Option<&str> {
+        let limit = (name.chars().count() / 3).max(2);
+        self.shaders
+            .iter()
+            .map(|s| (edit_distance(name, &s.name), s.name.as_str()))
+            .filter(|&(distance, _)| distance <= limit)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, name)| name)
+    }
+
     pub fn next(&self, current: usize) -> usize {
         (current + 1) % self.shaders.len()
     }
@@ -102,3 +285,210 @@ impl ShaderManager {
         }
     }
 }
+
+/// Levenshtein distance between `a` and `b`, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Most shaders kept in `ShaderHistory`
+const HISTORY_LIMIT: usize = 100;
+
+/// Shaders viewed before the current one, most recent last, for Backspace.
+/// Unlike `ShaderManager::prev` this follows the order they were shown in,
+/// whatever switched them
+#[derive(Default)]
+pub struct ShaderHistory {
+    visited: Vec<usize>,
+    current: Option<usize>,
+}
+
+impl ShaderHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that shader `index` is now on screen; reloading the same one
+    /// doesn't count
+    pub fn visit(&mut self, index: usize) {
+        match self.current.replace(index) {
+            Some(previous) if previous != index => {
+                if self.visited.len() == HISTORY_LIMIT {
+                    self.visited.remove(0);
+                }
+                self.visited.push(previous);
+            }
+            _ => {}
+        }
+    }
+
+    /// The shader viewed before the current one, which becomes current
+    /// without being recorded again
+    pub fn back(&mut self) -> Option<usize> {
+        let previous = self.visited.pop()?;
+        self.current = Some(previous);
+        Some(previous)
+    }
+}
+
+/// Shader for `--frag-spv` / `--vert-spv`, bypassing the directory scan
+///
+/// `frag` may be `-` to read the fragment module from stdin. Without `vert`
+/// the generated fullscreen vertex shader is used.
+pub fn direct_shader(
+    frag: &str,
+    vert: Option<&str>,
+    compiler: &ShaderCompiler,
+) -> Result<ShaderInfo, Box<dyn std::error::Error>> {
+    let work_dir = work_dir()?;
+
+    let (name, frag_path) = if frag == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        check_spirv(&bytes, "stdin")?;
+        let path = work_dir.join("stdin.frag.spv");
+        fs::write(&path, &bytes)?;
+        ("stdin".to_string(), path)
+    } else {
+        let path = PathBuf::from(frag);
+        check_spirv(&fs::read(&path)?, frag)?;
+        let name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .map(|s| s.trim_end_matches(".spv").trim_end_matches(".frag").to_string())
+            .unwrap_or_else(|| frag.to_string());
+        (name, path)
+    };
+
+    let vert_path = match vert {
+        Some(vert) => PathBuf::from(vert),
+        None => compiler.fullscreen_vertex_spirv(&work_dir)?,
+    };
+
+    let sidecar = Sidecar::read(&frag_path.with_file_name(format!("{}.json", name)));
+    Ok(ShaderInfo { name, vert_path, frag_path, resolution: sidecar.resolution })
+}
+
+/// Name a shader is listed under, from any of its files: `plasma.frag`,
+/// `plasma.wgsl` and `plasma.frag.spv` are all `plasma`
+pub fn name_from_path(path: &str) -> String {
+    let stem = Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or(path);
+    if stem.ends_with(".vert") || stem.ends_with(".frag") || stem.ends_with(".glsl") {
+        Path::new(stem).file_stem().and_then(|s| s.to_str()).unwrap_or(stem).to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+/// Directory for SPIR-V that doesn't live next to a shader
+fn work_dir() -> std::io::Result<PathBuf> {
+    crate::paths::cache_dir()
+}
+
+/// Write the embedded fullscreen vertex module to the work directory
+fn fullscreen_vertex() -> std::io::Result<PathBuf> {
+    let path = work_dir()?.join("fullscreen.vert.spv");
+    fs::write(&path, crate::shader_compiler::FULLSCREEN_VERT_SPV)?;
+    Ok(path)
+}
+
+/// Catch GLSL source or truncated output before it reaches the renderer
+fn check_spirv(bytes: &[u8], source: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let magic = bytes.get(..4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    if magic != Some(SPIRV_MAGIC) || bytes.len() % 4 != 0 {
+        return Err(format!("{} is not a SPIR-V module ({} bytes)", source, bytes.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidecar_resolution() {
+        let dir = std::env::temp_dir().join(format!("metalshader-sidecar-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let read = |json: &str| {
+            let path = dir.join("shader.json");
+            fs::write(&path, json).unwrap();
+            Sidecar::read(&path).resolution
+        };
+        assert_eq!(read(r#"{"resolution": [800, 600]}"#), Some((800, 600)));
+        assert_eq!(read(r#"{"author": "iq"}"#), None);
+        assert_eq!(read(r#"{"resolution": [0, 600]}"#), None);
+        assert_eq!(read("not json"), None);
+        assert_eq!(Sidecar::read(&dir.join("missing.json")).resolution, None);
+        fs::write(dir.join("shader.json"), r#"{"uniforms": [{"type": "float", "value": [2]}]}"#).unwrap();
+        assert_eq!(Sidecar::read(&dir.join("shader.json")).uniforms.len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_index_until_directory_changes() {
+        let dir = std::env::temp_dir().join(format!("metalshader-index-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in ["a.frag", "a.frag.spv", "a.wgsl", "b.frag"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(dir.join("a.json"), r#"{"resolution": [640, 480]}"#).unwrap();
+        assert!(Index::read(&dir).is_none());
+
+        assert_eq!(ShaderManager::write_index(&[dir.to_str().unwrap()]).unwrap(), 1);
+        let index = Index::read(&dir).unwrap();
+        assert_eq!(index.shaders, walk(&dir));
+        assert_eq!(index.shaders, [IndexEntry { name: "a".into(), vertex: false, resolution: Some((640, 480)) }]);
+
+        // Compiling b adds its .spv, which makes the index stale
+        fs::write(dir.join("b.frag.spv"), "").unwrap();
+        assert!(Index::read(&dir).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_history_goes_back_in_viewing_order() {
+        let mut history = ShaderHistory::new();
+        for index in [0, 5, 5, 2, 7] {
+            history.visit(index);
+        }
+        assert_eq!(history.back(), Some(2));
+        history.visit(2);
+        assert_eq!(history.back(), Some(5));
+        assert_eq!(history.back(), Some(0));
+        assert_eq!(history.back(), None);
+    }
+
+    #[test]
+    fn test_closest_name() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        let mut manager = ShaderManager::new();
+        for name in ["plasma", "tunnel", "clouds_bookofshaders"] {
+            let path = PathBuf::from(name);
+            manager.shaders.push(ShaderInfo { name: name.into(), vert_path: path.clone(), frag_path: path, resolution: None });
+        }
+        assert_eq!(manager.closest_name("plamsa"), Some("plasma"));
+        assert_eq!(manager.closest_name("tunel"), Some("tunnel"));
+        assert_eq!(manager.closest_name("clouds_bookofshader"), Some("clouds_bookofshaders"));
+        assert_eq!(manager.closest_name("gradient"), None);
+    }
+
+    #[test]
+    fn test_name_from_path() {
+        assert_eq!(name_from_path("/shaders/plasma.frag"), "plasma");
+        assert_eq!(name_from_path("plasma.frag.spv"), "plasma");
+        assert_eq!(name_from_path("tunnel.wgsl"), "tunnel");
+        assert_eq!(name_from_path("clouds"), "clouds");
+    }
+}
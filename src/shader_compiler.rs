@@ -2,34 +2,60 @@
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::MetalshaderError;
+use crate::shadertoy;
+
+/// SPIR-V of src/builtin/fullscreen.vert, the vertex stage of every shader
+/// without its own .vert; regenerate with
+/// `glslangValidator -V fullscreen.vert -o fullscreen.vert.spv`
+pub const FULLSCREEN_VERT_SPV: &[u8] = include_bytes!("builtin/fullscreen.vert.spv");
+
+/// SPIR-V of src/builtin/fullscreen_normalized.vert, used in place of
+/// `FULLSCREEN_VERT_SPV` for `--frag-coord normalized`; regenerate with
+/// `glslangValidator -V fullscreen_normalized.vert -o fullscreen_normalized.vert.spv`
+pub const FULLSCREEN_NORMALIZED_VERT_SPV: &[u8] = include_bytes!("builtin/fullscreen_normalized.vert.spv");
+
+/// Numbers glslang scratch files, so compiles running at the same time (the
+/// prefetch thread and the main thread) never share one
+static SCRATCH_SERIAL: AtomicUsize = AtomicUsize::new(0);
 
 pub struct ShaderCompiler {
     #[allow(dead_code)]
     shader_dir: PathBuf,
+    /// Treat glslangValidator warnings as compile failures
+    werror: bool,
 }
 
 impl ShaderCompiler {
     pub fn new() -> Self {
         Self {
             shader_dir: PathBuf::from("."),
+            werror: false,
         }
     }
 
+    pub fn with_werror(mut self, werror: bool) -> Self {
+        self.werror = werror;
+        self
+    }
+
     /// Try to compile a shader from source (.frag, .glsl) to SPIR-V (.spv)
     /// Returns the path to the compiled SPIR-V files (base name)
-    pub fn compile_if_needed(&self, input_path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    pub fn compile_if_needed(&self, input_path: &str) -> Result<String, MetalshaderError> {
         let input = Path::new(input_path);
 
         // Check if file exists
         if !input.exists() {
-            return Err(format!("Shader file not found: {}", input_path).into());
+            return Err(MetalshaderError::ShaderNotFound(input.to_path_buf()));
         }
 
         // Determine the base name and directory
         let base_name = input
             .file_stem()
             .and_then(|s| s.to_str())
-            .ok_or("Invalid shader path")?
+            .ok_or_else(|| MetalshaderError::ShaderNotFound(input.to_path_buf()))?
             .to_string();
 
         let shader_dir = input
@@ -45,13 +71,13 @@ impl ShaderCompiler {
             return Ok(base_name);
         }
 
-        // Need to compile - check if input is a GLSL file
+        // Need to compile - check if input is a GLSL or WGSL file
         if let Some(ext) = input.extension().and_then(|s| s.to_str()) {
             match ext {
-                "frag" | "glsl" | "fsh" => {
+                "frag" | "glsl" | "fsh" | "wgsl" => {
                     // Fragment shader source
                     println!("Compiling shader: {} -> {}", input_path, frag_spv.display());
-                    self.compile_glsl_to_spirv(input, &base_name, shader_dir)?;
+                    self.compile_to_files(input, &base_name, shader_dir)?;
                     return Ok(base_name);
                 }
                 "spv" => {
@@ -59,144 +85,321 @@ impl ShaderCompiler {
                     return Ok(base_name);
                 }
                 _ => {
-                    return Err(format!("Unknown shader extension: {}", ext).into());
+                    return Err(MetalshaderError::UnknownShaderType(input.to_path_buf()));
                 }
             }
         }
 
-        Err("Could not determine shader type".into())
+        Err(MetalshaderError::UnknownShaderType(input.to_path_buf()))
     }
 
-    fn compile_glsl_to_spirv(
-        &self,
-        input: &Path,
-        base_name: &str,
-        output_dir: &Path,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Step 1: Convert to Vulkan GLSL if needed
-        let vulkan_glsl = if self.is_vulkan_ready(input)? {
-            input.to_path_buf()
-        } else {
-            let temp_glsl = output_dir.join(format!("{}.glsl", base_name));
-            self.convert_to_vulkan_glsl(input, &temp_glsl)?;
-            temp_glsl
-        };
+    /// Compile a fragment source to SPIR-V next to it, even if up-to-date
+    /// looking .spv files exist. Returns the (vertex, fragment) SPIR-V paths.
+    pub fn compile(&self, input: &Path) -> Result<(PathBuf, PathBuf), MetalshaderError> {
+        let (base_name, shader_dir) = source_parts(input)?;
+        self.compile_to_files(input, base_name, shader_dir)?;
+        Ok((
+            shader_dir.join(format!("{}.vert.spv", base_name)),
+            shader_dir.join(format!("{}.frag.spv", base_name)),
+        ))
+    }
 
-        // Step 2: Generate vertex shader if not present
-        let vert_glsl = output_dir.join(format!("{}.vert", base_name));
-        if !vert_glsl.exists() {
-            self.generate_fullscreen_vertex_shader(&vert_glsl)?;
+    /// Compile a fragment source (and its custom `.vert`, if any) to SPIR-V
+    /// in memory, without writing `.spv` files; load the result with the
+    /// renderers' `load_shader_spirv`
+    pub fn compile_to_spirv(&self, input: &Path) -> Result<CompiledShader, MetalshaderError> {
+        let (base_name, shader_dir) = source_parts(input)?;
+        if input.extension().is_some_and(|ext| ext == "wgsl") {
+            self.compile_wgsl(input)
+        } else {
+            self.compile_glsl(input, base_name, shader_dir)
         }
+    }
 
-        // Step 3: Compile to SPIR-V
+    fn compile_to_files(&self, input: &Path, base_name: &str, output_dir: &Path) -> Result<(), MetalshaderError> {
+        let compiled = self.compile_to_spirv(input)?;
         let frag_spv = output_dir.join(format!("{}.frag.spv", base_name));
         let vert_spv = output_dir.join(format!("{}.vert.spv", base_name));
-
-        self.compile_glslang(&vulkan_glsl, &frag_spv, "frag")?;
-        self.compile_glslang(&vert_glsl, &vert_spv, "vert")?;
-
+        fs::write(&frag_spv, &compiled.fragment)?;
         println!("✓ Compiled: {}", frag_spv.display());
-        println!("✓ Compiled: {}", vert_spv.display());
-
+        fs::write(&vert_spv, &compiled.vertex)?;
+        if compiled.vertex != FULLSCREEN_VERT_SPV {
+            println!("✓ Compiled: {}", vert_spv.display());
+        }
         Ok(())
     }
 
-    fn is_vulkan_ready(&self, path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        Ok(content.contains("#version 450"))
-    }
-
-    fn convert_to_vulkan_glsl(
-        &self,
-        input: &Path,
-        output: &Path,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    fn compile_glsl(&self, input: &Path, base_name: &str, dir: &Path) -> Result<CompiledShader, MetalshaderError> {
+        // Convert to Vulkan GLSL if needed
         let content = fs::read_to_string(input)?;
+        let source = if content.contains("#version 450") { content } else { to_vulkan_glsl(&content) };
+        let fragment = self.glsl_to_spirv(&source, input, Stage::Fragment)?;
+
+        // The vertex stage is the embedded fullscreen module unless a custom
+        // .vert sits next to the shader
+        let vert_glsl = dir.join(format!("{}.vert", base_name));
+        let vertex = match fs::read_to_string(&vert_glsl) {
+            Ok(source) if !is_generated_vertex(&source) => self.glsl_to_spirv(&source, &vert_glsl, Stage::Vertex)?,
+            _ => FULLSCREEN_VERT_SPV.to_vec(),
+        };
+        Ok(CompiledShader { vertex, fragment })
+    }
 
-        // Basic conversion: wrap in Vulkan boilerplate
-        let vulkan_shader = format!(
-            r#"#version 450
-
-layout(location = 0) in vec2 fragCoord;
-layout(location = 0) out vec4 fragColor;
-
-layout(binding = 0, set = 0) uniform UniformBufferObject {{
-    vec3 iResolution;
-    float iTime;
-    vec4 iMouse;
-}} ubo;
-
-layout(binding = 1, set = 0) uniform sampler2D iChannel0;
-
-{}
-"#,
-            content
-        );
-
-        fs::write(output, vulkan_shader)?;
-        Ok(())
+    /// In-process with shaderc when the `shaderc` feature is on and its
+    /// library loads, with glslangValidator otherwise. `path` is the source
+    /// `source` came from, for diagnostics
+    fn glsl_to_spirv(&self, source: &str, path: &Path, stage: Stage) -> Result<Vec<u8>, MetalshaderError> {
+        #[cfg(feature = "shaderc")]
+        if let Some(result) = self.compile_shaderc(source, path, stage) {
+            return result;
+        }
+        self.compile_glslang(source, path, stage)
     }
 
-    fn generate_fullscreen_vertex_shader(
-        &self,
-        output: &Path,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let vert_shader = r#"#version 450
+    #[cfg(feature = "shaderc")]
+    fn compile_shaderc(&self, source: &str, path: &Path, stage: Stage) -> Option<Result<Vec<u8>, MetalshaderError>> {
+        let compiler = shaderc::Compiler::new()?;
+        let mut options = shaderc::CompileOptions::new()?;
+        options.set_target_env(shaderc::TargetEnv::Vulkan, shaderc::EnvVersion::Vulkan1_0 as u32);
+        let kind = match stage {
+            Stage::Vertex => shaderc::ShaderKind::Vertex,
+            Stage::Fragment => shaderc::ShaderKind::Fragment,
+        };
+        let name = path.display().to_string();
+        Some(match compiler.compile_into_spirv(source, kind, &name, "main", Some(&options)) {
+            Ok(artifact) => {
+                let messages = artifact.get_warning_messages();
+                let warnings: Vec<&str> = messages.lines().filter(|line| line.contains(": warning:")).collect();
+                self.check_warnings(path, &warnings).map(|()| artifact.as_binary_u8().to_vec())
+            }
+            Err(e) => Err(MetalshaderError::ShaderCompile { path: path.to_path_buf(), stderr: e.to_string() }),
+        })
+    }
 
-layout(location = 0) out vec2 fragCoord;
+    /// Translate a WGSL module with naga, no external compiler involved
+    ///
+    /// Its `@fragment` entry point becomes the fragment module and a
+    /// `@vertex` one, if present, the vertex module; otherwise the embedded
+    /// fullscreen vertex shader is used. Both are renamed to `main`.
+    #[cfg(feature = "wgsl")]
+    fn compile_wgsl(&self, input: &Path) -> Result<CompiledShader, MetalshaderError> {
+        let source = fs::read_to_string(input)?;
+        let failed = |stderr: String| MetalshaderError::ShaderCompile { path: input.to_path_buf(), stderr };
+
+        let module = naga::front::wgsl::parse_str(&source).map_err(|e| failed(e.emit_to_string(&source)))?;
+        let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+            .validate(&module)
+            .map_err(|e| failed(e.emit_to_string(&source)))?;
+
+        let stage_spirv = |stage: naga::ShaderStage| -> Result<Option<Vec<u8>>, MetalshaderError> {
+            let Some(entry) = module.entry_points.iter().find(|e| e.stage == stage) else {
+                return Ok(None);
+            };
+            let mut module = module.clone();
+            for e in module.entry_points.iter_mut().filter(|e| e.name == entry.name) {
+                e.name = "main".to_string();
+            }
+            let pipeline = naga::back::spv::PipelineOptions { shader_stage: stage, entry_point: "main".to_string() };
+            let words = naga::back::spv::write_vec(&module, &info, &naga::back::spv::Options::default(), Some(&pipeline))
+                .map_err(|e| failed(e.to_string()))?;
+            Ok(Some(words.iter().flat_map(|w| w.to_le_bytes()).collect()))
+        };
 
-layout(binding = 0, set = 0) uniform UniformBufferObject {
-    vec3 iResolution;
-    float iTime;
-    vec4 iMouse;
-} ubo;
+        Ok(CompiledShader {
+            fragment: stage_spirv(naga::ShaderStage::Fragment)?
+                .ok_or_else(|| failed("no @fragment entry point".to_string()))?,
+            vertex: stage_spirv(naga::ShaderStage::Vertex)?.unwrap_or_else(|| FULLSCREEN_VERT_SPV.to_vec()),
+        })
+    }
 
-void main() {
-    vec2 positions[6] = vec2[](
-        vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
-        vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
-    );
-    gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
-    fragCoord = (positions[gl_VertexIndex] * 0.5 + 0.5) * ubo.iResolution.xy;
-}
-"#;
+    #[cfg(not(feature = "wgsl"))]
+    fn compile_wgsl(&self, _input: &Path) -> Result<CompiledShader, MetalshaderError> {
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, ".wgsl shaders need the `wgsl` feature").into())
+    }
 
-        fs::write(output, vert_shader)?;
-        Ok(())
+    /// Write the embedded fullscreen vertex shader into `dir` and return the
+    /// SPIR-V path, for fragment modules loaded without one
+    pub fn fullscreen_vertex_spirv(&self, dir: &Path) -> Result<PathBuf, MetalshaderError> {
+        let vert_spv = dir.join("fullscreen.vert.spv");
+        fs::write(&vert_spv, FULLSCREEN_VERT_SPV)?;
+        Ok(vert_spv)
     }
 
-    fn compile_glslang(
-        &self,
-        input: &Path,
-        output: &Path,
-        stage: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    /// Compile `source` with the glslangValidator subprocess, through
+    /// scratch files in the temp directory
+    fn compile_glslang(&self, source: &str, path: &Path, stage: Stage) -> Result<Vec<u8>, MetalshaderError> {
         // Check if glslangValidator exists
         let check = Command::new("which")
             .arg("glslangValidator")
             .output()?;
 
         if !check.status.success() {
-            return Err("glslangValidator not found. Install with: brew install glslang".into());
+            return Err(MetalshaderError::CompilerNotFound);
         }
 
+        // The extension tells glslangValidator the stage
+        let scratch_dir = std::env::temp_dir().join("metalshader");
+        fs::create_dir_all(&scratch_dir)?;
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("shader");
+        let serial = SCRATCH_SERIAL.fetch_add(1, Ordering::Relaxed);
+        let input = scratch_dir.join(format!("{}-{}-{}.{}", stem, std::process::id(), serial, stage.extension()));
+        let output = input.with_extension(format!("{}.spv", stage.extension()));
+        fs::write(&input, source)?;
+
         let output_result = Command::new("glslangValidator")
             .arg("-V")
-            .arg(input)
+            .arg(&input)
             .arg("-o")
-            .arg(output)
-            .output()?;
+            .arg(&output)
+            .output();
+        let spirv = fs::read(&output);
+        let _ = fs::remove_file(&input);
+        let _ = fs::remove_file(&output);
+        let output_result = output_result?;
+
+        // glslangValidator reports on stdout, some builds also on stderr;
+        // point its messages at the real source instead of the scratch copy
+        let diagnostics = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output_result.stdout),
+            String::from_utf8_lossy(&output_result.stderr)
+        )
+        .replace(&input.display().to_string(), &path.display().to_string());
 
         if !output_result.status.success() {
-            let stderr = String::from_utf8_lossy(&output_result.stderr);
-            eprintln!("Compilation error:\n{}", stderr);
-            return Err(format!("Failed to compile {} shader", stage).into());
+            return Err(MetalshaderError::ShaderCompile { path: path.to_path_buf(), stderr: diagnostics });
         }
 
+        self.check_warnings(path, &warning_lines(&diagnostics))?;
+        // Success without a module (seen with odd output paths) would
+        // otherwise only fail later, when the renderer loads it
+        let command = format!("glslangValidator -V {} -o {}", input.display(), output.display());
+        written_spirv(spirv, &command).map_err(|e| MetalshaderError::ShaderCompile {
+            path: path.to_path_buf(),
+            stderr: format!("{}\n{}", e, diagnostics),
+        })
+    }
+
+    /// Print compiler warnings; with `--werror` they fail the compile
+    fn check_warnings(&self, path: &Path, warnings: &[&str]) -> Result<(), MetalshaderError> {
+        if warnings.is_empty() {
+            return Ok(());
+        }
+        eprintln!("Warnings in {}:", path.display());
+        for line in warnings {
+            eprintln!("  {}", line);
+        }
+        if self.werror {
+            return Err(MetalshaderError::CompileWarnings {
+                path: path.to_path_buf(),
+                count: warnings.len(),
+            });
+        }
         Ok(())
     }
 }
 
+/// SPIR-V of one shader's two stages, as `ShaderCompiler::compile_to_spirv`
+/// returns it
+#[derive(Debug, Clone)]
+pub struct CompiledShader {
+    pub vertex: Vec<u8>,
+    pub fragment: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Vertex,
+    Fragment,
+}
+
+impl Stage {
+    fn extension(self) -> &'static str {
+        match self {
+            Stage::Vertex => "vert",
+            Stage::Fragment => "frag",
+        }
+    }
+}
+
+/// Base name and directory of a shader source that must exist
+fn source_parts(input: &Path) -> Result<(&str, &Path), MetalshaderError> {
+    if !input.exists() {
+        return Err(MetalshaderError::ShaderNotFound(input.to_path_buf()));
+    }
+    let base_name = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| MetalshaderError::ShaderNotFound(input.to_path_buf()))?;
+    Ok((base_name, input.parent().unwrap_or_else(|| Path::new("."))))
+}
+
+/// Wrap plain ShaderToy-style GLSL in the Vulkan boilerplate
+fn to_vulkan_glsl(content: &str) -> String {
+    format!(
+        r#"#version 450
+
+layout(location = 0) in vec2 fragCoord;
+layout(location = 0) out vec4 fragColor;
+
+{}
+"#,
+        wrap_main_image(content)
+    )
+}
+
+/// ShaderToy-style sources define `mainImage` instead of `main`
+///
+/// Put the uniform declarations ahead of the source, for those also the
+/// uniform aliases ShaderToy code expects (`shadertoy::prelude`, as for
+/// imports) and a `main` calling it. Sources with their own `main` get no
+/// aliases, since they address the UBO as `ubo.iTime` and the aliases would
+/// rewrite that to `ubo.ubo.iTime`.
+fn wrap_main_image(source: &str) -> String {
+    let compact: String = source.chars().filter(|c| !c.is_whitespace()).collect();
+    if !compact.contains("voidmainImage(") || compact.contains("voidmain(") {
+        return format!("{}\n{}", shadertoy::uniform_declarations("sampler2D"), source);
+    }
+
+    format!(
+        r#"{}
+{}
+
+void main() {{
+    mainImage(fragColor, fragCoord);
+}}
+"#,
+        shadertoy::prelude("sampler2D"),
+        source
+    )
+}
+
+/// The SPIR-V `command` wrote, from reading its output file after it
+/// exited successfully; an error naming the command if there is none
+fn written_spirv(read: std::io::Result<Vec<u8>>, command: &str) -> Result<Vec<u8>, String> {
+    match read {
+        Ok(spirv) if !spirv.is_empty() => Ok(spirv),
+        Ok(_) => Err(format!("`{}` succeeded but wrote an empty file", command)),
+        Err(e) => Err(format!("`{}` succeeded but wrote no output: {}", command, e)),
+    }
+}
+
+/// Lines of glslangValidator output that are warnings
+fn warning_lines(diagnostics: &str) -> Vec<&str> {
+    diagnostics
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("WARNING:"))
+        .collect()
+}
+
+/// Older versions wrote the generated vertex shader next to each compiled
+/// shader as `<name>.vert`; those copies aren't custom and don't need glslang
+fn is_generated_vertex(source: &str) -> bool {
+    source.contains("vec2 positions[6]")
+        && source.contains("fragCoord = (positions[gl_VertexIndex] * 0.5 + 0.5) * ubo.iResolution.xy;")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,4 +409,49 @@ mod tests {
         let compiler = ShaderCompiler::new();
         // Test would go here
     }
+
+    #[test]
+    fn test_wrap_main_image() {
+        let toy = "void mainImage( out vec4 c, in vec2 p ) { c = vec4(iTime); }";
+        let wrapped = wrap_main_image(toy);
+        assert!(wrapped.contains("#define iTime ubo.iTime"));
+        assert!(wrapped.contains("#define iFrame "));
+        assert!(wrapped.contains("mainImage(fragColor, fragCoord);"));
+
+        let plain = "void main() { fragColor = vec4(ubo.iTime); }";
+        let wrapped = wrap_main_image(plain);
+        assert!(wrapped.contains("uniform sampler2D iChannel0;"));
+        assert!(wrapped.ends_with(plain) && !wrapped.contains("#define"));
+    }
+
+    #[test]
+    fn test_embedded_fullscreen_vertex() {
+        let words = crate::spirv_reflect::words_from_bytes(FULLSCREEN_VERT_SPV);
+        let reflection = crate::spirv_reflect::reflect(&words).unwrap();
+        assert!(reflection.expect_entry_point("main", "vertex").is_ok());
+        assert!(reflection.check_descriptor_layout(12).is_ok());
+        assert!(is_generated_vertex(include_str!("builtin/fullscreen.vert")));
+
+        // Same interface, so either can pair with any fragment shader
+        let words = crate::spirv_reflect::words_from_bytes(FULLSCREEN_NORMALIZED_VERT_SPV);
+        let reflection = crate::spirv_reflect::reflect(&words).unwrap();
+        assert!(reflection.expect_entry_point("main", "vertex").is_ok());
+        assert!(reflection.check_descriptor_layout(12).is_ok());
+    }
+
+    #[test]
+    fn test_warning_lines() {
+        let out = "shader.frag\nWARNING: 0:12: 'x' : unused variable\nERROR: nope\n";
+        assert_eq!(warning_lines(out), vec!["WARNING: 0:12: 'x' : unused variable"]);
+        assert!(warning_lines("shader.frag\n").is_empty());
+    }
+
+    #[test]
+    fn test_written_spirv() {
+        let command = "glslangValidator -V a.frag -o a.frag.spv";
+        assert_eq!(written_spirv(Ok(vec![3, 2, 35, 7]), command), Ok(vec![3, 2, 35, 7]));
+        assert!(written_spirv(Ok(Vec::new()), command).unwrap_err().contains(command));
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(written_spirv(Err(missing), command).unwrap_err().contains("wrote no output"));
+    }
 }
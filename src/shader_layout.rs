@@ -0,0 +1,198 @@
+// Descriptor set and pipeline layouts shared by both renderers
+//
+// Every shader sees the UBO at set 0, binding 0. The iChannel0 sampler at
+// binding 1, the sidecar uniform block at binding 5 and the push-constant
+// range are only part of a pipeline's layout when reflection shows the shader
+// uses them, so minimal shaders don't carry bindings they never read. All
+// variants are created up front; switching shaders only picks a different one.
+
+use ash::vk;
+
+use crate::ubo::{USER_BINDING, USER_UNIFORMS_BYTES, USER_UNIFORMS_OFFSET};
+
+/// Which optional resources a pipeline is laid out for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LayoutKey {
+    /// Combined image sampler (iChannel0) at binding 1
+    pub sampler: bool,
+    /// Sidecar uniform block at `USER_BINDING`
+    pub user_uniforms: bool,
+    pub push_constants: bool,
+}
+
+impl LayoutKey {
+    /// Index of the descriptor set variant
+    fn set_index(self) -> usize {
+        self.sampler as usize | (self.user_uniforms as usize) << 1
+    }
+}
+
+pub struct ShaderLayouts {
+    pool: vk::DescriptorPool,
+    /// Indexed by `LayoutKey::set_index`
+    set_layouts: [vk::DescriptorSetLayout; 4],
+    sets: [vk::DescriptorSet; 4],
+    /// Indexed by `[set_index][push_constants]`
+    pipeline_layouts: [[vk::PipelineLayout; 2]; 4],
+}
+
+impl ShaderLayouts {
+    /// Create the four descriptor sets, pointed at `uniform_buffer` (the UBO
+    /// in its first `uniform_bytes`, the sidecar block at
+    /// `USER_UNIFORMS_OFFSET`) and the iChannel0 texture, plus the eight
+    /// pipeline layouts
+    pub unsafe fn new(
+        device: &ash::Device,
+        uniform_buffer: vk::Buffer,
+        uniform_bytes: u64,
+        sampler: vk::Sampler,
+        texture_view: vk::ImageView,
+        push_constant_bytes: u32,
+    ) -> Result<Self, vk::Result> {
+        let ubo_binding = vk::DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT);
+        let sampler_binding = vk::DescriptorSetLayoutBinding::default()
+            .binding(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+        let user_binding = vk::DescriptorSetLayoutBinding::default()
+            .binding(USER_BINDING)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT);
+
+        let mut set_layouts = [vk::DescriptorSetLayout::null(); 4];
+        for (index, set_layout) in set_layouts.iter_mut().enumerate() {
+            let bindings: Vec<_> = [(true, ubo_binding), (index & 1 != 0, sampler_binding), (index & 2 != 0, user_binding)]
+                .into_iter()
+                .filter_map(|(used, binding)| used.then_some(binding))
+                .collect();
+            *set_layout = device.create_descriptor_set_layout(&vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings), None)?;
+        }
+
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(push_constant_bytes);
+        let mut pipeline_layouts = [[vk::PipelineLayout::null(); 2]; 4];
+        for (set_layout, layouts) in set_layouts.iter().zip(pipeline_layouts.iter_mut()) {
+            let plain_info = vk::PipelineLayoutCreateInfo::default()
+                .set_layouts(std::slice::from_ref(set_layout));
+            layouts[0] = device.create_pipeline_layout(&plain_info, None)?;
+
+            let push_info = vk::PipelineLayoutCreateInfo::default()
+                .set_layouts(std::slice::from_ref(set_layout))
+                .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+            layouts[1] = device.create_pipeline_layout(&push_info, None)?;
+        }
+
+        // One set per variant, all reading the same uniform buffer
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 6,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 2,
+            },
+        ];
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .max_sets(4)
+            .pool_sizes(&pool_sizes);
+        let pool = device.create_descriptor_pool(&pool_info, None)?;
+
+        let alloc_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(&set_layouts);
+        let allocated = device.allocate_descriptor_sets(&alloc_info)?;
+        let sets = [allocated[0], allocated[1], allocated[2], allocated[3]];
+
+        let buffer_info = vk::DescriptorBufferInfo::default()
+            .buffer(uniform_buffer)
+            .offset(0)
+            .range(uniform_bytes);
+        let user_buffer_info = vk::DescriptorBufferInfo::default()
+            .buffer(uniform_buffer)
+            .offset(USER_UNIFORMS_OFFSET as u64)
+            .range(USER_UNIFORMS_BYTES as u64);
+        let image_info = vk::DescriptorImageInfo::default()
+            .sampler(sampler)
+            .image_view(texture_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+        let mut writes = Vec::new();
+        for (index, &set) in sets.iter().enumerate() {
+            writes.push(
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(std::slice::from_ref(&buffer_info)),
+            );
+            if index & 1 != 0 {
+                writes.push(
+                    vk::WriteDescriptorSet::default()
+                        .dst_set(set)
+                        .dst_binding(1)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(std::slice::from_ref(&image_info)),
+                );
+            }
+            if index & 2 != 0 {
+                writes.push(
+                    vk::WriteDescriptorSet::default()
+                        .dst_set(set)
+                        .dst_binding(USER_BINDING)
+                        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                        .buffer_info(std::slice::from_ref(&user_buffer_info)),
+                );
+            }
+        }
+        device.update_descriptor_sets(&writes, &[]);
+
+        Ok(Self { pool, set_layouts, sets, pipeline_layouts })
+    }
+
+    /// Point the sampled sets' binding 1 at another iChannel0 image. No
+    /// submitted command buffer may still be using the sets.
+    pub unsafe fn set_texture(&self, device: &ash::Device, sampler: vk::Sampler, texture_view: vk::ImageView) {
+        let image_info = vk::DescriptorImageInfo::default()
+            .sampler(sampler)
+            .image_view(texture_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        let writes = [self.sets[1], self.sets[3]].map(|set| {
+            vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(std::slice::from_ref(&image_info))
+        });
+        device.update_descriptor_sets(&writes, &[]);
+    }
+
+    pub fn pipeline_layout(&self, key: LayoutKey) -> vk::PipelineLayout {
+        self.pipeline_layouts[key.set_index()][key.push_constants as usize]
+    }
+
+    pub fn descriptor_set(&self, key: LayoutKey) -> vk::DescriptorSet {
+        self.sets[key.set_index()]
+    }
+
+    /// Destroy everything; no pipeline built against these layouts may be in use
+    pub unsafe fn destroy(&self, device: &ash::Device) {
+        for layouts in &self.pipeline_layouts {
+            for &layout in layouts {
+                device.destroy_pipeline_layout(layout, None);
+            }
+        }
+        device.destroy_descriptor_pool(self.pool, None);
+        for &set_layout in &self.set_layouts {
+            device.destroy_descriptor_set_layout(set_layout, None);
+        }
+    }
+}
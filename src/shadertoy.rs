@@ -0,0 +1,230 @@
+// Import shaders from ShaderToy JSON exports
+//
+// An export (from the site's API or the "export" browser snippets) contains the
+// render passes of a shader. The image pass, plus any "common" code, is wrapped
+// into a Vulkan GLSL fragment shader that maps the ShaderToy uniforms onto our
+// UBO, then compiled like any other `.frag` source.
+
+use crate::shader_compiler::ShaderCompiler;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where imported shaders are written; scanned by `ShaderManager`
+const IMPORT_DIR: &str = "shaders";
+
+#[derive(Debug, Deserialize)]
+pub struct ShaderToyShader {
+    #[serde(default)]
+    pub info: ShaderInfo,
+    #[serde(rename = "renderpass")]
+    pub render_passes: Vec<RenderPass>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ShaderInfo {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenderPass {
+    /// "image", "buffer", "common", "sound" or "cubemap"
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub name: String,
+    pub code: String,
+    #[serde(default)]
+    pub inputs: Vec<ChannelInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelInput {
+    pub channel: u32,
+    /// "texture", "cubemap", "buffer", "keyboard", "music", ...
+    #[serde(rename = "type", alias = "ctype", default)]
+    pub kind: String,
+}
+
+/// The shapes an export comes in: the API wraps it in `{"Shader": ...}`,
+/// bulk exports are arrays, hand-saved files are often the bare object
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Export {
+    Wrapped {
+        #[serde(rename = "Shader")]
+        shader: ShaderToyShader,
+    },
+    List(Vec<ShaderToyShader>),
+    Bare(ShaderToyShader),
+}
+
+/// Parse an export, taking the first shader of a multi-shader file
+pub fn parse(json: &str) -> Result<ShaderToyShader, Box<dyn std::error::Error>> {
+    let export: Export = serde_json::from_str(json)
+        .map_err(|e| format!("Not a ShaderToy export: {}", e))?;
+    match export {
+        Export::Wrapped { shader } | Export::Bare(shader) => Ok(shader),
+        Export::List(shaders) => shaders
+            .into_iter()
+            .next()
+            .ok_or_else(|| "ShaderToy export contains no shaders".into()),
+    }
+}
+
+/// The uniform block and `iChannel0` (a `channel0_type`) as generated
+/// fragment shaders declare them
+pub fn uniform_declarations(channel0_type: &str) -> String {
+    format!(
+        r#"layout(binding = 0, set = 0) uniform UniformBufferObject {{
+    vec3 iResolution;
+    float iTime;
+    vec4 iMouse;
+    layout(offset = 72) float iFrameRate;
+}} ubo;
+
+layout(binding = 1, set = 0) uniform {} iChannel0;
+"#,
+        channel0_type
+    )
+}
+
+/// `uniform_declarations` plus the ShaderToy uniform names as macros into
+/// the UBO, for `mainImage` code from imports and plain `.frag` files alike
+pub fn prelude(channel0_type: &str) -> String {
+    format!(
+        r#"{}
+#define iResolution ubo.iResolution
+#define iTime ubo.iTime
+#define iMouse ubo.iMouse
+#define iFrameRate ubo.iFrameRate
+#define iTimeDelta (1.0 / ubo.iFrameRate)
+#define iFrame int(ubo.iTime * 60.0)
+#define iDate vec4(0.0, 0.0, 0.0, ubo.iTime)
+#define iChannelResolution vec3[4](vec3(textureSize(iChannel0, 0), 1.0), vec3(0.0), vec3(0.0), vec3(0.0))
+"#,
+        uniform_declarations(channel0_type)
+    )
+}
+
+impl ShaderToyShader {
+    /// File-system friendly name, falling back to the ShaderToy id
+    pub fn file_name(&self) -> String {
+        let source = if self.info.name.is_empty() { &self.info.id } else { &self.info.name };
+        let name: String = source
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+            .collect();
+        let name = name.trim_matches('_').to_string();
+        if name.is_empty() { "shadertoy".to_string() } else { name }
+    }
+
+    /// Wrap the image pass into a Vulkan GLSL fragment shader
+    pub fn to_vulkan_glsl(&self) -> Result<String, String> {
+        let image = self.render_passes
+            .iter()
+            .find(|p| p.kind == "image")
+            .ok_or("ShaderToy export has no image pass")?;
+
+        for pass in &self.render_passes {
+            if pass.kind != "image" && pass.kind != "common" {
+                eprintln!("Warning: ignoring {} pass '{}' (not supported)", pass.kind, pass.name);
+            }
+        }
+        for input in &image.inputs {
+            if input.channel != 0 || (input.kind != "texture" && input.kind != "cubemap") {
+                eprintln!(
+                    "Warning: iChannel{} ({}) is not bound; only iChannel0 textures and cubemaps are supported",
+                    input.channel, input.kind
+                );
+            }
+        }
+        // Bind it with --channel0 (six faces or --channel0-cube)
+        let channel0_type = if image.inputs.iter().any(|i| i.channel == 0 && i.kind == "cubemap") {
+            "samplerCube"
+        } else {
+            "sampler2D"
+        };
+
+        let common: String = self.render_passes
+            .iter()
+            .filter(|p| p.kind == "common")
+            .map(|p| format!("{}\n", p.code))
+            .collect();
+
+        Ok(format!(
+            r#"#version 450
+// Imported from ShaderToy: {name}
+
+layout(location = 0) in vec2 stFragCoord;
+layout(location = 0) out vec4 stFragColor;
+
+{prelude}
+{common}
+{image}
+
+void main() {{
+    mainImage(stFragColor, stFragCoord);
+}}
+"#,
+            name = self.info.name,
+            prelude = prelude(channel0_type),
+            common = common,
+            image = image.code,
+        ))
+    }
+}
+
+/// Convert a ShaderToy export into `shaders/<name>.frag`, compile it and
+/// return the shader name to load
+pub fn import(json_path: &Path, compiler: &ShaderCompiler) -> Result<String, Box<dyn std::error::Error>> {
+    let json = fs::read_to_string(json_path)
+        .map_err(|e| format!("Failed to read {}: {}", json_path.display(), e))?;
+    let shader = parse(&json)?;
+    let name = shader.file_name();
+    let glsl = shader.to_vulkan_glsl()?;
+
+    fs::create_dir_all(IMPORT_DIR)?;
+    let frag_path: PathBuf = Path::new(IMPORT_DIR).join(format!("{}.frag", name));
+    fs::write(&frag_path, glsl)?;
+    println!("Imported ShaderToy '{}' -> {}", shader.info.name, frag_path.display());
+
+    // A previous import of the same shader would otherwise be reused as is
+    let _ = fs::remove_file(Path::new(IMPORT_DIR).join(format!("{}.frag.spv", name)));
+
+    let frag_str = frag_path.to_str().ok_or("Invalid shader path")?;
+    compiler.compile_if_needed(frag_str)?;
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wrapped_export() {
+        let json = r#"{"Shader": {"info": {"id": "XsX3zn", "name": "Seascape"},
+            "renderpass": [{"type": "image", "name": "Image", "inputs": [],
+            "code": "void mainImage(out vec4 c, in vec2 p) { c = vec4(iTime); }"}]}}"#;
+        let shader = parse(json).unwrap();
+        assert_eq!(shader.file_name(), "seascape");
+
+        let glsl = shader.to_vulkan_glsl().unwrap();
+        assert!(glsl.starts_with("#version 450"));
+        assert!(glsl.contains("c = vec4(iTime);"));
+        assert!(glsl.contains("mainImage(stFragColor, stFragCoord);"));
+        assert!(glsl.contains("uniform sampler2D iChannel0;"));
+    }
+
+    #[test]
+    fn test_cubemap_channel_declares_sampler_cube() {
+        let json = r#"{"info": {"name": "Sky"}, "renderpass": [{"type": "image",
+            "inputs": [{"channel": 0, "ctype": "cubemap"}],
+            "code": "void mainImage(out vec4 c, in vec2 p) { c = texture(iChannel0, vec3(0, 0, 1)); }"}]}"#;
+        let glsl = parse(json).unwrap().to_vulkan_glsl().unwrap();
+        assert!(glsl.contains("uniform samplerCube iChannel0;"));
+    }
+}
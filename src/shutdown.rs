@@ -0,0 +1,32 @@
+// SIGINT/SIGTERM/SIGHUP turn into a polled "should exit" flag
+//
+// Dying inside the signal would skip every Drop, leaving the DRM CRTC or the
+// macOS display in the mode we switched to. The main loops check `requested()`
+// and leave through the normal path instead. A second signal while shutdown
+// is pending terminates immediately, so a hung loop can still be killed.
+
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+static EXIT_REQUESTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Install the handlers; call once at startup
+pub fn install() {
+    let flag = EXIT_REQUESTED.get_or_init(|| Arc::new(AtomicBool::new(false)));
+    for signal in [SIGINT, SIGTERM, SIGHUP] {
+        // Registered first so it sees the flag before this signal sets it
+        let result = signal_hook::flag::register_conditional_shutdown(signal, 1, Arc::clone(flag))
+            .and_then(|_| signal_hook::flag::register(signal, Arc::clone(flag)));
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to install handler for signal {}: {}", signal, e);
+        }
+    }
+}
+
+/// A termination signal arrived; finish the frame and shut down cleanly
+pub fn requested() -> bool {
+    EXIT_REQUESTED
+        .get()
+        .is_some_and(|flag| flag.load(Ordering::Relaxed))
+}
@@ -0,0 +1,116 @@
+// `--out FILE.exr`: render headlessly and write the frames as OpenEXR
+//
+// The shader runs at `--start-time` in a float render target of
+// `--out-size` (rgba16f unless `--format rgba32f` asks for more), copied out
+// through the OPTIMAL readback path and written as 32-bit float RGBA with
+// the `exr` crate (feature `exr`), so values above 1.0 survive for HDR work.
+// `--repeat N` renders N frames at `FRAME_RATE` into numbered files.
+
+use std::error::Error;
+
+use metalshader::animated_texture::AnimatedTexture;
+use metalshader::options::{Options, RenderFormat, RenderTiling};
+use metalshader::{hdr, mouse, RenderConfig, ShaderInfo, ShaderToyUBO, VulkanRenderer};
+
+const EXR_FEATURE: &str = "--out needs the `exr` feature (cargo build --release --features exr)";
+
+/// iTime step between the frames of `--repeat N`
+const FRAME_RATE: f64 = 60.0;
+
+pub fn run(options: &Options, shader: &ShaderInfo, mut channel0: Option<&mut AnimatedTexture>) -> Result<(), Box<dyn Error>> {
+    let out = options.out.as_deref().unwrap_or_default();
+    if !out.to_lowercase().ends_with(".exr") {
+        return Err(format!("--out writes OpenEXR; use a .exr file name, not '{}'", out).into());
+    }
+    if !cfg!(feature = "exr") {
+        return Err(EXR_FEATURE.into());
+    }
+    // 8-bit would clamp exactly what EXR is for
+    let format = if options.format.is_float() { options.format } else { RenderFormat::Rgba16f };
+    let (width, height) = options.out_size;
+    let render_config = RenderConfig {
+        tiling: RenderTiling::Optimal,
+        format,
+        blend: options.blend,
+        software: options.software,
+        flip_y: options.flip_y,
+        frag_coord: options.frag_coord,
+        channel0_sampler: options.channel0_sampler,
+        exportable: false,
+    };
+    let mut renderer = VulkanRenderer::new(width, height, render_config)?;
+    if let Some(channel0) = channel0.as_deref_mut() {
+        channel0.advance(options.start_time);
+        renderer.set_channel0(channel0.current())?;
+    }
+    renderer.set_uniforms(&shader.uniforms())?;
+    renderer.load_shader(&shader.vert_path, &shader.frag_path, &options.entry_points)?;
+
+    let count = options.frames.unwrap_or(1);
+    for index in 0..count {
+        let time = options.start_time + index as f64 / FRAME_RATE;
+        if let Some(frame) = channel0.as_deref_mut().and_then(|channel0| channel0.advance(time)) {
+            renderer.update_channel0(frame)?;
+        }
+        let ubo = ShaderToyUBO {
+            i_resolution: [width as f32, height as f32, 1.0],
+            i_time: time as f32,
+            i_mouse: mouse::shadertoy_mouse([0.0, 0.0], [0.0, 0.0], false, false),
+            i_frame_rate: FRAME_RATE as f32,
+            ..Default::default()
+        };
+        renderer.render_frame(&ubo)?;
+
+        let pixels = renderer.get_frame_buffer();
+        let row_pitch = renderer.get_row_pitch();
+        let pixel = |x: usize, y: usize| hdr::pixel(format, &pixels[y * row_pitch..], x);
+        let path = frame_path(out, index, count);
+        write_exr(&path, width as usize, height as usize, pixel)?;
+        println!("Wrote {} ({}x{} {:?}, '{}' at iTime {:.3})", path, width, height, format, shader.name, time);
+    }
+    Ok(())
+}
+
+/// `out` itself for a single frame, else `name_0000.exr`, `name_0001.exr`, ...
+fn frame_path(out: &str, index: u32, count: u32) -> String {
+    if count == 1 {
+        return out.to_string();
+    }
+    let (stem, extension) = out.rsplit_once('.').unwrap_or((out, "exr"));
+    format!("{}_{:04}.{}", stem, index, extension)
+}
+
+#[cfg(feature = "exr")]
+fn write_exr(
+    path: &str,
+    width: usize,
+    height: usize,
+    pixel: impl Fn(usize, usize) -> [f32; 4] + Sync,
+) -> Result<(), Box<dyn Error>> {
+    exr::prelude::write_rgba_file(path, width, height, |x, y| {
+        let [r, g, b, a] = pixel(x, y);
+        (r, g, b, a)
+    })?;
+    Ok(())
+}
+
+#[cfg(not(feature = "exr"))]
+fn write_exr(
+    _path: &str,
+    _width: usize,
+    _height: usize,
+    _pixel: impl Fn(usize, usize) -> [f32; 4] + Sync,
+) -> Result<(), Box<dyn Error>> {
+    Err(EXR_FEATURE.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_path() {
+        assert_eq!(frame_path("shot.exr", 0, 1), "shot.exr");
+        assert_eq!(frame_path("out/shot.EXR", 7, 120), "out/shot_0007.EXR");
+    }
+}
@@ -0,0 +1,573 @@
+// Minimal SPIR-V reflection
+//
+// Just enough of the module is decoded to learn what a shader expects from the
+// pipeline layout: its entry points, push-constant block and descriptor bindings,
+// plus the location-decorated inputs and outputs the two stages pass between them.
+// Types are sized from the Offset/ArrayStride decorations glslang always emits for
+// interface blocks.
+
+use std::collections::HashMap;
+
+use crate::ubo::{USER_BINDING, USER_UNIFORMS_BYTES};
+
+const MAGIC: u32 = 0x0723_0203;
+
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const DIM_CUBE: u32 = 3;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    /// Uniform block of the given size in bytes
+    UniformBuffer { size: u32 },
+    /// Sampler, image or combined image sampler; `cube` for samplerCube
+    Texture { cube: bool },
+    StorageBuffer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub set: u32,
+    pub binding: u32,
+    pub kind: BindingKind,
+}
+
+/// A `layout(location = N) in/out` variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Varying {
+    pub location: u32,
+    /// GLSL spelling of its type, e.g. `vec2`
+    pub type_name: String,
+    /// Variable name, when the module kept debug names
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reflection {
+    /// Size in bytes of the push-constant block, if the shader declares one
+    pub push_constant_size: Option<u32>,
+    pub bindings: Vec<Binding>,
+    /// Names of the module's OpEntryPoints
+    pub entry_points: Vec<String>,
+    /// Location-decorated inputs and outputs, sorted by location
+    pub inputs: Vec<Varying>,
+    pub outputs: Vec<Varying>,
+}
+
+impl Reflection {
+    pub fn binding(&self, set: u32, binding: u32) -> Option<&Binding> {
+        self.bindings.iter().find(|b| b.set == set && b.binding == binding)
+    }
+
+    /// Error unless the module declares an entry point called `name`
+    pub fn expect_entry_point(&self, name: &str, stage: &str) -> Result<(), String> {
+        if self.entry_points.iter().any(|e| e == name) {
+            return Ok(());
+        }
+        Err(format!(
+            "{} shader has no entry point '{}' (found: {})",
+            stage,
+            name,
+            self.entry_points.join(", ")
+        ))
+    }
+
+    /// Error unless every descriptor the shader uses exists in the renderers'
+    /// fixed layout: set 0 with the UBO (at most `ubo_bytes`) at binding 0,
+    /// the iChannel0 combined image sampler at binding 1 and the sidecar
+    /// uniforms at `USER_BINDING`
+    pub fn check_descriptor_layout(&self, ubo_bytes: u32) -> Result<(), String> {
+        for b in &self.bindings {
+            match (b.set, b.binding, b.kind) {
+                (0, 0, BindingKind::UniformBuffer { size }) if size > ubo_bytes => {
+                    return Err(format!(
+                        "Uniform block at set 0, binding 0 is {} bytes, but the renderer provides {}",
+                        size, ubo_bytes
+                    ));
+                }
+                (0, USER_BINDING, BindingKind::UniformBuffer { size }) if size as usize > USER_UNIFORMS_BYTES => {
+                    return Err(format!(
+                        "Uniform block at set 0, binding {} is {} bytes, at most {} are supported",
+                        USER_BINDING, size, USER_UNIFORMS_BYTES
+                    ));
+                }
+                (0, 0 | USER_BINDING, BindingKind::UniformBuffer { .. }) | (0, 1, BindingKind::Texture { .. }) => {}
+                (set, binding, kind) => {
+                    let expected = match kind {
+                        BindingKind::UniformBuffer { .. } => {
+                            "the uniform block belongs at set 0, binding 0 (binding 5 for sidecar uniforms)"
+                        }
+                        BindingKind::Texture { .. } => "iChannel0 belongs at set 0, binding 1",
+                        BindingKind::StorageBuffer => "storage buffers are not supported",
+                    };
+                    return Err(format!(
+                        "Shader declares {} at set {}, binding {}; {} (layout(set = ..., binding = ...))",
+                        kind.describe(), set, binding, expected
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Error if the shader samples iChannel0 as a different kind of texture
+    /// (2D vs cube) than the one bound
+    pub fn check_channel0(&self, cube_bound: bool) -> Result<(), String> {
+        match self.binding(0, 1).map(|b| b.kind) {
+            Some(BindingKind::Texture { cube }) if cube != cube_bound => Err(if cube {
+                "Shader declares iChannel0 as samplerCube, but a 2D texture is bound (pass a cubemap with --channel0)".to_string()
+            } else {
+                "Shader declares iChannel0 as sampler2D, but a cubemap is bound".to_string()
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Compare this vertex stage's outputs with `fragment`'s inputs
+    ///
+    /// A fragment input the vertex stage doesn't write, or writes with another
+    /// type, is an error: drivers reject the pipeline or read garbage. Vertex
+    /// outputs nobody reads are legal, but when the fragment shader does take
+    /// inputs they usually mean a varying declared at the wrong location, so
+    /// those come back as warnings.
+    pub fn check_stage_interface(&self, fragment: &Reflection) -> Result<Vec<String>, String> {
+        for input in &fragment.inputs {
+            match self.outputs.iter().find(|o| o.location == input.location) {
+                None => {
+                    return Err(format!(
+                        "Fragment shader reads {} at location {}, but the vertex shader writes nothing there{}",
+                        input.type_name,
+                        input.location,
+                        self.declaration_hint(" (it writes ", ")"),
+                    ));
+                }
+                Some(output) if output.type_name != input.type_name => {
+                    return Err(format!(
+                        "Fragment shader reads {} at location {}, but the vertex shader writes {}; declare {}",
+                        input.type_name,
+                        input.location,
+                        output.type_name,
+                        output.declaration(),
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        if fragment.inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .outputs
+            .iter()
+            .filter(|o| !fragment.inputs.iter().any(|i| i.location == o.location))
+            .map(|o| format!("fragment shader missing {}; the vertex output is unused", o.declaration()))
+            .collect())
+    }
+
+    /// The vertex outputs as declarations, wrapped in `prefix`/`suffix`
+    fn declaration_hint(&self, prefix: &str, suffix: &str) -> String {
+        if self.outputs.is_empty() {
+            return String::new();
+        }
+        let declarations: Vec<String> = self.outputs.iter().map(Varying::declaration).collect();
+        format!("{}{}{}", prefix, declarations.join(", "), suffix)
+    }
+
+    /// Combine the interfaces of the stages of one pipeline
+    pub fn merge(mut self, other: &Reflection) -> Reflection {
+        self.push_constant_size = match (self.push_constant_size, other.push_constant_size) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        for b in &other.bindings {
+            if self.binding(b.set, b.binding).is_none() {
+                self.bindings.push(*b);
+            }
+        }
+        self.entry_points.extend(other.entry_points.iter().cloned());
+        self
+    }
+}
+
+impl Varying {
+    /// The fragment-side declaration that would match this vertex output
+    fn declaration(&self) -> String {
+        format!(
+            "`layout(location = {}) in {} {}`",
+            self.location,
+            self.type_name,
+            self.name.as_deref().unwrap_or("...")
+        )
+    }
+}
+
+impl BindingKind {
+    fn describe(&self) -> &'static str {
+        match self {
+            BindingKind::UniformBuffer { .. } => "a uniform block",
+            BindingKind::Texture { .. } => "a texture/sampler",
+            BindingKind::StorageBuffer => "a storage buffer",
+        }
+    }
+}
+
+enum Type {
+    Scalar { bytes: u32, float: bool },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Array { element: u32, length_id: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+    Image { dim: u32 },
+    SampledImage { image: u32 },
+}
+
+#[derive(Default)]
+struct Module {
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    /// (target, decoration) -> first literal
+    decorations: HashMap<(u32, u32), u32>,
+    /// (struct, member, decoration) -> first literal
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+    /// (pointer type, variable id, storage class)
+    variables: Vec<(u32, u32, u32)>,
+    names: HashMap<u32, String>,
+    entry_points: Vec<String>,
+}
+
+/// Reflect a SPIR-V module given as 32-bit words
+pub fn reflect(words: &[u32]) -> Result<Reflection, String> {
+    if words.len() < 5 || words[0] != MAGIC {
+        return Err("Not a SPIR-V module (bad magic number)".to_string());
+    }
+
+    let mut module = Module::default();
+    let mut i = 5;
+    while i < words.len() {
+        let count = (words[i] >> 16) as usize;
+        let opcode = words[i] & 0xffff;
+        if count == 0 || i + count > words.len() {
+            return Err(format!("Truncated SPIR-V instruction at word {}", i));
+        }
+        let ops = &words[i + 1..i + count];
+        let op = |n: usize| ops.get(n).copied().unwrap_or(0);
+
+        match opcode {
+            OP_NAME if ops.len() > 1 => {
+                module.names.insert(op(0), literal_string(&ops[1..]));
+            }
+            OP_ENTRY_POINT if ops.len() > 2 => {
+                module.entry_points.push(literal_string(&ops[2..]));
+            }
+            OP_DECORATE => {
+                module.decorations.insert((op(0), op(1)), op(2));
+            }
+            OP_MEMBER_DECORATE => {
+                module.member_decorations.insert((op(0), op(1), op(2)), op(3));
+            }
+            OP_TYPE_INT | OP_TYPE_FLOAT => {
+                module.types.insert(op(0), Type::Scalar { bytes: op(1) / 8, float: opcode == OP_TYPE_FLOAT });
+            }
+            OP_TYPE_VECTOR => {
+                module.types.insert(op(0), Type::Vector { component: op(1), count: op(2) });
+            }
+            OP_TYPE_MATRIX => {
+                module.types.insert(op(0), Type::Matrix { column: op(1), count: op(2) });
+            }
+            OP_TYPE_IMAGE => {
+                module.types.insert(op(0), Type::Image { dim: op(2) });
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                module.types.insert(op(0), Type::SampledImage { image: op(1) });
+            }
+            OP_TYPE_ARRAY => {
+                module.types.insert(op(0), Type::Array { element: op(1), length_id: op(2) });
+            }
+            OP_TYPE_STRUCT => {
+                module.types.insert(op(0), Type::Struct { members: ops[1..].to_vec() });
+            }
+            OP_TYPE_POINTER => {
+                module.types.insert(op(0), Type::Pointer { pointee: op(2) });
+            }
+            OP_CONSTANT => {
+                module.constants.insert(op(1), op(2));
+            }
+            OP_VARIABLE => {
+                module.variables.push((op(0), op(1), op(2)));
+            }
+            _ => {}
+        }
+        i += count;
+    }
+
+    let mut reflection = Reflection { entry_points: module.entry_points.clone(), ..Default::default() };
+    for &(pointer, variable, storage) in &module.variables {
+        let pointee = match module.types.get(&pointer) {
+            Some(Type::Pointer { pointee }) => *pointee,
+            _ => continue,
+        };
+
+        if storage == STORAGE_PUSH_CONSTANT {
+            reflection.push_constant_size = Some(module.size_of(pointee));
+            continue;
+        }
+
+        // Built-ins (gl_Position, gl_FragCoord) carry no Location
+        if storage == STORAGE_INPUT || storage == STORAGE_OUTPUT {
+            if let Some(&location) = module.decorations.get(&(variable, DECORATION_LOCATION)) {
+                let varying = Varying {
+                    location,
+                    type_name: module.type_name(pointee),
+                    name: module.names.get(&variable).filter(|n| !n.is_empty()).cloned(),
+                };
+                if storage == STORAGE_INPUT {
+                    reflection.inputs.push(varying);
+                } else {
+                    reflection.outputs.push(varying);
+                }
+            }
+            continue;
+        }
+
+        let kind = match storage {
+            STORAGE_UNIFORM if module.decorations.contains_key(&(pointee, DECORATION_BUFFER_BLOCK)) => BindingKind::StorageBuffer,
+            STORAGE_UNIFORM => BindingKind::UniformBuffer { size: module.size_of(pointee) },
+            STORAGE_STORAGE_BUFFER => BindingKind::StorageBuffer,
+            STORAGE_UNIFORM_CONSTANT => BindingKind::Texture { cube: module.is_cube(pointee) },
+            _ => continue,
+        };
+        reflection.bindings.push(Binding {
+            set: module.decorations.get(&(variable, DECORATION_DESCRIPTOR_SET)).copied().unwrap_or(0),
+            binding: module.decorations.get(&(variable, DECORATION_BINDING)).copied().unwrap_or(0),
+            kind,
+        });
+    }
+    reflection.bindings.sort_by_key(|b| (b.set, b.binding));
+    reflection.inputs.sort_by_key(|v| v.location);
+    reflection.outputs.sort_by_key(|v| v.location);
+    Ok(reflection)
+}
+
+impl Module {
+    /// Whether a sampled image or image type has the Cube dimension
+    fn is_cube(&self, id: u32) -> bool {
+        match self.types.get(&id) {
+            Some(Type::SampledImage { image }) => self.is_cube(*image),
+            Some(Type::Image { dim }) => *dim == DIM_CUBE,
+            _ => false,
+        }
+    }
+
+    /// GLSL name of a scalar, vector or matrix type (`float`, `ivec3`, `mat4`)
+    fn type_name(&self, id: u32) -> String {
+        match self.types.get(&id) {
+            Some(Type::Scalar { float: true, bytes: 8 }) => "double".to_string(),
+            Some(Type::Scalar { float: true, .. }) => "float".to_string(),
+            Some(Type::Scalar { .. }) => "int".to_string(),
+            Some(Type::Vector { component, count }) => match self.types.get(component) {
+                Some(Type::Scalar { float: true, .. }) => format!("vec{}", count),
+                _ => format!("ivec{}", count),
+            },
+            Some(Type::Matrix { count, .. }) => format!("mat{}", count),
+            Some(Type::Array { element, length_id }) => {
+                let length = self.constants.get(length_id).copied().unwrap_or(1);
+                format!("{}[{}]", self.type_name(*element), length)
+            }
+            Some(Type::Struct { .. }) => "struct".to_string(),
+            _ => "?".to_string(),
+        }
+    }
+
+    /// Byte size of a type as laid out in an explicitly offset block
+    fn size_of(&self, id: u32) -> u32 {
+        match self.types.get(&id) {
+            Some(Type::Scalar { bytes, .. }) => *bytes,
+            Some(Type::Vector { component, count }) => self.size_of(*component) * count,
+            Some(Type::Matrix { column, count }) => {
+                // Members carry a MatrixStride (handled below); otherwise vec3
+                // columns are padded to vec4 as in std140/std430
+                let stride = match self.types.get(column) {
+                    Some(Type::Vector { component, count: 3 }) => self.size_of(*component) * 4,
+                    _ => self.size_of(*column),
+                };
+                stride * count
+            }
+            Some(Type::Array { element, length_id }) => {
+                let length = self.constants.get(length_id).copied().unwrap_or(1);
+                let stride = self.decorations
+                    .get(&(id, DECORATION_ARRAY_STRIDE))
+                    .copied()
+                    .unwrap_or_else(|| self.size_of(*element));
+                stride * length
+            }
+            Some(Type::Struct { members }) => members
+                .iter()
+                .enumerate()
+                .map(|(m, &member)| {
+                    let offset = self.member_decorations
+                        .get(&(id, m as u32, DECORATION_OFFSET))
+                        .copied()
+                        .unwrap_or(0);
+                    let size = match (self.types.get(&member), self.member_decorations.get(&(id, m as u32, DECORATION_MATRIX_STRIDE))) {
+                        (Some(Type::Matrix { count, .. }), Some(stride)) => stride * count,
+                        _ => self.size_of(member),
+                    };
+                    offset + size
+                })
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+/// Decode a nul-terminated literal string packed into words
+fn literal_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .take_while(|&b| b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Reinterpret SPIR-V bytes as words (little endian, as glslang writes them)
+pub fn words_from_bytes(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inst(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    #[test]
+    fn test_push_constants_and_bindings() {
+        let mut words = vec![MAGIC, 0x0001_0000, 0, 100, 0];
+        // OpEntryPoint Fragment %9 "mainImage"
+        let name = u32::from_le_bytes(*b"main");
+        let suffix = u32::from_le_bytes(*b"Imag");
+        words.extend(inst(OP_ENTRY_POINT, &[4, 9, name, suffix, u32::from(b'e')]));
+        // %1 float, %2 vec4, %3 struct { vec4; float } (push block), %4 ptr, %5 var
+        words.extend(inst(OP_TYPE_FLOAT, &[1, 32]));
+        words.extend(inst(OP_TYPE_VECTOR, &[2, 1, 4]));
+        words.extend(inst(OP_MEMBER_DECORATE, &[3, 0, DECORATION_OFFSET, 0]));
+        words.extend(inst(OP_MEMBER_DECORATE, &[3, 1, DECORATION_OFFSET, 16]));
+        words.extend(inst(OP_TYPE_STRUCT, &[3, 2, 1]));
+        words.extend(inst(OP_TYPE_POINTER, &[4, STORAGE_PUSH_CONSTANT, 3]));
+        words.extend(inst(OP_VARIABLE, &[4, 5, STORAGE_PUSH_CONSTANT]));
+        // Same struct as a uniform block at set 0, binding 2
+        words.extend(inst(OP_DECORATE, &[7, DECORATION_DESCRIPTOR_SET, 0]));
+        words.extend(inst(OP_DECORATE, &[7, DECORATION_BINDING, 2]));
+        words.extend(inst(OP_TYPE_POINTER, &[6, STORAGE_UNIFORM, 3]));
+        words.extend(inst(OP_VARIABLE, &[6, 7, STORAGE_UNIFORM]));
+
+        let r = reflect(&words).unwrap();
+        assert_eq!(r.push_constant_size, Some(20));
+        assert_eq!(r.bindings, vec![Binding { set: 0, binding: 2, kind: BindingKind::UniformBuffer { size: 20 } }]);
+        assert_eq!(r.entry_points, vec!["mainImage".to_string()]);
+        assert!(r.expect_entry_point("mainImage", "Fragment").is_ok());
+        assert!(r.expect_entry_point("main", "Fragment").is_err());
+        assert!(reflect(&[0, 0, 0, 0, 0]).is_err());
+
+        let err = r.check_descriptor_layout(64).unwrap_err();
+        assert!(err.contains("set 0, binding 2"), "{}", err);
+        let fixed = Reflection { bindings: vec![Binding { set: 0, binding: 0, kind: BindingKind::UniformBuffer { size: 20 } }], ..r };
+        assert!(fixed.check_descriptor_layout(64).is_ok());
+        assert!(fixed.check_descriptor_layout(16).is_err());
+        let user = Reflection { bindings: vec![Binding { set: 0, binding: USER_BINDING, kind: BindingKind::UniformBuffer { size: 20 } }], ..fixed };
+        assert!(user.check_descriptor_layout(16).is_ok());
+    }
+
+    #[test]
+    fn test_cube_sampler() {
+        let mut words = vec![MAGIC, 0x0001_0000, 0, 100, 0];
+        // %1 float, %2 image Cube, %3 sampled image, %4 ptr, %5 var at binding 1
+        words.extend(inst(OP_TYPE_FLOAT, &[1, 32]));
+        words.extend(inst(OP_TYPE_IMAGE, &[2, 1, DIM_CUBE, 0, 0, 0, 1, 0]));
+        words.extend(inst(OP_TYPE_SAMPLED_IMAGE, &[3, 2]));
+        words.extend(inst(OP_TYPE_POINTER, &[4, STORAGE_UNIFORM_CONSTANT, 3]));
+        words.extend(inst(OP_DECORATE, &[5, DECORATION_BINDING, 1]));
+        words.extend(inst(OP_VARIABLE, &[4, 5, STORAGE_UNIFORM_CONSTANT]));
+
+        let r = reflect(&words).unwrap();
+        assert_eq!(r.bindings, vec![Binding { set: 0, binding: 1, kind: BindingKind::Texture { cube: true } }]);
+        assert!(r.check_descriptor_layout(64).is_ok());
+        assert!(r.check_channel0(true).is_ok());
+        assert!(r.check_channel0(false).unwrap_err().contains("samplerCube"));
+    }
+
+    /// A module with one `vecN` variable of `storage` at `location`
+    fn varying_module(storage: u32, location: u32, count: u32, name: &[u8; 4]) -> Reflection {
+        let mut words = vec![MAGIC, 0x0001_0000, 0, 100, 0];
+        words.extend(inst(OP_NAME, &[5, u32::from_le_bytes(*name), 0]));
+        words.extend(inst(OP_DECORATE, &[5, DECORATION_LOCATION, location]));
+        words.extend(inst(OP_TYPE_FLOAT, &[1, 32]));
+        words.extend(inst(OP_TYPE_VECTOR, &[2, 1, count]));
+        words.extend(inst(OP_TYPE_POINTER, &[4, storage, 2]));
+        words.extend(inst(OP_VARIABLE, &[4, 5, storage]));
+        reflect(&words).unwrap()
+    }
+
+    #[test]
+    fn test_stage_interface() {
+        let vertex = varying_module(STORAGE_OUTPUT, 0, 2, b"frag");
+        assert_eq!(
+            vertex.outputs,
+            vec![Varying { location: 0, type_name: "vec2".to_string(), name: Some("frag".to_string()) }]
+        );
+
+        // Matching input, and a fragment shader that only uses gl_FragCoord
+        assert_eq!(vertex.check_stage_interface(&varying_module(STORAGE_INPUT, 0, 2, b"uv\0\0")), Ok(vec![]));
+        assert_eq!(vertex.check_stage_interface(&Reflection::default()), Ok(vec![]));
+
+        let err = vertex.check_stage_interface(&varying_module(STORAGE_INPUT, 0, 4, b"uv\0\0")).unwrap_err();
+        assert!(err.contains("reads vec4 at location 0") && err.contains("`layout(location = 0) in vec2 frag`"), "{}", err);
+        let err = vertex.check_stage_interface(&varying_module(STORAGE_INPUT, 1, 2, b"uv\0\0")).unwrap_err();
+        assert!(err.contains("writes nothing there (it writes `layout(location = 0) in vec2 frag`)"), "{}", err);
+
+        // With location 1 written too, the unread location 0 only warns
+        let fragment = varying_module(STORAGE_INPUT, 1, 2, b"uv\0\0");
+        let both = Reflection { outputs: vec![vertex.outputs[0].clone(), Varying { location: 1, ..vertex.outputs[0].clone() }], ..vertex };
+        let warnings = both.check_stage_interface(&fragment).unwrap();
+        assert_eq!(warnings, vec!["fragment shader missing `layout(location = 0) in vec2 frag`; the vertex output is unused".to_string()]);
+    }
+}
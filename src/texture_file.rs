@@ -0,0 +1,486 @@
+// Texture files for the iChannel0 slot: DDS and KTX2 containers
+//
+// Both are parsed by hand to keep dependencies small. Only what the renderer
+// can upload directly is accepted: a 2D image or a cubemap with its mip
+// chain, in RGBA8/BGRA8 or one of the BC1-BC7 block-compressed formats.
+// Supercompressed KTX2 (Basis, zstd) is rejected with a hint to re-export
+// without it. Cubemaps can also be assembled from six face files or
+// converted from an equirectangular panorama.
+
+use std::path::Path;
+
+use crate::bc_decode;
+use crate::error::MetalshaderError;
+
+/// Pixel formats a channel texture can be in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8,
+    Rgba8Srgb,
+    Bgra8,
+    Bgra8Srgb,
+    Bc1,
+    Bc1Srgb,
+    Bc2,
+    Bc2Srgb,
+    Bc3,
+    Bc3Srgb,
+    Bc4,
+    Bc5,
+    Bc6hUfloat,
+    Bc7,
+    Bc7Srgb,
+}
+
+impl TextureFormat {
+    /// The matching `VkFormat` value
+    pub fn vk_raw(self) -> i32 {
+        match self {
+            Self::Rgba8 => 37,
+            Self::Rgba8Srgb => 43,
+            Self::Bgra8 => 44,
+            Self::Bgra8Srgb => 50,
+            Self::Bc1 => 133,
+            Self::Bc1Srgb => 134,
+            Self::Bc2 => 135,
+            Self::Bc2Srgb => 136,
+            Self::Bc3 => 137,
+            Self::Bc3Srgb => 138,
+            Self::Bc4 => 139,
+            Self::Bc5 => 141,
+            Self::Bc6hUfloat => 143,
+            Self::Bc7 => 145,
+            Self::Bc7Srgb => 146,
+        }
+    }
+
+    fn from_vk_raw(raw: u32) -> Option<Self> {
+        Some(match raw {
+            37 => Self::Rgba8,
+            43 => Self::Rgba8Srgb,
+            44 => Self::Bgra8,
+            50 => Self::Bgra8Srgb,
+            // BC1_RGB shares the RGBA block layout; alpha decodes as opaque
+            131 | 133 => Self::Bc1,
+            132 | 134 => Self::Bc1Srgb,
+            135 => Self::Bc2,
+            136 => Self::Bc2Srgb,
+            137 => Self::Bc3,
+            138 => Self::Bc3Srgb,
+            139 => Self::Bc4,
+            141 => Self::Bc5,
+            143 => Self::Bc6hUfloat,
+            145 => Self::Bc7,
+            146 => Self::Bc7Srgb,
+            _ => return None,
+        })
+    }
+
+    fn from_dxgi(dxgi: u32) -> Option<Self> {
+        Some(match dxgi {
+            28 => Self::Rgba8,
+            29 => Self::Rgba8Srgb,
+            87 => Self::Bgra8,
+            91 => Self::Bgra8Srgb,
+            71 => Self::Bc1,
+            72 => Self::Bc1Srgb,
+            74 => Self::Bc2,
+            75 => Self::Bc2Srgb,
+            77 => Self::Bc3,
+            78 => Self::Bc3Srgb,
+            80 => Self::Bc4,
+            83 => Self::Bc5,
+            95 => Self::Bc6hUfloat,
+            98 => Self::Bc7,
+            99 => Self::Bc7Srgb,
+            _ => return None,
+        })
+    }
+
+    /// Bytes per 4x4 block, or `None` for uncompressed formats
+    pub fn block_bytes(self) -> Option<usize> {
+        match self {
+            Self::Rgba8 | Self::Rgba8Srgb | Self::Bgra8 | Self::Bgra8Srgb => None,
+            Self::Bc1 | Self::Bc1Srgb | Self::Bc4 => Some(8),
+            _ => Some(16),
+        }
+    }
+
+    pub fn is_srgb(self) -> bool {
+        matches!(
+            self,
+            Self::Rgba8Srgb | Self::Bgra8Srgb | Self::Bc1Srgb | Self::Bc2Srgb | Self::Bc3Srgb | Self::Bc7Srgb
+        )
+    }
+
+    /// Bytes of one `width` x `height` image in this format
+    pub fn image_bytes(self, width: u32, height: u32) -> usize {
+        match self.block_bytes() {
+            Some(block) => width.div_ceil(4) as usize * height.div_ceil(4) as usize * block,
+            None => width as usize * height as usize * 4,
+        }
+    }
+}
+
+/// A decoded container: level 0 is the full-size image, each following level
+/// halves both dimensions (rounding down, at least 1)
+#[derive(Debug, Clone)]
+pub struct TextureData {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// 1, or 6 for a cubemap in Vulkan face order (+X, -X, +Y, -Y, +Z, -Z)
+    pub faces: u32,
+    /// Per mip level, the images of all faces back to back
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl TextureData {
+    /// Size of mip `level`
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    pub fn is_cube(&self) -> bool {
+        self.faces == 6
+    }
+
+    /// Decode block-compressed data to RGBA8 on the CPU, for devices that
+    /// can't sample the format. Uncompressed data is returned as is.
+    pub fn decode_to_rgba(&self) -> Result<TextureData, String> {
+        let decode: fn(&[u8], u32, u32) -> Vec<u8> = match self.format {
+            TextureFormat::Rgba8 | TextureFormat::Rgba8Srgb | TextureFormat::Bgra8 | TextureFormat::Bgra8Srgb => {
+                return Ok(self.clone());
+            }
+            TextureFormat::Bc1 | TextureFormat::Bc1Srgb => bc_decode::decode_bc1,
+            TextureFormat::Bc2 | TextureFormat::Bc2Srgb => bc_decode::decode_bc2,
+            TextureFormat::Bc3 | TextureFormat::Bc3Srgb => bc_decode::decode_bc3,
+            TextureFormat::Bc4 => bc_decode::decode_bc4,
+            TextureFormat::Bc5 => bc_decode::decode_bc5,
+            TextureFormat::Bc6hUfloat | TextureFormat::Bc7 | TextureFormat::Bc7Srgb => {
+                return Err(format!(
+                    "{:?} has no CPU decoder; re-encode the texture as BC3 or RGBA8", self.format
+                ));
+            }
+        };
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let (w, h) = self.level_size(level);
+                data.chunks(self.format.image_bytes(w, h)).flat_map(|face| decode(face, w, h)).collect()
+            })
+            .collect();
+        let format = if self.format.is_srgb() { TextureFormat::Rgba8Srgb } else { TextureFormat::Rgba8 };
+        Ok(TextureData { format, levels, ..*self })
+    }
+}
+
+/// Length of a full mip chain down to 1x1 for a `width` x `height` image
+pub fn mip_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Load a `.dds` or `.ktx2` file
+pub fn load(path: &Path) -> Result<TextureData, MetalshaderError> {
+    let bytes = std::fs::read(path)?;
+    let parsed = if bytes.starts_with(KTX2_IDENTIFIER) {
+        parse_ktx2(&bytes)
+    } else if bytes.starts_with(b"DDS ") {
+        parse_dds(&bytes)
+    } else {
+        Err("not a DDS or KTX2 file".to_string())
+    };
+    parsed.map_err(|reason| MetalshaderError::InvalidTexture { path: path.to_path_buf(), reason })
+}
+
+/// Load a `--channel0` value: one file, or six comma-separated cube face
+/// files in +X, -X, +Y, -Y, +Z, -Z order. With `equirect_cube` a single 2D
+/// file is an equirectangular panorama and is converted to a cubemap.
+pub fn load_channel(spec: &str, equirect_cube: bool) -> Result<TextureData, MetalshaderError> {
+    let invalid = |reason| MetalshaderError::InvalidTexture { path: spec.into(), reason };
+    let paths: Vec<&str> = spec.split(',').map(str::trim).collect();
+    match paths.len() {
+        1 if equirect_cube => load(Path::new(spec))?.equirect_to_cube().map_err(invalid),
+        1 => load(Path::new(spec)),
+        6 => {
+            let faces = paths.iter().map(|path| load(Path::new(path))).collect::<Result<Vec<_>, _>>()?;
+            cube_from_faces(&faces).map_err(invalid)
+        }
+        n => Err(invalid(format!("expected one file or six cube faces, got {}", n))),
+    }
+}
+
+/// Assemble a cubemap from six square 2D images of the same format and size.
+/// Mip levels beyond the shortest chain are dropped.
+pub fn cube_from_faces(faces: &[TextureData]) -> Result<TextureData, String> {
+    let first = faces.first().ok_or("no cube faces")?;
+    if faces.len() != 6 {
+        return Err(format!("a cubemap needs 6 faces, got {}", faces.len()));
+    }
+    if first.width != first.height {
+        return Err(format!("cube faces must be square, got {}x{}", first.width, first.height));
+    }
+    if let Some(face) = faces
+        .iter()
+        .find(|f| (f.format, f.width, f.height, f.faces) != (first.format, first.width, first.height, 1))
+    {
+        return Err(format!(
+            "cube faces differ: {:?} {}x{} vs {:?} {}x{}",
+            first.format, first.width, first.height, face.format, face.width, face.height
+        ));
+    }
+    let level_count = faces.iter().map(|f| f.levels.len()).min().unwrap_or(1);
+    let levels = (0..level_count)
+        .map(|level| faces.iter().flat_map(|f| f.levels[level].iter().copied()).collect())
+        .collect();
+    Ok(TextureData { faces: 6, levels, ..*first })
+}
+
+impl TextureData {
+    /// Resample an equirectangular panorama (longitude across, latitude down)
+    /// into a cubemap with faces a quarter of the panorama's width. Only level
+    /// 0 is used; compressed data is decoded first.
+    pub fn equirect_to_cube(&self) -> Result<TextureData, String> {
+        if self.is_cube() {
+            return Err("already a cubemap".to_string());
+        }
+        let rgba = self.decode_to_rgba()?;
+        let (src, width, height) = (&rgba.levels[0], self.width as usize, self.height as usize);
+        let texel = |x: usize, y: usize| &src[(y * width + x) * 4..(y * width + x) * 4 + 4];
+        // Bilinear, wrapping in longitude and clamping in latitude
+        let sample = |u: f32, v: f32| -> [u8; 4] {
+            let (fx, fy) = (u * width as f32 - 0.5, (v * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0));
+            let (x0, y0) = (fx.floor(), fy.floor());
+            let (tx, ty) = (fx - x0, fy - y0);
+            let xs = [x0.rem_euclid(width as f32) as usize, (x0 + 1.0).rem_euclid(width as f32) as usize];
+            let ys = [y0 as usize, (y0 as usize + 1).min(height - 1)];
+            std::array::from_fn(|c| {
+                let row = |y| texel(xs[0], y)[c] as f32 * (1.0 - tx) + texel(xs[1], y)[c] as f32 * tx;
+                (row(ys[0]) * (1.0 - ty) + row(ys[1]) * ty).round() as u8
+            })
+        };
+
+        let size = (self.width / 4).max(1);
+        let mut level = Vec::with_capacity(size as usize * size as usize * 4 * 6);
+        for face in 0..6 {
+            for y in 0..size {
+                for x in 0..size {
+                    let sc = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let tc = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let [dx, dy, dz] = cube_direction(face, sc, tc);
+                    let u = 0.5 + dx.atan2(-dz) / std::f32::consts::TAU;
+                    let v = 0.5 - dy.atan2(dx.hypot(dz)) / std::f32::consts::PI;
+                    level.extend_from_slice(&sample(u, v));
+                }
+            }
+        }
+        Ok(TextureData { format: rgba.format, width: size, height: size, faces: 6, levels: vec![level] })
+    }
+}
+
+/// Direction through face coordinates (`sc`, `tc`) in [-1, 1] of cube face
+/// `face`, following the Vulkan/GL face orientation (y up)
+fn cube_direction(face: usize, sc: f32, tc: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -tc, -sc],
+        1 => [-1.0, -tc, sc],
+        2 => [sc, 1.0, tc],
+        3 => [sc, -1.0, -tc],
+        4 => [sc, -tc, 1.0],
+        _ => [-sc, -tc, -1.0],
+    }
+}
+
+const KTX2_IDENTIFIER: &[u8] = &[0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format!("truncated header at byte {}", offset))
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, String> {
+    Ok(u32_at(bytes, offset)? as u64 | (u32_at(bytes, offset + 4)? as u64) << 32)
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<Vec<u8>, String> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| format!("image data runs past the end of the file ({} + {} bytes)", offset, len))
+}
+
+pub fn parse_ktx2(bytes: &[u8]) -> Result<TextureData, String> {
+    let vk_format = u32_at(bytes, 12)?;
+    let width = u32_at(bytes, 20)?;
+    let height = u32_at(bytes, 24)?.max(1);
+    let depth = u32_at(bytes, 28)?;
+    let layers = u32_at(bytes, 32)?;
+    let faces = u32_at(bytes, 36)?;
+    let level_count = u32_at(bytes, 40)?.max(1);
+    let supercompression = u32_at(bytes, 44)?;
+
+    let format = TextureFormat::from_vk_raw(vk_format)
+        .ok_or_else(|| format!("unsupported KTX2 vkFormat {}", vk_format))?;
+    if supercompression != 0 {
+        return Err(format!(
+            "supercompression scheme {} is not supported; export without Basis/zstd", supercompression
+        ));
+    }
+    if depth > 1 || layers > 1 || (faces != 1 && faces != 6) {
+        return Err("only 2D images and cubemaps are supported (no 3D or arrays)".to_string());
+    }
+    if width == 0 {
+        return Err("zero width".to_string());
+    }
+
+    let mut texture = TextureData { format, width, height, faces, levels: Vec::new() };
+    for level in 0..level_count as usize {
+        // Level index: byteOffset, byteLength, uncompressedByteLength (u64
+        // each); a level holds its faces back to back
+        let entry = 80 + level * 24;
+        let offset = u64_at(bytes, entry)? as usize;
+        let (w, h) = texture.level_size(level);
+        let expected = format.image_bytes(w, h) * faces as usize;
+        texture.levels.push(slice(bytes, offset, expected)?);
+    }
+    Ok(texture)
+}
+
+pub fn parse_dds(bytes: &[u8]) -> Result<TextureData, String> {
+    const DDPF_FOURCC: u32 = 0x4;
+    const DDPF_RGB: u32 = 0x40;
+
+    let height = u32_at(bytes, 12)?;
+    let width = u32_at(bytes, 16)?;
+    let mip_count = u32_at(bytes, 28)?.max(1);
+    let pf_flags = u32_at(bytes, 80)?;
+    let four_cc = bytes.get(84..88).ok_or("truncated pixel format")?;
+    let caps2 = u32_at(bytes, 112)?;
+    if width == 0 || height == 0 {
+        return Err("zero-sized image".to_string());
+    }
+    const DDSCAPS2_CUBEMAP: u32 = 0x200;
+    const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xfc00;
+    if caps2 & 0x200000 != 0 {
+        return Err("volume textures are not supported".to_string());
+    }
+    let mut faces = 1;
+    if caps2 & DDSCAPS2_CUBEMAP != 0 {
+        if caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES {
+            return Err("partial cubemaps are not supported".to_string());
+        }
+        faces = 6;
+    }
+
+    let mut data_offset = 128;
+    let format = if pf_flags & DDPF_FOURCC != 0 {
+        match four_cc {
+            b"DXT1" => TextureFormat::Bc1,
+            b"DXT2" | b"DXT3" => TextureFormat::Bc2,
+            b"DXT4" | b"DXT5" => TextureFormat::Bc3,
+            b"ATI1" | b"BC4U" => TextureFormat::Bc4,
+            b"ATI2" | b"BC5U" => TextureFormat::Bc5,
+            b"DX10" => {
+                let dxgi = u32_at(bytes, 128)?;
+                let array_size = u32_at(bytes, 140)?;
+                // D3D10_RESOURCE_MISC_TEXTURECUBE
+                if u32_at(bytes, 136)? & 0x4 != 0 {
+                    faces = 6;
+                }
+                if array_size > 1 {
+                    return Err("texture arrays are not supported".to_string());
+                }
+                data_offset += 20;
+                TextureFormat::from_dxgi(dxgi).ok_or_else(|| format!("unsupported DXGI format {}", dxgi))?
+            }
+            other => return Err(format!("unsupported FourCC '{}'", String::from_utf8_lossy(other))),
+        }
+    } else if pf_flags & DDPF_RGB != 0 && u32_at(bytes, 88)? == 32 {
+        match u32_at(bytes, 92)? {
+            0x00ff_0000 => TextureFormat::Bgra8,
+            0x0000_00ff => TextureFormat::Rgba8,
+            mask => return Err(format!("unsupported 32-bit channel layout (red mask {:#x})", mask)),
+        }
+    } else {
+        return Err("unsupported pixel format (need BC1-BC7 or 32-bit RGBA)".to_string());
+    };
+
+    // DDS stores each face's whole mip chain in turn; regroup by level
+    let mut texture = TextureData { format, width, height, faces, levels: vec![Vec::new(); mip_count as usize] };
+    let mut offset = data_offset;
+    for _ in 0..faces {
+        for level in 0..mip_count as usize {
+            let (w, h) = texture.level_size(level);
+            let len = format.image_bytes(w, h);
+            let face = slice(bytes, offset, len)?;
+            texture.levels[level].extend_from_slice(&face);
+            offset += len;
+        }
+    }
+    Ok(texture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn test_parse_dds_bc1_mip_chain() {
+        // 8x8 DXT1 with 3 levels: 4 + 1 + 1 blocks of 8 bytes
+        let mut bytes = vec![0u8; 128 + 48];
+        bytes[..4].copy_from_slice(b"DDS ");
+        put(&mut bytes, 12, 8);
+        put(&mut bytes, 16, 8);
+        put(&mut bytes, 28, 3);
+        put(&mut bytes, 80, 0x4);
+        bytes[84..88].copy_from_slice(b"DXT1");
+        let texture = parse_dds(&bytes).unwrap();
+        assert_eq!(texture.format, TextureFormat::Bc1);
+        assert_eq!(texture.levels.iter().map(Vec::len).collect::<Vec<_>>(), vec![32, 8, 8]);
+        assert_eq!((mip_count(8, 8), mip_count(1024, 3), mip_count(1, 1)), (4, 11, 1));
+        // One byte short of the last level
+        assert!(parse_dds(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_parse_ktx2_rejects_supercompression() {
+        let mut bytes = vec![0u8; 80 + 24 + 16];
+        bytes[..12].copy_from_slice(KTX2_IDENTIFIER);
+        put(&mut bytes, 12, 145); // BC7_UNORM
+        put(&mut bytes, 20, 4);
+        put(&mut bytes, 24, 4);
+        put(&mut bytes, 36, 1);
+        put(&mut bytes, 40, 1);
+        put(&mut bytes, 80, 104);
+        let texture = parse_ktx2(&bytes).unwrap();
+        assert_eq!((texture.format, texture.levels[0].len()), (TextureFormat::Bc7, 16));
+        put(&mut bytes, 44, 2); // zstd
+        assert!(parse_ktx2(&bytes).unwrap_err().contains("supercompression"));
+    }
+
+    #[test]
+    fn test_equirect_to_cube() {
+        // Top half red, bottom half blue: +Y is red, -Y blue, side faces split
+        let (w, h) = (16u32, 8u32);
+        let pixels = (0..w * h).flat_map(|i| if i / w < h / 2 { [255, 0, 0, 255] } else { [0, 0, 255, 255] }).collect();
+        let panorama = TextureData { format: TextureFormat::Rgba8, width: w, height: h, faces: 1, levels: vec![pixels] };
+        let cube = panorama.equirect_to_cube().unwrap();
+        assert_eq!((cube.width, cube.faces, cube.levels[0].len()), (4, 6, 4 * 4 * 4 * 6));
+        let face = |f: usize| &cube.levels[0][f * 64..(f + 1) * 64];
+        assert!(face(2).chunks(4).all(|p| p == [255, 0, 0, 255]));
+        assert!(face(3).chunks(4).all(|p| p == [0, 0, 255, 255]));
+        // +Z: top row red, bottom row blue
+        assert_eq!(&face(4)[..4], &[255, 0, 0, 255]);
+        assert_eq!(&face(4)[60..], &[0, 0, 255, 255]);
+        assert!(cube_from_faces(&[panorama.clone(), panorama]).is_err());
+    }
+}
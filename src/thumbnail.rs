@@ -0,0 +1,189 @@
+// `--thumbnails DIR`: one `<name>.png` per shader, for launchers and pickers
+//
+// `Thumbnailer` renders a shader's frame at `--start-time` in a headless
+// `VulkanRenderer` of `--thumb-size`; `--contact-sheet` tiles the same
+// frames. Thumbnails newer than everything their shader is built from are
+// kept, so rerunning only renders what changed. PNGs need the `png` feature.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use metalshader::options::{EntryPoints, Options, RenderFormat, RenderTiling};
+use metalshader::overlay::{Corner, Label};
+use metalshader::{mouse, MetalshaderError, RenderConfig, ShaderInfo, ShaderManager, ShaderToyUBO, VulkanRenderer};
+
+const PNG_FEATURE: &str = "PNG output needs the `png` feature (cargo build --release --features png)";
+
+/// Renders one frame per shader at a fixed size and time
+pub struct Thumbnailer {
+    renderer: VulkanRenderer,
+    config: RenderConfig,
+    size: (u32, u32),
+    ubo: ShaderToyUBO,
+}
+
+impl Thumbnailer {
+    pub fn new(options: &Options) -> Result<Self, MetalshaderError> {
+        let (width, height) = options.thumb_size;
+        let config = RenderConfig {
+            // Tightly packed rows to copy from
+            tiling: RenderTiling::Optimal,
+            format: RenderFormat::Rgba8,
+            software: options.software,
+            flip_y: options.flip_y,
+            frag_coord: options.frag_coord,
+            channel0_sampler: options.channel0_sampler,
+            ..RenderConfig::default()
+        };
+        let ubo = ShaderToyUBO {
+            i_resolution: [width as f32, height as f32, 1.0],
+            i_time: options.start_time as f32,
+            i_mouse: mouse::shadertoy_mouse([0.0, 0.0], [0.0, 0.0], false, false),
+            i_frame_rate: 60.0,
+            ..Default::default()
+        };
+        let renderer = VulkanRenderer::new(width, height, config)?;
+        Ok(Self { renderer, config, size: (width, height), ubo })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// `shader`'s frame as opaque RGBA8 rows, with its name in the top-left
+    /// corner if `label`
+    pub fn render(&mut self, shader: &ShaderInfo, entry_points: &EntryPoints, label: bool)
+        -> Result<Vec<u8>, MetalshaderError>
+    {
+        let (width, height) = self.size;
+        let labels = if label { vec![Label::layout(&shader.name, Corner::TopLeft, width, height)] } else { Vec::new() };
+        let renderer = &mut self.renderer;
+        let result = renderer
+            .set_overlay(labels)
+            .and_then(|()| renderer.set_uniforms(&shader.uniforms()))
+            .and_then(|()| renderer.load_shader(&shader.vert_path, &shader.frag_path, entry_points))
+            .and_then(|()| renderer.render_frame(&self.ubo));
+        if let Err(e) = result {
+            // A hung frame may still own the device; go on with a fresh one
+            if matches!(
+                e,
+                MetalshaderError::FrameTimeout(_) | MetalshaderError::VulkanError(ash::vk::Result::ERROR_DEVICE_LOST)
+            ) {
+                self.renderer = VulkanRenderer::new(width, height, self.config)?;
+            }
+            return Err(e);
+        }
+
+        let (frame, row_pitch) = (self.renderer.get_frame_buffer(), self.renderer.get_row_pitch());
+        let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height as usize {
+            for bgra in frame[y * row_pitch..][..width as usize * 4].chunks_exact(4) {
+                rgba.extend_from_slice(&[bgra[2], bgra[1], bgra[0], 255]);
+            }
+        }
+        Ok(rgba)
+    }
+}
+
+pub fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(options.thumbnails.as_deref().unwrap_or_default());
+    if !cfg!(feature = "png") {
+        return Err(PNG_FEATURE.into());
+    }
+    std::fs::create_dir_all(dir)?;
+
+    let mut shader_manager = ShaderManager::new();
+    let search_dirs: Vec<&str> = options.search_dirs.iter().map(String::as_str).collect();
+    shader_manager.scan_shaders(&search_dirs)?;
+    if shader_manager.is_empty() {
+        return Err(format!("No compiled shaders found in {}", options.search_dirs.join(" ")).into());
+    }
+
+    let mut thumbnailer = Thumbnailer::new(options)?;
+    let (width, height) = thumbnailer.size();
+    let (mut written, mut fresh, mut failed) = (0, 0, 0);
+    for index in 0..shader_manager.len() {
+        let shader = shader_manager.get(index).unwrap();
+        let path = dir.join(format!("{}.png", shader.name));
+        if is_fresh(&path, &inputs(shader)) {
+            fresh += 1;
+            continue;
+        }
+        match thumbnailer.render(shader, &options.entry_points, false) {
+            Ok(rgba) => {
+                write_png(&path, &rgba, width, height)?;
+                println!("Wrote {}", path.display());
+                written += 1;
+            }
+            Err(e) => {
+                eprintln!("Skipping '{}': {}", shader.name, e);
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "{} written, {} up to date, {} failed ({}x{} at iTime {:.3})",
+        written, fresh, failed, width, height, options.start_time
+    );
+    Ok(())
+}
+
+/// Files `shader`'s frame depends on: its SPIR-V, source and sidecar
+fn inputs(shader: &ShaderInfo) -> Vec<PathBuf> {
+    let sidecar = shader.frag_path.with_file_name(format!("{}.json", shader.name));
+    [Some(shader.frag_path.clone()), Some(shader.vert_path.clone()), shader.source_path(), Some(sidecar)]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Whether `output` exists and is newer than every existing `input`
+fn is_fresh(output: &Path, inputs: &[PathBuf]) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let Some(output_time) = modified(output) else {
+        return false;
+    };
+    inputs
+        .iter()
+        .filter_map(|input| modified(input))
+        .all(|input_time: SystemTime| input_time < output_time)
+}
+
+/// Write opaque RGBA8 `pixels` as a PNG
+#[cfg(feature = "png")]
+pub fn write_png(path: &Path, pixels: &[u8], width: u32, height: u32) -> Result<(), Box<dyn Error>> {
+    image::save_buffer(path, pixels, width, height, image::ColorType::Rgba8)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "png"))]
+pub fn write_png(_path: &Path, _pixels: &[u8], _width: u32, _height: u32) -> Result<(), Box<dyn Error>> {
+    Err(PNG_FEATURE.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_is_fresh() {
+        let dir = std::env::temp_dir().join(format!("metalshader-thumbnail-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("a.frag"), dir.join("a.png"));
+        let touch = |path: &Path, time: SystemTime| {
+            std::fs::File::create(path).unwrap().set_modified(time).unwrap();
+        };
+        let now = SystemTime::now();
+
+        touch(&input, now);
+        assert!(!is_fresh(&output, &[input.clone()]));
+        touch(&output, now + Duration::from_secs(10));
+        assert!(is_fresh(&output, &[input.clone(), dir.join("missing.json")]));
+        touch(&input, now + Duration::from_secs(20));
+        assert!(!is_fresh(&output, &[input.clone()]));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+// Uniform block layouts shared with the shaders
+
+use serde::Deserialize;
+
+/// Number of `iParams` floats, declared as `vec4 iParams[2]`
+pub const PARAM_COUNT: usize = 8;
+
+/// Byte offset of `iFrameRate`, after the macOS viewer's extended block
+pub const FRAME_RATE_OFFSET: usize = 72;
+
+/// Byte offset of `iParams`, after `iFrameRate`
+pub const PARAMS_OFFSET: usize = 80;
+
+/// The ShaderToy uniforms every shader sees at set 0, binding 0
+///
+/// Matches `UniformBufferObject` in the generated vertex shader and the
+/// README examples (std140: `vec3` + `float` pack into 16 bytes). The macOS
+/// viewer's extended block starts with the same fields; its extra members
+/// read as zero here, so `iFrameRate` and `iParams` are at the same offsets
+/// on every platform.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ShaderToyUBO {
+    pub i_resolution: [f32; 3],
+    pub i_time: f32,
+    pub i_mouse: [f32; 4],
+    /// iScroll, iButton* and iPan of the macOS block
+    pub _macos: [f32; 10],
+    /// Rolling frames-per-second estimate (`clock::FrameRate`), after the
+    /// macOS members so blocks declared without it keep their layout
+    pub i_frame_rate: f32,
+    pub _pad: f32,
+    /// Free parameters for shaders, set over OSC (`--osc`)
+    pub i_params: [f32; PARAM_COUNT],
+}
+
+/// Binding (in set 0) of the block a shader's sidecar JSON fills
+/// (`"uniforms"`), for data the ShaderToy block has no field for
+pub const USER_BINDING: u32 = 5;
+
+/// Where that block starts in the renderers' uniform buffer, after the
+/// ShaderToy block; 256 meets every device's minUniformBufferOffsetAlignment
+pub const USER_UNIFORMS_OFFSET: usize = 256;
+
+/// Largest block the renderers provide at `USER_BINDING`
+pub const USER_UNIFORMS_BYTES: usize = 4096;
+
+/// One member of the sidecar uniform block, in declaration order:
+///
+/// ```json
+/// { "type": "vec4", "value": [1, 0, 0, 1, 0, 0, 1, 1] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UserUniform {
+    /// float, int, uint, vecN, ivecN, uvecN or matN
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// Components, column by column for matrices; more than one element's
+    /// worth makes an array (`vec4 palette[2]` above)
+    pub value: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Float,
+    Int,
+    Uint,
+}
+
+/// Scalar kind, components per column and columns of a GLSL type name
+fn parse_type(name: &str) -> Option<(Scalar, usize, usize)> {
+    let size = |n: &str| n.parse().ok().filter(|n| (2..=4).contains(n));
+    match name {
+        "float" => Some((Scalar::Float, 1, 1)),
+        "int" => Some((Scalar::Int, 1, 1)),
+        "uint" => Some((Scalar::Uint, 1, 1)),
+        _ => {
+            if let Some(n) = name.strip_prefix("mat") {
+                return size(n).map(|n| (Scalar::Float, n, n));
+            }
+            let (scalar, n) = [("vec", Scalar::Float), ("ivec", Scalar::Int), ("uvec", Scalar::Uint)]
+                .into_iter()
+                .find_map(|(prefix, scalar)| Some((scalar, name.strip_prefix(prefix)?)))?;
+            size(n).map(|n| (scalar, n, 1))
+        }
+    }
+}
+
+/// The bytes of a `layout(std140, binding = 5) uniform` block declaring
+/// `members` in order, padded to a multiple of 16
+pub fn pack_user_uniforms(members: &[UserUniform]) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for member in members {
+        let (scalar, components, columns) = parse_type(&member.type_name)
+            .ok_or_else(|| format!("unknown uniform type '{}'", member.type_name))?;
+        let element = components * columns;
+        if member.value.is_empty() || member.value.len() % element != 0 {
+            return Err(format!(
+                "{} takes a multiple of {} values, got {}",
+                member.type_name, element, member.value.len()
+            ));
+        }
+        // Array elements and matrix columns are padded to a vec4; a single
+        // vector is aligned to its size, with vec3 aligned like vec4
+        let (align, stride) = if columns > 1 || member.value.len() > element {
+            (16, 16)
+        } else {
+            (if components == 1 { 4 } else { 4 * components.next_power_of_two() }, 4 * components)
+        };
+        bytes.resize(bytes.len().next_multiple_of(align), 0);
+        for column in member.value.chunks(components) {
+            let start = bytes.len();
+            for &value in column {
+                bytes.extend(match scalar {
+                    Scalar::Float => (value as f32).to_le_bytes(),
+                    Scalar::Int => (value as i32).to_le_bytes(),
+                    Scalar::Uint => (value as u32).to_le_bytes(),
+                });
+            }
+            bytes.resize(start + stride, 0);
+        }
+    }
+    bytes.resize(bytes.len().next_multiple_of(16), 0);
+    if bytes.len() > USER_UNIFORMS_BYTES {
+        return Err(format!("uniforms take {} bytes, at most {} are supported", bytes.len(), USER_UNIFORMS_BYTES));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_offset() {
+        assert_eq!(std::mem::offset_of!(ShaderToyUBO, i_frame_rate), FRAME_RATE_OFFSET);
+        assert_eq!(std::mem::offset_of!(ShaderToyUBO, i_params), PARAMS_OFFSET);
+        assert_eq!(std::mem::size_of::<ShaderToyUBO>(), PARAMS_OFFSET + 4 * PARAM_COUNT);
+    }
+
+    #[test]
+    fn test_pack_user_uniforms_std140() {
+        let member = |type_name: &str, value: &[f64]| UserUniform { type_name: type_name.to_string(), value: value.to_vec() };
+        let words = |bytes: Vec<u8>| -> Vec<u32> {
+            bytes.chunks_exact(4).map(|w| u32::from_le_bytes(w.try_into().unwrap())).collect()
+        };
+        let one = 1f32.to_bits();
+        // float at 0, vec3 at 16 with an int packed after it at 28,
+        // float[2] at 32 with a 16-byte stride, mat2 columns at 64 and 80
+        let bytes = pack_user_uniforms(&[
+            member("float", &[1.0]),
+            member("vec3", &[1.0, 1.0, 1.0]),
+            member("int", &[-1.0]),
+            member("float", &[1.0, 1.0]),
+            member("mat2", &[1.0, 0.0, 0.0, 1.0]),
+        ])
+        .unwrap();
+        assert_eq!(
+            words(bytes),
+            [
+                one, 0, 0, 0, one, one, one, u32::MAX,
+                one, 0, 0, 0, one, 0, 0, 0,
+                one, 0, 0, 0, 0, one, 0, 0,
+            ]
+        );
+        // vec2 aligns to 8
+        assert_eq!(words(pack_user_uniforms(&[member("uint", &[3.0]), member("vec2", &[1.0, 1.0])]).unwrap()), [3, 0, one, one]);
+
+        assert!(pack_user_uniforms(&[member("vec5", &[0.0; 5])]).is_err());
+        assert!(pack_user_uniforms(&[member("vec4", &[0.0; 6])]).is_err());
+        assert!(pack_user_uniforms(&[member("vec4", &[0.0; 4 * 257])]).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+// Where the rendered frame lands on the output
+//
+// Normally the whole display or window. With `--aspect W:H` it is the largest
+// centered rectangle of that aspect ratio, and the rest is left black.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn full(width: u32, height: u32) -> Self {
+        Self { x: 0, y: 0, width, height }
+    }
+
+    /// Largest centered `aspect` rectangle inside `width` x `height`, or the
+    /// whole output when `aspect` is `None`
+    pub fn letterbox(width: u32, height: u32, aspect: Option<(u32, u32)>) -> Self {
+        let Some((aw, ah)) = aspect.filter(|&(aw, ah)| aw > 0 && ah > 0) else {
+            return Self::full(width, height);
+        };
+        // Compare width/height against aw/ah without floating point
+        let (w, h) = if width as u64 * ah as u64 > height as u64 * aw as u64 {
+            ((height as u64 * aw as u64 / ah as u64) as u32, height)
+        } else {
+            (width, (width as u64 * ah as u64 / aw as u64) as u32)
+        };
+        let (w, h) = (w.max(1), h.max(1));
+        Self { x: (width - w.min(width)) / 2, y: (height - h.min(height)) / 2, width: w, height: h }
+    }
+
+    pub fn is_full(&self, width: u32, height: u32) -> bool {
+        *self == Self::full(width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letterbox_centers_aspect_rect() {
+        // 4:3 on 1920x1080: pillarboxed
+        assert_eq!(Viewport::letterbox(1920, 1080, Some((4, 3))), Viewport { x: 240, y: 0, width: 1440, height: 1080 });
+        // 21:9 on 1920x1080: letterboxed
+        assert_eq!(Viewport::letterbox(1920, 1080, Some((21, 9))), Viewport { x: 0, y: 129, width: 1920, height: 822 });
+        assert!(Viewport::letterbox(1920, 1080, None).is_full(1920, 1080));
+    }
+}
@@ -0,0 +1,172 @@
+// Vulkan loader discovery with user-facing diagnostics
+//
+// A missing loader or driver otherwise surfaces as a cryptic libloading or
+// VkResult error. These helpers turn both cases into an actionable message.
+// They also pick the physical device, falling back to a CPU implementation
+// (lavapipe, SwiftShader) only when no hardware GPU is available.
+
+use crate::error::MetalshaderError;
+
+#[cfg(target_os = "macos")]
+pub(crate) const INSTALL_HINT: &str = "\
+Install the Vulkan loader and MoltenVK:
+  brew install molten-vk vulkan-loader
+  export DYLD_LIBRARY_PATH=\"/opt/homebrew/lib:$DYLD_LIBRARY_PATH\"
+When running from MetalShader.app, Contents/Frameworks must contain libvulkan
+and Contents/Resources/vulkan/icd.d/MoltenVK_icd.json must exist (or set VK_ICD_FILENAMES).
+Run ./check-vulkan.sh to verify the installation.";
+
+#[cfg(target_os = "linux")]
+pub(crate) const INSTALL_HINT: &str = "\
+Install the Vulkan loader and a driver for your GPU, e.g.:
+  Alpine:        apk add vulkan-loader mesa-vulkan-virtio
+  Debian/Ubuntu: apt install libvulkan1 mesa-vulkan-drivers
+Under QEMU, the guest needs virtio-gpu with Venus enabled.";
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub(crate) const INSTALL_HINT: &str = "\
+Make sure the Vulkan loader (libvulkan) and a Vulkan driver are installed.";
+
+/// Load the Vulkan entry points, explaining how to install the loader on failure
+pub fn load_entry() -> Result<ash::Entry, MetalshaderError> {
+    unsafe { ash::Entry::load() }.map_err(|e| MetalshaderError::LoaderNotFound(e.to_string()))
+}
+
+/// Map instance creation failures; with no ICD installed the loader reports
+/// ERROR_INCOMPATIBLE_DRIVER, which is really "no device"
+pub fn instance_error(result: ash::vk::Result) -> MetalshaderError {
+    match result {
+        ash::vk::Result::ERROR_INCOMPATIBLE_DRIVER => MetalshaderError::NoDevice,
+        other => MetalshaderError::InstanceCreation(other),
+    }
+}
+
+/// Well-known install locations of the lavapipe / SwiftShader ICD manifests
+const SOFTWARE_ICDS: &[&str] = &[
+    "/usr/share/vulkan/icd.d/lvp_icd.x86_64.json",
+    "/usr/share/vulkan/icd.d/lvp_icd.aarch64.json",
+    "/usr/share/vulkan/icd.d/lvp_icd.json",
+    "/usr/local/share/vulkan/icd.d/lvp_icd.json",
+    "/opt/homebrew/share/vulkan/icd.d/lvp_icd.aarch64.json",
+    "/opt/homebrew/share/vulkan/icd.d/lvp_icd.json",
+    "/usr/share/vulkan/icd.d/vk_swiftshader_icd.json",
+    "/usr/local/share/vulkan/icd.d/vk_swiftshader_icd.json",
+];
+
+/// Point the loader at a software ICD (`--software`)
+///
+/// Must run before `load_entry`. An explicit `VK_ICD_FILENAMES` or
+/// `VK_DRIVER_FILES` from the user always wins.
+pub fn use_software_icd() {
+    if std::env::var_os("VK_ICD_FILENAMES").is_some() || std::env::var_os("VK_DRIVER_FILES").is_some() {
+        return;
+    }
+    match SOFTWARE_ICDS.iter().find(|p| std::path::Path::new(p).exists()) {
+        Some(icd) => {
+            println!("Software rendering: using {}", icd);
+            unsafe { std::env::set_var("VK_ICD_FILENAMES", icd) };
+        }
+        None => eprintln!(
+            "Warning: --software given but no lavapipe/SwiftShader ICD found \
+             (install mesa-vulkan-swrast or set VK_ICD_FILENAMES)"
+        ),
+    }
+}
+
+/// Physical devices in order of preference
+///
+/// Hardware GPUs come first and CPU implementations last, so lavapipe is only
+/// used when nothing else exists. With `software` the order is reversed.
+pub unsafe fn ranked_physical_devices(
+    instance: &ash::Instance,
+    software: bool,
+) -> Result<Vec<ash::vk::PhysicalDevice>, MetalshaderError> {
+    let mut devices = instance.enumerate_physical_devices()?;
+    if devices.is_empty() {
+        return Err(MetalshaderError::NoDevice);
+    }
+
+    let is_cpu = |pd: &ash::vk::PhysicalDevice| {
+        instance.get_physical_device_properties(*pd).device_type == ash::vk::PhysicalDeviceType::CPU
+    };
+    // Stable sort keeps the driver's order within each group
+    devices.sort_by_key(|pd| is_cpu(pd) != software);
+
+    match (is_cpu(&devices[0]), software) {
+        (true, false) => eprintln!("No hardware GPU found, falling back to software rendering"),
+        (false, true) => eprintln!("Warning: --software given but no CPU Vulkan device found, using hardware"),
+        _ => {}
+    }
+    Ok(devices)
+}
+
+/// First queue family of `device` with graphics support, which also
+/// covers the transfers the renderers submit. Family 0 usually has it, but
+/// not on every driver
+pub unsafe fn graphics_queue_family(instance: &ash::Instance, device: ash::vk::PhysicalDevice) -> Option<u32> {
+    instance
+        .get_physical_device_queue_family_properties(device)
+        .iter()
+        .position(|family| family.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS))
+        .map(|index| index as u32)
+}
+
+/// Print API/driver version, vendor and device type of the chosen device,
+/// plus the loader version; the first thing to compare between machines
+pub unsafe fn print_device_info(entry: &ash::Entry, instance: &ash::Instance, device: ash::vk::PhysicalDevice) {
+    let props = instance.get_physical_device_properties(device);
+    let loader = match entry.try_enumerate_instance_version() {
+        Ok(Some(version)) => format_version(version),
+        // Vulkan 1.0 loaders lack vkEnumerateInstanceVersion
+        Ok(None) => "1.0".to_string(),
+        Err(e) => format!("unknown ({})", e),
+    };
+    println!(
+        "Vulkan {} | driver {} | vendor {} | {:?} | loader {}",
+        format_version(props.api_version),
+        format_driver_version(props.vendor_id, props.driver_version),
+        vendor_name(props.vendor_id),
+        props.device_type,
+        loader
+    );
+}
+
+fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        ash::vk::api_version_major(version),
+        ash::vk::api_version_minor(version),
+        ash::vk::api_version_patch(version)
+    )
+}
+
+/// driverVersion is vendor-defined; NVIDIA and Intel on Windows deviate from
+/// the VK_MAKE_VERSION packing everyone else uses
+fn format_driver_version(vendor_id: u32, version: u32) -> String {
+    match vendor_id {
+        0x10DE => format!(
+            "{}.{}.{}.{}",
+            version >> 22,
+            (version >> 14) & 0xff,
+            (version >> 6) & 0xff,
+            version & 0x3f
+        ),
+        0x8086 if cfg!(windows) => format!("{}.{}", version >> 14, version & 0x3fff),
+        _ => format_version(version),
+    }
+}
+
+fn vendor_name(vendor_id: u32) -> String {
+    let name = match vendor_id {
+        0x1002 => "AMD",
+        0x106B => "Apple",
+        0x10DE => "NVIDIA",
+        0x13B5 => "ARM",
+        0x1AF4 => "virtio",
+        0x5143 => "Qualcomm",
+        0x8086 => "Intel",
+        0x10005 => "Mesa",
+        _ => return format!("{:#06x}", vendor_id),
+    };
+    format!("{} ({:#06x})", name, vendor_id)
+}
@@ -0,0 +1,45 @@
+// Window geometry remembered between launches (macOS)
+//
+// Stored as JSON in the data directory,
+// ~/Library/Application Support/metalshader/window.json.
+// A missing or unreadable file just means the default window.
+#![cfg(target_os = "macos")]
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    /// Outer position in physical pixels
+    pub x: i32,
+    pub y: i32,
+    /// Inner size in physical pixels of the last windowed (non-fullscreen) state
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+}
+
+fn state_path() -> Option<PathBuf> {
+    metalshader::paths::data_dir().map(|dir| dir.join("window.json"))
+}
+
+impl WindowState {
+    pub fn load() -> Option<Self> {
+        let json = fs::read_to_string(state_path()?).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    pub fn save(&self) {
+        let Some(path) = state_path() else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, serde_json::to_string_pretty(self).unwrap_or_default()));
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to save window state to {}: {}", path.display(), e);
+        }
+    }
+}